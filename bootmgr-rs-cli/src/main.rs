@@ -10,14 +10,32 @@ extern crate alloc;
 
 use alloc::string::ToString;
 
-use bootmgr::{BootResult, boot::BootMgr, system::log_backend::UefiLogger};
+use bootmgr::{
+    BootResult,
+    boot::{BootMgr, loader::progress::set_progress_callback},
+    system::{log_backend::UefiLogger, log_file::FileLogger, syslog::SyslogLogger},
+};
 use getargs::{Arg, Options};
 use uefi::{
-    Handle, ResultExt, Status, boot, cstr16, entry, println, proto::loaded_image::LoadedImage,
+    Handle, ResultExt, Status, boot, cstr16, entry, print, println,
+    proto::loaded_image::LoadedImage,
 };
 
-/// The global logging instance.
-static LOGGER: UefiLogger = UefiLogger::new();
+/// The global logging instance, which also writes logs to the log file and a syslog collector if they are configured.
+static LOGGER: SyslogLogger<FileLogger<UefiLogger>> =
+    SyslogLogger::new(FileLogger::new(UefiLogger::new()));
+
+/// Print the progress of a network download on the same line.
+///
+/// The line is finished once the download is complete, which is always reported with the total size, even if the
+/// size was not known while downloading.
+fn print_progress(name: &str, done: usize, len: Option<usize>) {
+    match len {
+        Some(len) if done >= len => println!("\rDownloading {name}: {done}/{len} bytes"),
+        Some(len) => print!("\rDownloading {name}: {done}/{len} bytes"),
+        None => print!("\rDownloading {name}: {done} bytes"),
+    }
+}
 
 /// The actual main function of the program, which returns an [`anyhow::Result`].
 ///
@@ -44,6 +62,7 @@ fn main_func() -> BootResult<Option<Handle>> {
     };
 
     let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Warn));
+    set_progress_callback(Some(print_progress));
 
     let mut boot_mgr = BootMgr::new()?;
 
@@ -18,7 +18,10 @@ use alloc::boxed::Box;
 use bootmgr::{
     boot::{BootMgr, action::reboot},
     error::BootError,
-    system::{helper::locate_protocol, log_backend::UefiLogger},
+    system::{
+        helper::locate_protocol, log_backend::UefiLogger, log_file::FileLogger,
+        syslog::SyslogLogger,
+    },
 };
 use log::error;
 use uefi::{
@@ -28,8 +31,9 @@ use uefi::{
     system::with_stdout,
 };
 
-/// The global logging instance.
-static LOGGER: UefiLogger = UefiLogger::new();
+/// The global logging instance, which also writes logs to the log file and a syslog collector if they are configured.
+static LOGGER: SyslogLogger<FileLogger<UefiLogger>> =
+    SyslogLogger::new(FileLogger::new(UefiLogger::new()));
 
 /// The actual main function of the program, which returns a [`Result`].
 ///
@@ -6,13 +6,15 @@
 //! This is where the main loop of the whole application is located, and is where terminal, boot manager,
 //! and editor interact.
 
+use core::fmt::Write;
+
 use bootmgr::{
-    boot::BootMgr,
+    boot::{BootMgr, secure_boot::status::SecureBootStatus},
     config::editor::persist::PersistentConfig,
     error::BootError,
     system::helper::{create_timer, locate_protocol},
 };
-use log::error;
+use log::{LevelFilter, error};
 use ratatui_core::terminal::Terminal;
 use thiserror::Error;
 use uefi::{
@@ -22,15 +24,14 @@ use uefi::{
 };
 
 use crate::{
-    MainError,
+    LOGGER, MainError,
     editor::EditorState,
     features::editor::Editor,
+    logs::LogViewer,
+    password::{PasswordPrompt, ProtectedAction},
     ui::{boot_list::BootList, ratatui_backend::UefiBackend, theme::Theme},
 };
 
-/// The error delay in microseconds.
-const ERROR_DELAY: usize = 5_000_000; // 5 seconds
-
 /// The timeout timer interval in microseconds.
 const TIMER_INTERVAL: u64 = 10_000_000; // 1 second
 
@@ -85,6 +86,15 @@ pub struct App {
     /// The current state of the [`App`].
     pub state: AppState,
 
+    /// The Secure Boot status, if the status screen is currently shown.
+    pub secure_boot_status: Option<SecureBootStatus>,
+
+    /// The password prompt, if it is currently shown.
+    pub password_prompt: Option<PasswordPrompt>,
+
+    /// The log viewer, if it is currently shown.
+    pub log_viewer: Option<LogViewer>,
+
     /// The [`App`]'s editor, if included and enabled.
     pub editor: Editor,
 }
@@ -102,8 +112,9 @@ impl App {
         let mut boot_mgr = BootMgr::new()?;
 
         let persist = PersistentConfig::new()?;
+        let policy = boot_mgr.edit_policy().clone();
         for config in boot_mgr.list_mut() {
-            persist.swap_config_in_persist(config);
+            persist.swap_config_in_persist(config, &policy);
         }
 
         let boot_list = BootList::new(&boot_mgr);
@@ -128,6 +139,9 @@ impl App {
             timeout,
             set_default: false,
             state: AppState::Running,
+            secure_boot_status: None,
+            password_prompt: None,
+            log_viewer: None,
             editor,
         })
     }
@@ -169,7 +183,14 @@ impl App {
         match handle {
             Err(e) => {
                 error!("Fatal error occurred: {e}");
-                error!("Press any key to restart");
+
+                // the logger does not write to the console, so the error is written there directly
+                terminal.backend_mut().reset_color();
+                let _ = terminal.clear();
+                uefi::system::with_stdout(|stdout| {
+                    let _ = writeln!(stdout, "Fatal error occurred: {e}");
+                    let _ = writeln!(stdout, "Press any key to restart");
+                });
 
                 if let Some(event) = self.input.wait_for_key_event() {
                     let _ = uefi::boot::wait_for_event(&mut [event]);
@@ -215,6 +236,13 @@ impl App {
             return Ok(None);
         };
 
+        if self.boot_mgr.boot_requires_unlock(option) {
+            self.password_prompt = Some(PasswordPrompt::new(ProtectedAction::Boot));
+            self.timeout = -1;
+            self.state = AppState::Running;
+            return Ok(None);
+        }
+
         if self.set_default {
             self.boot_mgr.set_default(option);
         }
@@ -222,12 +250,12 @@ impl App {
         match self.boot_mgr.load(option) {
             Ok(handle) => Ok(Some(handle)),
             Err(e) => {
-                terminal.backend_mut().reset_color();
                 error!("Failed to load image: {e}");
-                boot::stall(ERROR_DELAY); // wait for 5 seconds so the error is visible
+                self.log_viewer = Some(LogViewer::with_level(LevelFilter::Error)); // show the error in the log viewer
                 self.timeout = -1;
                 self.state = AppState::Running;
-                terminal.clear()?; // clear screen so we dont have a messed up terminal
+                terminal.backend_mut().reset_color();
+                terminal.clear()?; // clear screen so the progress of a download is not left over
                 self.boot_list = BootList::new(&self.boot_mgr);
                 Ok(None)
             }
@@ -247,8 +275,10 @@ impl App {
             && self.boot_mgr.boot_config.editor
             && let Some(option) = self.boot_list.state.selected()
         {
+            let policy = self.boot_mgr.edit_policy().clone();
             let config = self.boot_mgr.get_config(option);
-            self.editor.run(config, &mut self.input, terminal)?;
+            self.editor
+                .run(config, &policy, &mut self.input, terminal)?;
 
             self.boot_mgr.validate();
             self.boot_list = BootList::new(&self.boot_mgr);
@@ -310,6 +340,8 @@ impl App {
     fn handle_key(&mut self) -> Result<(), MainError> {
         self.wait_for_events()?;
         match self.input.read_key().map_err(BootError::Uefi)? {
+            Some(key) if self.password_prompt.is_some() => self.handle_prompt_key(key),
+            Some(key) if self.log_viewer.is_some() => self.handle_log_key(key),
             Some(Key::Special(key)) => self.handle_special_key(key),
             Some(Key::Printable(key)) => self.handle_printable_key(key.into()),
             _ => (),
@@ -317,9 +349,81 @@ impl App {
         Ok(())
     }
 
+    /// Handle a key while the password prompt is shown.
+    ///
+    /// The escape key closes the prompt. If the password is submitted and it is correct, then the action that the
+    /// prompt was shown for is done. Otherwise, the prompt stays open.
+    fn handle_prompt_key(&mut self, key: Key) {
+        let Some(mut prompt) = self.password_prompt.take() else {
+            return;
+        };
+
+        match key {
+            Key::Special(ScanCode::ESCAPE) => (), // the prompt was already taken, so it is closed
+            Key::Printable(key) if prompt.handle_printable_key(key.into()) => {
+                if self.boot_mgr.unlock(&prompt.input) {
+                    self.run_protected_action(prompt.action);
+                } else {
+                    prompt.fail();
+                    self.password_prompt = Some(prompt);
+                }
+            }
+            _ => self.password_prompt = Some(prompt),
+        }
+    }
+
+    /// Handle a key while the log viewer is shown.
+    ///
+    /// The up and down arrow keys or w/s scroll through the messages, f changes the level of the messages that are
+    /// shown, and the escape key or l closes the log viewer.
+    fn handle_log_key(&mut self, key: Key) {
+        let Some(viewer) = &mut self.log_viewer else {
+            return;
+        };
+
+        match key {
+            Key::Special(ScanCode::ESCAPE) => self.log_viewer = None,
+            Key::Special(ScanCode::UP) => viewer.scroll_up(),
+            Key::Special(ScanCode::DOWN) => viewer.scroll_down(LOGGER.count(viewer.level)),
+            Key::Printable(key) => match char::from(key).to_ascii_lowercase() {
+                'l' => self.log_viewer = None,
+                'w' => viewer.scroll_up(),
+                's' => viewer.scroll_down(LOGGER.count(viewer.level)),
+                'f' => viewer.cycle_level(),
+                _ => (),
+            },
+            Key::Special(_) => (),
+        }
+    }
+
+    /// Do an action that may require the boot manager to be unlocked.
+    ///
+    /// If the boot manager is locked and the action requires it to be unlocked, then the password prompt is shown
+    /// instead.
+    fn run_protected_action(&mut self, action: ProtectedAction) {
+        let locked = match action {
+            ProtectedAction::Boot => self
+                .boot_list
+                .state
+                .selected()
+                .is_some_and(|option| self.boot_mgr.boot_requires_unlock(option)),
+            ProtectedAction::Edit => self.boot_mgr.is_locked(),
+        };
+
+        if locked {
+            self.password_prompt = Some(PasswordPrompt::new(action));
+            return;
+        }
+
+        match action {
+            ProtectedAction::Boot => self.state = AppState::Booting,
+            ProtectedAction::Edit => self.editor.state = EditorState::Editing,
+        }
+    }
+
     /// Handle a special key.
     ///
-    /// This includes the arrow keys for selection, and the escape key for exiting.
+    /// This includes the arrow keys for selection, and the escape key for exiting or closing the status screen.
     fn handle_special_key(&mut self, key: ScanCode) {
         match key {
             ScanCode::UP => {
@@ -330,6 +434,9 @@ impl App {
                 self.boot_list.state.select_next();
                 self.timeout = -1;
             }
+            ScanCode::ESCAPE if self.secure_boot_status.is_some() => {
+                self.secure_boot_status = None;
+            }
             ScanCode::ESCAPE => self.state = AppState::Exiting,
             _ => (),
         }
@@ -337,8 +444,8 @@ impl App {
 
     /// Handle a printable key.
     ///
-    /// This includes w/s for alternate selection, +/= for setting the default, e for editing, or the
-    /// enter key for selecting a boot option.
+    /// This includes w/s for alternate selection, +/= for setting the default, e for editing, i for toggling the
+    /// Secure Boot status screen, l for opening the log viewer, or the enter key for selecting a boot option.
     fn handle_printable_key(&mut self, key: char) {
         let key = key.to_ascii_lowercase();
         match key {
@@ -351,8 +458,17 @@ impl App {
                 self.timeout = -1;
             }
             '+' | '=' => self.set_default = !self.set_default,
-            '\r' => self.state = AppState::Booting, // return key
-            'e' => self.editor.state = EditorState::Editing,
+            '\r' => self.run_protected_action(ProtectedAction::Boot), // return key
+            'e' if self.boot_mgr.boot_config.editor => {
+                self.run_protected_action(ProtectedAction::Edit);
+            }
+            'i' => {
+                self.secure_boot_status = match self.secure_boot_status {
+                    Some(_) => None,
+                    None => Some(self.boot_mgr.secure_boot_status()),
+                };
+            }
+            'l' => self.log_viewer = Some(LogViewer::new()),
             _ => (),
         }
         self.timeout = -1;
@@ -16,9 +16,14 @@ use bootmgr::{
     BootResult,
     config::{
         Config,
-        editor::{ConfigEditor, persist::PersistentConfig},
+        editor::{
+            ConfigEditor,
+            persist::{PersistentConfig, reset_boot_counter},
+            policy::EditPolicy,
+        },
     },
 };
+use log::error;
 use ratatui_core::{layout::Position, terminal::Terminal};
 use uefi::{
     Event,
@@ -49,6 +54,9 @@ pub enum EditorState {
 
     /// The [`Editor`] is exiting, and is deleting the contents from the filesystem.
     Deleting,
+
+    /// The [`Editor`] is exiting, and is resetting the boot counter of the [`Config`].
+    ResettingCounter,
 }
 
 /// The basic editor
@@ -103,6 +111,7 @@ impl Editor {
     pub fn run(
         &mut self,
         config: &mut Config,
+        policy: &EditPolicy,
         input: &mut ScopedProtocol<Input>,
         terminal: &mut Terminal<UefiBackend>,
     ) -> BootResult<()> {
@@ -112,7 +121,7 @@ impl Editor {
 
         terminal.clear()?;
 
-        self.edit = ConfigEditor::new(config);
+        self.edit = ConfigEditor::with_policy(config, policy);
 
         self.cursor_pos = self.edit.current_field().chars().count();
 
@@ -136,6 +145,10 @@ impl Editor {
         } else if self.state == EditorState::Deleting {
             self.persist.remove_config_from_persist(config);
             let _ = self.persist.save_to_fs();
+        } else if self.state == EditorState::ResettingCounter
+            && let Err(e) = reset_boot_counter(config)
+        {
+            error!("Failed to reset boot counter: {e}");
         }
 
         terminal.hide_cursor()?;
@@ -172,6 +185,8 @@ impl Editor {
     /// If the key is up or down, then the current field will be saved and a new field will be loaded.
     /// If the key is left or right, then the cursor position is moved.
     /// If the key is F1, then the values will be saved to the filesystem persistently and the editor exits.
+    /// If the key is F2, then the values will be removed from the filesystem and the editor exits.
+    /// If the key is F3, then the boot counter of the `Config` will be reset and the editor exits.
     fn handle_special_key(&mut self, key: ScanCode) {
         match key {
             ScanCode::ESCAPE => {
@@ -183,6 +198,9 @@ impl Editor {
             ScanCode::FUNCTION_2 => {
                 self.state = EditorState::Deleting;
             }
+            ScanCode::FUNCTION_3 => {
+                self.state = EditorState::ResettingCounter;
+            }
             ScanCode::UP => {
                 self.edit.prev_field();
                 self.cursor_pos = self.edit.chars();
@@ -205,7 +223,12 @@ impl Editor {
     ///
     /// If the key is a backspace, then it will remove the current value and push the cursor position back by one.
     /// If the key is anything else, then that key will be inserted into the current value.
+    /// If the current field is read-only, then nothing is changed.
     fn handle_printable_key(&mut self, key: char) {
+        if self.edit.is_locked(self.edit.idx()) {
+            return;
+        }
+
         let mut value = self.edit.current_field().to_owned();
         match key {
             '\x08' => {
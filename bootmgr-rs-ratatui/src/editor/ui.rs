@@ -11,7 +11,7 @@ use bootmgr::BootResult;
 use ratatui_core::{
     buffer::Buffer,
     layout::{Alignment, Rect},
-    style::Style,
+    style::{Color, Style},
     terminal::Terminal,
     text::{Line, Span, Text},
     widgets::Widget,
@@ -37,11 +37,12 @@ impl Editor {
         let title_block = Block::default()
             .borders(Borders::ALL)
             .style(Style::default());
-        let title = Paragraph::new(Text::styled(
-            format!("Currently editing {}", self.edit.current_name()),
-            self.theme.base,
-        ))
-        .block(title_block);
+        let title = if self.edit.is_locked(self.edit.idx()) {
+            format!("Currently viewing {} (read-only)", self.edit.current_name())
+        } else {
+            format!("Currently editing {}", self.edit.current_name())
+        };
+        let title = Paragraph::new(Text::styled(title, self.theme.base)).block(title_block);
 
         Widget::render(title, area, buf);
     }
@@ -49,9 +50,12 @@ impl Editor {
     /// Displays the content of the current field.
     pub fn render_editor(&self, area: Rect, buf: &mut Buffer) {
         let value = self.edit.current_field();
-        let text = Line::raw(value)
-            .style(self.theme.base)
-            .alignment(Alignment::Left);
+        let style = if self.edit.is_locked(self.edit.idx()) {
+            self.theme.base.fg(Color::DarkGray) // grey out read-only fields
+        } else {
+            self.theme.base
+        };
+        let text = Line::raw(value).style(style).alignment(Alignment::Left);
 
         Widget::render(text, area, buf);
     }
@@ -65,6 +69,7 @@ impl Editor {
             ("ESC", "Exit"),
             ("F1", "Persistently Save and Exit"),
             ("F2", "Delete From Persistent Cache and Exit"),
+            ("F3", "Reset Boot Counter and Exit"),
         ];
         let spans: Vec<_> = keys
             .iter()
@@ -12,7 +12,10 @@ pub mod editor {
 /// The editor feature.
 #[cfg(not(feature = "editor"))]
 pub mod editor {
-    use bootmgr::{BootResult, config::Config};
+    use bootmgr::{
+        BootResult,
+        config::{Config, editor::policy::EditPolicy},
+    };
     use ratatui_core::terminal::Terminal;
     use uefi::{boot::ScopedProtocol, proto::console::text::Input};
 
@@ -25,7 +28,7 @@ pub mod editor {
             Ok(Self)
         }
 
-        pub fn swap_config_in_persist(&self, _config: &mut Config) {}
+        pub fn swap_config_in_persist(&self, _config: &mut Config, _policy: &EditPolicy) {}
     }
 
     /// A disabled editor. Has only one field, which does nothing.
@@ -54,6 +57,7 @@ pub mod editor {
         pub fn run(
             &mut self,
             _config: &mut Config,
+            _policy: &EditPolicy,
             _input: &mut ScopedProtocol<Input>,
             _terminal: &mut Terminal<UefiBackend>,
         ) -> BootResult<()> {
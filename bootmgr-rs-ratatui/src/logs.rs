@@ -0,0 +1,53 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The log viewer.
+//!
+//! This shows the messages that were kept by the global logger, which would otherwise be drawn over by the UI. The
+//! messages may be filtered by their level, and scrolled through.
+
+use log::LevelFilter;
+
+/// The state of the log viewer.
+pub struct LogViewer {
+    /// The least severe level of the messages that are shown.
+    pub level: LevelFilter,
+
+    /// The index of the first message that is shown.
+    pub scroll: usize,
+}
+
+impl LogViewer {
+    /// Creates a new [`LogViewer`], which shows every message that was kept.
+    pub const fn new() -> Self {
+        Self::with_level(LevelFilter::Trace)
+    }
+
+    /// Creates a new [`LogViewer`], which shows only the messages that are at least as severe as a level.
+    pub const fn with_level(level: LevelFilter) -> Self {
+        Self { level, scroll: 0 }
+    }
+
+    /// Scroll up by one message.
+    pub const fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// Scroll down by one message, given the amount of messages that are shown.
+    pub fn scroll_down(&mut self, count: usize) {
+        self.scroll = (self.scroll + 1).min(count.saturating_sub(1));
+    }
+
+    /// Show only the messages that are more severe than the current level, or every message again if only errors
+    /// were shown. This also scrolls back to the first message.
+    pub fn cycle_level(&mut self) {
+        self.level = match self.level {
+            LevelFilter::Trace => LevelFilter::Debug,
+            LevelFilter::Debug => LevelFilter::Info,
+            LevelFilter::Info => LevelFilter::Warn,
+            LevelFilter::Warn => LevelFilter::Error,
+            _ => LevelFilter::Trace,
+        };
+        self.scroll = 0;
+    }
+}
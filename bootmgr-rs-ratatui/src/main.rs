@@ -13,22 +13,38 @@
 
 extern crate alloc;
 
-use bootmgr::{boot::action::reboot, system::log_backend::UefiLogger};
+use bootmgr::{
+    boot::{action::reboot, loader::progress::set_progress_callback},
+    system::{
+        log_backend::{NullLogger, RingLogger},
+        log_file::FileLogger,
+        syslog::SyslogLogger,
+    },
+};
 use ratatui_core::terminal::Terminal;
 use thiserror::Error;
 use uefi::{boot::start_image, prelude::*};
 
-use crate::{app::App, ui::ratatui_backend::UefiBackend};
+use crate::{
+    app::App,
+    ui::{progress::draw_progress, ratatui_backend::UefiBackend},
+};
 
 mod app;
 mod features;
+mod logs;
+mod password;
 mod ui;
 
 #[cfg(feature = "editor")]
 mod editor;
 
-/// The global logging instance.
-static LOGGER: UefiLogger = UefiLogger::new();
+/// The global logging instance, which also writes logs to the log file and a syslog collector if they are configured.
+///
+/// Messages are not written to the console, since they would be drawn over by the UI. Instead, the most recent messages
+/// are kept in memory, so that they can be shown in the log viewer.
+static LOGGER: RingLogger<SyslogLogger<FileLogger<NullLogger>>> =
+    RingLogger::new(SyslogLogger::new(FileLogger::new(NullLogger)));
 
 /// An error that may occur when running the application.
 #[derive(Error, Debug)]
@@ -49,6 +65,7 @@ pub enum MainError {
 /// or ran.
 fn main_func() -> Result<Option<Handle>, MainError> {
     let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Warn)); // if the logger was already set, then ignore it
+    set_progress_callback(Some(draw_progress));
 
     let backend = UefiBackend::new()?;
     let mut terminal = Terminal::new(backend)?;
@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The password prompt.
+//!
+//! When a password is set in the boot manager's configuration, the prompt is shown before the editor is opened or a
+//! protected boot option is booted. Once the boot manager is unlocked, the prompt is not shown again.
+
+use alloc::string::String;
+
+/// An action that is done once the boot manager is unlocked.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProtectedAction {
+    /// Boot the selected boot option.
+    Boot,
+
+    /// Open the editor for the selected boot option.
+    Edit,
+}
+
+/// A prompt for the password of the boot manager.
+pub struct PasswordPrompt {
+    /// The password that has been typed so far.
+    pub input: String,
+
+    /// The action that is done once the boot manager is unlocked.
+    pub action: ProtectedAction,
+
+    /// If the previously submitted password was incorrect.
+    pub failed: bool,
+}
+
+impl PasswordPrompt {
+    /// Creates a new [`PasswordPrompt`] for an action.
+    pub const fn new(action: ProtectedAction) -> Self {
+        Self {
+            input: String::new(),
+            action,
+            failed: false,
+        }
+    }
+
+    /// Handle a printable key.
+    ///
+    /// If the key is a backspace, then the last character is removed. If the key is the return key, then the
+    /// password is submitted, and `true` is returned. If the key is anything else, then that key is added to the
+    /// password.
+    pub fn handle_printable_key(&mut self, key: char) -> bool {
+        match key {
+            '\x08' => {
+                self.input.pop();
+            } // backspace
+            '\r' => return true, // return key
+            key => self.input.push(key),
+        }
+        false
+    }
+
+    /// Mark the submitted password as incorrect, and clear the input.
+    pub fn fail(&mut self) {
+        self.input.clear();
+        self.failed = true;
+    }
+
+    /// Get a masked representation of the password that has been typed so far.
+    pub fn masked(&self) -> String {
+        "*".repeat(self.input.chars().count())
+    }
+}
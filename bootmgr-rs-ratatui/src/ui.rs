@@ -10,8 +10,9 @@
 //! The theme of the UI can be changed through the bootloader's config file. There is support for changing the color,
 //! and the highlight color.
 
-use alloc::format;
+use alloc::{format, vec::Vec};
 
+use bootmgr::boot::secure_boot::status::SecureBootStatus;
 use ratatui_core::{
     buffer::Buffer,
     layout::{Alignment, Rect},
@@ -28,11 +29,15 @@ use ratatui_widgets::{
 };
 use tinyvec::ArrayVec;
 
-use crate::{MainError, app::App, ui::ratatui_backend::UefiBackend};
+use crate::{
+    LOGGER, MainError, app::App, logs::LogViewer, password::PasswordPrompt,
+    ui::ratatui_backend::UefiBackend,
+};
 
 mod widget;
 
 pub mod boot_list;
+pub mod progress;
 pub mod ratatui_backend;
 pub mod theme;
 
@@ -84,15 +89,17 @@ impl App {
 
     /// Renders the help bar at the bottom of the screen.
     pub fn render_help(&self, area: Rect, buf: &mut Buffer) {
-        const KEYS: [(&str, &str); 5] = [
+        const KEYS: [(&str, &str); 7] = [
             (" ↑/W ", " Up "),
             (" ↓/S ", " Down "),
             (" Return ", " Start "),
             (" ESC ", " Exit "),
             (" +/= ", " Toggle Default "),
+            (" I ", " Secure Boot Status "),
+            (" L ", " Logs "),
         ];
 
-        let mut spans: ArrayVec<[_; 12]> = ArrayVec::new();
+        let mut spans: ArrayVec<[_; 16]> = ArrayVec::new();
 
         for (key, desc) in &KEYS {
             spans.push(Span::styled(*key, self.theme.highlight));
@@ -112,6 +119,79 @@ impl App {
             .render(area, buf);
     }
 
+    /// Renders the Secure Boot status screen in place of the `BootList`.
+    pub fn render_secure_boot_status(
+        &self,
+        status: &SecureBootStatus,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Secure Boot Status ")
+            .style(self.theme.base);
+        let lines: Vec<_> = status
+            .fields()
+            .into_iter()
+            .map(|(label, value)| Line::raw(format!("{label}: {value}")))
+            .collect();
+        let status = Paragraph::new(Text::from(lines))
+            .style(self.theme.base)
+            .block(block);
+
+        Widget::render(status, area, buf);
+    }
+
+    /// Renders the password prompt in place of the `BootList`.
+    pub fn render_password_prompt(&self, prompt: &PasswordPrompt, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Password Required ")
+            .style(self.theme.base);
+        let message = if prompt.failed {
+            "Incorrect password, try again"
+        } else {
+            "Enter the password to continue"
+        };
+        let lines = [
+            Line::raw(message),
+            Line::raw(""),
+            Line::raw(format!("Password: {}", prompt.masked())),
+            Line::raw(""),
+            Line::raw("Press Return to unlock or ESC to cancel"),
+        ];
+        let prompt = Paragraph::new(Text::from_iter(lines))
+            .style(self.theme.base)
+            .block(block);
+
+        Widget::render(prompt, area, buf);
+    }
+
+    /// Renders the log viewer in place of the `BootList`.
+    pub fn render_logs(&self, viewer: &LogViewer, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Logs ({}) ", viewer.level))
+            .title_bottom(" ↑/↓ Scroll  F Level  ESC Close ")
+            .style(self.theme.base);
+        let lines: Vec<_> = LOGGER
+            .entries(viewer.level)
+            .into_iter()
+            .map(|entry| {
+                Line::from_iter([
+                    Span::styled(format!("[{:<5}]", entry.level), self.theme.highlight),
+                    Span::raw(format!(" {}: {}", entry.location, entry.message)),
+                ])
+            })
+            .collect();
+        let logs = Paragraph::new(Text::from(lines))
+            .style(self.theme.base)
+            .scroll((u16::try_from(viewer.scroll).unwrap_or(u16::MAX), 0))
+            .block(block);
+
+        Widget::render(logs, area, buf);
+    }
+
     /// Renders a status, which is currently used only for indicating setting default.
     pub fn render_status(&self, area: Rect, buf: &mut Buffer) {
         let mut lines: ArrayVec<[_; 2]> = ArrayVec::new();
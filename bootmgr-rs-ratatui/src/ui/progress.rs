@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Drawing of the progress of network downloads.
+//!
+//! The terminal is not redrawn while a boot option is loaded, so the progress is written straight to the last row of
+//! the console instead of through ratatui.
+
+use alloc::format;
+use core::fmt::Write;
+
+use uefi::system;
+
+/// Draw the progress of a download on the last row of the console, with the `ProgressCallback` signature.
+pub fn draw_progress(name: &str, done: usize, len: Option<usize>) {
+    let text = match len {
+        Some(len) => format!("Downloading {name}: {done}/{len} bytes"),
+        None => format!("Downloading {name}: {done} bytes"),
+    };
+
+    system::with_stdout(|stdout| {
+        let Ok(Some(mode)) = stdout.current_mode() else {
+            return;
+        };
+
+        // the last column is left empty, as writing to it would scroll the console
+        let width = mode.columns().saturating_sub(1);
+        let _ = stdout.set_cursor_position(0, mode.rows().saturating_sub(1));
+        let _ = write!(stdout, "{text:<width$.width$}");
+    });
+}
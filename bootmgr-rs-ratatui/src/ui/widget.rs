@@ -40,7 +40,20 @@ impl Widget for &mut App {
 
         self.render_help(vertical[2], buf);
         self.render_status(vertical[1], buf);
+
+        // the log viewer takes up the whole screen above the status, since messages may be long
+        if let Some(viewer) = &self.log_viewer {
+            self.render_logs(viewer, vertical[0], buf);
+            return;
+        }
+
         self.render_timeout(middle[3], buf);
-        self.render_list(horizontal[1], buf);
+        if let Some(prompt) = &self.password_prompt {
+            self.render_password_prompt(prompt, horizontal[1], buf);
+        } else if let Some(status) = &self.secure_boot_status {
+            self.render_secure_boot_status(status, horizontal[1], buf);
+        } else {
+            self.render_list(horizontal[1], buf);
+        }
     }
 }
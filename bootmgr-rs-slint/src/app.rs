@@ -9,10 +9,13 @@
 use alloc::{rc::Rc, vec};
 
 use bootmgr::{
-    boot::BootMgr, config::editor::persist::PersistentConfig, system::helper::locate_protocol,
+    boot::BootMgr,
+    config::editor::persist::{PersistentConfig, reset_boot_counter},
+    system::helper::locate_protocol,
 };
 use heapless::mpmc::Queue;
-use slint::{ModelRc, ToSharedString};
+use log::{LevelFilter, error};
+use slint::{ModelRc, SharedString, ToSharedString};
 use uefi::{
     Event, Handle,
     boot::ScopedProtocol,
@@ -23,7 +26,10 @@ use crate::{
     MainError,
     editor::Editor,
     input::MouseState,
-    ui::{slint_backend::SlintBltPixel, slint_inc::Ui},
+    ui::{
+        log_entries, progress::ProgressGuard, secure_boot_status_fields,
+        slint_backend::SlintBltPixel, slint_inc::Ui,
+    },
 };
 
 /// The possible commands that may be pushed through the Slint-Rust queue.
@@ -43,11 +49,23 @@ pub enum Command {
     /// Remove a persistent [`Config`] from the filesystem.
     RemoveConfigFromFs(usize),
 
+    /// Reset the boot counter of a [`Config`].
+    ResetBootCounter(usize),
+
     /// Try to boot an entry.
     TryBoot(usize),
 
     /// Try to edit an entry.
     TryEdit(usize),
+
+    /// Show the Secure Boot status of the system.
+    ShowSecureBootStatus,
+
+    /// Show the kept log messages that are at least as severe as a level.
+    ShowLogs(LevelFilter),
+
+    /// Try to unlock the boot manager with a password.
+    Unlock(SharedString),
 }
 
 /// The main application logic of the bootloader.
@@ -78,6 +96,9 @@ pub struct App {
 
     /// Stores the collection of persistently saved [`Config`]s.
     pub persist: PersistentConfig,
+
+    /// The command that is run once the boot manager is unlocked, if the password prompt is shown.
+    pub pending: Option<Command>,
 }
 
 impl App {
@@ -90,8 +111,9 @@ impl App {
     pub fn new() -> Result<Self, MainError> {
         let mut boot_mgr = BootMgr::new()?;
         let persist = PersistentConfig::new()?;
+        let policy = boot_mgr.edit_policy().clone();
         for config in boot_mgr.list_mut() {
-            persist.swap_config_in_persist(config);
+            persist.swap_config_in_persist(config, &policy);
         }
 
         let timeout = boot_mgr.boot_config.timeout;
@@ -118,6 +140,7 @@ impl App {
             editor,
             queue,
             persist,
+            pending: None,
         })
     }
 
@@ -156,37 +179,8 @@ impl App {
                 window.draw_if_needed(|renderer| self.draw_frame(renderer, &mut fb, w, h));
 
                 while let Some(message) = self.queue.dequeue() {
-                    match message {
-                        Command::SaveChanges { fields, idx } => {
-                            let config = self.boot_mgr.get_config(idx);
-                            self.editor.save_config(config, &fields);
-                            Self::refresh_boot_items(&self.boot_mgr, &ui);
-                        }
-                        Command::SaveConfigToFs(idx) => {
-                            let config = self.boot_mgr.get_config(idx);
-                            if !self.persist.contains(config) {
-                                self.persist.add_config_to_persist(config);
-                            }
-                            let _ = self.persist.save_to_fs();
-                        }
-                        Command::RemoveConfigFromFs(idx) => {
-                            let config = self.boot_mgr.get_config(idx);
-                            self.persist.remove_config_from_persist(config);
-                            let _ = self.persist.save_to_fs();
-                        }
-                        Command::TryBoot(idx) => {
-                            if let Some(handle) = self.maybe_boot(idx, &ui) {
-                                return Ok(Some(handle));
-                            }
-                            skip_wait = true; // skip wait so that state changes take place immediately
-                        }
-                        Command::TryEdit(idx) => {
-                            let config = self.boot_mgr.get_config(idx);
-                            self.editor.load_config(config);
-
-                            ui.invoke_fill_fields(self.editor.get_fields());
-                            skip_wait = true;
-                        }
+                    if let Some(handle) = self.handle_command(message, &ui, &mut skip_wait) {
+                        return Ok(Some(handle));
                     }
                 }
 
@@ -215,6 +209,86 @@ impl App {
         }
     }
 
+    /// Handle a [`Command`] that was sent from the UI.
+    ///
+    /// If the command booted an image, then a [`Handle`] to that image is returned. If the UI should be updated
+    /// immediately, then `skip_wait` is set.
+    fn handle_command(
+        &mut self,
+        command: Command,
+        ui: &Ui,
+        skip_wait: &mut bool,
+    ) -> Option<Handle> {
+        match command {
+            Command::SaveChanges { fields, idx } => {
+                let config = self.boot_mgr.get_config(idx);
+                self.editor.save_config(config, &fields);
+                Self::refresh_boot_items(&self.boot_mgr, ui);
+            }
+            Command::SaveConfigToFs(idx) => {
+                let config = self.boot_mgr.get_config(idx);
+                if !self.persist.contains(config) {
+                    self.persist.add_config_to_persist(config);
+                }
+                let _ = self.persist.save_to_fs();
+            }
+            Command::RemoveConfigFromFs(idx) => {
+                let config = self.boot_mgr.get_config(idx);
+                self.persist.remove_config_from_persist(config);
+                let _ = self.persist.save_to_fs();
+            }
+            Command::ResetBootCounter(idx) => {
+                let config = self.boot_mgr.get_config(idx);
+                if let Err(e) = reset_boot_counter(config) {
+                    error!("Failed to reset boot counter: {e}");
+                }
+                Self::refresh_boot_items(&self.boot_mgr, ui);
+            }
+            Command::TryBoot(idx) if self.boot_mgr.boot_requires_unlock(idx) => {
+                self.prompt_password(Command::TryBoot(idx), ui);
+                *skip_wait = true;
+            }
+            Command::TryBoot(idx) => {
+                let handle = self.maybe_boot(idx, ui);
+                *skip_wait = true; // skip wait so that state changes take place immediately
+                return handle;
+            }
+            Command::TryEdit(idx) if self.boot_mgr.is_locked() => {
+                self.prompt_password(Command::TryEdit(idx), ui);
+                *skip_wait = true;
+            }
+            Command::TryEdit(idx) => {
+                let policy = self.boot_mgr.edit_policy().clone();
+                let config = self.boot_mgr.get_config(idx);
+                self.editor.load_config(config, &policy);
+
+                ui.invoke_fill_fields(self.editor.get_fields(), self.editor.get_locked_fields());
+                ui.invoke_show_edit();
+                *skip_wait = true;
+            }
+            Command::ShowSecureBootStatus => {
+                let status = self.boot_mgr.secure_boot_status();
+                ui.invoke_display_secure_boot_status(secure_boot_status_fields(&status));
+                *skip_wait = true;
+            }
+            Command::ShowLogs(level) => {
+                ui.set_logs(log_entries(level));
+                *skip_wait = true;
+            }
+            Command::Unlock(password) => {
+                if self.boot_mgr.unlock(&password) {
+                    if let Some(command) = self.pending.take() {
+                        let _ = self.queue.enqueue(command);
+                    }
+                } else {
+                    ui.invoke_show_password_prompt(true);
+                }
+                *skip_wait = true;
+            }
+        }
+        None
+    }
+
     /// Set up the interactions between Slint and Rust.
     ///
     /// The UI and the main loop communicate through a [`Command`] queue, where changes
@@ -251,6 +325,15 @@ impl App {
             }
         });
 
+        let tx = Rc::downgrade(&self.queue);
+        ui.on_reset_counter(move |idx| {
+            if let Some(tx) = tx.upgrade()
+                && let Ok(idx) = usize::try_from(idx)
+            {
+                let _ = tx.enqueue(Command::ResetBootCounter(idx));
+            }
+        });
+
         let tx = Rc::downgrade(&self.queue);
         ui.on_try_boot(move |idx| {
             if let Some(tx) = tx.upgrade()
@@ -268,6 +351,39 @@ impl App {
                 let _ = tx.enqueue(Command::TryEdit(idx));
             }
         });
+
+        let tx = Rc::downgrade(&self.queue);
+        ui.on_try_secure_boot_status(move || {
+            if let Some(tx) = tx.upgrade() {
+                let _ = tx.enqueue(Command::ShowSecureBootStatus);
+            }
+        });
+
+        let tx = Rc::downgrade(&self.queue);
+        ui.on_try_logs(move |level| {
+            if let Some(tx) = tx.upgrade() {
+                let level = usize::try_from(level)
+                    .ok()
+                    .and_then(|level| LevelFilter::iter().nth(level))
+                    .unwrap_or(LevelFilter::Trace);
+                let _ = tx.enqueue(Command::ShowLogs(level));
+            }
+        });
+
+        let tx = Rc::downgrade(&self.queue);
+        ui.on_unlock(move |password| {
+            if let Some(tx) = tx.upgrade() {
+                let _ = tx.enqueue(Command::Unlock(password));
+            }
+        });
+    }
+
+    /// Show the password prompt, and run a [`Command`] once the boot manager is unlocked.
+    fn prompt_password(&mut self, command: Command, ui: &Ui) {
+        self.pending = Some(command);
+        self.timeout = -1;
+        ui.set_timeout(-1);
+        ui.invoke_show_password_prompt(false);
     }
 
     /// Might try to boot the currently selected boot option, probably. Will return a handle to the loaded image
@@ -275,7 +391,12 @@ impl App {
     ///
     /// This will return [`None`] if the image could not be loaded.
     fn maybe_boot(&mut self, idx: usize, ui: &Ui) -> Option<Handle> {
-        match self.boot_mgr.load(idx) {
+        let boot_config = &self.boot_mgr.boot_config;
+        let guard = ProgressGuard::new(&mut self.gop, boot_config.fg, boot_config.bg);
+        let result = self.boot_mgr.load(idx);
+        drop(guard);
+
+        match result {
             Ok(handle) => Some(handle),
             Err(e) => {
                 ui.invoke_display_err(e.to_shared_string());
@@ -5,7 +5,10 @@
 
 use alloc::vec::Vec;
 
-use bootmgr::config::{Config, editor::ConfigEditor};
+use bootmgr::config::{
+    Config,
+    editor::{ConfigEditor, policy::EditPolicy},
+};
 use slint::{Model, ModelRc, SharedString, ToSharedString};
 
 /// The basic editor
@@ -18,9 +21,9 @@ impl Editor {
         Self::default()
     }
 
-    /// Load an editor from a config.
-    pub fn load_config(&mut self, config: &Config) {
-        self.0 = ConfigEditor::new(config);
+    /// Load an editor from a config, where the fields that are read-only by the policy may not be edited.
+    pub fn load_config(&mut self, config: &Config, policy: &EditPolicy) {
+        self.0 = ConfigEditor::with_policy(config, policy);
     }
 
     /// Save an editor to a config.
@@ -45,6 +48,15 @@ impl Editor {
         ModelRc::from(&*fields)
     }
 
+    /// Get which of the fields of the config are read-only.
+    pub fn get_locked_fields(&self) -> ModelRc<bool> {
+        let locked: Vec<_> = (0..self.0.fields().len())
+            .map(|idx| self.0.is_locked(idx))
+            .collect();
+
+        ModelRc::from(&*locked)
+    }
+
     /// Save the fields to the config.
     pub fn save_fields(&mut self, fields: &ModelRc<(SharedString, SharedString)>) {
         for (label, value) in fields.iter() {
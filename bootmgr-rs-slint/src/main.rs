@@ -11,17 +11,32 @@
 
 extern crate alloc;
 
-use bootmgr::boot::action::reboot;
+use bootmgr::{
+    boot::{action::reboot, loader::progress::set_progress_callback},
+    system::{
+        log_backend::{NullLogger, RingLogger},
+        log_file::FileLogger,
+        syslog::SyslogLogger,
+    },
+};
 use thiserror::Error;
 use uefi::{Handle, ResultExt, Status, boot::start_image, entry};
 
-use crate::app::App;
+use crate::{app::App, ui::progress::draw_progress};
 
 mod app;
 mod editor;
 mod input;
 mod ui;
 
+/// The global logging instance, which also sends logs to a syslog collector and appends them to the log file if they
+/// are configured.
+///
+/// Messages are not written to the console, since anything written to it would be drawn over. Instead, they are kept
+/// in memory for the log viewer.
+static LOGGER: RingLogger<SyslogLogger<FileLogger<NullLogger>>> =
+    RingLogger::new(SyslogLogger::new(FileLogger::new(NullLogger)));
+
 /// An error that may occur when running the application.
 #[derive(Error, Debug)]
 pub enum MainError {
@@ -42,6 +57,9 @@ pub enum MainError {
 ///
 /// May return an `Error` if a failure occurs while the app is running.
 fn main_func() -> Result<Option<Handle>, MainError> {
+    let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Warn)); // if the logger was already set, then ignore it
+    set_progress_callback(Some(draw_progress));
+
     // This is all done to ensure that GOP, Input, etc. are properly dropped before the next program is started.
     // If the image was simply booted directly from the tryboot function, then it would result in this program
     // still holding on to GOP and other protocols, which in the case of loading this program again, would result
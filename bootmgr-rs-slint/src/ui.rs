@@ -3,21 +3,22 @@
 
 //! The user interface rendering of the Slint bootloader.
 
-use alloc::{rc::Rc, vec::Vec};
+use alloc::{format, rc::Rc, vec::Vec};
 
 use bootmgr::{
-    boot::BootMgr,
+    boot::{BootMgr, secure_boot::status::SecureBootStatus},
     config::{Config, parsers::Parsers},
 };
 use bytemuck::TransparentWrapper;
+use log::LevelFilter;
 use slint::{
-    Image, Model, ModelRc, PhysicalSize, SharedString,
+    Image, Model, ModelRc, PhysicalSize, SharedString, ToSharedString,
     platform::software_renderer::{MinimalSoftwareWindow, SoftwareRenderer},
 };
 use uefi::proto::console::gop::{BltOp, BltRegion};
 
 use crate::{
-    MainError,
+    LOGGER, MainError,
     app::App,
     ui::{
         slint_backend::{SlintBltPixel, create_window, ueficolor_to_slintcolor},
@@ -25,6 +26,7 @@ use crate::{
     },
 };
 
+pub mod progress;
 pub mod slint_backend;
 pub mod slint_inc;
 
@@ -123,6 +125,35 @@ impl App {
     }
 }
 
+/// Convert the fields of a [`SecureBootStatus`] into labels and values for the status popup.
+pub fn secure_boot_status_fields(
+    status: &SecureBootStatus,
+) -> ModelRc<(SharedString, SharedString)> {
+    let fields: Vec<_> = status
+        .fields()
+        .iter()
+        .map(|(label, value)| (label.to_shared_string(), value.to_shared_string()))
+        .collect();
+
+    ModelRc::from(&*fields)
+}
+
+/// Convert the kept log messages that are at least as severe as a level into levels and messages for the log viewer.
+pub fn log_entries(level: LevelFilter) -> ModelRc<(SharedString, SharedString)> {
+    let entries: Vec<_> = LOGGER
+        .entries(level)
+        .iter()
+        .map(|entry| {
+            (
+                entry.level.to_shared_string(),
+                format!("{}: {}", entry.location, entry.message).into(),
+            )
+        })
+        .collect();
+
+    ModelRc::from(&*entries)
+}
+
 /// Pick an image based on the origin of the [`Config`].
 fn choose_image(images: &ModelRc<(Image, SharedString)>, config: &Config) -> Image {
     let origin = config.origin.map(Parsers::as_str);
@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Drawing of the progress of network downloads.
+//!
+//! The UI is not redrawn while a boot option is loaded, so the progress is drawn straight to the screen as a bar at
+//! the bottom of it, the same way that the mouse cursor is drawn over the UI. The bar can only be drawn while a
+//! [`ProgressGuard`] lends it the [`GraphicsOutput`] of the app.
+
+use core::{cell::Cell, marker::PhantomData, ptr::NonNull};
+
+use uefi::proto::console::{
+    gop::{BltOp, BltPixel, GraphicsOutput},
+    text::Color as UefiColor,
+};
+
+use crate::ui::slint_backend::ueficolor_to_slintcolor;
+
+/// The height of the progress bar in pixels.
+const BAR_HEIGHT: usize = 8;
+
+/// The amount of bytes that moves the bar by one step, if the size of the download is not known.
+const PULSE_BYTES: usize = 64 * 1024;
+
+/// The [`GraphicsOutput`] and the colors that the progress bar is drawn with.
+#[derive(Clone, Copy)]
+struct ProgressTarget {
+    /// The [`GraphicsOutput`] that is lent by a [`ProgressGuard`].
+    gop: NonNull<GraphicsOutput>,
+
+    /// The color of the done part of the bar.
+    fg: BltPixel,
+
+    /// The color of the rest of the bar.
+    bg: BltPixel,
+}
+
+/// The target of the progress bar, if a [`ProgressGuard`] exists.
+static PROGRESS_TARGET: ProgressTargetCell = ProgressTargetCell(Cell::new(None));
+
+/// Storage struct for the [`ProgressTarget`].
+struct ProgressTargetCell(Cell<Option<ProgressTarget>>);
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for ProgressTargetCell {}
+
+/// Lets the progress of downloads be drawn to a [`GraphicsOutput`] for as long as it exists.
+pub struct ProgressGuard<'a>(PhantomData<&'a mut GraphicsOutput>);
+
+impl<'a> ProgressGuard<'a> {
+    /// Lend a [`GraphicsOutput`] to [`draw_progress`], which draws with the given colors.
+    pub fn new(gop: &'a mut GraphicsOutput, fg: UefiColor, bg: UefiColor) -> Self {
+        let to_pixel = |color| {
+            let color = ueficolor_to_slintcolor(color);
+            BltPixel::new(color.red(), color.green(), color.blue())
+        };
+
+        PROGRESS_TARGET.0.set(Some(ProgressTarget {
+            gop: NonNull::from(gop),
+            fg: to_pixel(fg),
+            bg: to_pixel(bg),
+        }));
+        Self(PhantomData)
+    }
+}
+
+impl Drop for ProgressGuard<'_> {
+    fn drop(&mut self) {
+        PROGRESS_TARGET.0.set(None);
+    }
+}
+
+/// Draw the progress of a download as a bar at the bottom of the screen, with the `ProgressCallback` signature.
+///
+/// If the size of the download is not known, then a quarter of the bar moves along it as the download progresses.
+pub fn draw_progress(_name: &str, done: usize, len: Option<usize>) {
+    let Some(target) = PROGRESS_TARGET.0.get() else {
+        return;
+    };
+
+    // SAFETY: the target is only set while a `ProgressGuard` mutably borrows the `GraphicsOutput`, and it is only used
+    // for the duration of this function.
+    let gop = unsafe { &mut *target.gop.as_ptr() };
+    let (w, h) = gop.current_mode_info().resolution();
+    let y = h.saturating_sub(BAR_HEIGHT);
+
+    let (start, filled) = if let Some(len) = len {
+        let filled = done.min(len).saturating_mul(w).checked_div(len);
+        (0, filled.unwrap_or(w))
+    } else {
+        let block = w / 4;
+        let start = (done / PULSE_BYTES)
+            .saturating_mul(BAR_HEIGHT)
+            .checked_rem(w - block)
+            .unwrap_or(0);
+        (start, block)
+    };
+
+    let _ = gop.blt(BltOp::VideoFill {
+        color: target.bg,
+        dest: (0, y),
+        dims: (w, BAR_HEIGHT),
+    });
+    let _ = gop.blt(BltOp::VideoFill {
+        color: target.fg,
+        dest: (start, y),
+        dims: (filled, BAR_HEIGHT),
+    });
+}
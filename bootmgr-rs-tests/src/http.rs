@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+use bootmgr::{
+    boot::{action::BootAction, loader::load_boot_option},
+    config::builder::ConfigBuilder,
+};
+use uefi::{boot, println};
+
+use crate::read_key;
+
+/// The URL of the image, which is served from the host through QEMU user networking.
+const URL: &str = "http://10.0.2.2:8000/test.efi";
+
+/// Test if an image could be downloaded and loaded through HTTP.
+///
+/// This test passes if the image is downloaded and started. QEMU should be started with user networking, and a local
+/// HTTP server should serve an EFI executable as `test.efi` on port 8000, such as with `python3 -m http.server`.
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be downloaded or started.
+pub fn test_http() -> anyhow::Result<()> {
+    println!("Will try to download and start an image from {URL}");
+    println!("The test is passed if the image is started");
+    println!("Press a key to continue");
+    let _ = read_key();
+
+    let config = ConfigBuilder::new(URL, "")
+        .action(BootAction::BootHttp)
+        .build();
+
+    let handle = load_boot_option(&config)?;
+    boot::start_image(handle)?;
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+use anyhow::anyhow;
+use bootmgr::{
+    boot::{action::BootAction, loader::load_boot_option},
+    config::builder::ConfigBuilder,
+    system::fs::UefiFileSystem,
+};
+use uefi::{
+    CStr16, boot, cstr16, println,
+    proto::{device_path::DevicePath, loaded_image::LoadedImage, media::fs::SimpleFileSystem},
+};
+
+use crate::{press_for_reboot, read_key};
+
+const KERNEL_PATH: &CStr16 = cstr16!("\\vmlinuz");
+const INITRD_PATH: &CStr16 = cstr16!("\\initrd.img");
+const OPTIONS: &str = "console=ttyS0 earlyprintk=serial panic=-1";
+const OPTIONS_WITH_INITRD: &str = "console=ttyS0 earlyprintk=serial panic=-1 initrd=\\initrd.img";
+
+/// Test if a Linux kernel could be booted through the Linux boot protocol.
+///
+/// This test passes if the kernel starts and prints its boot messages to the serial console. QEMU should be started
+/// with `-serial stdio` to see them.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem could not be opened, or the kernel could not be booted.
+pub fn test_linux() -> anyhow::Result<()> {
+    println!(
+        "Will try to boot a kernel from {KERNEL_PATH} with the initrd {INITRD_PATH} if it exists"
+    );
+    println!("The test is passed if the kernel prints its boot messages to the serial console");
+    println!("Press a key to continue");
+    let _ = read_key();
+
+    let options = {
+        let mut fs = UefiFileSystem::from_image_fs()?;
+
+        if !fs.exists(KERNEL_PATH) {
+            println!("Cannot test if Linux boot works, as {KERNEL_PATH} does not exist");
+            println!("Press a key to reboot");
+            press_for_reboot();
+        }
+
+        if fs.exists(INITRD_PATH) {
+            OPTIONS_WITH_INITRD
+        } else {
+            OPTIONS
+        }
+    }; // fs dropped here
+
+    let handle = {
+        let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
+        let device_handle = loaded_image
+            .device()
+            .ok_or_else(|| anyhow!("Image handle was not loaded from a storage device"))?;
+        let device_path = boot::open_protocol_exclusive::<DevicePath>(device_handle)?;
+        boot::locate_device_path::<SimpleFileSystem>(&mut &*device_path)?
+    };
+
+    let config = ConfigBuilder::new("vmlinuz", "")
+        .efi_path(KERNEL_PATH)
+        .options(options)
+        .action(BootAction::BootLinux)
+        .fs_handle(handle)
+        .build();
+
+    load_boot_option(&config)?;
+
+    Ok(())
+}
@@ -20,13 +20,21 @@ use uefi::{
 use crate::{
     action::test_custom_actions,
     fs::test_filesystem,
+    http::test_http,
+    linux::test_linux,
     load::{check_loaded, test_loading},
+    multiboot::test_multiboot,
+    pxe::test_pxe_ipv6,
     variables::{check_variable, test_variables},
 };
 
 mod action;
 mod fs;
+mod http;
+mod linux;
 mod load;
+mod multiboot;
+mod pxe;
 mod variables;
 
 static LOGGER: UefiLogger = UefiLogger::new();
@@ -48,6 +56,10 @@ fn main_func() -> anyhow::Result<()> {
     println!("2. Variables test");
     println!("3. Filesystem test");
     println!("4. Load image test");
+    println!("5. Linux boot test");
+    println!("6. Multiboot2 boot test");
+    println!("7. HTTP boot test");
+    println!("8. IPv6 PXE boot test");
     println!(
         "It's recommended that the tests are tested in order, as they will rely on each other in that order."
     );
@@ -59,6 +71,10 @@ fn main_func() -> anyhow::Result<()> {
                 '2' => Ok(test_variables()?),
                 '3' => Ok(test_filesystem()?),
                 '4' => test_loading(),
+                '5' => test_linux(),
+                '6' => test_multiboot(),
+                '7' => test_http(),
+                '8' => test_pxe_ipv6(),
                 _ => Ok(()),
             };
         }
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+use anyhow::anyhow;
+use bootmgr::{
+    boot::{action::BootAction, loader::load_boot_option},
+    config::builder::ConfigBuilder,
+    system::fs::UefiFileSystem,
+};
+use uefi::{
+    CStr16, boot, cstr16, println,
+    proto::{device_path::DevicePath, loaded_image::LoadedImage, media::fs::SimpleFileSystem},
+};
+
+use crate::{press_for_reboot, read_key};
+
+const KERNEL_PATH: &CStr16 = cstr16!("\\xen.gz");
+const DOM0_PATH: &CStr16 = cstr16!("\\vmlinuz");
+const INITRD_PATH: &CStr16 = cstr16!("\\initrd.img");
+const OPTIONS: &str = "console=com1 com1=115200 loglvl=all";
+const DOM0_MODULE: &str = "\\vmlinuz console=hvc0 earlyprintk=xen panic=-1";
+const INITRD_MODULE: &str = "\\initrd.img";
+
+/// Test if a Multiboot2 kernel could be booted with its modules.
+///
+/// This test passes if Xen starts and prints its boot messages to the serial console, and if `\vmlinuz` exists,
+/// then it is started as dom0. QEMU should be started with `-serial stdio` to see them.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem could not be opened, or the kernel could not be booted.
+pub fn test_multiboot() -> anyhow::Result<()> {
+    println!(
+        "Will try to boot a Multiboot2 kernel from {KERNEL_PATH} with {DOM0_PATH} and {INITRD_PATH} as modules if they exist"
+    );
+    println!("The test is passed if the kernel prints its boot messages to the serial console");
+    println!("Press a key to continue");
+    let _ = read_key();
+
+    let modules = {
+        let mut fs = UefiFileSystem::from_image_fs()?;
+
+        if !fs.exists(KERNEL_PATH) {
+            println!("Cannot test if Multiboot2 boot works, as {KERNEL_PATH} does not exist");
+            println!("Press a key to reboot");
+            press_for_reboot();
+        }
+
+        [(DOM0_PATH, DOM0_MODULE), (INITRD_PATH, INITRD_MODULE)]
+            .map(|(path, module)| fs.exists(path).then_some(module))
+    }; // fs dropped here
+
+    let handle = {
+        let loaded_image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())?;
+        let device_handle = loaded_image
+            .device()
+            .ok_or_else(|| anyhow!("Image handle was not loaded from a storage device"))?;
+        let device_path = boot::open_protocol_exclusive::<DevicePath>(device_handle)?;
+        boot::locate_device_path::<SimpleFileSystem>(&mut &*device_path)?
+    };
+
+    let config = modules
+        .into_iter()
+        .flatten()
+        .fold(ConfigBuilder::new("xen.gz", ""), ConfigBuilder::module)
+        .efi_path(KERNEL_PATH)
+        .options(OPTIONS)
+        .action(BootAction::BootMultiboot)
+        .fs_handle(handle)
+        .build();
+
+    load_boot_option(&config)?;
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+use anyhow::anyhow;
+use bootmgr::boot::{action::pxe::get_pxe_offer, loader::load_boot_option};
+use uefi::{boot, println};
+
+use crate::read_key;
+
+/// Test if a boot file could be offered through `DHCPv6`, then downloaded and loaded through TFTP over IPv6.
+///
+/// This test passes if the image is downloaded and started. QEMU should be started with IPv6 only user networking,
+/// which serves an EFI executable through its TFTP server, such as with
+/// `-nic user,model=virtio-net-pci,ipv4=off,ipv6=on,tftp=<dir>,bootfile=test.efi`.
+///
+/// # Errors
+///
+/// May return an `Error` if no boot file was offered, the server is not an IPv6 address, or the image could not be
+/// downloaded or started.
+pub fn test_pxe_ipv6() -> anyhow::Result<()> {
+    println!(
+        "Will try to obtain a boot file through DHCPv6, then download and start it through TFTP"
+    );
+    println!("The test is passed if the image is started");
+    println!("Press a key to continue");
+    let _ = read_key();
+
+    let config = get_pxe_offer()?.ok_or_else(|| anyhow!("No boot file was offered"))?;
+    let server = config
+        .tftp_server
+        .ok_or_else(|| anyhow!("The boot file is not on a TFTP server"))?;
+    if !server.is_ipv6() {
+        return Err(anyhow!("The TFTP server {server} is not an IPv6 address"));
+    }
+
+    println!("Downloading {} from {server}", config.filename);
+    let handle = load_boot_option(&config)?;
+    boot::start_image(handle)?;
+
+    Ok(())
+}
@@ -10,9 +10,21 @@ use uefi::Handle;
 
 use crate::{
     BootResult,
-    boot::{action::add_special_boot, config::BootConfig, loader::load_boot_option},
-    config::{Config, scan_configs},
-    system::drivers::load_drivers,
+    boot::{
+        action::{BootAction, add_special_boot, pxe},
+        bli::LoaderSystemInfo,
+        config::BootConfig,
+        loader::load_boot_option,
+        password::PasswordError,
+        secure_boot::{enroll, status::SecureBootStatus},
+    },
+    config::{
+        Config,
+        editor::policy::EditPolicy,
+        parsers::{Parsers, counter::decrement_boot_counter},
+        scan_configs,
+    },
+    system::{drivers::load_drivers, log_file, syslog, variable::RuntimeUefiVariableStorage},
 };
 
 pub mod action;
@@ -20,6 +32,7 @@ pub mod bli;
 pub mod config;
 pub mod devicetree;
 pub mod loader;
+pub mod password;
 pub mod secure_boot;
 
 /// The storage for configuration files.
@@ -29,6 +42,12 @@ pub struct BootMgr {
 
     /// The boot options.
     configs: Vec<Config>,
+
+    /// If the editor and protected boot options require the password.
+    locked: bool,
+
+    /// The policy that decides which fields of the boot options may be edited.
+    edit_policy: EditPolicy,
 }
 
 impl BootMgr {
@@ -43,41 +62,87 @@ impl BootMgr {
     /// May return an `Error` if a fatal error occurred when parsing the [`BootConfig`] (such as the image handle not
     /// supporting `SimpleFileSystem`) or when parsing the [`Config`]s.
     pub fn new() -> BootResult<Self> {
-        let _ = bli::export_variables();
+        let _ = bli::export_variables(&mut RuntimeUefiVariableStorage, &LoaderSystemInfo::new());
+        if let Err(e) = bli::apply_console_mode(&RuntimeUefiVariableStorage) {
+            log::warn!("Failed to set the console mode from LoaderConfigConsoleMode: {e}");
+        }
 
         let boot_config = BootConfig::new()?;
+        log_file::start(boot_config.log_file);
+        if let Some(level) = boot_config.log_level {
+            log::set_max_level(level);
+        }
+        #[cfg(feature = "authenticode")]
+        secure_boot::authenticode::set_enabled(boot_config.authenticode);
+        #[cfg(not(feature = "authenticode"))]
+        if boot_config.authenticode {
+            log::warn!(
+                "Authenticode verification was enabled, but bootmgr-rs was built without it"
+            );
+        }
+
         if boot_config.drivers {
             load_drivers(&boot_config.driver_path)?; // load drivers before configs from other fs are parsed
         }
 
-        let mut configs = scan_configs()?;
+        let mut configs = scan_configs(&boot_config)?;
         add_special_boot(&mut configs, &boot_config);
+        syslog::start(boot_config.syslog_server.or_else(pxe::get_log_server));
+        for config in &mut configs {
+            config.protected |= boot_config.protected.contains(&config.filename);
+            if boot_config.reboot_for_bitlocker
+                && matches!(config.origin, Some(Parsers::Windows))
+                && config.action == BootAction::BootEfi
+            {
+                config.action = BootAction::BootNext;
+            }
+        }
+        enroll::auto_enroll(&configs, boot_config.secure_boot_enroll);
 
         if let Some(default) = boot_config.default
-            && !bli::default_oneshot_exists()
+            && !bli::default_oneshot_exists(&RuntimeUefiVariableStorage)
         {
-            let _ = bli::set_default_entry(&configs, default);
+            let _ = bli::set_default_entry(&mut RuntimeUefiVariableStorage, &configs, default);
         }
 
-        let _ = bli::set_loader_entries(&configs);
+        let _ = bli::set_loader_entries(&mut RuntimeUefiVariableStorage, &configs);
+
+        let locked = boot_config.password.is_some();
+        let edit_policy = EditPolicy::new(&boot_config);
 
         Ok(Self {
             boot_config,
             configs,
+            locked,
+            edit_policy,
         })
     }
 
     /// Load a boot option from a [`Config`] given the index.
     ///
+    /// If the boot option has a boot counter, then it is decremented before the boot option is loaded.
+    ///
     /// # Errors
     ///
-    /// May return an `Error` if an error occurred while loading the boot option.
+    /// May return an `Error` if the boot option is protected and the boot manager is locked, or an error occurred
+    /// while loading the boot option.
     pub fn load(&mut self, selected: usize) -> BootResult<Handle> {
+        if self.boot_requires_unlock(selected) {
+            return Err(PasswordError::Locked.into());
+        }
+
+        let config = &mut self.configs[selected];
+        log_file::set_selected_entry(&config.filename);
+        if let Err(e) = decrement_boot_counter(config) {
+            error!("Could not update boot counter of {}: {e}", config.filename);
+        }
+
         let config = &self.configs[selected];
-        match load_boot_option(config) {
+        let result = load_boot_option(config);
+
+        match result {
             Ok(handle) => {
-                let _ = bli::generate_random_seed();
-                let _ = bli::record_exit_time();
+                bli::prepare_handoff(&mut RuntimeUefiVariableStorage, config);
                 Ok(handle)
             }
             Err(e) => {
@@ -109,7 +174,8 @@ impl BootMgr {
     /// If the default boot option is not set, then 0 is returned
     #[must_use = "Has no effect if the result is unused"]
     pub fn get_default(&self) -> usize {
-        if let Some(default) = bli::get_default_entry(&self.configs)
+        if let Some(default) =
+            bli::get_default_entry(&mut RuntimeUefiVariableStorage, &self.configs)
             && default < self.configs.len()
         {
             default
@@ -124,12 +190,55 @@ impl BootMgr {
     /// cannot be completely reliable across all firmware implementations.
     pub fn set_default(&self, option: usize) {
         if option < self.configs.len()
-            && let Err(e) = bli::set_default_entry(&self.configs, option)
+            && let Err(e) =
+                bli::set_default_entry(&mut RuntimeUefiVariableStorage, &self.configs, option)
         {
             error!("Failed to set LoaderEntryDefault UEFI variable: {e}");
         }
     }
 
+    /// Returns a reference to the [`EditPolicy`], which decides which fields of the boot options may be edited.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn edit_policy(&self) -> &EditPolicy {
+        &self.edit_policy
+    }
+
+    /// Checks if the boot manager is locked.
+    ///
+    /// While the boot manager is locked, the editor should not be opened, and protected boot options cannot be
+    /// loaded. The boot manager is only locked if a password is set in the [`BootConfig`].
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Checks if a boot option can only be loaded after the boot manager is unlocked.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn boot_requires_unlock(&self, option: usize) -> bool {
+        self.locked && self.configs.get(option).is_some_and(|x| x.protected)
+    }
+
+    /// Unlocks the boot manager given the password.
+    ///
+    /// Returns `true` if the password was correct, or there is no password. Once the boot manager is unlocked, it
+    /// stays unlocked.
+    pub fn unlock(&mut self, password: &str) -> bool {
+        if let Some(hash) = &self.boot_config.password
+            && self.locked
+            && !hash.verify(password)
+        {
+            return false;
+        }
+        self.locked = false;
+        true
+    }
+
+    /// Gets the Secure Boot state of the system, including the state of Shim and the signature databases.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn secure_boot_status(&self) -> SecureBootStatus {
+        SecureBootStatus::new()
+    }
+
     /// Validates the inner [`Vec<Config>`] through various criteria.
     ///
     /// If any of the [`Config`]s are found to be invalid, then they will be
@@ -9,10 +9,15 @@ use uefi::Handle;
 
 use crate::{
     BootResult,
-    boot::{config::BootConfig, loader},
+    boot::{
+        config::{BootConfig, SecureBootEnroll},
+        loader,
+        secure_boot::enroll,
+    },
     config::{Config, parsers::Parsers},
 };
 
+pub mod boot_next;
 pub mod firmware;
 pub mod pxe;
 pub mod reboot;
@@ -31,6 +36,19 @@ pub enum BootAction {
     /// Boot using the TFTP boot loader.
     BootTftp,
 
+    /// Boot using the HTTP boot loader.
+    BootHttp,
+
+    /// Boot a Linux kernel without the EFI stub through the Linux boot protocol. This is only supported on `x86_64`.
+    BootLinux,
+
+    /// Boot a Multiboot2 kernel, such as Xen, with its modules. This is only supported on `x86_64`.
+    BootMultiboot,
+
+    /// Boot the firmware boot option that points at the same EFI executable through `BootNext`, then reboot the
+    /// system. This keeps the measurements that `BitLocker` expects.
+    BootNext,
+
     /// Reboot the system.
     Reboot,
 
@@ -39,6 +57,9 @@ pub enum BootAction {
 
     /// Reboot the system into firmware setup.
     ResetToFirmware,
+
+    /// Enroll Secure Boot keys, then reboot the system.
+    EnrollKeys,
 }
 
 impl BootAction {
@@ -52,13 +73,27 @@ impl BootAction {
             Self::Reboot => reboot::reset(),
             Self::Shutdown => shutdown::shutdown(),
             Self::ResetToFirmware => firmware::reset_to_firmware(),
+            Self::EnrollKeys => enroll::enroll_keys(config),
             Self::BootEfi => loader::efi::load_boot_option(config),
             Self::BootTftp => loader::tftp::load_boot_option(config),
+            Self::BootHttp => loader::http::load_boot_option(config),
+            Self::BootNext => boot_next::boot_next(config),
+            #[cfg(target_arch = "x86_64")]
+            Self::BootLinux => loader::linux::load_boot_option(config),
+            #[cfg(target_arch = "x86_64")]
+            Self::BootMultiboot => loader::multiboot::load_boot_option(config),
+            #[cfg(not(target_arch = "x86_64"))]
+            Self::BootLinux | Self::BootMultiboot => {
+                Err(loader::LoadError::UnsupportedAction(config.filename.clone()).into())
+            }
         }
     }
 }
 
-/// Adds reboot, shutdown, reset into firmware, and optionally a PXE boot entry.
+/// Adds reboot, shutdown, reset into firmware, and optionally PXE boot entries and Secure Boot enrollment entries.
+///
+/// The reset into firmware entry is always protected, so that the firmware setup cannot be entered without the
+/// password if one is set.
 pub(super) fn add_special_boot(configs: &mut Vec<Config>, boot_config: &BootConfig) {
     let actions = [
         ("Reboot", BootAction::Reboot),
@@ -74,6 +109,7 @@ pub(super) fn add_special_boot(configs: &mut Vec<Config>, boot_config: &BootConf
             filename: title.to_owned(),
             title: Some(title.to_owned()),
             action,
+            protected: action == BootAction::ResetToFirmware,
             origin: Some(Parsers::Special),
             ..Config::default()
         };
@@ -82,8 +118,12 @@ pub(super) fn add_special_boot(configs: &mut Vec<Config>, boot_config: &BootConf
     }
 
     if boot_config.pxe
-        && let Ok(Some(config)) = pxe::get_pxe_offer()
+        && let Ok(pxe_configs) = pxe::get_pxe_configs()
     {
-        configs.push(config);
+        configs.extend(pxe_configs);
+    }
+
+    if boot_config.secure_boot_enroll != SecureBootEnroll::Off && enroll::setup_mode_enabled() {
+        configs.extend(enroll::get_enroll_configs());
     }
 }
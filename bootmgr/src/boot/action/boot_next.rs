@@ -0,0 +1,326 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Provides [`boot_next`], which boots a firmware boot option on the next boot instead of loading its image.
+//!
+//! When `bootmgfw.efi` is chainloaded, the boot manager is measured into PCR 4 before it, so the measurements differ
+//! from those that `BitLocker` sealed its key to, and `BitLocker` asks for the recovery key. Instead, the `Boot####`
+//! option of the firmware that points at the same `bootmgfw.efi` is found, `BootNext` is set to it, and the system is
+//! reset, so that the firmware loads it directly on the next boot.
+//!
+//! This is similar to `reboot-for-bitlocker` in systemd-boot. If there is no matching `Boot####` option, then the
+//! image is loaded as usual.
+
+use alloc::{borrow::ToOwned, format, string::String};
+
+use log::warn;
+use uefi::{
+    CString16, Handle, cstr16,
+    runtime::{VariableAttributes, VariableVendor},
+};
+
+use crate::{
+    BootResult,
+    boot::{
+        action::reboot,
+        loader::{self, LoadError},
+    },
+    config::Config,
+    system::{
+        fs::get_partition_guid,
+        helper::normalize_path,
+        variable::{RuntimeUefiVariableStorage, UefiVariableStorage},
+    },
+};
+
+/// The size of the attributes and the length of the device path at the start of an `EFI_LOAD_OPTION`.
+const LOAD_OPTION_HEADER_SIZE: usize = 6;
+
+/// The type of a media device path node.
+const MEDIA_DEVICE_PATH: u8 = 4;
+
+/// The subtype of a hard drive media device path node.
+const MEDIA_HARDDRIVE_DP: u8 = 1;
+
+/// The subtype of a file path media device path node.
+const MEDIA_FILEPATH_DP: u8 = 4;
+
+/// The type of the device path node that ends the device path.
+const END_DEVICE_PATH_TYPE: u8 = 0x7F;
+
+/// The offset of the partition signature in a hard drive media device path node.
+const HARDDRIVE_SIGNATURE: usize = 24;
+
+/// The offset of the signature type in a hard drive media device path node.
+const HARDDRIVE_SIGNATURE_TYPE: usize = 41;
+
+/// The signature type of a partition signature that is the unique partition GUID of a GPT partition.
+const SIGNATURE_TYPE_GUID: u8 = 2;
+
+/// The attributes of `BootNext`, which is persistent and accessible at boot and runtime.
+const BOOT_NEXT_ATTRS: VariableAttributes = VariableAttributes::NON_VOLATILE
+    .union(VariableAttributes::BOOTSERVICE_ACCESS)
+    .union(VariableAttributes::RUNTIME_ACCESS);
+
+/// Boots the EFI executable of a [`Config`] through the `Boot####` option of the firmware that points at it.
+///
+/// If there is a matching `Boot####` option, then `BootNext` is set to it and the system is reset, so this never
+/// returns. Otherwise, the image is loaded through the EFI boot loader.
+///
+/// # Errors
+///
+/// May return an `Error` if the [`Config`] does not have an EFI executable, `BootOrder` or `BootNext` could not be
+/// accessed, or the image could not be loaded.
+pub(crate) fn boot_next(config: &Config) -> BootResult<Handle> {
+    let path = config
+        .efi_path
+        .as_deref()
+        .ok_or_else(|| LoadError::ConfigMissingEfi(config.filename.clone()))?;
+
+    let mut vars = RuntimeUefiVariableStorage;
+    let option = match config.fs_handle.and_then(|x| get_partition_guid(*x)) {
+        Some(guid) => find_boot_option(&vars, &guid.to_bytes(), path)?,
+        None => None,
+    };
+
+    let Some(option) = option else {
+        warn!("No firmware boot option found for {path}, loading it directly");
+        return loader::efi::load_boot_option(config);
+    };
+
+    set_boot_next(&mut vars, option)?;
+    reboot::reset()
+}
+
+/// Find the `Boot####` option in `BootOrder` that loads a path from the partition with a signature.
+///
+/// # Errors
+///
+/// May return an `Error` if the variables could not be read.
+fn find_boot_option(
+    vars: &impl UefiVariableStorage,
+    signature: &[u8; 16],
+    path: &str,
+) -> BootResult<Option<u16>> {
+    let Some(order) =
+        vars.get_variable_bytes(cstr16!("BootOrder"), &VariableVendor::GLOBAL_VARIABLE)?
+    else {
+        return Ok(None);
+    };
+
+    for num in order
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .map(|x| u16::from_le_bytes(*x))
+    {
+        let Ok(name) = CString16::try_from(&*format!("Boot{num:04X}")) else {
+            continue;
+        };
+
+        if let Some(option) = vars.get_variable_bytes(&name, &VariableVendor::GLOBAL_VARIABLE)?
+            && load_option_matches(&option, signature, path)
+        {
+            return Ok(Some(num));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Set `BootNext` to a `Boot####` option.
+///
+/// # Errors
+///
+/// May return an `Error` if the variable could not be set.
+fn set_boot_next(vars: &mut impl UefiVariableStorage, option: u16) -> BootResult<()> {
+    vars.set_variable::<u16>(
+        cstr16!("BootNext"),
+        Some(VariableVendor::GLOBAL_VARIABLE),
+        Some(BOOT_NEXT_ATTRS),
+        Some(option),
+    )
+}
+
+/// Check if an `EFI_LOAD_OPTION` loads a path from the partition with a signature.
+///
+/// The device path of the option may be either a full device path or a short form device path that starts with the
+/// hard drive node, so only the hard drive node and the file path nodes are compared. Paths are compared case
+/// insensitively, as the ESP is FAT.
+fn load_option_matches(option: &[u8], signature: &[u8; 16], path: &str) -> bool {
+    let Some(path_len) = option
+        .get(4..LOAD_OPTION_HEADER_SIZE)
+        .and_then(|x| x.try_into().ok())
+        .map(|x| usize::from(u16::from_le_bytes(x)))
+    else {
+        return false;
+    };
+
+    // skip the description, which is a nul terminated UCS-2 string
+    let Some(description_len) = option
+        .get(LOAD_OPTION_HEADER_SIZE..)
+        .and_then(|x| x.as_chunks::<2>().0.iter().position(|&c| c == [0, 0]))
+    else {
+        return false;
+    };
+    let start = LOAD_OPTION_HEADER_SIZE + (description_len + 1) * 2;
+    let Some(mut device_path) = option.get(start..start + path_len) else {
+        return false;
+    };
+
+    let mut same_partition = false;
+    let mut file_path = String::new();
+    while let [ty, subtype, a, b, ..] = *device_path {
+        let len = usize::from(u16::from_le_bytes([a, b]));
+        let Some(node) = device_path.get(..len).filter(|_| len >= 4) else {
+            return false;
+        };
+
+        match (ty, subtype) {
+            (END_DEVICE_PATH_TYPE, _) => break,
+            (MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP) => {
+                same_partition = node.get(HARDDRIVE_SIGNATURE_TYPE) == Some(&SIGNATURE_TYPE_GUID)
+                    && node.get(HARDDRIVE_SIGNATURE..HARDDRIVE_SIGNATURE + 16)
+                        == Some(signature.as_slice());
+            }
+            (MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP) => {
+                let chars = node[4..]
+                    .as_chunks::<2>()
+                    .0
+                    .iter()
+                    .map(|x| u16::from_le_bytes(*x));
+                let name: String = char::decode_utf16(chars.take_while(|&c| c != 0))
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+
+                // a path may be split across multiple file path nodes
+                if !file_path.is_empty() && !file_path.ends_with('\\') && !name.starts_with('\\') {
+                    file_path.push('\\');
+                }
+                file_path.push_str(&name);
+            }
+            _ => (),
+        }
+
+        device_path = &device_path[len..];
+    }
+
+    let trim = |x: &str| normalize_path(x).trim_start_matches('\\').to_owned();
+    same_partition && trim(&file_path).eq_ignore_ascii_case(&trim(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+    use crate::system::variable::MemoryUefiVariableStorage;
+
+    /// The partition signature of the test partition.
+    const SIGNATURE: [u8; 16] = [0xAB; 16];
+
+    /// Build an `EFI_LOAD_OPTION` with a description, and a device path with a partition signature and file path.
+    fn load_option(signature: [u8; 16], file_path: &str) -> Vec<u8> {
+        let mut device_path = Vec::new();
+
+        // a PCI node that should be skipped
+        device_path.extend([1, 1, 6, 0, 0, 0x1F]);
+
+        let mut harddrive = vec![MEDIA_DEVICE_PATH, MEDIA_HARDDRIVE_DP, 42, 0];
+        harddrive.resize(HARDDRIVE_SIGNATURE, 0);
+        harddrive.extend(signature);
+        harddrive.extend([2, SIGNATURE_TYPE_GUID]);
+        device_path.extend(harddrive);
+
+        let name: Vec<u8> = file_path
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let len = u16::try_from(4 + name.len()).unwrap_or_default();
+        device_path.extend([MEDIA_DEVICE_PATH, MEDIA_FILEPATH_DP]);
+        device_path.extend(len.to_le_bytes());
+        device_path.extend(name);
+        device_path.extend([END_DEVICE_PATH_TYPE, 0xFF, 4, 0]);
+
+        let mut option = Vec::new();
+        option.extend(1u32.to_le_bytes());
+        option.extend(
+            u16::try_from(device_path.len())
+                .unwrap_or_default()
+                .to_le_bytes(),
+        );
+        option.extend(
+            "Windows Boot Manager\0"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        option.extend(device_path);
+        option
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_load_option_matches() {
+        let path = "\\EFI\\Microsoft\\Boot\\bootmgfw.efi";
+        let option = load_option(SIGNATURE, path);
+        assert!(load_option_matches(&option, &SIGNATURE, path));
+        assert!(load_option_matches(
+            &option,
+            &SIGNATURE,
+            "/efi/microsoft/boot/BOOTMGFW.EFI"
+        ));
+        assert!(!load_option_matches(&option, &[0; 16], path));
+        assert!(!load_option_matches(
+            &option,
+            &SIGNATURE,
+            "\\EFI\\BOOT\\BOOTX64.EFI"
+        ));
+        assert!(!load_option_matches(&option[..20], &SIGNATURE, path));
+        assert!(!load_option_matches(&[], &SIGNATURE, path));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_find_boot_option() {
+        let path = "\\EFI\\Microsoft\\Boot\\bootmgfw.efi";
+        let mut vars = MemoryUefiVariableStorage::default();
+        let global = Some(VariableVendor::GLOBAL_VARIABLE);
+
+        assert_eq!(find_boot_option(&vars, &SIGNATURE, path).ok(), Some(None));
+
+        let options = [
+            (cstr16!("Boot0001"), load_option([0; 16], path)),
+            (cstr16!("Boot000A"), load_option(SIGNATURE, path)),
+        ];
+        for (name, option) in options {
+            assert!(
+                vars.set_variable_u8_slice(name, global, None, &option)
+                    .is_ok()
+            );
+        }
+        assert!(
+            vars.set_variable_u8_slice(cstr16!("BootOrder"), global, None, &[0, 0, 1, 0, 10, 0])
+                .is_ok()
+        );
+
+        assert_eq!(
+            find_boot_option(&vars, &SIGNATURE, path).ok(),
+            Some(Some(10))
+        );
+
+        assert!(set_boot_next(&mut vars, 10).is_ok());
+        assert_eq!(
+            vars.get_variable::<u16>(cstr16!("BootNext"), global).ok(),
+            Some(10)
+        );
+        assert_eq!(
+            vars.attributes(cstr16!("BootNext"), &VariableVendor::GLOBAL_VARIABLE),
+            Some(BOOT_NEXT_ATTRS)
+        );
+    }
+}
@@ -2,66 +2,635 @@
 // SPDX-License-Identifier: MIT
 
 //! Provides [`get_pxe_offer`] which obtains offers through DHCP and parses the response into a [`Config`]
+//!
+//! [`get_pxe_configs`] also fetches a boot menu from the TFTP server named in the DHCP reply, and turns each of its
+//! entries into a [`Config`] that is booted through TFTP.
+//!
+//! # IPv6
+//!
+//! If `DHCPv4` fails and the network interface supports IPv6, then [`BaseCode`] is restarted in IPv6 mode and
+//! `DHCPv6` is used instead. The boot file is then taken from the boot file URL option (option 59) of the `DHCPv6`
+//! reply, which is either a `tftp://` URL with the address of the server, such as `tftp://[2001:db8::1]/bootx64.efi`,
+//! or an `http://` or `https://` URL. Host names are not supported in `tftp://` URLs.
+//!
+//! # Boot menus
+//!
+//! The menu is looked up like PXELINUX does. If the DHCP reply has a configuration file (option 209), then only that
+//! file is fetched, relative to the path prefix (option 210) if there is one. Otherwise, these files are tried in order
+//! in the `bootmgr-rs.cfg` directory under the path prefix:
+//!
+//! 1. The hardware type and MAC address of the client, such as `01-52-54-00-12-34-56`.
+//! 2. The IPv4 address of the client in uppercase hexadecimal, such as `0A00020F`, removing one digit at a time.
+//! 3. `default`.
+//!
+//! The menu consists of BLS entries that follow each other, where every `title` key starts a new entry. The `linux`
+//! or `efi` executable and every `initrd` are downloaded from the same server, relative to the path prefix if their
+//! path is not absolute.
+//!
+//! In IPv6 mode, the PXELINUX options do not exist, so only `bootmgr-rs.cfg/default` is tried on the server of the boot
+//! file.
+//!
+//! ```text
+//! title Arch Linux
+//! linux /arch/vmlinuz-linux
+//! initrd /arch/initramfs-linux.img
+//! options ip=dhcp root=/dev/nfs nfsroot=10.0.2.2:/srv/arch
+//!
+//! title Memtest86+
+//! efi /memtest.efi
+//! ```
+//!
+//! # Integrity
+//!
+//! In IPv4 mode, the SHA-256 hash of the boot file may be given through sub-option 200 of the vendor specific
+//! information option (option 43), either as 64 hexadecimal characters or as the 32 raw bytes of the hash. The boot
+//! file is then refused if its hash does not match. Entries of a boot menu may give their own hash with the `sha256`
+//! key. As these hashes are received from the network, they are checked in addition to the normal validation of the
+//! boot file, so they never cause a boot file to be trusted.
 
-use alloc::{format, string::ToString};
-use core::{ffi::CStr, net::Ipv4Addr};
+use alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
+use core::{
+    ffi::CStr,
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
 
-use uefi::proto::network::pxe::{BaseCode, BootstrapType, DhcpV4Packet};
+use log::{info, warn};
+use uefi::{
+    boot::ScopedProtocol,
+    proto::network::{
+        IpAddress,
+        pxe::{BaseCode, BootstrapType, DhcpV4Packet, DhcpV6Packet, Packet},
+    },
+};
 
 use crate::{
     BootResult,
-    boot::action::BootAction,
-    config::{Config, builder::ConfigBuilder, parsers::Parsers},
-    system::helper::locate_protocol,
+    boot::{
+        action::BootAction,
+        loader::tftp::read_file,
+        secure_boot::hash_pin::{Sha256Hash, parse_sha256},
+    },
+    config::{
+        Config,
+        builder::ConfigBuilder,
+        parsers::{Parsers, bls::parse_tftp_menu},
+    },
+    system::{helper::locate_protocol, syslog::SYSLOG_PORT},
 };
 
+/// The offset of the options in a DHCP packet, after the magic cookie.
+const DHCP_OPTIONS_OFFSET: usize = 240;
+
+/// The DHCP option that pads the options.
+const DHCP_OPTION_PAD: u8 = 0;
+
+/// The DHCP option that ends the options.
+const DHCP_OPTION_END: u8 = 255;
+
+/// The PXELINUX DHCP option with the path to the configuration file.
+const DHCP_OPTION_CONFIG_FILE: u8 = 209;
+
+/// The PXELINUX DHCP option with the prefix of the paths to fetch.
+const DHCP_OPTION_PATH_PREFIX: u8 = 210;
+
+/// The DHCP option with the addresses of log servers.
+const DHCP_OPTION_LOG_SERVER: u8 = 7;
+
+/// The DHCP option with vendor specific information, which consists of sub-options.
+const DHCP_OPTION_VENDOR: u8 = 43;
+
+/// The vendor specific sub-option with the SHA-256 hash of the boot file.
+const VENDOR_OPTION_SHA256: u8 = 200;
+
+/// The `DHCPv6` option with the URL of the boot file.
+const DHCPV6_OPTION_BOOTFILE_URL: u16 = 59;
+
+/// The directory that boot menus are looked up in, if the configuration file is not given through DHCP.
+const MENU_DIR: &str = "bootmgr-rs.cfg/";
+
 /// Attempts to obtain a response through PXE DHCP. If one is obtained, create a [`Config`] for it.
 ///
 /// PXE works through using DHCP to provide the boot file, possibly parameters, and the IP address where
 /// the file is hosted. This function provides a basic means to obtain a boot file from a DHCP server, as
 /// well as the server where the boot file was obtained from. Respectively, these are stored in the EFI
-/// and filename fields of the [`Config`].
+/// and TFTP server fields of the [`Config`]. The server may be either an IPv4 or an IPv6 address.
 ///
-/// If the [`Config`] is an HTTP boot configuration, detected by checking if the boot name starts with
-/// `http://` or `https://`, those will not return a [`Config`]. This is because there is no support for
-/// HTTP boot, and if a DHCP server is replying with HTTP boot configurations, there is likely some type of
-/// mismatch or error. If HTTP boot is needed, chainload a more feature complete loader like `iPXE`.
+/// If the boot file is a URL, detected by checking if the boot name starts with `http://` or `https://`, then
+/// the [`Config`] is booted through HTTP instead, and the URL is stored in the filename field.
 ///
 /// # Errors
 ///
 /// May return an `Error` if the firmware does not support [`BaseCode`].
 pub fn get_pxe_offer() -> BootResult<Option<Config>> {
+    let base_code = start_dhcp()?;
+    Ok(get_offer_config(&base_code))
+}
+
+/// Attempts to obtain a response through PXE DHCP, then fetches a boot menu from the TFTP server.
+///
+/// If a boot menu is found and it has entries, then a [`Config`] is created for each of them. Otherwise, this falls
+/// back to the single [`Config`] of [`get_pxe_offer`].
+///
+/// # Errors
+///
+/// May return an `Error` if the firmware does not support [`BaseCode`].
+pub fn get_pxe_configs() -> BootResult<Vec<Config>> {
+    let mut base_code = start_dhcp()?;
+
+    let menu = get_menu_configs(&mut base_code);
+    if !menu.is_empty() {
+        return Ok(menu);
+    }
+
+    Ok(get_offer_config(&base_code).into_iter().collect())
+}
+
+/// Start the [`BaseCode`] protocol if it is not started, and run DHCP and a PXE discover.
+///
+/// If `DHCPv4` fails and the network interface supports IPv6, then `DHCPv6` is tried instead. A PXE discover is only
+/// done in IPv4 mode.
+///
+/// # Errors
+///
+/// May return an `Error` if the firmware does not support [`BaseCode`], or DHCP fails.
+fn start_dhcp() -> BootResult<ScopedProtocol<BaseCode>> {
     let mut base_code = locate_protocol::<BaseCode>()?;
     if !base_code.mode().started() {
         base_code.start(false)?;
     }
 
-    base_code.dhcp(true)?;
+    if let Err(e) = base_code.dhcp(true) {
+        let mode = base_code.mode();
+        if mode.using_ipv6() || !mode.ipv6_available() {
+            return Err(e.into());
+        }
 
-    let mut initial_layer = 0; // when starting a discover, use layer 0
-    base_code.discover(BootstrapType::BOOTSTRAP, &mut initial_layer, false, None)?;
+        // networks that are IPv6 only have no DHCPv4 server
+        warn!("DHCPv4 failed ({e}), trying DHCPv6");
+        base_code.stop()?;
+        base_code.start(true)?;
+        base_code.dhcp(true)?;
+    }
 
-    if base_code.mode().pxe_reply_received() {
-        let reply: &DhcpV4Packet = base_code.mode().pxe_reply().as_ref();
-        let Ok(file) = CStr::from_bytes_with_nul(&reply.bootp_boot_file) else {
-            return Ok(None);
-        };
-        let file = file.to_string_lossy();
+    if !base_code.mode().using_ipv6() {
+        let mut initial_layer = 0; // when starting a discover, use layer 0
+        base_code.discover(BootstrapType::BOOTSTRAP, &mut initial_layer, false, None)?;
+    }
+
+    Ok(base_code)
+}
+
+/// A boot file that was offered through DHCP.
+#[derive(Debug, PartialEq, Eq)]
+enum BootFile {
+    /// A boot file on a TFTP server, given by the address of the server and the path to the file.
+    Tftp(IpAddr, String),
+
+    /// A boot file that is downloaded from an HTTP or HTTPS URL.
+    Http(String),
+}
+
+/// Get the boot file that was offered through DHCP, if there is one.
+///
+/// In IPv4 mode, it is taken from the PXE reply. In IPv6 mode, it is taken from the boot file URL option of the
+/// `DHCPv6` reply, or of the proxy offer if there is no such option in the reply.
+fn get_boot_file(base_code: &BaseCode) -> Option<BootFile> {
+    let mode = base_code.mode();
+
+    if mode.using_ipv6() {
+        let ack: &DhcpV6Packet = mode.dhcp_ack().as_ref();
+        let proxy: Option<&DhcpV6Packet> = mode
+            .proxy_offer_received()
+            .then(|| mode.proxy_offer().as_ref());
+
+        let url =
+            get_dhcpv6_option(&ack.dhcp_options, DHCPV6_OPTION_BOOTFILE_URL).or_else(|| {
+                proxy.and_then(|x| get_dhcpv6_option(&x.dhcp_options, DHCPV6_OPTION_BOOTFILE_URL))
+            })?;
+        let url = str::from_utf8(url).ok()?;
+
+        let file = parse_boot_url(url);
+        if file.is_none() {
+            warn!("Unsupported boot file URL {url}");
+        }
+        return file;
+    }
+
+    if !mode.pxe_reply_received() {
+        return None;
+    }
+
+    let reply: &DhcpV4Packet = mode.pxe_reply().as_ref();
+    let file = CStr::from_bytes_with_nul(&reply.bootp_boot_file).ok()?;
+    let file = file.to_string_lossy().into_owned();
+
+    if file.starts_with("http://") || file.starts_with("https://") {
+        return Some(BootFile::Http(file));
+    }
+
+    let server = IpAddr::V4(Ipv4Addr::from(reply.bootp_si_addr));
+    Some(BootFile::Tftp(server, file))
+}
+
+/// Parse a boot file URL of `DHCPv6`.
+///
+/// Returns `None` if the URL is neither a `tftp://` URL with an IP address nor an HTTP or HTTPS URL.
+fn parse_boot_url(url: &str) -> Option<BootFile> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return Some(BootFile::Http(url.to_owned()));
+    }
 
-        if !file.starts_with("http://") && !file.starts_with("https://") {
-            let addr = Ipv4Addr::from(reply.bootp_si_addr).to_string();
+    let rest = url.strip_prefix("tftp://")?;
+    let (host, path) = rest.split_once('/')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|x| x.strip_suffix(']'))
+        .unwrap_or(host);
+
+    let server = host.parse().ok()?;
+    (!path.is_empty()).then(|| BootFile::Tftp(server, path.to_owned()))
+}
+
+/// Get the syslog collector from the log server option (option 7) of the DHCP acknowledgement, if the network was
+/// started in IPv4 mode.
+///
+/// Only the first log server is used.
+pub(crate) fn get_log_server() -> Option<SocketAddr> {
+    let base_code = locate_protocol::<BaseCode>().ok()?;
+    let mode = base_code.mode();
+    if !mode.started() || mode.using_ipv6() || !mode.dhcp_ack_received() {
+        return None;
+    }
+
+    let servers = get_dhcp_option(raw_packet(mode.dhcp_ack()), DHCP_OPTION_LOG_SERVER)?;
+    let server = <[u8; 4]>::try_from(servers.get(..4)?).ok()?;
+    Some(SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::from(server)),
+        SYSLOG_PORT,
+    ))
+}
+
+/// Create a [`Config`] for the boot file that was offered through DHCP, if there is one.
+fn get_offer_config(base_code: &BaseCode) -> Option<Config> {
+    let config = match get_boot_file(base_code)? {
+        BootFile::Http(url) => {
+            let title = format!("HTTP Boot: {url}");
+
+            ConfigBuilder::new(url, "")
+                .title(title)
+                .action(BootAction::BootHttp)
+                .origin(Parsers::Special)
+                .build()
+        }
+        BootFile::Tftp(server, file) => {
             let title = format!("PXE Boot: {file}");
 
-            let config = ConfigBuilder::new(addr, "")
+            ConfigBuilder::new(&*file, "")
                 .efi_path(file)
                 .title(title)
+                .tftp_server(server)
+                .assign_if_some(
+                    get_vendor_sha256(base_code),
+                    ConfigBuilder::download_sha256_bytes,
+                )
                 .action(BootAction::BootTftp)
                 .origin(Parsers::Special)
-                .build();
+                .build()
+        }
+    };
 
-            return Ok(Some(config));
+    Some(config)
+}
+
+/// Fetch a boot menu from the TFTP server, and create a [`Config`] for each of its entries.
+fn get_menu_configs(base_code: &mut BaseCode) -> Vec<Config> {
+    let (server, prefix, paths) = if base_code.mode().using_ipv6() {
+        let Some(BootFile::Tftp(server, _)) = get_boot_file(base_code) else {
+            return Vec::new();
+        };
+        (server, None, get_menu_paths(None, None, 0, &[], [0; 4]))
+    } else {
+        get_menu_paths_v4(base_code)
+    };
+
+    if server.is_unspecified() {
+        return Vec::new();
+    }
+    let addr = IpAddress::from(server);
+
+    for path in paths {
+        if let Ok(content) = read_file(base_code, &addr, &path) {
+            info!("Found boot menu {path} on {server}");
+            return parse_tftp_menu(&content, server, prefix.as_deref().unwrap_or_default());
+        }
+    }
+
+    Vec::new()
+}
+
+/// Get the TFTP server, the path prefix, and the paths of the boot menus to try from the `DHCPv4` packets.
+///
+/// The server and the options are taken from the PXE reply if there is one, otherwise from the DHCP acknowledgement.
+fn get_menu_paths_v4(base_code: &BaseCode) -> (IpAddr, Option<String>, Vec<String>) {
+    let mode = base_code.mode();
+    let ack = mode.dhcp_ack();
+    let reply = mode.pxe_reply_received().then(|| mode.pxe_reply());
+
+    let option = |code| {
+        reply
+            .and_then(|reply| get_dhcp_option(raw_packet(reply), code))
+            .or_else(|| get_dhcp_option(raw_packet(ack), code))
+            .map(|x| String::from_utf8_lossy(x).trim_end_matches('\0').to_owned())
+    };
+    let config_file = option(DHCP_OPTION_CONFIG_FILE);
+    let prefix = option(DHCP_OPTION_PATH_PREFIX);
+
+    let ack: &DhcpV4Packet = ack.as_ref();
+    let server = reply.map_or(ack.bootp_si_addr, |reply| {
+        AsRef::<DhcpV4Packet>::as_ref(reply).bootp_si_addr
+    });
+
+    let mac_len = usize::from(ack.bootp_hw_addr_len).min(ack.bootp_hw_addr.len());
+    let paths = get_menu_paths(
+        config_file.as_deref(),
+        prefix.as_deref(),
+        ack.bootp_hw_type,
+        &ack.bootp_hw_addr[..mac_len],
+        ack.bootp_yi_addr,
+    );
+
+    (IpAddr::V4(Ipv4Addr::from(server)), prefix, paths)
+}
+
+/// Get the raw bytes of a [`Packet`].
+fn raw_packet(packet: &Packet) -> &[u8] {
+    AsRef::<[u8; 1472]>::as_ref(packet)
+}
+
+/// Get the value of an option from a raw DHCP packet.
+///
+/// Returns `None` if the packet does not have the magic cookie, or it does not have the option.
+fn get_dhcp_option(packet: &[u8], code: u8) -> Option<&[u8]> {
+    let magic = packet.get(DHCP_OPTIONS_OFFSET - 4..DHCP_OPTIONS_OFFSET)?;
+    if magic != DhcpV4Packet::DHCP_MAGIK.to_be_bytes() {
+        return None;
+    }
+
+    find_option(packet.get(DHCP_OPTIONS_OFFSET..)?, code)
+}
+
+/// Get the value of an option from DHCP options, or the sub-options of an option, which use the same format.
+fn find_option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    while let [option, rest @ ..] = options {
+        match *option {
+            DHCP_OPTION_END => break,
+            DHCP_OPTION_PAD => options = rest,
+            option => {
+                let (&len, rest) = rest.split_first()?;
+                let (value, rest) = rest.split_at_checked(usize::from(len))?;
+                if option == code {
+                    return Some(value);
+                }
+                options = rest;
+            }
+        }
+    }
+
+    None
+}
+
+/// Get the SHA-256 hash of the boot file from the vendor specific information of the `DHCPv4` packets, if there is one.
+fn get_vendor_sha256(base_code: &BaseCode) -> Option<Sha256Hash> {
+    let mode = base_code.mode();
+    if mode.using_ipv6() {
+        return None;
+    }
+
+    let reply = mode.pxe_reply_received().then(|| mode.pxe_reply());
+    let vendor = reply
+        .and_then(|reply| get_dhcp_option(raw_packet(reply), DHCP_OPTION_VENDOR))
+        .or_else(|| get_dhcp_option(raw_packet(mode.dhcp_ack()), DHCP_OPTION_VENDOR))?;
+
+    let sha256 = parse_vendor_sha256(find_option(vendor, VENDOR_OPTION_SHA256)?);
+    if sha256.is_none() {
+        warn!("The SHA-256 hash in the vendor specific information is not valid");
+    }
+    sha256
+}
+
+/// Parse a SHA-256 hash that is either 32 raw bytes or 64 hexadecimal characters.
+fn parse_vendor_sha256(value: &[u8]) -> Option<Sha256Hash> {
+    Sha256Hash::try_from(value).ok().or_else(|| {
+        str::from_utf8(value)
+            .ok()
+            .and_then(|x| parse_sha256(x.trim_end_matches('\0')))
+    })
+}
+
+/// Get the value of an option from the options of a `DHCPv6` packet.
+///
+/// Each option has a 16-bit code and a 16-bit length, followed by its value. Returns `None` if the options do not have
+/// the option.
+fn get_dhcpv6_option(mut options: &[u8], code: u16) -> Option<&[u8]> {
+    while let [a, b, c, d, rest @ ..] = options {
+        let option = u16::from_be_bytes([*a, *b]);
+        if option == 0 {
+            break; // the rest of the buffer is unused
+        }
+
+        let (value, rest) = rest.split_at_checked(usize::from(u16::from_be_bytes([*c, *d])))?;
+        if option == code {
+            return Some(value);
         }
+        options = rest;
     }
 
-    Ok(None)
+    None
+}
+
+/// Get the paths of the boot menus to try in order, like PXELINUX.
+///
+/// If there is a configuration file, then only that file is tried. Otherwise, the hardware address, the IP address
+/// with one digit removed at a time, and `default` are tried in the [`MENU_DIR`] directory.
+fn get_menu_paths(
+    config_file: Option<&str>,
+    prefix: Option<&str>,
+    hw_type: u8,
+    mac: &[u8],
+    ip: [u8; 4],
+) -> Vec<String> {
+    let prefix = prefix.unwrap_or_default();
+
+    if let Some(config_file) = config_file.filter(|x| !x.is_empty()) {
+        if config_file.starts_with('/') {
+            return vec![config_file.to_owned()];
+        }
+        return vec![format!("{prefix}{config_file}")];
+    }
+
+    let mut paths = Vec::new();
+
+    if !mac.is_empty() {
+        let mut name = format!("{hw_type:02x}");
+        for byte in mac {
+            let _ = write!(name, "-{byte:02x}");
+        }
+        paths.push(format!("{prefix}{MENU_DIR}{name}"));
+    }
+
+    if ip != [0; 4] {
+        let hex = format!("{:08X}", u32::from_be_bytes(ip));
+        for len in (1..=hex.len()).rev() {
+            paths.push(format!("{prefix}{MENU_DIR}{}", &hex[..len]));
+        }
+    }
+
+    paths.push(format!("{prefix}{MENU_DIR}default"));
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_dhcp_option() {
+        let mut packet = vec![0; DHCP_OPTIONS_OFFSET];
+        assert_eq!(get_dhcp_option(&packet, DHCP_OPTION_CONFIG_FILE), None);
+
+        packet[DHCP_OPTIONS_OFFSET - 4..].copy_from_slice(&DhcpV4Packet::DHCP_MAGIK.to_be_bytes());
+        packet.extend([DHCP_OPTION_PAD, 53, 1, 5]);
+        packet.extend([DHCP_OPTION_CONFIG_FILE, 4]);
+        packet.extend(b"menu");
+        packet.extend([DHCP_OPTION_END, DHCP_OPTION_PATH_PREFIX, 1, b'/']);
+
+        assert_eq!(
+            get_dhcp_option(&packet, DHCP_OPTION_CONFIG_FILE),
+            Some(&b"menu"[..])
+        );
+        assert_eq!(get_dhcp_option(&packet, 53), Some(&[5][..]));
+        assert_eq!(get_dhcp_option(&packet, DHCP_OPTION_PATH_PREFIX), None);
+
+        // an option that is longer than the packet
+        packet.truncate(DHCP_OPTIONS_OFFSET);
+        packet.extend([DHCP_OPTION_CONFIG_FILE, 10, b'a']);
+        assert_eq!(get_dhcp_option(&packet, DHCP_OPTION_CONFIG_FILE), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_vendor_sha256() {
+        let vendor = [DHCP_OPTION_PAD, 1, 2, 0, 0, VENDOR_OPTION_SHA256, 32];
+        let mut options = vendor.to_vec();
+        options.extend([0xAB; 32]);
+        options.push(DHCP_OPTION_END);
+
+        let value = find_option(&options, VENDOR_OPTION_SHA256);
+        assert_eq!(value, Some(&[0xAB; 32][..]));
+        assert_eq!(parse_vendor_sha256(&[0xAB; 32]), Some([0xAB; 32]));
+        assert_eq!(find_option(&options, 2), None);
+
+        let hex = "ab".repeat(32);
+        assert_eq!(parse_vendor_sha256(hex.as_bytes()), Some([0xAB; 32]));
+        assert_eq!(parse_vendor_sha256(b"abcd"), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_dhcpv6_option() {
+        let mut options = vec![0; 16];
+        assert_eq!(
+            get_dhcpv6_option(&options, DHCPV6_OPTION_BOOTFILE_URL),
+            None
+        );
+
+        options.clear();
+        options.extend([0, 1, 0, 2, 0xAB, 0xCD]);
+        options.extend(DHCPV6_OPTION_BOOTFILE_URL.to_be_bytes());
+        options.extend([0, 4]);
+        options.extend(b"tftp");
+        options.extend([0; 8]);
+        assert_eq!(
+            get_dhcpv6_option(&options, DHCPV6_OPTION_BOOTFILE_URL),
+            Some(&b"tftp"[..])
+        );
+        assert_eq!(get_dhcpv6_option(&options, 1), Some(&[0xAB, 0xCD][..]));
+        assert_eq!(get_dhcpv6_option(&options, 2), None);
+
+        // an option that is longer than the buffer
+        assert_eq!(get_dhcpv6_option(&[0, 59, 0, 10, 1], 59), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_boot_url() {
+        assert_eq!(
+            parse_boot_url("tftp://[2001:db8::1]/efi/bootx64.efi"),
+            Some(BootFile::Tftp(
+                "2001:db8::1"
+                    .parse()
+                    .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                "efi/bootx64.efi".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_boot_url("tftp://10.0.2.2/bootx64.efi"),
+            Some(BootFile::Tftp(
+                IpAddr::V4(Ipv4Addr::new(10, 0, 2, 2)),
+                "bootx64.efi".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_boot_url("https://[2001:db8::1]/bootx64.efi"),
+            Some(BootFile::Http(
+                "https://[2001:db8::1]/bootx64.efi".to_owned()
+            ))
+        );
+        assert_eq!(parse_boot_url("tftp://server.example/bootx64.efi"), None);
+        assert_eq!(parse_boot_url("tftp://[2001:db8::1]/"), None);
+        assert_eq!(parse_boot_url("nfs://10.0.2.2/bootx64.efi"), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_menu_paths() {
+        assert_eq!(
+            get_menu_paths(Some("menu.conf"), Some("/boot/"), 1, &[], [0; 4]),
+            ["/boot/menu.conf"]
+        );
+        assert_eq!(
+            get_menu_paths(Some("/menu.conf"), Some("/boot/"), 1, &[], [0; 4]),
+            ["/menu.conf"]
+        );
+
+        let paths = get_menu_paths(
+            None,
+            None,
+            1,
+            &[0x52, 0x54, 0x00, 0x12, 0x34, 0x56],
+            [10, 0, 2, 15],
+        );
+        assert_eq!(paths.len(), 10);
+        assert_eq!(paths[0], "bootmgr-rs.cfg/01-52-54-00-12-34-56");
+        assert_eq!(paths[1], "bootmgr-rs.cfg/0A00020F");
+        assert_eq!(paths[8], "bootmgr-rs.cfg/0");
+        assert_eq!(paths[9], "bootmgr-rs.cfg/default");
+
+        assert_eq!(
+            get_menu_paths(None, Some("/pxe/"), 1, &[], [0; 4]),
+            ["/pxe/bootmgr-rs.cfg/default"]
+        );
+    }
 }
@@ -11,6 +11,8 @@ use uefi::{
 /// Resets the system.
 ///
 /// This function wraps around [`runtime::reset`] and provides a slightly more straightforward way to reboot the system.
+/// The logger is flushed first, so that buffered messages are not lost.
 pub fn reset() -> ! {
+    log::logger().flush();
     runtime::reset(ResetType::WARM, Status::SUCCESS, None)
 }
@@ -10,17 +10,38 @@
 //!
 //! This module provides an essentially complete implementation of this interface as per the
 //! [Boot Loader Interface](https://systemd.io/BOOT_LOADER_INTERFACE/) specification. Only the features necessary
-//! for interaction with a tool like `bootctl` are implemented in this module. Even if `bootctl` defines more
-//! features, these are purely for reporting in `bootctl status`.
+//! for interaction with a tool like `bootctl` are implemented in this module, as well as the variables that are
+//! purely for reporting in `bootctl status`, such as the firmware information and the selected entry.
+//!
+//! The `StubPcr*` variables are not exported, as these are set by `systemd-stub` itself when a UKI is booted.
+//! `LoaderConfigConsoleMode` is not exported either, as it is set by the OS. If it is set, then the console is switched
+//! to that mode at startup.
+//!
+//! Every function in this module takes a [`UefiVariableStorage`]. This will usually be
+//! [`RuntimeUefiVariableStorage`](crate::system::variable::RuntimeUefiVariableStorage), but may be an in-memory
+//! variable store when testing on host.
 
-use alloc::{string::ToString, vec::Vec};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
 
 use bitflags::bitflags;
+use log::LevelFilter;
 use sha2::Digest;
 use uefi::{
-    CStr16, boot, cstr16, guid,
-    proto::rng::Rng,
-    runtime::{self, VariableAttributes, VariableVendor},
+    CStr16, Status,
+    boot::{self, MemoryType},
+    cstr16, guid,
+    proto::{loaded_image::LoadedImage, rng::Rng},
+    runtime::{VariableAttributes, VariableVendor},
+    system,
 };
 
 use crate::{
@@ -28,9 +49,10 @@ use crate::{
     config::Config,
     system::{
         fs::{UefiFileSystem, get_partition_guid},
-        helper::{locate_protocol, str_to_cstr},
+        helper::{device_path_to_text, locate_protocol, str_to_cstr},
         time::Instant,
-        variable::{get_variable_str, set_variable, set_variable_str, set_variable_u16_slice},
+        tpm::get_active_pcr_banks,
+        variable::UefiVariableStorage,
     },
 };
 
@@ -46,9 +68,31 @@ const BLI_VENDOR: VariableVendor = VariableVendor(guid!("4a67b082-0a4c-41cf-b6c7
 const VOLATILE_ATTRS: VariableAttributes =
     VariableAttributes::BOOTSERVICE_ACCESS.union(VariableAttributes::RUNTIME_ACCESS);
 
+/// The attributes for a variable accessible at boot and runtime, which is persistent.
+const PERSISTENT_ATTRS: VariableAttributes = VOLATILE_ATTRS.union(VariableAttributes::NON_VOLATILE);
+
 /// The path of the random seed.
 const RANDOM_SEED_PATH: &CStr16 = cstr16!("\\loader\\random-seed");
 
+/// The GUID of the configuration table that passes a random seed to the Linux kernel.
+const LINUX_EFI_RANDOM_SEED_TABLE_GUID: uefi::Guid = guid!("1ce1e5bc-7ceb-42f2-81e5-8aadf180f57b");
+
+/// The size of the random seeds and the system token that are generated.
+const SEED_SIZE: usize = 32;
+
+/// The size of the seed for the kernel, as stored at the start of the random seed configuration table.
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "the seed size is a small constant that always fits in a u32"
+)]
+const SEED_TABLE_SIZE: u32 = SEED_SIZE as u32;
+
+/// The maximum size of a seed in the random seed configuration table that Linux accepts.
+const MAX_SEED_TABLE_SIZE: usize = 512;
+
+/// The random seed configuration table that was installed by the boot manager, if there is one.
+static SEED_TABLE: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
 bitflags! {
     /// Feature flags for Boot Loader Interface.
     ///
@@ -85,12 +129,70 @@ bitflags! {
     }
 }
 
+/// Information about the firmware and the loader that is exported at initialization.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct LoaderSystemInfo {
+    /// The time in microseconds at which the loader was initialized.
+    pub(crate) init_time: u128,
+
+    /// The unique partition GUID of the partition the loader was loaded from.
+    pub(crate) partition_guid: Option<String>,
+
+    /// The firmware vendor and revision, such as `EDK II 1.00`.
+    pub(crate) firmware_info: String,
+
+    /// The firmware type and the UEFI revision it implements, such as `UEFI 2.70`.
+    pub(crate) firmware_type: String,
+
+    /// The path of the loader image on its partition, such as `\EFI\BOOT\BOOTX64.EFI`.
+    pub(crate) image_identifier: Option<String>,
+
+    /// The bitmap of active TPM2 PCR banks, which is 0 if there is no TPM.
+    pub(crate) active_pcr_banks: u32,
+}
+
+impl LoaderSystemInfo {
+    /// Gather the [`LoaderSystemInfo`] from the system table and the loaded image of the loader.
+    pub(crate) fn new() -> Self {
+        let firmware_revision = system::firmware_revision();
+        let uefi_revision = system::uefi_revision();
+
+        let image_identifier = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())
+            .ok()
+            .and_then(|image| {
+                let path = device_path_to_text(image.file_path()?).ok()?;
+                Some(String::from(&*path))
+            });
+
+        Self {
+            init_time: Instant::zero().elapsed().as_micros(),
+            partition_guid: get_partition_guid(boot::image_handle()).map(|x| x.to_string()),
+            firmware_info: format!(
+                "{} {}.{:02}",
+                system::firmware_vendor(),
+                firmware_revision >> 16,
+                firmware_revision & 0xffff
+            ),
+            firmware_type: format!(
+                "UEFI {}.{:02}",
+                uefi_revision.major(),
+                uefi_revision.minor()
+            ),
+            image_identifier,
+            active_pcr_banks: get_active_pcr_banks().unwrap_or(0),
+        }
+    }
+}
+
 /// Export the variables at system initialization for Boot Loader Interface.
 ///
 /// # Errors
 ///
 /// May return an `Error` if the variable could not be set.
-pub(crate) fn export_variables() -> BootResult<()> {
+pub(crate) fn export_variables(
+    vars: &mut impl UefiVariableStorage,
+    info: &LoaderSystemInfo,
+) -> BootResult<()> {
     let supported = LoaderFeatures::TIMEOUT
         | LoaderFeatures::TIMEOUT_ONESHOT
         | LoaderFeatures::ENTRY_DEFAULT
@@ -102,39 +204,65 @@ pub(crate) fn export_variables() -> BootResult<()> {
         | LoaderFeatures::SAVED_ENTRY // bootmgr-rs sets the default entry through its own config file, though
         | LoaderFeatures::SORT_KEY
         | LoaderFeatures::DEVICETREE
+        | LoaderFeatures::SECUREBOOT_ENROLL
         | LoaderFeatures::RETAIN_SHIM
-        | LoaderFeatures::MENU_DISABLED; // this is frontend dependent, depending on how input events are handled.
+        | LoaderFeatures::MENU_DISABLED // this is frontend dependent, depending on how input events are handled.
+        | LoaderFeatures::TPM2_ACTIVE_PCR_BANKS;
 
     let mut buffer = itoa::Buffer::new();
 
-    let time = buffer.format(Instant::zero().elapsed().as_micros());
-    let partition_guid = get_partition_guid(boot::image_handle()).map(|x| x.to_string());
-    let info = concat!("bootmgr-rs ", env!("CARGO_PKG_VERSION"));
-    set_variable_str(
+    let loader_info = concat!("bootmgr-rs ", env!("CARGO_PKG_VERSION"));
+    vars.set_variable_str(
         cstr16!("LoaderTimeInitUSec"),
         Some(BLI_VENDOR),
         Some(VOLATILE_ATTRS),
-        Some(time),
+        Some(buffer.format(info.init_time)),
     )?;
-    set_variable(
+    vars.set_variable(
         cstr16!("LoaderFeatures"),
         Some(BLI_VENDOR),
         Some(VOLATILE_ATTRS),
         Some(supported.bits()),
     )?;
-    if let Some(partition_guid) = partition_guid {
-        set_variable_str(
+    if let Some(partition_guid) = &info.partition_guid {
+        vars.set_variable_str(
             cstr16!("LoaderDevicePartUUID"),
             Some(BLI_VENDOR),
             Some(VOLATILE_ATTRS),
-            Some(&partition_guid),
+            Some(partition_guid),
         )?;
     }
-    set_variable_str(
+    vars.set_variable_str(
         cstr16!("LoaderInfo"),
         Some(BLI_VENDOR),
         Some(VOLATILE_ATTRS),
-        Some(info),
+        Some(loader_info),
+    )?;
+    vars.set_variable_str(
+        cstr16!("LoaderFirmwareInfo"),
+        Some(BLI_VENDOR),
+        Some(VOLATILE_ATTRS),
+        Some(&info.firmware_info),
+    )?;
+    vars.set_variable_str(
+        cstr16!("LoaderFirmwareType"),
+        Some(BLI_VENDOR),
+        Some(VOLATILE_ATTRS),
+        Some(&info.firmware_type),
+    )?;
+    if let Some(image_identifier) = &info.image_identifier {
+        vars.set_variable_str(
+            cstr16!("LoaderImageIdentifier"),
+            Some(BLI_VENDOR),
+            Some(VOLATILE_ATTRS),
+            Some(image_identifier),
+        )?;
+    }
+    vars.set_variable_str(
+        cstr16!("LoaderTpm2ActivePcrBanks"),
+        Some(BLI_VENDOR),
+        Some(VOLATILE_ATTRS),
+        Some(&format!("{:#010x}", info.active_pcr_banks)),
     )?;
     Ok(())
 }
@@ -144,11 +272,11 @@ pub(crate) fn export_variables() -> BootResult<()> {
 /// # Errors
 ///
 /// May return an `Error` if the variable could not be set.
-pub(crate) fn record_exit_time() -> BootResult<()> {
+pub(crate) fn record_exit_time(vars: &mut impl UefiVariableStorage) -> BootResult<()> {
     let mut buffer = itoa::Buffer::new();
 
     let time = buffer.format(Instant::zero().elapsed().as_micros());
-    set_variable_str(
+    vars.set_variable_str(
         cstr16!("LoaderTimeExecUSec"),
         Some(BLI_VENDOR),
         Some(VOLATILE_ATTRS),
@@ -157,12 +285,67 @@ pub(crate) fn record_exit_time() -> BootResult<()> {
     Ok(())
 }
 
+/// Set the variables for the [`Config`] that is about to be booted, immediately before its image is executed.
+///
+/// This generates the random seed, sets the selected entry and the path of its boot counter, then records the exit
+/// time. Loaders that start a kernel by themselves never return to [`crate::boot::BootMgr::load`], so they must call
+/// this before the kernel is started.
+pub(crate) fn prepare_handoff(vars: &mut impl UefiVariableStorage, config: &Config) {
+    let _ = generate_random_seed(vars);
+    let _ = set_entry_selected(vars, config);
+    let _ = set_boot_count_path(vars, config);
+    let _ = record_exit_time(vars);
+}
+
+/// Set the path of the boot counter file of the [`Config`] that is about to be booted, if it has one.
+///
+/// This allows for `systemd-bless-boot` to find the boot counter file after it was renamed, so that it may mark
+/// the entry as good or bad.
+///
+/// # Errors
+///
+/// May return an `Error` if the variable could not be set.
+pub(crate) fn set_boot_count_path(
+    vars: &mut impl UefiVariableStorage,
+    config: &Config,
+) -> BootResult<()> {
+    if let Some(path) = &config.boot_counter_path {
+        vars.set_variable_str(
+            cstr16!("LoaderBootCountPath"),
+            Some(BLI_VENDOR),
+            Some(VOLATILE_ATTRS),
+            Some(path),
+        )?;
+    }
+    Ok(())
+}
+
+/// Set the entry identifier of the [`Config`] that is about to be booted.
+///
+/// # Errors
+///
+/// May return an `Error` if the variable could not be set.
+pub(crate) fn set_entry_selected(
+    vars: &mut impl UefiVariableStorage,
+    config: &Config,
+) -> BootResult<()> {
+    vars.set_variable_str(
+        cstr16!("LoaderEntrySelected"),
+        Some(BLI_VENDOR),
+        Some(VOLATILE_ATTRS),
+        Some(&config.filename),
+    )
+}
+
 /// Set the loader entries based off the filenames.
 ///
 /// # Errors
 ///
 /// May return an `Error` if the variable could not be set.
-pub(crate) fn set_loader_entries(configs: &[Config]) -> BootResult<()> {
+pub(crate) fn set_loader_entries(
+    vars: &mut impl UefiVariableStorage,
+    configs: &[Config],
+) -> BootResult<()> {
     let filenames: Vec<_> = configs
         .iter()
         .flat_map(|x: &Config| str_to_cstr(&x.filename))
@@ -172,7 +355,7 @@ pub(crate) fn set_loader_entries(configs: &[Config]) -> BootResult<()> {
         .map(|x| x.to_u16_slice_with_nul())
         .flat_map(|x| x.iter().copied())
         .collect();
-    set_variable_u16_slice(
+    vars.set_variable_u16_slice(
         cstr16!("LoaderEntries"),
         Some(BLI_VENDOR),
         Some(VOLATILE_ATTRS),
@@ -181,21 +364,28 @@ pub(crate) fn set_loader_entries(configs: &[Config]) -> BootResult<()> {
 }
 
 /// Check if the oneshot variable for the default entry exists.
-pub(crate) fn default_oneshot_exists() -> bool {
-    get_variable_str(cstr16!("LoaderEntryOneShot"), Some(BLI_VENDOR)).is_ok_and(|x| !x.is_empty())
+pub(crate) fn default_oneshot_exists(vars: &impl UefiVariableStorage) -> bool {
+    vars.get_variable_str(cstr16!("LoaderEntryOneShot"), Some(BLI_VENDOR))
+        .is_ok_and(|x| !x.is_empty())
 }
 
 /// Get the default entry based off the BLI identifier.
 ///
+/// If the oneshot entry exists, then it will be consumed and take priority over the default entry.
+///
 /// May return `None` if the variable does not exist.
-pub(crate) fn get_default_entry(configs: &[Config]) -> Option<usize> {
-    let default = get_variable_str(cstr16!("LoaderEntryDefault"), Some(BLI_VENDOR)).ok();
-    let oneshot = get_variable_str(cstr16!("LoaderEntryOneShot"), Some(BLI_VENDOR)).ok();
+pub(crate) fn get_default_entry(
+    vars: &mut impl UefiVariableStorage,
+    configs: &[Config],
+) -> Option<usize> {
+    let default = get_var_nonempty(vars, cstr16!("LoaderEntryDefault"));
+    let oneshot = get_var_nonempty(vars, cstr16!("LoaderEntryOneShot"));
 
     oneshot.map_or_else(
         || default.and_then(|default| configs.iter().position(|x| x.filename == default)),
         |oneshot| {
-            let _ = set_variable_str(cstr16!("LoaderEntryOneShot"), Some(BLI_VENDOR), None, None);
+            let _ =
+                vars.set_variable_str(cstr16!("LoaderEntryOneShot"), Some(BLI_VENDOR), None, None);
             configs.iter().position(|x| x.filename == oneshot)
         },
     )
@@ -203,14 +393,15 @@ pub(crate) fn get_default_entry(configs: &[Config]) -> Option<usize> {
 
 /// Set the default entry from Boot Loader Interface.
 ///
-/// This function is disabled when testing on host to avoid causing a panic while unit tests for `BootConfig`
-/// are being done.
-///
 /// # Errors
 ///
 /// May return an `Error` if the variable could not be set.
-pub(crate) fn set_default_entry(configs: &[Config], idx: usize) -> BootResult<()> {
-    set_variable_str(
+pub(crate) fn set_default_entry(
+    vars: &mut impl UefiVariableStorage,
+    configs: &[Config],
+    idx: usize,
+) -> BootResult<()> {
+    vars.set_variable_str(
         cstr16!("LoaderEntryDefault"),
         Some(BLI_VENDOR),
         None,
@@ -225,14 +416,14 @@ pub(crate) fn set_default_entry(configs: &[Config], idx: usize) -> BootResult<()
 ///
 /// May return `None` if the variable does not exist.
 #[allow(dead_code)]
-pub(crate) fn get_timeout_var() -> Option<i64> {
-    let timeout = get_variable_str(cstr16!("LoaderConfigTimeout"), Some(BLI_VENDOR)).ok();
-    let oneshot = get_variable_str(cstr16!("LoaderConfigTimeoutOneshot"), Some(BLI_VENDOR)).ok();
+pub(crate) fn get_timeout_var(vars: &mut impl UefiVariableStorage) -> Option<i64> {
+    let timeout = get_var_nonempty(vars, cstr16!("LoaderConfigTimeout"));
+    let oneshot = get_var_nonempty(vars, cstr16!("LoaderConfigTimeoutOneshot"));
 
     oneshot.map_or_else(
         || timeout.and_then(|timeout| match_timeout(&timeout)),
         |oneshot| {
-            let _ = set_variable_str(
+            let _ = vars.set_variable_str(
                 cstr16!("LoaderConfigTimeoutOneshot"),
                 Some(BLI_VENDOR),
                 None,
@@ -252,9 +443,9 @@ pub(crate) fn get_timeout_var() -> Option<i64> {
 ///
 /// May return an `Error` if the variable could not be set.
 #[allow(dead_code)]
-pub(crate) fn set_timeout_var(timeout: i64) -> BootResult<()> {
+pub(crate) fn set_timeout_var(vars: &mut impl UefiVariableStorage, timeout: i64) -> BootResult<()> {
     let mut buffer = itoa::Buffer::new();
-    set_variable_str(
+    vars.set_variable_str(
         cstr16!("LoaderConfigTimeout"),
         Some(BLI_VENDOR),
         None,
@@ -262,6 +453,52 @@ pub(crate) fn set_timeout_var(timeout: i64) -> BootResult<()> {
     )
 }
 
+/// Get the log level variable `LoaderConfigLogLevel`, if there is any.
+///
+/// This has `dead_code` allowed since in tests, this will produce a false warning since the UEFI-specific code using
+/// this function is not included.
+///
+/// May return `None` if the variable does not exist, or is not one of `off`, `error`, `warn`, `info`, `debug`, or
+/// `trace`.
+#[allow(dead_code)]
+pub(crate) fn get_log_level_var(vars: &impl UefiVariableStorage) -> Option<LevelFilter> {
+    get_var_nonempty(vars, cstr16!("LoaderConfigLogLevel")).and_then(|x| x.trim().parse().ok())
+}
+
+/// Get the console mode from the `LoaderConfigConsoleMode` variable.
+///
+/// May return `None` if the variable does not exist, or is not a mode number.
+fn get_console_mode_var(vars: &impl UefiVariableStorage) -> Option<usize> {
+    get_var_nonempty(vars, cstr16!("LoaderConfigConsoleMode")).and_then(|x| x.trim().parse().ok())
+}
+
+/// Switch the console to the mode in the `LoaderConfigConsoleMode` variable, if it is set.
+///
+/// # Errors
+///
+/// May return an `Error` if the console does not support the mode, or the mode could not be set.
+pub(crate) fn apply_console_mode(vars: &impl UefiVariableStorage) -> BootResult<()> {
+    let Some(index) = get_console_mode_var(vars) else {
+        return Ok(());
+    };
+
+    system::with_stdout(|stdout| {
+        let mode = stdout
+            .modes()
+            .find(|mode| mode.index() == index)
+            .ok_or(Status::UNSUPPORTED)?;
+        stdout.set_mode(mode)
+    })?;
+    Ok(())
+}
+
+/// Get a string variable in the Boot Loader Interface namespace, if it exists and is not empty.
+fn get_var_nonempty(vars: &impl UefiVariableStorage, name: &CStr16) -> Option<String> {
+    vars.get_variable_str(name, Some(BLI_VENDOR))
+        .ok()
+        .filter(|x| !x.is_empty())
+}
+
 /// Match a BLI timeout string into a `bootmgr-rs` compatible timeout value.
 fn match_timeout(timeout: &str) -> Option<i64> {
     if timeout == "menu-force" {
@@ -275,44 +512,429 @@ fn match_timeout(timeout: &str) -> Option<i64> {
 
 /// Generate a random seed given the system RNG, the on-disk seed, and the system token.
 ///
-/// This will generate a random seed as per the Boot Loader Interface, which list that the random seed should be hashed
-/// with the available source of entropy (the Rng protocol), the random seed, and the system token. Additionally, it will
-/// also hash in the available time (microseconds since boot). Both the random seed and system token must be present as per
-/// [systemd's usage of random seeds](https://systemd.io/RANDOM_SEEDS/), otherwise this function will return immediately.
+/// This follows [systemd's usage of random seeds](https://systemd.io/RANDOM_SEEDS/). The available sources of entropy
+/// (the Rng protocol, the on-disk seed along with the system token, any seed already passed to the kernel, and the
+/// time in microseconds since boot) are hashed together. From this hash, two separate values are derived. One
+/// replaces the on-disk seed, and the other is installed as a `LINUX_EFI_RANDOM_SEED_TABLE_GUID` configuration table
+/// for the kernel to use.
+///
+/// The on-disk seed is only used if the system token is present, as the on-disk seed may be shared with other
+/// installations cloned from the same image. If the system token is missing, then it will be created from the
+/// Rng protocol so that the on-disk seed can be used on future boots.
 ///
 /// # Errors
 ///
-/// May return an `Error` if the filesystem could not be opened, or the finalized seed could not be written back to.
-pub(crate) fn generate_random_seed() -> BootResult<()> {
+/// May return an `Error` if the filesystem could not be opened, the new seed could not be written back to the disk,
+/// or the configuration table could not be installed.
+pub(crate) fn generate_random_seed(vars: &mut impl UefiVariableStorage) -> BootResult<()> {
     let mut fs = UefiFileSystem::from_image_fs()?;
 
-    let mut hasher = sha2::Sha256::new();
+    let mut rng_buf = [0; SEED_SIZE];
+    let rng = get_rng(&mut rng_buf).then_some(&rng_buf[..]);
+
+    let token = get_system_token(vars, || {
+        let mut token = [0; SEED_SIZE];
+        get_rng(&mut token).then_some(token)
+    });
+
+    let disk_seed = token
+        .as_ref()
+        .and_then(|_| fs.read(RANDOM_SEED_PATH).ok())
+        .filter(|seed| !seed.is_empty());
+
+    if rng.is_none() && disk_seed.is_none() {
+        return Ok(()); // there is no source of entropy that is not predictable
+    }
+
+    let prev_seed = get_seed_table();
+    let time = Instant::zero().elapsed().as_micros().to_le_bytes();
 
-    if let Ok(content) = fs.read(RANDOM_SEED_PATH)
-        && let Ok((token, _)) =
-            runtime::get_variable_boxed(cstr16!("LoaderSystemToken"), &BLI_VENDOR)
+    let (new_disk_seed, kernel_seed) = derive_seeds(&[
+        rng.unwrap_or_default(),
+        token.as_deref().unwrap_or_default(),
+        disk_seed.as_deref().unwrap_or_default(),
+        prev_seed.as_deref().unwrap_or_default(),
+        &time,
+    ]);
+
+    // the disk seed must be updated before the kernel seed is handed off, so that the same seed is never used twice
+    if disk_seed.is_some() {
+        let _ = fs.delete(RANDOM_SEED_PATH);
+        fs.create(RANDOM_SEED_PATH)?;
+        fs.write(RANDOM_SEED_PATH, &new_disk_seed)?;
+    }
+
+    install_seed_table(&kernel_seed)
+}
+
+/// Get the system token, or create it if it does not exist and new content for the system token is provided.
+///
+/// The system token is persistent, and is supposed to be unique to each installation.
+fn get_system_token(
+    vars: &mut impl UefiVariableStorage,
+    new_token: impl FnOnce() -> Option<[u8; SEED_SIZE]>,
+) -> Option<Box<[u8]>> {
+    if let Ok(Some(token)) = vars.get_variable_bytes(cstr16!("LoaderSystemToken"), &BLI_VENDOR)
+        && !token.is_empty()
     {
-        hasher.update(&content);
-        hasher.update(&token);
-    } else {
-        return Ok(());
+        return Some(token);
     }
 
-    if let Ok(mut rng) = locate_protocol::<Rng>() {
-        let mut buf = [0; 64];
-        let _ = rng.get_rng(None, &mut buf);
+    let token = new_token()?;
+    vars.set_variable_bytes(
+        cstr16!("LoaderSystemToken"),
+        &BLI_VENDOR,
+        PERSISTENT_ATTRS,
+        &token,
+    )
+    .ok()?;
+    Some(Box::new(token))
+}
 
-        hasher.update(buf);
+/// Fill a buffer with random data from the Rng protocol.
+///
+/// Returns `false` if there is no Rng protocol, or it could not generate random data.
+fn get_rng(buf: &mut [u8]) -> bool {
+    locate_protocol::<Rng>().is_ok_and(|mut rng| rng.get_rng(None, buf).is_ok())
+}
+
+/// Hash every source of entropy together, then derive a seed for the disk and a seed for the kernel.
+///
+/// The length of each source is hashed in as well, so that the boundaries between the sources are unambiguous.
+fn derive_seeds(sources: &[&[u8]]) -> ([u8; SEED_SIZE], [u8; SEED_SIZE]) {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(b"bootmgr-rs random seed label v1");
+    for source in sources {
+        hasher.update((source.len() as u64).to_le_bytes());
+        hasher.update(source);
     }
+    let base = hasher.finalize();
 
-    // Add in the current time in there just for fun (and extra entropy)
-    hasher.update(Instant::zero().elapsed().as_micros().to_le_bytes());
+    let derive = |label: &[u8]| {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(base);
+        hasher.update(label);
+        hasher.finalize().into()
+    };
 
-    let result = hasher.finalize();
+    (derive(b"disk seed"), derive(b"kernel seed"))
+}
+
+/// Get the content of the random seed configuration table, if one was already installed.
+///
+/// The content is capped to the maximum size that Linux accepts.
+fn get_seed_table() -> Option<Vec<u8>> {
+    system::with_config_table(|tables| {
+        let table = tables
+            .iter()
+            .find(|table| table.guid == LINUX_EFI_RANDOM_SEED_TABLE_GUID)?;
+        if table.address.is_null() {
+            return None;
+        }
 
-    let _ = fs.delete(RANDOM_SEED_PATH);
-    fs.create(RANDOM_SEED_PATH)?;
-    fs.write(RANDOM_SEED_PATH, &result)?;
+        let ptr = table.address.cast::<u8>();
 
+        // SAFETY: the table starts with a u32 containing the size of the seed, which is then followed by the seed
+        let size = unsafe { ptr.cast::<u32>().read_unaligned() } as usize;
+
+        // SAFETY: the seed directly follows the size, and the size is capped to the largest size Linux accepts
+        let seed = unsafe {
+            core::slice::from_raw_parts(ptr.add(size_of::<u32>()), size.min(MAX_SEED_TABLE_SIZE))
+        };
+        Some(seed.to_vec())
+    })
+}
+
+/// Install a seed for the kernel as a `LINUX_EFI_RANDOM_SEED_TABLE_GUID` configuration table.
+///
+/// This replaces any table that was already installed, though the seed in that table should have been hashed into
+/// the new seed. If a table was already installed by the boot manager, such as when a boot option returned and
+/// another one was booted, then its memory is reused rather than allocating a new table.
+///
+/// # Errors
+///
+/// May return an `Error` if the memory for the table could not be allocated, or the table could not be installed.
+fn install_seed_table(seed: &[u8; SEED_SIZE]) -> BootResult<()> {
+    let size = size_of::<u32>() + SEED_SIZE;
+
+    let (ptr, allocated) = match NonNull::new(SEED_TABLE.load(Ordering::Relaxed)) {
+        Some(ptr) => (ptr, false),
+        None => (boot::allocate_pool(MemoryType::ACPI_RECLAIM, size)?, true),
+    };
+
+    // SAFETY: the memory was allocated with the given size, and is only ever used for the seed table
+    let table = unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr(), size) };
+    table[..size_of::<u32>()].copy_from_slice(&SEED_TABLE_SIZE.to_le_bytes());
+    table[size_of::<u32>()..].copy_from_slice(seed);
+
+    // SAFETY: the table is never freed after it is installed, and is only modified to replace the seed
+    if let Err(e) = unsafe {
+        boot::install_configuration_table(&LINUX_EFI_RANDOM_SEED_TABLE_GUID, ptr.as_ptr().cast())
+    } {
+        if allocated {
+            // SAFETY: the table was not installed, so there are no other references to it
+            let _ = unsafe { boot::free_pool(ptr) };
+        }
+        return Err(e.into());
+    }
+
+    SEED_TABLE.store(ptr.as_ptr(), Ordering::Relaxed);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+
+    use super::*;
+    use crate::{config::builder::ConfigBuilder, system::variable::MemoryUefiVariableStorage};
+
+    /// Get a string variable in the Boot Loader Interface namespace from the [`MemoryUefiVariableStorage`].
+    fn get_str(vars: &MemoryUefiVariableStorage, name: &CStr16) -> String {
+        vars.get_variable_str(name, Some(BLI_VENDOR))
+            .unwrap_or_default()
+    }
+
+    /// Create a list of [`Config`]s from entry identifiers.
+    fn configs(ids: &[&str]) -> Vec<Config> {
+        ids.iter()
+            .map(|id| ConfigBuilder::new(*id, ".conf").build())
+            .collect()
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_export_variables() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let info = LoaderSystemInfo {
+            init_time: 1234,
+            partition_guid: Some("23600d08-561e-4e68-a024-1d7d6e04ee4e".to_owned()),
+            firmware_info: "EDK II 1.00".to_owned(),
+            firmware_type: "UEFI 2.70".to_owned(),
+            image_identifier: Some("\\EFI\\BOOT\\BOOTX64.EFI".to_owned()),
+            active_pcr_banks: 0x6,
+        };
+
+        assert!(export_variables(&mut vars, &info).is_ok());
+
+        assert_eq!(get_str(&vars, cstr16!("LoaderTimeInitUSec")), "1234");
+        assert_eq!(
+            get_str(&vars, cstr16!("LoaderDevicePartUUID")),
+            "23600d08-561e-4e68-a024-1d7d6e04ee4e"
+        );
+        assert_eq!(get_str(&vars, cstr16!("LoaderFirmwareInfo")), "EDK II 1.00");
+        assert_eq!(get_str(&vars, cstr16!("LoaderFirmwareType")), "UEFI 2.70");
+        assert_eq!(
+            get_str(&vars, cstr16!("LoaderImageIdentifier")),
+            "\\EFI\\BOOT\\BOOTX64.EFI"
+        );
+        assert_eq!(
+            get_str(&vars, cstr16!("LoaderTpm2ActivePcrBanks")),
+            "0x00000006"
+        );
+        assert!(get_str(&vars, cstr16!("LoaderInfo")).starts_with("bootmgr-rs "));
+        assert!(
+            vars.get_variable::<u64>(cstr16!("LoaderFeatures"), Some(BLI_VENDOR))
+                .is_ok_and(|x| x & LoaderFeatures::BOOT_COUNTER.bits() != 0)
+        );
+        assert_eq!(
+            vars.attributes(cstr16!("LoaderFirmwareInfo"), &BLI_VENDOR),
+            Some(VOLATILE_ATTRS)
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_export_optional_variables() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let info = LoaderSystemInfo::default();
+
+        assert!(export_variables(&mut vars, &info).is_ok());
+
+        // the console mode is only set by the OS
+        assert_eq!(get_str(&vars, cstr16!("LoaderConfigConsoleMode")), "");
+
+        // optional variables that were not found are not exported
+        assert_eq!(get_str(&vars, cstr16!("LoaderImageIdentifier")), "");
+        assert_eq!(get_str(&vars, cstr16!("LoaderDevicePartUUID")), "");
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_entries() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let mut configs = configs(&["a.conf", "b.conf"]);
+        configs[1].boot_counter_path = Some("\\loader\\entries\\b+2-1.conf".to_owned());
+
+        assert!(set_loader_entries(&mut vars, &configs).is_ok());
+        let entries = vars
+            .get_variable_bytes(cstr16!("LoaderEntries"), &BLI_VENDOR)
+            .ok()
+            .flatten();
+        let expected: Vec<u8> = "a.conf\0b.conf\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(entries.as_deref(), Some(&*expected));
+
+        assert!(set_entry_selected(&mut vars, &configs[1]).is_ok());
+        assert!(set_boot_count_path(&mut vars, &configs[1]).is_ok());
+        assert_eq!(get_str(&vars, cstr16!("LoaderEntrySelected")), "b.conf");
+        assert_eq!(
+            get_str(&vars, cstr16!("LoaderBootCountPath")),
+            "\\loader\\entries\\b+2-1.conf"
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_default_entry() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let configs = configs(&["a.conf", "b.conf", "c.conf"]);
+
+        assert_eq!(get_default_entry(&mut vars, &configs), None);
+
+        assert!(set_default_entry(&mut vars, &configs, 1).is_ok());
+        assert!(!default_oneshot_exists(&vars));
+        assert_eq!(get_default_entry(&mut vars, &configs), Some(1));
+
+        assert!(
+            vars.set_variable_str(
+                cstr16!("LoaderEntryOneShot"),
+                Some(BLI_VENDOR),
+                None,
+                Some("c.conf")
+            )
+            .is_ok()
+        );
+        assert!(default_oneshot_exists(&vars));
+        assert_eq!(get_default_entry(&mut vars, &configs), Some(2));
+
+        // the oneshot entry is consumed after it is read once
+        assert!(!default_oneshot_exists(&vars));
+        assert_eq!(get_default_entry(&mut vars, &configs), Some(1));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_timeout() {
+        let mut vars = MemoryUefiVariableStorage::default();
+
+        assert_eq!(get_timeout_var(&mut vars), None);
+
+        assert!(set_timeout_var(&mut vars, 5).is_ok());
+        assert_eq!(get_timeout_var(&mut vars), Some(5));
+
+        for (oneshot, expected) in [("menu-force", -1), ("menu-hidden", 0), ("10", 10)] {
+            assert!(
+                vars.set_variable_str(
+                    cstr16!("LoaderConfigTimeoutOneshot"),
+                    Some(BLI_VENDOR),
+                    None,
+                    Some(oneshot)
+                )
+                .is_ok()
+            );
+            assert_eq!(get_timeout_var(&mut vars), Some(expected));
+            assert_eq!(get_timeout_var(&mut vars), Some(5));
+        }
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_log_level() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        assert_eq!(get_log_level_var(&vars), None);
+
+        for (value, expected) in [
+            ("info", Some(LevelFilter::Info)),
+            ("TRACE", Some(LevelFilter::Trace)),
+            ("verbose", None),
+        ] {
+            assert!(
+                vars.set_variable_str(
+                    cstr16!("LoaderConfigLogLevel"),
+                    Some(BLI_VENDOR),
+                    None,
+                    Some(value)
+                )
+                .is_ok()
+            );
+            assert_eq!(get_log_level_var(&vars), expected);
+        }
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_console_mode() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        assert_eq!(get_console_mode_var(&vars), None);
+
+        for (value, expected) in [("2", Some(2)), (" 0 ", Some(0)), ("max", None)] {
+            assert!(
+                vars.set_variable_str(
+                    cstr16!("LoaderConfigConsoleMode"),
+                    Some(BLI_VENDOR),
+                    None,
+                    Some(value)
+                )
+                .is_ok()
+            );
+            assert_eq!(get_console_mode_var(&vars), expected);
+        }
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_derive_seeds() {
+        let (disk, kernel) = derive_seeds(&[b"rng", b"token", b"seed"]);
+        assert_ne!(disk, kernel);
+        assert_eq!(derive_seeds(&[b"rng", b"token", b"seed"]), (disk, kernel));
+
+        // moving bytes between sources must change the seeds
+        assert_ne!(derive_seeds(&[b"rngt", b"oken", b"seed"]).0, disk);
+        assert_ne!(derive_seeds(&[b"rng", b"token", b"seee"]).1, kernel);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_system_token() {
+        let mut vars = MemoryUefiVariableStorage::default();
+
+        // without a source for the token, it cannot be created
+        assert_eq!(get_system_token(&mut vars, || None), None);
+        assert_eq!(
+            vars.attributes(cstr16!("LoaderSystemToken"), &BLI_VENDOR),
+            None
+        );
+
+        let token = get_system_token(&mut vars, || Some([1; SEED_SIZE]));
+        assert_eq!(token.as_deref(), Some(&[1; SEED_SIZE][..]));
+        assert_eq!(
+            vars.attributes(cstr16!("LoaderSystemToken"), &BLI_VENDOR),
+            Some(PERSISTENT_ATTRS)
+        );
+
+        // an existing token is never replaced
+        let token = get_system_token(&mut vars, || Some([2; SEED_SIZE]));
+        assert_eq!(token.as_deref(), Some(&[1; SEED_SIZE][..]));
+    }
+}
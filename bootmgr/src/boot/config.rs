@@ -27,6 +27,40 @@
 //! # Enable or disable PXE boot discovery
 //! pxe true
 //!
+//! # Search for XBOOTLDR partitions on every disk, not just the disk bootmgr-rs was loaded from
+//! xbootldr_any_disk false
+//!
+//! # Offer to enroll Secure Boot keys from \loader\keys in Setup Mode (off, manual, or force)
+//! secure_boot_enroll manual
+//!
+//! # Verify the Authenticode signatures of images against db, dbx, and \loader\certs without Shim or the firmware
+//! # (only if bootmgr-rs was built with the `authenticode` feature, and the firmware still verifies images while
+//! # Secure Boot is enabled)
+//! authenticode false
+//!
+//! # Require a password for the editor and for protected boot options (see the password module for the format)
+//! password pbkdf2-sha256$100000$<salt>$<hash>
+//!
+//! # Protect boot options by their filename, in addition to those protected in their own configuration
+//! protected windows.conf shellx64.efi
+//!
+//! # Boot Windows through its firmware boot option with `BootNext`, so that BitLocker does not ask for the recovery key
+//! reboot_for_bitlocker false
+//!
+//! # Append logs to \loader\bootmgr-rs.log, which is rotated to \loader\bootmgr-rs.log.old once it grows too large
+//! log_file false
+//!
+//! # Send logs to a syslog collector over UDP, with an optional port (otherwise the log server from DHCP is used).
+//! # If the network was not started for PXE boot, then it is started through DHCP, which is given up after a few seconds.
+//! syslog_server 10.0.2.2:514
+//!
+//! # Change the level of the messages that are logged (off, error, warn, info, debug, or trace), which is overridden by
+//! # the LoaderConfigLogLevel variable if it is set
+//! log_level info
+//!
+//! # Make fields read-only in the editor, in addition to those locked by Secure Boot
+//! locked_fields efi devicetree
+//!
 //! # Change the colors of the application
 //! bg magenta
 //! fg light_yellow
@@ -41,15 +75,23 @@
 //! Note that colors are stored as UEFI [`Color`]. Therefore, a frontend may need to convert
 //! from this color type.
 
-use alloc::{borrow::ToOwned, string::String};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{net::SocketAddr, str::FromStr};
 
+use log::LevelFilter;
 use uefi::{CStr16, Status, cstr16, proto::console::text::Color};
 
 use crate::{
     BootResult,
+    boot::password::PasswordHash,
     system::{
         fs::{FsError, UefiFileSystem},
         helper::normalize_path,
+        syslog::parse_collector,
     },
 };
 
@@ -57,6 +99,10 @@ use crate::{
 const CONFIG_PATH: &CStr16 = cstr16!("\\loader\\bootmgr-rs.conf");
 
 /// The configuration file for the bootloader.
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each bool is an independent setting parsed from the configuration file"
+)]
 pub struct BootConfig {
     /// The timeout for the bootloader before the default boot option is selected.
     pub timeout: i64,
@@ -73,9 +119,39 @@ pub struct BootConfig {
     /// Allows for the editor to be enabled, if there is one.
     pub editor: bool,
 
-    /// Allows for the basic PXE/TFTP loader to be enabled.
+    /// Allows for the basic PXE/TFTP loader to be enabled, including boot menus fetched from the TFTP server.
     pub pxe: bool,
 
+    /// Allows `XBOOTLDR` partitions to be found on any disk, instead of only the disk the bootloader is on.
+    pub xbootldr_any_disk: bool,
+
+    /// How Secure Boot keys in `\loader\keys` are enrolled when the firmware is in Setup Mode.
+    pub secure_boot_enroll: SecureBootEnroll,
+
+    /// Verify the Authenticode signatures of images in the boot manager, instead of delegating to Shim or the firmware.
+    pub authenticode: bool,
+
+    /// The password that is required for the editor and for protected boot options, if there is one.
+    pub password: Option<PasswordHash>,
+
+    /// The filenames of boot options that are protected by the password.
+    pub protected: Vec<String>,
+
+    /// Boot Windows entries by setting `BootNext` to their firmware boot option and rebooting, instead of loading them.
+    pub reboot_for_bitlocker: bool,
+
+    /// The fields that are always read-only in the editor.
+    pub locked_fields: Vec<String>,
+
+    /// Append logs to a log file on the same filesystem as the bootloader.
+    pub log_file: bool,
+
+    /// The syslog collector that logs are sent to, if there is one.
+    pub syslog_server: Option<SocketAddr>,
+
+    /// The level of the messages that are logged, overriding the level set by the frontend.
+    pub log_level: Option<LevelFilter>,
+
     /// Allows adjusting the background of the UI.
     pub bg: Color,
 
@@ -117,7 +193,9 @@ impl BootConfig {
         let slice = &content[0..bytes.unwrap_or(content.len()).min(content.len())];
 
         #[cfg(not(test))]
-        if let Some(timeout) = super::bli::get_timeout_var() {
+        if let Some(timeout) =
+            super::bli::get_timeout_var(&mut crate::system::variable::RuntimeUefiVariableStorage)
+        {
             config.timeout = timeout;
         }
 
@@ -132,6 +210,14 @@ impl BootConfig {
             }
         }
 
+        // the variable is applied after the file is parsed, so that it overrides the file
+        #[cfg(not(test))]
+        if let Some(level) =
+            super::bli::get_log_level_var(&crate::system::variable::RuntimeUefiVariableStorage)
+        {
+            config.log_level = Some(level);
+        }
+
         config
     }
 
@@ -145,7 +231,10 @@ impl BootConfig {
                         self.timeout = value;
 
                         #[cfg(not(test))]
-                        let _ = super::bli::set_timeout_var(value);
+                        let _ = super::bli::set_timeout_var(
+                            &mut crate::system::variable::RuntimeUefiVariableStorage,
+                            value,
+                        );
                     }
                 }
                 "default" => {
@@ -172,6 +261,57 @@ impl BootConfig {
                         self.pxe = value;
                     }
                 }
+                "xbootldr_any_disk" => {
+                    if let Ok(value) = value.parse() {
+                        self.xbootldr_any_disk = value;
+                    }
+                }
+                "secure_boot_enroll" => {
+                    if let Ok(value) = value.parse() {
+                        self.secure_boot_enroll = value;
+                    }
+                }
+                "authenticode" => {
+                    if let Ok(value) = value.parse() {
+                        self.authenticode = value;
+                    }
+                }
+                "password" => {
+                    self.password = Some(value.parse().unwrap_or_else(|()| {
+                        log::error!(
+                            "Could not parse the password hash, the boot manager will stay locked"
+                        );
+                        PasswordHash::locked()
+                    }));
+                }
+                "protected" => {
+                    self.protected
+                        .extend(value.split_whitespace().map(ToString::to_string));
+                }
+                "reboot_for_bitlocker" => {
+                    if let Ok(value) = value.parse() {
+                        self.reboot_for_bitlocker = value;
+                    }
+                }
+                "locked_fields" => {
+                    self.locked_fields
+                        .extend(value.split_whitespace().map(ToString::to_string));
+                }
+                "log_file" => {
+                    if let Ok(value) = value.parse() {
+                        self.log_file = value;
+                    }
+                }
+                "syslog_server" => {
+                    if let Some(value) = parse_collector(&value) {
+                        self.syslog_server = Some(value);
+                    }
+                }
+                "log_level" => {
+                    if let Ok(value) = value.parse() {
+                        self.log_level = Some(value);
+                    }
+                }
                 "background" => self.bg = match_str_color_bg(&value),
                 "foreground" => self.fg = match_str_color_fg(&value),
                 "highlight_background" => self.highlight_bg = match_str_color_bg(&value),
@@ -182,6 +322,34 @@ impl BootConfig {
     }
 }
 
+/// How Secure Boot keys are enrolled when the firmware is in Setup Mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SecureBootEnroll {
+    /// Keys are never enrolled.
+    Off,
+
+    /// Every key set is offered as a boot option, which enrolls the keys when selected.
+    #[default]
+    Manual,
+
+    /// The key set named `auto` is enrolled automatically after a countdown, which can be cancelled.
+    Force,
+}
+
+impl FromStr for SecureBootEnroll {
+    type Err = ();
+
+    /// Get a [`SecureBootEnroll`] from its string representation, which is one of `off`, `manual`, or `force`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(Self::Off),
+            "manual" => Ok(Self::Manual),
+            "force" => Ok(Self::Force),
+            _ => Err(()),
+        }
+    }
+}
+
 impl Default for BootConfig {
     fn default() -> Self {
         Self {
@@ -191,6 +359,16 @@ impl Default for BootConfig {
             driver_path: "\\EFI\\BOOT\\drivers".to_owned(),
             editor: false,
             pxe: false,
+            xbootldr_any_disk: false,
+            secure_boot_enroll: SecureBootEnroll::Manual,
+            authenticode: false,
+            password: None,
+            protected: Vec::new(),
+            reboot_for_bitlocker: false,
+            locked_fields: Vec::new(),
+            log_file: false,
+            syslog_server: None,
+            log_level: None,
             bg: Color::Black,
             fg: Color::White,
             highlight_bg: Color::LightGray,
@@ -255,6 +433,16 @@ mod tests {
             driver_path /efi/drivers
             editor true
             pxe false
+            xbootldr_any_disk true
+            secure_boot_enroll force
+            authenticode true
+            password pbkdf2-sha256$1000$73616c74$632c2812e46d4604102ba7618e9d6d7d2f8128f6266b4a03264d2a0460b7dcb3
+            protected windows.conf shellx64.efi
+            reboot_for_bitlocker true
+            locked_fields efi devicetree
+            log_file true
+            syslog_server 10.0.2.2
+            log_level debug
             background gray
             foreground white
             highlight_background black
@@ -267,10 +455,24 @@ mod tests {
         assert_eq!(config.driver_path, "\\efi\\drivers".to_owned());
         assert!(config.editor);
         assert!(!config.pxe);
+        assert!(config.xbootldr_any_disk);
+        assert_eq!(config.secure_boot_enroll, SecureBootEnroll::Force);
+        assert!(config.authenticode);
+        assert!(config.password.is_some_and(|x| x.verify("password")));
+        assert_eq!(config.protected, ["windows.conf", "shellx64.efi"]);
+        assert!(config.reboot_for_bitlocker);
+        assert_eq!(config.locked_fields, ["efi", "devicetree"]);
+        assert!(config.log_file);
+        assert_eq!(config.syslog_server, "10.0.2.2:514".parse().ok());
+        assert_eq!(config.log_level, Some(LevelFilter::Debug));
         assert!(matches!(config.bg, Color::LightGray));
         assert!(matches!(config.fg, Color::White));
         assert!(matches!(config.highlight_bg, Color::Black));
         assert!(matches!(config.highlight_fg, Color::White));
+
+        // a hash that could not be parsed keeps the boot manager locked
+        let config = BootConfig::get_boot_config(b"password pbkdf2-sha256$1000$invalid", None);
+        assert_eq!(config.password, Some(PasswordHash::locked()));
     }
 
     proptest! {
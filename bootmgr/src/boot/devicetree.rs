@@ -4,7 +4,8 @@
 //! Handles devicetree installations and fixups.
 //!
 //! This will install a `Devicetree` into the UEFI configuration table, and may optionally
-//! apply fixups if the firmware supports it via the [`DevicetreeFixup`] protocol.
+//! apply fixups if the firmware supports it via the [`DevicetreeFixup`] protocol. The devicetree blob is measured
+//! into the TPM before any fixups are applied.
 //!
 //! This is mostly based off of systemd-boot's implementation.
 //!
@@ -24,6 +25,7 @@
 
 use core::{ffi::c_void, ptr::NonNull};
 
+use log::warn;
 use thiserror::Error;
 use uefi::{boot::ScopedProtocol, guid, prelude::*};
 
@@ -34,6 +36,7 @@ use crate::{
         fs::UefiFileSystem,
         helper::{get_arch, locate_protocol, normalize_path, str_to_cstr},
         protos::DevicetreeFixup,
+        tpm::{TPM2_PCR_KERNEL_CONFIG, measure},
     },
 };
 
@@ -258,6 +261,10 @@ pub(super) fn install_devicetree(devicetree: &str, fs: &mut UefiFileSystem) -> B
         let path = str_to_cstr(&normalize_path(devicetree))?;
         let f = fs.read(&path)?;
 
+        if let Err(e) = measure(TPM2_PCR_KERNEL_CONFIG, &f, devicetree) {
+            warn!("Failed to measure devicetree {devicetree}: {e}");
+        }
+
         let mut devicetree = DevicetreeGuard::new(&f, None)?;
 
         fixup_devicetree(&mut devicetree)?;
@@ -10,10 +10,20 @@ use alloc::string::String;
 
 use thiserror::Error;
 use uefi::Handle;
+use uefi_raw::protocol::network::http::HttpStatusCode;
 
 use crate::{BootResult, config::Config};
 
+#[cfg(target_arch = "x86_64")]
+mod direct;
 pub mod efi;
+pub mod http;
+mod initrd;
+#[cfg(target_arch = "x86_64")]
+pub mod linux;
+#[cfg(target_arch = "x86_64")]
+pub mod multiboot;
+pub mod progress;
 pub mod tftp;
 
 /// An `Error` that may result from loading an image.
@@ -27,18 +37,44 @@ pub enum LoadError {
     #[error("Config \"{0}\" attempted to boot without an EFI executable")]
     ConfigMissingEfi(String),
 
+    /// A [`Config`] that is booted through TFTP did not have the address of a server.
+    #[error("Config \"{0}\" attempted to boot through TFTP without a server address")]
+    ConfigMissingServer(String),
+
     /// Failed to parse a string as an IP address.
     #[error("Failed to parse as IP address: {0}")]
     IpParse(#[from] core::net::AddrParseError),
 
+    /// A [`Config`] used a boot action that is not supported on this architecture.
+    #[error(
+        "Config \"{0}\" attempted to boot with an action that is not supported on this architecture"
+    )]
+    UnsupportedAction(String),
+
     /// The HTTP response did not have a valid content-length header.
     #[error("Nonexistent or invalid content length header found in address \"{0}\"")]
     InvalidContentLen(String),
+
+    /// The HTTP server responded with a status other than 200 OK.
+    #[error("HTTP request to \"{0}\" failed with status {1:?}")]
+    HttpStatus(String, HttpStatusCode),
+
+    /// The HTTP response ended before its content length was reached, or exceeded it.
+    #[error("Downloaded {1} bytes from \"{0}\", but the content length was {2} bytes")]
+    IncompleteDownload(String, usize, usize),
+
+    /// The HTTP response or the TFTP file had a size that is too large to be downloaded into memory.
+    #[error("Content length of {1} bytes in address \"{0}\" is too large")]
+    ContentLenTooLarge(String, usize),
+
+    /// There is no network interface that supports the protocol required by the boot option.
+    #[error("No network interface supports the required protocol")]
+    NoNetworkInterface,
 }
 
 /// Loads a boot option given a [`Config`].
 ///
-/// It simply delegates to `BootAction::run`.
+/// It simply delegates to `BootAction::run`, after removing any initrd that was installed for a previous boot option.
 ///
 /// # Errors
 ///
@@ -74,6 +110,7 @@ pub enum LoadError {
 /// boot::start_image(image).expect("Failed to start image");
 /// ```
 pub fn load_boot_option(config: &Config) -> BootResult<Handle> {
+    initrd::uninstall_initrd(); // an initrd installed for a previous boot option must not be given to this one
     config.action.run(config)
 }
 
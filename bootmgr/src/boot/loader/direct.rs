@@ -0,0 +1,181 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Helpers for the boot loaders that boot kernels directly, rather than through `LoadImage`.
+//!
+//! # Safety
+//!
+//! This module uses unsafe in 2 places.
+//!
+//! 1. The "size" passed to `from_raw_parts_mut` is always within the pages that were just allocated. The pages are
+//!    never freed, as they are handed over to the kernel, so the slice may be `'static`.
+//! 2. The [`GraphicsOutput`] protocol is opened with [`OpenProtocolAttributes::GetProtocol`], so that the frontend
+//!    may keep its exclusive handle to it. It is only read, and it is dropped before the kernel is started.
+
+use alloc::vec::Vec;
+
+use log::warn;
+use uefi::{
+    boot::{
+        self, AllocateType, MemoryType, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol,
+    },
+    proto::{
+        console::gop::{GraphicsOutput, PixelFormat},
+        tcg::PcrIndex,
+    },
+};
+
+use crate::{
+    BootResult,
+    boot::secure_boot::{self, hash_pin::Sha256Hash},
+    system::{
+        fs::UefiFileSystem,
+        helper::{normalize_path, str_to_cstr},
+        tpm::measure,
+    },
+};
+
+/// The size of a page.
+pub(super) const PAGE_SIZE: usize = boot::PAGE_SIZE;
+
+/// The framebuffer of the [`GraphicsOutput`] protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Framebuffer {
+    /// The width of the framebuffer in pixels.
+    pub width: u32,
+
+    /// The height of the framebuffer in pixels.
+    pub height: u32,
+
+    /// The bytes per line of the framebuffer.
+    pub pitch: u32,
+
+    /// The address of the framebuffer.
+    pub base: u64,
+
+    /// The size of the framebuffer in bytes.
+    pub size: u64,
+
+    /// The red, green, blue and reserved masks of a pixel.
+    pub masks: [u32; 4],
+}
+
+/// Read a file that is booted directly, and check and measure it.
+///
+/// As the file is not loaded through `LoadImage`, it is checked with [`secure_boot::check_unverified_image`] instead
+/// of by the firmware, which requires it to be verified by Shim or trusted by `db` while Secure Boot is enabled. It is
+/// then measured into `pcr`.
+///
+/// # Errors
+///
+/// May return an `Error` if the file could not be read, or it was refused.
+pub(super) fn read_verified(
+    fs: &mut UefiFileSystem,
+    path: &str,
+    pin: Option<Sha256Hash>,
+    pcr: PcrIndex,
+) -> BootResult<Vec<u8>> {
+    let content = fs.read(&str_to_cstr(&normalize_path(path))?)?;
+    secure_boot::check_unverified_image(&content, pin)?;
+
+    if let Err(e) = measure(pcr, &content, path) {
+        warn!("Failed to measure {path}: {e}");
+    }
+
+    Ok(content)
+}
+
+/// Allocate memory for the kernel that is never freed.
+///
+/// The memory is allocated as `LOADER_DATA` pages, and the returned slice is aligned to `alignment`, which must be a
+/// power of two that is at least [`PAGE_SIZE`].
+///
+/// # Errors
+///
+/// May return an `Error` if the pages could not be allocated.
+pub(super) fn allocate(
+    size: usize,
+    ty: AllocateType,
+    alignment: usize,
+) -> BootResult<&'static mut [u8]> {
+    let pages = (size + alignment - PAGE_SIZE).div_ceil(PAGE_SIZE).max(1);
+    let ptr = boot::allocate_pages(ty, MemoryType::LOADER_DATA, pages)?;
+    let offset = ptr.as_ptr().align_offset(alignment);
+
+    // SAFETY: the pages were just allocated with enough space for `offset + size` bytes, and are never freed
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr().add(offset), size) })
+}
+
+/// Get the address of a memory region.
+pub(super) fn address(region: &[u8]) -> u64 {
+    region.as_ptr().addr() as u64
+}
+
+/// Get the [`Framebuffer`] of the current mode of the [`GraphicsOutput`] protocol.
+///
+/// Returns `None` if there is no [`GraphicsOutput`], or it does not have a framebuffer.
+pub(super) fn get_framebuffer() -> Option<Framebuffer> {
+    let mut gop = open_gop()?;
+    let info = gop.current_mode_info();
+
+    let masks = match info.pixel_format() {
+        PixelFormat::Rgb => [0xFF, 0xFF00, 0x00FF_0000, 0xFF00_0000],
+        PixelFormat::Bgr => [0x00FF_0000, 0xFF00, 0xFF, 0xFF00_0000],
+        PixelFormat::Bitmask => {
+            let mask = info.pixel_bitmask()?;
+            [mask.red, mask.green, mask.blue, mask.reserved]
+        }
+        PixelFormat::BltOnly => return None,
+    };
+
+    let (width, height) = info.resolution();
+    let mut fb = gop.frame_buffer();
+
+    Some(Framebuffer {
+        width: u32::try_from(width).ok()?,
+        height: u32::try_from(height).ok()?,
+        pitch: u32::try_from(info.stride() * 4).ok()?,
+        base: fb.as_mut_ptr().addr() as u64,
+        size: fb.size() as u64,
+        masks,
+    })
+}
+
+/// Open the [`GraphicsOutput`] protocol without requiring exclusive access.
+fn open_gop() -> Option<ScopedProtocol<GraphicsOutput>> {
+    let handle = boot::get_handle_for_protocol::<GraphicsOutput>().ok()?;
+
+    // SAFETY: the protocol is only read from, and is dropped before the kernel is started
+    unsafe {
+        boot::open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()
+}
+
+/// Read a little endian [`u16`] at an offset.
+pub(super) fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian [`u32`] at an offset.
+pub(super) fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian [`u64`] at an offset.
+pub(super) fn read_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        buf.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
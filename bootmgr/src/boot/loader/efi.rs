@@ -3,7 +3,11 @@
 
 //! The boot loader for EFI executables
 //!
-//! This will also handle devicetree installs and Shim authentication if either are available.
+//! This will also handle devicetree installs and Shim authentication if either are available. The command line and
+//! any initrds specified in it are measured into the TPM, if there is one. The initrds that were measured are given to
+//! the image through the initrd media device path, so that the image loads the same content that was measured. This is
+//! only done for BLS entries, as only the `initrd` keys of their Linux kernels are turned into `initrd=` options. Other
+//! images, such as UKIs, bring their own initrd.
 //!
 //! # Safety
 //!
@@ -15,6 +19,9 @@
 
 use core::cell::RefCell;
 
+use alloc::{string::ToString, vec::Vec};
+
+use log::warn;
 use uefi::{
     CStr16, CString16, Handle,
     boot::{self, ScopedProtocol},
@@ -25,13 +32,14 @@ use crate::{
     BootResult,
     boot::{
         devicetree::install_devicetree,
-        loader::{LoadError, get_efi},
-        secure_boot::shim::shim_load_image,
+        loader::{LoadError, get_efi, initrd::install_initrd},
+        secure_boot::{self, hash_pin::Sha256Hash},
     },
-    config::Config,
+    config::{Config, parsers::Parsers},
     system::{
         fs::UefiFileSystem,
-        helper::{join_to_device_path, str_to_cstr},
+        helper::{join_to_device_path, normalize_path, str_to_cstr},
+        tpm::{TPM2_PCR_INITRD, TPM2_PCR_KERNEL_CONFIG, measure},
     },
 };
 
@@ -40,7 +48,7 @@ use crate::{
 /// passed into [`LoadOptions::set_load_options`].
 ///
 /// A [`RefCell`] is used here as `LoadOptions` may need to be modified more than once.
-/// This is the case if the `shim_load_image` function fails.
+/// This is the case if the `load_image` function fails.
 static LOAD_OPTIONS: LoadOptions = LoadOptions::new();
 
 /// Storage struct for a [`CString16`] with load options.
@@ -104,17 +112,21 @@ pub(crate) fn load_boot_option(config: &Config) -> BootResult<Handle> {
 
     let s = str_to_cstr(file)?;
 
-    let handle = load_image_from_path(handle, &s)?;
+    let handle = load_image_from_path(handle, &s, config.sha256)?;
 
     setup_image(&mut fs, handle, config)
 }
 
-/// Load an image given a [`Handle`] and a path.
+/// Load an image given a [`Handle`], a path, and optionally the SHA-256 hash that the image must have.
 ///
 /// # Errors
 ///
 /// May return an `Error` if the handle does not support [`DevicePath`], or the image could not be loaded.
-fn load_image_from_path(handle: Handle, path: &CStr16) -> BootResult<Handle> {
+fn load_image_from_path(
+    handle: Handle,
+    path: &CStr16,
+    pin: Option<Sha256Hash>,
+) -> BootResult<Handle> {
     let dev_path = boot::open_protocol_exclusive::<DevicePath>(handle)?;
     let mut buf = [0; 2048]; // it should be rare for a devicepath to exceed 2048 bytes
     let path = join_to_device_path(&dev_path, path, &mut buf)?;
@@ -123,15 +135,16 @@ fn load_image_from_path(handle: Handle, path: &CStr16) -> BootResult<Handle> {
         device_path: &path,
         boot_policy: uefi::proto::BootPolicy::BootSelection,
     };
-    shim_load_image(boot::image_handle(), src) // this will either load with shim validation, or just load the image
+    secure_boot::load_image(boot::image_handle(), src, pin) // this will either load with hash pinning, Authenticode or shim validation, or just load the image
 }
 
-/// Sets up the image for boot with load options and optionally loading a devicetree.
+/// Sets up the image for boot with load options and optionally loading a devicetree and initrds.
 ///
 /// # Errors
 ///
 /// May return an `Error` if the image does not support [`LoadedImage`], or, if a devicetree
-/// is present, the devicetree could not be installed.
+/// is present, the devicetree could not be installed, or an initrd in the load options of a BLS entry could not be
+/// read.
 fn setup_image(fs: &mut UefiFileSystem, handle: Handle, config: &Config) -> BootResult<Handle> {
     if let Some(devicetree) = &config.devicetree_path {
         install_devicetree(devicetree, fs)?;
@@ -140,12 +153,93 @@ fn setup_image(fs: &mut UefiFileSystem, handle: Handle, config: &Config) -> Boot
     let mut image = boot::open_protocol_exclusive::<LoadedImage>(handle)?;
 
     if let Some(options) = config.options.as_deref() {
-        let load_options = &LOAD_OPTIONS;
+        set_load_options(&mut image, options)?;
+        if matches!(config.origin, Some(Parsers::Bls)) {
+            install_initrds(fs, options)?;
+        }
+    }
+
+    Ok(handle)
+}
+
+/// Set the load options of an image to a command line, and measure the command line into the TPM.
+///
+/// # Errors
+///
+/// May return an `Error` if the command line is not a valid UCS-2 string.
+pub(super) fn set_load_options(
+    image: &mut ScopedProtocol<LoadedImage>,
+    options: &str,
+) -> BootResult<()> {
+    let load_options = &LOAD_OPTIONS;
+
+    let cmdline = str_to_cstr(options)?;
+    load_options.set(&cmdline);
+
+    load_options.set_load_options(image);
+
+    measure_cmdline(&cmdline);
+    Ok(())
+}
 
-        load_options.set(&str_to_cstr(options)?);
+/// Measure the command line into the TPM, as it is passed to the image.
+pub(super) fn measure_cmdline(cmdline: &CStr16) {
+    let bytes = bytemuck::must_cast_slice(cmdline.to_u16_slice_with_nul());
+    if let Err(e) = measure(TPM2_PCR_KERNEL_CONFIG, bytes, &cmdline.to_string()) {
+        warn!("Failed to measure command line: {e}");
+    }
+}
+
+/// Read every initrd specified in the command line, measure them into the TPM, then give them to the image through
+/// the initrd media device path.
+///
+/// Since the EFI stub of Linux prefers the initrd media device path over the `initrd=` options, it loads the same
+/// content that was measured rather than reading the initrds again. Kernels older than Linux 5.8 do not support the
+/// initrd media device path, so the `initrd=` options are kept in the command line for them.
+///
+/// # Errors
+///
+/// May return an `Error` if an initrd could not be read, or the initrd could not be installed.
+fn install_initrds(fs: &mut UefiFileSystem, options: &str) -> BootResult<()> {
+    let mut initrd = Vec::new();
+    for path in get_initrds(options) {
+        let content = fs.read(&str_to_cstr(&normalize_path(path))?)?;
+        if let Err(e) = measure(TPM2_PCR_INITRD, &content, path) {
+            warn!("Failed to measure initrd {path}: {e}");
+        }
+        initrd.extend(content);
+    }
 
-        load_options.set_load_options(&mut image);
+    if !initrd.is_empty() {
+        install_initrd(initrd)?;
     }
+    Ok(())
+}
 
-    Ok(handle)
+/// Get the paths of every initrd specified in the command line.
+pub(super) fn get_initrds(options: &str) -> impl Iterator<Item = &str> {
+    options
+        .split_ascii_whitespace()
+        .filter_map(|option| option.strip_prefix("initrd="))
+        .filter(|initrd| !initrd.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_get_initrds() {
+        let options = "root=/dev/sda1 initrd=/intel-ucode.img quiet initrd=\\initramfs.img initrd=";
+        assert_eq!(
+            get_initrds(options).collect::<Vec<_>>(),
+            ["/intel-ucode.img", "\\initramfs.img"]
+        );
+        assert_eq!(get_initrds("quiet splash").count(), 0);
+    }
 }
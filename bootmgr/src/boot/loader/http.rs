@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The boot loader for EFI executables downloaded over HTTP or HTTPS
+//!
+//! It downloads a file through the [`Http`](uefi::proto::network::http::Http) protocol, which is provided by
+//! `HttpDxe` in most firmware, then uses that file buffer as an EFI executable. The URL of the file is stored in the
+//! filename of the [`Config`]. HTTPS is only supported if the firmware supports TLS.
+//!
+//! The network interface is configured through DHCP if it does not have an address yet. The loaded image is given a
+//! device path that ends with a URI node, so that it may find out where it was downloaded from. The progress of the
+//! download is reported through [`super::progress`].
+
+use alloc::{string::String, vec::Vec};
+
+use uefi::{
+    Handle,
+    boot::{self, SearchType},
+    proto::{
+        device_path::DevicePath,
+        network::{
+            http::{HttpBinding, HttpHelper},
+            ip4config2::Ip4Config2,
+        },
+    },
+};
+use uefi_raw::protocol::network::http::HttpStatusCode;
+
+use crate::{
+    BootResult,
+    boot::{
+        loader::{LoadError, progress::Progress},
+        secure_boot,
+    },
+    config::Config,
+    system::{
+        fs::ONE_GIGABYTE,
+        helper::{join_uri_to_device_path, str_to_cstr},
+    },
+};
+
+/// Loads a boot option from a given [`Config`] through HTTP.
+///
+/// # Errors
+///
+/// May return an `Error` if there is no network interface that supports HTTP, the interface could not be configured,
+/// the URL is invalid, the server did not respond with the file and a valid content length, or
+/// [`boot::load_image`] fails.
+pub(crate) fn load_boot_option(config: &Config) -> BootResult<Handle> {
+    let url = &config.filename;
+    str_to_cstr(url)?; // the URL must be a valid UCS-2 string
+
+    let nic = *boot::locate_handle_buffer(SearchType::from_proto::<HttpBinding>())?
+        .first()
+        .ok_or(LoadError::NoNetworkInterface)?;
+
+    Ip4Config2::new(nic)?.ifup(false)?;
+
+    let body = download(nic, url)?;
+
+    let dev_path = boot::open_protocol_exclusive::<DevicePath>(nic)?;
+    let mut buf = [0; 512];
+    let file_path = join_uri_to_device_path(&dev_path, url, &mut buf)?;
+
+    let src = boot::LoadImageSource::FromBuffer {
+        buffer: &body,
+        file_path: Some(&file_path),
+    };
+    secure_boot::load_image(boot::image_handle(), src, config.sha256)
+}
+
+/// Download a file from a URL through a network interface.
+///
+/// # Errors
+///
+/// May return an `Error` if the request failed, the server responded with an error, or the response did not have a
+/// valid content length, the content length is larger than one gigabyte or could not be allocated, or the body did
+/// not match the content length.
+fn download(nic: Handle, url: &str) -> BootResult<Vec<u8>> {
+    let mut http = HttpHelper::new(nic)?;
+    http.configure()?;
+    http.request_get(url)?;
+
+    let response = http.response_first(true)?;
+    if response.status != HttpStatusCode::STATUS_200_OK {
+        return Err(LoadError::HttpStatus(url.into(), response.status).into());
+    }
+
+    let len = get_content_len(&response.headers)
+        .ok_or_else(|| LoadError::InvalidContentLen(url.into()))?;
+
+    let mut body = Vec::new();
+    if len > ONE_GIGABYTE || body.try_reserve_exact(len).is_err() {
+        return Err(LoadError::ContentLenTooLarge(url.into(), len).into());
+    }
+    body.extend(response.body);
+    let mut progress = Progress::new(url, Some(len));
+
+    while body.len() < len {
+        let chunk = http.response_more()?;
+        if chunk.is_empty() {
+            break;
+        }
+        body.extend(chunk);
+        progress.update(body.len());
+    }
+
+    if body.len() != len {
+        return Err(LoadError::IncompleteDownload(url.into(), body.len(), len).into());
+    }
+    progress.finish(len);
+
+    Ok(body)
+}
+
+/// Get the content length from the headers of a response.
+///
+/// The names of the headers must be lowercase. Returns `None` if there is no content length, or it is not a valid
+/// number.
+fn get_content_len(headers: &[(String, String)]) -> Option<usize> {
+    headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::ToOwned, vec};
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_content_len() {
+        let headers = vec![
+            ("content-type".to_owned(), "application/efi".to_owned()),
+            ("content-length".to_owned(), " 1234 ".to_owned()),
+        ];
+        assert_eq!(get_content_len(&headers), Some(1234));
+
+        let headers = vec![("content-length".to_owned(), "-1".to_owned())];
+        assert_eq!(get_content_len(&headers), None);
+        assert_eq!(get_content_len(&[]), None);
+    }
+}
@@ -0,0 +1,249 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Provides initrds to Linux kernels through the `LoadFile2` protocol
+//!
+//! Since Linux 5.8, the EFI stub first looks for a `LoadFile2` protocol on a device path that consists of only a vendor
+//! media node with `LINUX_EFI_INITRD_MEDIA_GUID`, and loads its initrd from it. This allows initrds to be given to
+//! images that were not loaded from a filesystem, such as images that were downloaded over TFTP, where the `initrd=`
+//! options of the command line cannot be opened by the EFI stub. It also ensures that the initrds that were measured
+//! into the TPM are the same initrds that the EFI stub loads.
+//!
+//! Only one initrd may be installed at a time. Multiple initrds are concatenated into one, which the kernel supports.
+//!
+//! # Safety
+//!
+//! This uses unsafe in three places.
+//!
+//! 1. [`boot::install_protocol_interface`] requires that the interface lasts as long as it is installed. Both the
+//!    device path and the `LoadFile2` interface are statics, so they last for the lifetime of the program.
+//! 2. [`boot::uninstall_protocol_interface`] requires that the interface was installed on the handle. The handle is
+//!    only stored once both interfaces were installed on it.
+//! 3. The `LoadFile2` callback dereferences the size pointer and writes to the buffer given by the caller. The caller
+//!    must give a valid size pointer, and a buffer of at least that size if it is not null, which is checked as much as
+//!    possible before the buffer is written to.
+
+use alloc::vec::Vec;
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    ptr,
+};
+
+use log::warn;
+use uefi::{Guid, Handle, Status, boot, guid};
+use uefi_raw::{
+    Boolean,
+    protocol::{device_path::DevicePathProtocol, media::LoadFile2Protocol},
+};
+
+use crate::BootResult;
+
+/// The vendor GUID of the device path that the EFI stub of Linux loads its initrd from.
+const LINUX_EFI_INITRD_MEDIA_GUID: Guid = guid!("5568e427-68fc-4f3d-ac74-ca555231cc68");
+
+/// The size of the vendor media node, including its header.
+const VENDOR_NODE_SIZE: u8 = 20;
+
+/// The device path of the initrd, which is a vendor media node followed by an end node.
+static INITRD_DEVICE_PATH: [u8; 24] = initrd_device_path();
+
+/// The `LoadFile2` interface that provides the initrd.
+static LOAD_FILE2: LoadFile2Protocol = LoadFile2Protocol { load_file };
+
+/// The initrd that is currently installed, which must last as long as it is installed.
+static INITRD: Initrd = Initrd::new();
+
+/// Storage struct for the content of the initrd and the handle it is installed on.
+struct Initrd {
+    /// The content of the initrd.
+    data: RefCell<Vec<u8>>,
+
+    /// The handle that the initrd is installed on, if it is installed.
+    handle: Cell<Option<Handle>>,
+}
+
+impl Initrd {
+    /// Get a new instance of [`Initrd`].
+    const fn new() -> Self {
+        Self {
+            data: RefCell::new(Vec::new()),
+            handle: Cell::new(None),
+        }
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for Initrd {}
+
+/// Install an initrd, so that the next Linux kernel that is started loads it.
+///
+/// If an initrd is already installed, then its content is replaced.
+///
+/// # Errors
+///
+/// May return an `Error` if the protocols could not be installed.
+pub(crate) fn install_initrd(data: Vec<u8>) -> BootResult<()> {
+    *INITRD.data.borrow_mut() = data;
+
+    if INITRD.handle.get().is_some() {
+        return Ok(());
+    }
+
+    let dev_path = INITRD_DEVICE_PATH.as_ptr().cast::<c_void>();
+    let load_file = ptr::from_ref(&LOAD_FILE2).cast::<c_void>();
+
+    // SAFETY: both interfaces are statics, so they last as long as they are installed
+    let handle = unsafe {
+        let handle = boot::install_protocol_interface(None, &DevicePathProtocol::GUID, dev_path)?;
+        if let Err(e) =
+            boot::install_protocol_interface(Some(handle), &LoadFile2Protocol::GUID, load_file)
+        {
+            let _ = boot::uninstall_protocol_interface(handle, &DevicePathProtocol::GUID, dev_path);
+            return Err(e.into());
+        }
+        handle
+    };
+
+    INITRD.handle.set(Some(handle));
+    Ok(())
+}
+
+/// Uninstall the initrd if one is installed, so that it is not given to another image.
+pub(crate) fn uninstall_initrd() {
+    INITRD.data.borrow_mut().clear();
+
+    let Some(handle) = INITRD.handle.take() else {
+        return;
+    };
+
+    let dev_path = INITRD_DEVICE_PATH.as_ptr().cast::<c_void>();
+    let load_file = ptr::from_ref(&LOAD_FILE2).cast::<c_void>();
+
+    // SAFETY: the handle is only stored once both interfaces were installed on it
+    let result = unsafe {
+        boot::uninstall_protocol_interface(handle, &LoadFile2Protocol::GUID, load_file).and_then(
+            |()| boot::uninstall_protocol_interface(handle, &DevicePathProtocol::GUID, dev_path),
+        )
+    };
+
+    if let Err(e) = result {
+        warn!("Failed to uninstall initrd: {e}");
+    }
+}
+
+/// Build the device path of the initrd.
+const fn initrd_device_path() -> [u8; 24] {
+    let guid = LINUX_EFI_INITRD_MEDIA_GUID.to_bytes();
+    let mut path = [0; 24];
+
+    // a vendor media node
+    path[0] = 4;
+    path[1] = 3;
+    path[2] = VENDOR_NODE_SIZE;
+
+    let mut i = 0;
+    while i < guid.len() {
+        path[4 + i] = guid[i];
+        i += 1;
+    }
+
+    // the end of the device path
+    path[20] = 0x7F;
+    path[21] = 0xFF;
+    path[22] = 4;
+    path
+}
+
+/// Copy the initrd into a buffer, or get the size of the buffer that is required if it is too small.
+fn copy_initrd(buffer: Option<&mut [u8]>, size: &mut usize) -> Status {
+    let data = INITRD.data.borrow();
+    if data.is_empty() {
+        return Status::NOT_FOUND;
+    }
+
+    match buffer {
+        Some(buffer) if buffer.len() >= data.len() => {
+            buffer[..data.len()].copy_from_slice(&data);
+            *size = data.len();
+            Status::SUCCESS
+        }
+        _ => {
+            *size = data.len();
+            Status::BUFFER_TOO_SMALL
+        }
+    }
+}
+
+/// The `LoadFile2` callback, which copies the initrd into the buffer given by the caller.
+///
+/// # Safety
+///
+/// The size pointer must be valid if it is not null, and the buffer must be at least that size if it is not null.
+unsafe extern "efiapi" fn load_file(
+    _this: *mut LoadFile2Protocol,
+    _file_path: *const DevicePathProtocol,
+    boot_policy: Boolean,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Status {
+    // the boot policy must be false for LoadFile2
+    if bool::from(boot_policy) || buffer_size.is_null() {
+        return Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: the size pointer is not null, and the caller must give a buffer of at least that size if it is not null
+    unsafe {
+        let size = &mut *buffer_size;
+        let buffer = (!buffer.is_null())
+            .then(|| core::slice::from_raw_parts_mut(buffer.cast::<u8>(), *size));
+        copy_initrd(buffer, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_device_path() {
+        assert_eq!(&INITRD_DEVICE_PATH[..4], &[4, 3, 20, 0]);
+        assert_eq!(
+            &INITRD_DEVICE_PATH[4..20],
+            &LINUX_EFI_INITRD_MEDIA_GUID.to_bytes()
+        );
+        assert_eq!(&INITRD_DEVICE_PATH[20..], &[0x7F, 0xFF, 4, 0]);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_copy_initrd() {
+        let mut size = 0;
+        assert_eq!(copy_initrd(None, &mut size), Status::NOT_FOUND);
+
+        *INITRD.data.borrow_mut() = vec![1, 2, 3];
+        assert_eq!(copy_initrd(None, &mut size), Status::BUFFER_TOO_SMALL);
+        assert_eq!(size, 3);
+
+        let mut buffer = [0; 2];
+        assert_eq!(
+            copy_initrd(Some(&mut buffer), &mut size),
+            Status::BUFFER_TOO_SMALL
+        );
+
+        let mut buffer = [0; 4];
+        assert_eq!(copy_initrd(Some(&mut buffer), &mut size), Status::SUCCESS);
+        assert_eq!(buffer, [1, 2, 3, 0]);
+        assert_eq!(size, 3);
+
+        uninstall_initrd();
+        assert_eq!(copy_initrd(None, &mut size), Status::NOT_FOUND);
+    }
+}
@@ -0,0 +1,963 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The boot loader for Linux kernels through the `x86_64` Linux boot protocol
+//!
+//! This boots kernels that were built without the EFI stub, which cannot be loaded with `LoadImage`. The setup header
+//! of the bzImage is parsed, and the `boot_params` structure (also known as the zero page) is built with the command
+//! line, the initrd, the framebuffer and the memory map. The kernel is then started through the EFI handover protocol
+//! if it supports it, or otherwise through its 64-bit entry point after boot services are exited.
+//!
+//! The initrds are taken from the `initrd=` options of the command line, like the EFI stub does, and are concatenated
+//! into one initrd. The kernel, the command line and the initrds are measured into the TPM. As the kernel is not
+//! loaded through `LoadImage`, the firmware does not verify it. If its SHA-256 hash is pinned, then it must match.
+//! With Secure Boot enabled, it may only be booted if it is also verified by Shim, or its SHA-256 hash is in `db` or
+//! in the `ImageSha256Allowlist` variable, as pins on the ESP can be changed by anyone that can write to it.
+//!
+//! See <https://docs.kernel.org/arch/x86/boot.html> for the boot protocol.
+//!
+//! # Safety
+//!
+//! This module uses unsafe in 3 places.
+//!
+//! 1. Exiting boot services requires that no references to boot services data are used afterwards. The filesystem
+//!    protocol is closed before the kernel is started. After boot services are exited, only the `boot_params` and the
+//!    memory map are written to, which are `LOADER_DATA`, and nothing is allocated, freed or logged.
+//! 2. The EFI handover entry point is called as a function. It is only called if the kernel supports the 64-bit
+//!    handover protocol, and the kernel was loaded as required by the boot protocol.
+//! 3. The 64-bit entry point is jumped to with inline assembly, which loads a flat GDT and the segments that the boot
+//!    protocol requires. The firmware has already identity mapped all memory, and interrupts are disabled.
+
+use alloc::{string::String, vec::Vec};
+use core::{arch::asm, ffi::c_void, ptr};
+
+use log::warn;
+use thiserror::Error;
+use uefi::{
+    Handle,
+    boot::{self, AllocateType, MemoryType},
+    mem::memory_map::{MemoryMap, MemoryMapOwned},
+};
+
+use crate::{
+    BootResult,
+    boot::{
+        bli,
+        loader::{
+            LoadError,
+            direct::{
+                Framebuffer, PAGE_SIZE, address, allocate, get_framebuffer, read_u16, read_u32,
+                read_u64, read_verified,
+            },
+            efi::{get_initrds, measure_cmdline},
+            get_efi,
+        },
+    },
+    config::Config,
+    system::{
+        fs::UefiFileSystem,
+        helper::{normalize_path, str_to_cstr},
+        tpm::{TPM2_PCR_BOOT_LOADER_CODE, TPM2_PCR_INITRD, measure},
+        variable::RuntimeUefiVariableStorage,
+    },
+};
+
+/// The size of the `boot_params` structure.
+const BOOT_PARAMS_SIZE: usize = 0x1000;
+
+/// The offset of the setup header, in both the bzImage and `boot_params`.
+const SETUP_SECTS: usize = 0x1F1;
+
+/// The offset of the boot flag.
+const BOOT_FLAG: usize = 0x1FE;
+
+/// The offset of the jump instruction, whose second byte is the length of the rest of the setup header.
+const JUMP: usize = 0x200;
+
+/// The offset of the `HdrS` magic number.
+const HEADER: usize = 0x202;
+
+/// The offset of the boot protocol version.
+const VERSION: usize = 0x206;
+
+/// The offset of the type of the boot loader.
+const TYPE_OF_LOADER: usize = 0x210;
+
+/// The offset of the load address of the protected-mode kernel.
+const CODE32_START: usize = 0x214;
+
+/// The offset of the lower 32 bits of the initrd address.
+const RAMDISK_IMAGE: usize = 0x218;
+
+/// The offset of the lower 32 bits of the initrd size.
+const RAMDISK_SIZE: usize = 0x21C;
+
+/// The offset of the lower 32 bits of the command line address.
+const CMD_LINE_PTR: usize = 0x228;
+
+/// The offset of the highest address that the initrd may occupy.
+const INITRD_ADDR_MAX: usize = 0x22C;
+
+/// The offset of the alignment that a relocatable kernel must be loaded at.
+const KERNEL_ALIGNMENT: usize = 0x230;
+
+/// The offset of the flag that indicates if the kernel is relocatable.
+const RELOCATABLE_KERNEL: usize = 0x234;
+
+/// The offset of the extended load flags.
+const XLOADFLAGS: usize = 0x236;
+
+/// The offset of the maximum size of the command line.
+const CMDLINE_SIZE: usize = 0x238;
+
+/// The offset of the preferred load address of the kernel.
+const PREF_ADDRESS: usize = 0x258;
+
+/// The offset of the amount of memory that the kernel requires to initialize.
+const INIT_SIZE: usize = 0x260;
+
+/// The offset of the EFI handover entry point, relative to the 64-bit entry point.
+const HANDOVER_OFFSET: usize = 0x264;
+
+/// The offset of the upper 32 bits of the initrd address.
+const EXT_RAMDISK_IMAGE: usize = 0x0C0;
+
+/// The offset of the upper 32 bits of the initrd size.
+const EXT_RAMDISK_SIZE: usize = 0x0C4;
+
+/// The offset of the upper 32 bits of the command line address.
+const EXT_CMD_LINE_PTR: usize = 0x0C8;
+
+/// The offset of the EFI information, which holds the system table and the memory map.
+const EFI_INFO: usize = 0x1C0;
+
+/// The offset of the amount of entries in the e820 table.
+const E820_ENTRIES: usize = 0x1E8;
+
+/// The offset of the e820 table.
+const E820_TABLE: usize = 0x2D0;
+
+/// The maximum amount of entries in the e820 table.
+const E820_MAX_ENTRIES: usize = 128;
+
+/// The size of an entry in the e820 table.
+const E820_ENTRY_SIZE: usize = 20;
+
+/// The `HdrS` magic number of the setup header.
+const HEADER_MAGIC: &[u8; 4] = b"HdrS";
+
+/// The boot flag of the setup header.
+const BOOT_FLAG_MAGIC: u16 = 0xAA55;
+
+/// The minimum supported boot protocol version, 2.12, which added the extended load flags.
+const MIN_VERSION: u16 = 0x020C;
+
+/// The kernel has a 64-bit entry point.
+const XLF_KERNEL_64: u16 = 1 << 0;
+
+/// The kernel and the initrd may be loaded above 4 GiB.
+const XLF_CAN_BE_LOADED_ABOVE_4G: u16 = 1 << 1;
+
+/// The kernel supports the 64-bit EFI handover protocol.
+const XLF_EFI_HANDOVER_64: u16 = 1 << 3;
+
+/// The offset of the 64-bit entry point from the start of the protected-mode kernel.
+const ENTRY_64: u64 = 0x200;
+
+/// The type of boot loader that is written to `boot_params`, which is "undefined".
+const LOADER_TYPE_UNDEFINED: u8 = 0xFF;
+
+/// The signature of the EFI information for 64-bit firmware.
+const EFI_LOADER_SIGNATURE: &[u8; 4] = b"EL64";
+
+/// The e820 type of usable memory.
+const E820_RAM: u32 = 1;
+
+/// The e820 type of reserved memory.
+const E820_RESERVED: u32 = 2;
+
+/// The e820 type of ACPI reclaimable memory.
+const E820_ACPI: u32 = 3;
+
+/// The e820 type of ACPI NVS memory.
+const E820_NVS: u32 = 4;
+
+/// The e820 type of unusable memory.
+const E820_UNUSABLE: u32 = 5;
+
+/// The e820 type of persistent memory.
+const E820_PMEM: u32 = 7;
+
+/// The offset of the video type in the screen information.
+const ORIG_VIDEO_IS_VGA: usize = 0x0F;
+
+/// The offset of the framebuffer width in the screen information.
+const LFB_WIDTH: usize = 0x12;
+
+/// The offset of the framebuffer height in the screen information.
+const LFB_HEIGHT: usize = 0x14;
+
+/// The offset of the framebuffer depth in the screen information.
+const LFB_DEPTH: usize = 0x16;
+
+/// The offset of the lower 32 bits of the framebuffer address in the screen information.
+const LFB_BASE: usize = 0x18;
+
+/// The offset of the framebuffer size in the screen information.
+const LFB_SIZE: usize = 0x1C;
+
+/// The offset of the bytes per line of the framebuffer in the screen information.
+const LFB_LINELENGTH: usize = 0x24;
+
+/// The offset of the color sizes and positions in the screen information.
+const LFB_COLORS: usize = 0x26;
+
+/// The offset of the capabilities in the screen information.
+const CAPABILITIES: usize = 0x36;
+
+/// The offset of the upper 32 bits of the framebuffer address in the screen information.
+const EXT_LFB_BASE: usize = 0x3A;
+
+/// The video type of an EFI framebuffer.
+const VIDEO_TYPE_EFI: u8 = 0x70;
+
+/// The framebuffer address has 64 bits.
+const VIDEO_CAPABILITY_64BIT_BASE: u32 = 1 << 1;
+
+/// The code segment selector that the 64-bit entry point requires.
+const BOOT_CS: u16 = 0x10;
+
+/// The data segment selector that the 64-bit entry point requires.
+const BOOT_DS: u16 = 0x18;
+
+/// A GDT with a flat 64-bit code segment at [`BOOT_CS`] and a flat data segment at [`BOOT_DS`].
+static GDT: [u64; 4] = [0, 0, 0x00AF_9A00_0000_FFFF, 0x00CF_9200_0000_FFFF];
+
+/// The function signature of the 64-bit EFI handover entry point.
+type HandoverEntry = unsafe extern "sysv64" fn(*mut c_void, *mut c_void, *mut u8) -> !;
+
+/// An `Error` that may result from booting a Linux kernel.
+#[derive(Error, Debug)]
+pub enum LinuxError {
+    /// The image does not have a valid setup header.
+    #[error("Image is not a bzImage kernel")]
+    NotBzImage,
+
+    /// The boot protocol version of the kernel is too old.
+    #[error("Kernel uses boot protocol version {0:#06x}, but at least 2.12 is required")]
+    UnsupportedVersion(u16),
+
+    /// The kernel does not have a 64-bit entry point.
+    #[error("Kernel does not have a 64-bit entry point")]
+    NoEntry64,
+
+    /// The command line is longer than the kernel supports.
+    #[error("Command line is {0} bytes long, but the kernel supports up to {1} bytes")]
+    CmdlineTooLong(usize, u32),
+}
+
+/// The fields of the setup header of a bzImage that are needed to boot it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SetupHeader {
+    /// The offset of the protected-mode kernel in the bzImage.
+    kernel_offset: usize,
+
+    /// The offset of the end of the setup header.
+    header_end: usize,
+
+    /// If the kernel is relocatable.
+    relocatable: bool,
+
+    /// The alignment that a relocatable kernel must be loaded at.
+    kernel_alignment: usize,
+
+    /// The preferred load address of the kernel.
+    pref_address: u64,
+
+    /// The amount of memory that the kernel requires to initialize.
+    init_size: usize,
+
+    /// The extended load flags.
+    xloadflags: u16,
+
+    /// The offset of the EFI handover entry point.
+    handover_offset: u32,
+
+    /// The highest address that the initrd may occupy.
+    initrd_addr_max: u32,
+
+    /// The maximum size of the command line.
+    cmdline_size: u32,
+}
+
+impl SetupHeader {
+    /// Parse the setup header of a bzImage.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the image is not a bzImage, the boot protocol is older than 2.12, or the kernel does
+    /// not have a 64-bit entry point.
+    fn parse(kernel: &[u8]) -> Result<Self, LinuxError> {
+        if !has_setup_header(kernel) {
+            return Err(LinuxError::NotBzImage);
+        }
+
+        let version = read_u16(kernel, VERSION).ok_or(LinuxError::NotBzImage)?;
+        if version < MIN_VERSION {
+            return Err(LinuxError::UnsupportedVersion(version));
+        }
+
+        let setup_sects = match kernel[SETUP_SECTS] {
+            0 => 4,
+            sects => usize::from(sects),
+        };
+        let kernel_offset = (setup_sects + 1) * 512;
+        let header_end = HEADER + usize::from(kernel[JUMP + 1]);
+        if header_end < HANDOVER_OFFSET + 4
+            || header_end > kernel_offset
+            || kernel_offset > kernel.len()
+        {
+            return Err(LinuxError::NotBzImage);
+        }
+
+        let read_u32 = |offset| read_u32(kernel, offset).unwrap_or_default();
+        let xloadflags = read_u16(kernel, XLOADFLAGS).unwrap_or_default();
+        if xloadflags & XLF_KERNEL_64 == 0 {
+            return Err(LinuxError::NoEntry64);
+        }
+
+        let kernel_alignment = usize::try_from(read_u32(KERNEL_ALIGNMENT)).unwrap_or_default();
+
+        Ok(Self {
+            kernel_offset,
+            header_end,
+            relocatable: kernel[RELOCATABLE_KERNEL] != 0,
+            kernel_alignment: if kernel_alignment.is_power_of_two() {
+                kernel_alignment.max(PAGE_SIZE)
+            } else {
+                PAGE_SIZE
+            },
+            pref_address: read_u64(kernel, PREF_ADDRESS).unwrap_or_default(),
+            init_size: usize::try_from(read_u32(INIT_SIZE)).unwrap_or_default(),
+            xloadflags,
+            handover_offset: read_u32(HANDOVER_OFFSET),
+            initrd_addr_max: read_u32(INITRD_ADDR_MAX),
+            cmdline_size: read_u32(CMDLINE_SIZE),
+        })
+    }
+
+    /// Get the highest address that the kernel may be loaded at.
+    fn kernel_max_address(&self) -> u64 {
+        if self.xloadflags & XLF_CAN_BE_LOADED_ABOVE_4G == 0 {
+            u32::MAX.into()
+        } else {
+            u64::MAX
+        }
+    }
+
+    /// Get the highest address that the initrd may be loaded at.
+    fn initrd_max_address(&self) -> u64 {
+        if self.xloadflags & XLF_CAN_BE_LOADED_ABOVE_4G == 0 {
+            self.initrd_addr_max.into()
+        } else {
+            u64::MAX
+        }
+    }
+
+    /// Check if the kernel supports the 64-bit EFI handover protocol.
+    const fn supports_handover(&self) -> bool {
+        self.xloadflags & XLF_EFI_HANDOVER_64 != 0 && self.handover_offset != 0
+    }
+}
+
+/// The `boot_params` structure that is passed to the kernel.
+struct BootParams<'a>(&'a mut [u8]);
+
+impl<'a> BootParams<'a> {
+    /// Create a new [`BootParams`] in a buffer, with the setup header copied from the bzImage.
+    fn new(buf: &'a mut [u8], kernel: &[u8], header: &SetupHeader) -> Self {
+        buf.fill(0);
+        buf[SETUP_SECTS..header.header_end]
+            .copy_from_slice(&kernel[SETUP_SECTS..header.header_end]);
+        buf[TYPE_OF_LOADER] = LOADER_TYPE_UNDEFINED;
+        Self(buf)
+    }
+
+    /// Write bytes at an offset.
+    fn write<const N: usize>(&mut self, offset: usize, bytes: [u8; N]) {
+        self.0[offset..offset + N].copy_from_slice(&bytes);
+    }
+
+    /// Write the lower and upper 32 bits of a value at two different offsets.
+    fn write_split(&mut self, lo: usize, hi: usize, value: u64) {
+        let bytes = value.to_le_bytes();
+        self.0[lo..lo + 4].copy_from_slice(&bytes[..4]);
+        self.0[hi..hi + 4].copy_from_slice(&bytes[4..]);
+    }
+
+    /// Set the address of the protected-mode kernel.
+    fn set_kernel(&mut self, kernel: &[u8]) {
+        self.write(
+            CODE32_START,
+            u32::try_from(address(kernel))
+                .unwrap_or_default()
+                .to_le_bytes(),
+        );
+    }
+
+    /// Set the address of the command line.
+    fn set_cmdline(&mut self, cmdline: &[u8]) {
+        self.write_split(CMD_LINE_PTR, EXT_CMD_LINE_PTR, address(cmdline));
+    }
+
+    /// Set the address and the size of the initrd.
+    fn set_initrd(&mut self, initrd: &[u8]) {
+        self.write_split(RAMDISK_IMAGE, EXT_RAMDISK_IMAGE, address(initrd));
+        self.write_split(RAMDISK_SIZE, EXT_RAMDISK_SIZE, initrd.len() as u64);
+    }
+
+    /// Set the screen information from the framebuffer.
+    fn set_screen_info(&mut self, info: &Framebuffer) {
+        let to_u16 = |x| u16::try_from(x).unwrap_or(u16::MAX);
+        self.0[ORIG_VIDEO_IS_VGA] = VIDEO_TYPE_EFI;
+        self.write(LFB_WIDTH, to_u16(info.width).to_le_bytes());
+        self.write(LFB_HEIGHT, to_u16(info.height).to_le_bytes());
+        self.write(LFB_DEPTH, 32u16.to_le_bytes());
+        self.write_split(LFB_BASE, EXT_LFB_BASE, info.base);
+        self.write(
+            LFB_SIZE,
+            u32::try_from(info.size).unwrap_or(u32::MAX).to_le_bytes(),
+        );
+        self.write(LFB_LINELENGTH, to_u16(info.pitch).to_le_bytes());
+
+        for (i, mask) in info.masks.iter().enumerate() {
+            let size = u8::try_from(mask.count_ones()).unwrap_or_default();
+            let pos = u8::try_from(mask.trailing_zeros()).unwrap_or_default();
+            self.write(LFB_COLORS + i * 2, [size, pos]);
+        }
+
+        if info.base > u32::MAX.into() {
+            self.write(CAPABILITIES, VIDEO_CAPABILITY_64BIT_BASE.to_le_bytes());
+        }
+    }
+
+    /// Set the e820 table from an iterator of memory regions, given as their address, size and e820 type.
+    ///
+    /// Adjacent regions of the same type are merged. If there are more regions than fit in the table, then the rest
+    /// are dropped.
+    fn set_e820(&mut self, regions: impl Iterator<Item = (u64, u64, u32)>) {
+        let mut count = 0;
+        let mut last: Option<(u64, u64, u32)> = None;
+
+        for (addr, size, ty) in regions {
+            if let Some((last_addr, last_size, last_ty)) = &mut last
+                && *last_ty == ty
+                && *last_addr + *last_size == addr
+            {
+                *last_size += size;
+                continue;
+            }
+
+            if let Some(entry) = last.replace((addr, size, ty)) {
+                if count == E820_MAX_ENTRIES {
+                    last = None;
+                    break;
+                }
+                self.write_e820(count, entry);
+                count += 1;
+            }
+        }
+
+        if let Some(entry) = last
+            && count < E820_MAX_ENTRIES
+        {
+            self.write_e820(count, entry);
+            count += 1;
+        }
+
+        self.0[E820_ENTRIES] = u8::try_from(count).unwrap_or_default();
+    }
+
+    /// Write an entry of the e820 table.
+    fn write_e820(&mut self, index: usize, (addr, size, ty): (u64, u64, u32)) {
+        let offset = E820_TABLE + index * E820_ENTRY_SIZE;
+        self.write(offset, addr.to_le_bytes());
+        self.write(offset + 8, size.to_le_bytes());
+        self.write(offset + 16, ty.to_le_bytes());
+    }
+
+    /// Set the e820 table and the EFI information from the final memory map.
+    fn set_memory_map(&mut self, map: &MemoryMapOwned) {
+        self.set_e820(map.entries().map(|desc| {
+            (
+                desc.phys_start,
+                desc.page_count * PAGE_SIZE as u64,
+                e820_type(desc.ty),
+            )
+        }));
+
+        let meta = map.meta();
+        let systab = uefi::table::system_table_raw().map_or(0, |x| x.as_ptr().addr() as u64);
+        let memmap = map.buffer().as_ptr().addr() as u64;
+
+        self.write(EFI_INFO, *EFI_LOADER_SIGNATURE);
+        self.write_split(EFI_INFO + 4, EFI_INFO + 24, systab);
+        self.write(
+            EFI_INFO + 8,
+            u32::try_from(meta.desc_size)
+                .unwrap_or_default()
+                .to_le_bytes(),
+        );
+        self.write(EFI_INFO + 12, meta.desc_version.to_le_bytes());
+        self.write_split(EFI_INFO + 16, EFI_INFO + 28, memmap);
+        self.write(
+            EFI_INFO + 20,
+            u32::try_from(meta.map_size)
+                .unwrap_or_default()
+                .to_le_bytes(),
+        );
+    }
+}
+
+/// Loads a boot option from a given [`Config`] through the Linux boot protocol.
+///
+/// If the kernel is booted, then this never returns.
+///
+/// # Errors
+///
+/// May return an `Error` if the kernel or the initrds could not be read, the kernel is not a supported bzImage, the
+/// kernel was refused while Secure Boot is enabled, or memory could not be allocated for the kernel.
+pub(crate) fn load_boot_option(config: &Config) -> BootResult<Handle> {
+    let handle = *config
+        .fs_handle
+        .ok_or_else(|| LoadError::ConfigMissingHandle(config.filename.clone()))?;
+
+    let mut fs = UefiFileSystem::from_handle(handle)?;
+
+    let path = get_efi(config)?;
+    let kernel = read_verified(&mut fs, path, config.sha256, TPM2_PCR_BOOT_LOADER_CODE)?;
+    let header = SetupHeader::parse(&kernel)?;
+
+    let options = config.options.as_deref().unwrap_or_default();
+    let cmdline = get_cmdline(options);
+    if cmdline.len() > usize::try_from(header.cmdline_size).unwrap_or(usize::MAX) {
+        return Err(LinuxError::CmdlineTooLong(cmdline.len(), header.cmdline_size).into());
+    }
+    measure_cmdline(&str_to_cstr(&cmdline)?);
+
+    let initrd = load_initrd(&mut fs, options, header.initrd_max_address())?;
+    drop(fs); // close the filesystem protocol, as it must not be open once boot services are exited
+    let kernel_region = load_kernel(&kernel[header.kernel_offset..], &header)?;
+
+    let cmdline_region = allocate(
+        cmdline.len() + 1,
+        AllocateType::MaxAddress(u32::MAX.into()),
+        PAGE_SIZE,
+    )?;
+    cmdline_region[..cmdline.len()].copy_from_slice(cmdline.as_bytes());
+    cmdline_region[cmdline.len()] = 0;
+
+    let params_region = allocate(
+        BOOT_PARAMS_SIZE,
+        AllocateType::MaxAddress(u32::MAX.into()),
+        PAGE_SIZE,
+    )?;
+    let mut params = BootParams::new(params_region, &kernel, &header);
+    params.set_kernel(kernel_region);
+    params.set_cmdline(cmdline_region);
+    if let Some(initrd) = initrd {
+        params.set_initrd(initrd);
+    }
+    if let Some(info) = get_framebuffer() {
+        params.set_screen_info(&info);
+    }
+
+    drop(kernel); // the kernel was copied into its own region, so the pool allocation can be freed
+
+    bli::prepare_handoff(&mut RuntimeUefiVariableStorage, config);
+    log::logger().flush(); // nothing can be logged once boot services are exited
+    boot_kernel(params, address(kernel_region), &header)
+}
+
+/// Check if a file is a bzImage kernel that was built without the EFI stub, by reading its setup header.
+///
+/// Kernels that were built with the EFI stub are also valid PE images, which are booted through EFI instead.
+pub(crate) fn is_raw_kernel(fs: &mut UefiFileSystem, path: &str) -> bool {
+    let mut buf = [0; HEADER + HEADER_MAGIC.len()];
+    str_to_cstr(&normalize_path(path))
+        .ok()
+        .and_then(|path| fs.read_at(&path, 0, &mut buf).ok())
+        .is_some_and(|read| has_setup_header(&buf[..read]) && buf.get(..2) != Some(b"MZ"))
+}
+
+/// Check if the start of an image has the boot flag and the magic number of a bzImage setup header.
+fn has_setup_header(kernel: &[u8]) -> bool {
+    read_u16(kernel, BOOT_FLAG) == Some(BOOT_FLAG_MAGIC)
+        && kernel.get(HEADER..HEADER + 4) == Some(HEADER_MAGIC)
+}
+
+/// Get the kernel command line from the options of a [`Config`], without the `initrd=` options.
+fn get_cmdline(options: &str) -> String {
+    options
+        .split_ascii_whitespace()
+        .filter(|option| !option.starts_with("initrd="))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Read every initrd specified in the options, and concatenate them into one initrd below an address.
+///
+/// Every initrd is aligned to 4 bytes, as is required for concatenated cpio archives.
+///
+/// # Errors
+///
+/// May return an `Error` if an initrd could not be read, or memory could not be allocated for the initrd.
+fn load_initrd(
+    fs: &mut UefiFileSystem,
+    options: &str,
+    max_address: u64,
+) -> BootResult<Option<&'static mut [u8]>> {
+    let mut initrds = Vec::new();
+    for initrd in get_initrds(options) {
+        let content = fs.read(&str_to_cstr(&normalize_path(initrd))?)?;
+        if let Err(e) = measure(TPM2_PCR_INITRD, &content, initrd) {
+            warn!("Failed to measure initrd {initrd}: {e}");
+        }
+        initrds.push(content);
+    }
+
+    let size = initrds.iter().fold(0, |size: usize, initrd| {
+        size.next_multiple_of(4) + initrd.len()
+    });
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let region = allocate(size, AllocateType::MaxAddress(max_address), PAGE_SIZE)?;
+    region.fill(0);
+
+    let mut offset: usize = 0;
+    for initrd in initrds {
+        offset = offset.next_multiple_of(4);
+        region[offset..offset + initrd.len()].copy_from_slice(&initrd);
+        offset += initrd.len();
+    }
+
+    Ok(Some(region))
+}
+
+/// Copy the protected-mode kernel into memory.
+///
+/// The kernel is loaded at its preferred address if possible. Otherwise, if it is relocatable, then it is loaded at
+/// any address with the alignment that it requires.
+///
+/// # Errors
+///
+/// May return an `Error` if memory could not be allocated for the kernel.
+fn load_kernel(payload: &[u8], header: &SetupHeader) -> BootResult<&'static mut [u8]> {
+    let size = header.init_size.max(payload.len());
+
+    let region = match allocate(size, AllocateType::Address(header.pref_address), PAGE_SIZE) {
+        Ok(region) => region,
+        Err(_) if header.relocatable => allocate(
+            size,
+            AllocateType::MaxAddress(header.kernel_max_address()),
+            header.kernel_alignment,
+        )?,
+        Err(e) => return Err(e),
+    };
+
+    region[..payload.len()].copy_from_slice(payload);
+    Ok(region)
+}
+
+/// Start the kernel.
+///
+/// If the kernel supports the EFI handover protocol, then it is called with boot services still available, so that it
+/// may set up the framebuffer and memory map by itself. Otherwise, boot services are exited, the memory map is added
+/// to the `boot_params`, and the 64-bit entry point is jumped to.
+fn boot_kernel(mut params: BootParams<'static>, kernel: u64, header: &SetupHeader) -> ! {
+    if header.supports_handover() {
+        let entry = kernel + ENTRY_64 + u64::from(header.handover_offset);
+        let systab = uefi::table::system_table_raw().map_or(ptr::null_mut(), |x| x.as_ptr().cast());
+
+        // SAFETY: the kernel supports the 64-bit handover protocol, so there is an entry point at this address with
+        // this signature.
+        unsafe {
+            let handover = core::mem::transmute::<u64, HandoverEntry>(entry);
+            handover(boot::image_handle().as_ptr(), systab, params.0.as_mut_ptr())
+        }
+    }
+
+    // SAFETY: the filesystem protocol and the buffer of the kernel were dropped in `load_boot_option`. The only memory
+    // that is used after this point is the kernel, the command line, the initrd and the boot params, which are
+    // `LOADER_DATA` allocations that are never freed.
+    let map = unsafe { boot::exit_boot_services(None) };
+    params.set_memory_map(&map);
+    core::mem::forget(map); // the kernel uses the memory map through the EFI information
+
+    // SAFETY: boot services were exited, the kernel was loaded according to the boot protocol, and the boot params
+    // are complete.
+    unsafe { jump_to_kernel(kernel + ENTRY_64, params.0.as_mut_ptr()) }
+}
+
+/// Jump to the 64-bit entry point of the kernel with `boot_params` in `rsi`.
+///
+/// A flat GDT is loaded, and the code and data segments are set to [`BOOT_CS`] and [`BOOT_DS`], as the boot protocol
+/// requires.
+///
+/// # Safety
+///
+/// Boot services must have been exited, `entry` must be the 64-bit entry point of a loaded kernel, and
+/// `boot_params` must point to complete boot params.
+unsafe fn jump_to_kernel(entry: u64, boot_params: *mut u8) -> ! {
+    /// The operand of the `lgdt` instruction.
+    #[repr(C, packed)]
+    struct Gdtr {
+        /// The size of the GDT minus one.
+        limit: u16,
+
+        /// The address of the GDT.
+        base: u64,
+    }
+
+    let gdtr = Gdtr {
+        limit: u16::try_from(size_of_val(&GDT) - 1).unwrap_or_default(),
+        base: GDT.as_ptr().addr() as u64,
+    };
+
+    // SAFETY: the caller guarantees that the kernel is loaded and boot services were exited. The GDT is static, so it
+    // is still valid once loaded.
+    unsafe {
+        asm!(
+            "cli",
+            "lgdt [rdx]",
+            "mov ax, {ds}",
+            "mov ds, ax",
+            "mov es, ax",
+            "mov ss, ax",
+            "mov fs, ax",
+            "mov gs, ax",
+            "push {cs}",
+            "lea rax, [rip + 2f]",
+            "push rax",
+            "retfq",
+            "2:",
+            "jmp r8",
+            ds = const BOOT_DS,
+            cs = const BOOT_CS,
+            in("rdx") &raw const gdtr,
+            in("r8") entry,
+            in("rsi") boot_params,
+            options(noreturn),
+        )
+    }
+}
+
+/// Convert a UEFI [`MemoryType`] into an e820 type.
+///
+/// Memory that is used by boot services or the boot manager is usable once boot services are exited.
+fn e820_type(ty: MemoryType) -> u32 {
+    match ty {
+        MemoryType::CONVENTIONAL
+        | MemoryType::LOADER_CODE
+        | MemoryType::LOADER_DATA
+        | MemoryType::BOOT_SERVICES_CODE
+        | MemoryType::BOOT_SERVICES_DATA => E820_RAM,
+        MemoryType::ACPI_RECLAIM => E820_ACPI,
+        MemoryType::ACPI_NON_VOLATILE => E820_NVS,
+        MemoryType::UNUSABLE => E820_UNUSABLE,
+        MemoryType::PERSISTENT_MEMORY => E820_PMEM,
+        _ => E820_RESERVED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    /// Build a bzImage with a setup header and a protected-mode kernel.
+    fn bzimage(version: u16, xloadflags: u16) -> Vec<u8> {
+        let mut kernel = vec![0; 5 * 512 + 16];
+        kernel[SETUP_SECTS] = 4;
+        kernel[BOOT_FLAG..BOOT_FLAG + 2].copy_from_slice(&BOOT_FLAG_MAGIC.to_le_bytes());
+        kernel[JUMP..JUMP + 2].copy_from_slice(&[0xEB, 0x6A]);
+        kernel[HEADER..HEADER + 4].copy_from_slice(HEADER_MAGIC);
+        kernel[VERSION..VERSION + 2].copy_from_slice(&version.to_le_bytes());
+        kernel[INITRD_ADDR_MAX..INITRD_ADDR_MAX + 4].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+        kernel[KERNEL_ALIGNMENT..KERNEL_ALIGNMENT + 4].copy_from_slice(&0x20_0000u32.to_le_bytes());
+        kernel[RELOCATABLE_KERNEL] = 1;
+        kernel[XLOADFLAGS..XLOADFLAGS + 2].copy_from_slice(&xloadflags.to_le_bytes());
+        kernel[CMDLINE_SIZE..CMDLINE_SIZE + 4].copy_from_slice(&2047u32.to_le_bytes());
+        kernel[PREF_ADDRESS..PREF_ADDRESS + 8].copy_from_slice(&0x100_0000u64.to_le_bytes());
+        kernel[INIT_SIZE..INIT_SIZE + 4].copy_from_slice(&0x10_0000u32.to_le_bytes());
+        kernel[HANDOVER_OFFSET..HANDOVER_OFFSET + 4].copy_from_slice(&0x190u32.to_le_bytes());
+        kernel
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_header() {
+        let kernel = bzimage(0x020F, XLF_KERNEL_64 | XLF_CAN_BE_LOADED_ABOVE_4G);
+        let header = SetupHeader::parse(&kernel).expect("Header should be valid");
+        assert_eq!(
+            header,
+            SetupHeader {
+                kernel_offset: 5 * 512,
+                header_end: 0x26C,
+                relocatable: true,
+                kernel_alignment: 0x20_0000,
+                pref_address: 0x100_0000,
+                init_size: 0x10_0000,
+                xloadflags: XLF_KERNEL_64 | XLF_CAN_BE_LOADED_ABOVE_4G,
+                handover_offset: 0x190,
+                initrd_addr_max: 0x7FFF_FFFF,
+                cmdline_size: 2047,
+            }
+        );
+        assert!(!header.supports_handover());
+        assert_eq!(header.kernel_max_address(), u64::MAX);
+        assert_eq!(header.initrd_max_address(), u64::MAX);
+
+        let kernel = bzimage(0x020F, XLF_KERNEL_64 | XLF_EFI_HANDOVER_64);
+        let header = SetupHeader::parse(&kernel).expect("Header should be valid");
+        assert!(header.supports_handover());
+        assert_eq!(header.kernel_max_address(), u32::MAX.into());
+        assert_eq!(header.initrd_max_address(), 0x7FFF_FFFF);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_invalid_header() {
+        assert!(matches!(
+            SetupHeader::parse(&[0; 4096]),
+            Err(LinuxError::NotBzImage)
+        ));
+        assert!(matches!(
+            SetupHeader::parse(&bzimage(0x020F, XLF_KERNEL_64)[..1024]),
+            Err(LinuxError::NotBzImage)
+        ));
+        assert!(matches!(
+            SetupHeader::parse(&bzimage(0x020A, XLF_KERNEL_64)),
+            Err(LinuxError::UnsupportedVersion(0x020A))
+        ));
+        assert!(matches!(
+            SetupHeader::parse(&bzimage(0x020F, 0)),
+            Err(LinuxError::NoEntry64)
+        ));
+
+        let mut kernel = bzimage(0x020F, XLF_KERNEL_64);
+        kernel[..2].copy_from_slice(b"MZ");
+        assert!(has_setup_header(&kernel));
+        assert!(SetupHeader::parse(&kernel).is_ok());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_get_cmdline() {
+        assert_eq!(
+            get_cmdline("root=/dev/sda1 initrd=/intel-ucode.img  quiet initrd=\\initramfs.img"),
+            "root=/dev/sda1 quiet"
+        );
+        assert_eq!(get_cmdline(""), "");
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_boot_params() {
+        let kernel = bzimage(0x020F, XLF_KERNEL_64);
+        let header = SetupHeader::parse(&kernel).expect("Header should be valid");
+        let mut buf = vec![0xFF; BOOT_PARAMS_SIZE];
+        let mut params = BootParams::new(&mut buf, &kernel, &header);
+
+        params.write_split(CMD_LINE_PTR, EXT_CMD_LINE_PTR, 0x1_2345_6789);
+        params.set_e820(
+            [
+                (0, 0x1000, E820_RAM),
+                (0x1000, 0x1000, E820_RAM),
+                (0x3000, 0x1000, E820_RAM),
+                (0x4000, 0x1000, E820_ACPI),
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(
+            buf[SETUP_SECTS..TYPE_OF_LOADER],
+            kernel[SETUP_SECTS..TYPE_OF_LOADER]
+        );
+        assert_eq!(
+            buf[INITRD_ADDR_MAX..header.header_end],
+            kernel[INITRD_ADDR_MAX..header.header_end]
+        );
+        assert_eq!(buf[TYPE_OF_LOADER], LOADER_TYPE_UNDEFINED);
+        assert_eq!(buf[header.header_end..E820_TABLE], [0; E820_TABLE - 0x26C]);
+        assert_eq!(read_u32(&buf, CMD_LINE_PTR), Some(0x2345_6789));
+        assert_eq!(read_u32(&buf, EXT_CMD_LINE_PTR), Some(1));
+
+        assert_eq!(buf[E820_ENTRIES], 3);
+        let entry = |i| {
+            let offset = E820_TABLE + i * E820_ENTRY_SIZE;
+            (
+                read_u64(&buf, offset),
+                read_u64(&buf, offset + 8),
+                read_u32(&buf, offset + 16),
+            )
+        };
+        assert_eq!(entry(0), (Some(0), Some(0x2000), Some(E820_RAM)));
+        assert_eq!(entry(1), (Some(0x3000), Some(0x1000), Some(E820_RAM)));
+        assert_eq!(entry(2), (Some(0x4000), Some(0x1000), Some(E820_ACPI)));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_e820_overflow() {
+        let mut buf = vec![0; BOOT_PARAMS_SIZE];
+        let mut params = BootParams(&mut buf);
+        params.set_e820((0..200).map(|i| (i * 0x2000, 0x1000, E820_RAM)));
+        assert_eq!(usize::from(buf[E820_ENTRIES]), E820_MAX_ENTRIES);
+
+        assert_eq!(e820_type(MemoryType::BOOT_SERVICES_DATA), E820_RAM);
+        assert_eq!(e820_type(MemoryType::ACPI_NON_VOLATILE), E820_NVS);
+        assert_eq!(e820_type(MemoryType::RUNTIME_SERVICES_CODE), E820_RESERVED);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_screen_info() {
+        let mut buf = vec![0; BOOT_PARAMS_SIZE];
+        let mut params = BootParams(&mut buf);
+        params.set_screen_info(&Framebuffer {
+            width: 1280,
+            height: 800,
+            pitch: 1280 * 4,
+            base: 0x8_0000_0000,
+            size: 1280 * 800 * 4,
+            masks: [0x00FF_0000, 0xFF00, 0xFF, 0xFF00_0000],
+        });
+
+        assert_eq!(buf[ORIG_VIDEO_IS_VGA], VIDEO_TYPE_EFI);
+        assert_eq!(read_u16(&buf, LFB_WIDTH), Some(1280));
+        assert_eq!(read_u16(&buf, LFB_HEIGHT), Some(800));
+        assert_eq!(read_u32(&buf, LFB_BASE), Some(0));
+        assert_eq!(read_u32(&buf, EXT_LFB_BASE), Some(8));
+        assert_eq!(buf[LFB_COLORS..LFB_COLORS + 8], [8, 16, 8, 8, 8, 0, 8, 24]);
+        assert_eq!(
+            read_u32(&buf, CAPABILITIES),
+            Some(VIDEO_CAPABILITY_64BIT_BASE)
+        );
+    }
+}
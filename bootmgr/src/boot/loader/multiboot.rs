@@ -0,0 +1,1189 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The boot loader for Multiboot2 kernels, such as Xen
+//!
+//! The Multiboot2 header of the kernel is parsed, then the kernel is loaded from its ELF segments (or from the address
+//! tag of the header, if it has one). Kernels that are compressed with gzip, such as `xen.gz`, are decompressed first.
+//! The modules are loaded below 4 GiB, and the boot information is built with the command line, the modules, the
+//! memory map, the framebuffer, the ACPI tables and the EFI system table and image handle.
+//!
+//! Only the EFI amd64 machine state is supported, so the kernel must have the EFI boot services tag and the EFI amd64
+//! entry address tag in its header. The kernel is started with boot services still available, and it is expected to
+//! exit boot services by itself.
+//!
+//! Like GRUB, the command line of the kernel or a module starts with its path, as kernels such as Xen skip the first
+//! word of every command line. The kernel and the modules are measured into the TPM. As they are not loaded through
+//! `LoadImage`, they are checked like the kernels of the Linux boot protocol: with Secure Boot enabled, each of them
+//! must be verified by Shim, or have a SHA-256 hash in `db` or in the `ImageSha256Allowlist` variable.
+//!
+//! See <https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html> for the specification.
+//!
+//! # Safety
+//!
+//! This module uses unsafe in 2 places.
+//!
+//! 1. The RSDP is read from the address in the ACPI configuration table, which the firmware guarantees points to a
+//!    valid RSDP. Only its length field is read before the whole table is copied.
+//! 2. The entry point is called with inline assembly, with the magic number in `rax` and the address of the boot
+//!    information in `rbx`, as the EFI amd64 machine state requires. The rest of the machine state is left as the
+//!    firmware set it up, with boot services still available.
+
+use alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
+use core::arch::asm;
+
+use thiserror::Error;
+use uefi::{
+    Handle,
+    boot::{self, AllocateType, MemoryType},
+    mem::memory_map::MemoryMap,
+    table::cfg::{ACPI_GUID, ACPI2_GUID},
+};
+
+use crate::{
+    BootResult,
+    boot::{
+        bli,
+        loader::{
+            LoadError,
+            direct::{
+                Framebuffer, PAGE_SIZE, address, allocate, get_framebuffer, read_u16, read_u32,
+                read_u64, read_verified,
+            },
+            efi::measure_cmdline,
+            get_efi,
+        },
+    },
+    config::Config,
+    system::{
+        fs::UefiFileSystem,
+        helper::str_to_cstr,
+        tpm::{TPM2_PCR_BOOT_LOADER_CODE, TPM2_PCR_INITRD},
+        variable::RuntimeUefiVariableStorage,
+    },
+};
+
+/// The magic number of the Multiboot2 header.
+const HEADER_MAGIC: u32 = 0xE852_50D6;
+
+/// The magic number that is passed to the kernel in `rax`.
+const BOOTLOADER_MAGIC: u64 = 0x36D7_6289;
+
+/// The amount of bytes at the start of the kernel that the header must be within.
+const SEARCH_LIMIT: usize = 32768;
+
+/// The alignment of the header and of its tags.
+const ALIGN: usize = 8;
+
+/// The name of the boot loader that is passed to the kernel.
+const BOOT_LOADER_NAME: &str = "bootmgr-rs";
+
+/// The flag of a header tag that indicates that the tag may be ignored.
+const TAG_OPTIONAL: u16 = 1;
+
+/// The header tag that ends the tags.
+const HEADER_TAG_END: u16 = 0;
+
+/// The header tag that requests tags in the boot information.
+const HEADER_TAG_INFORMATION_REQUEST: u16 = 1;
+
+/// The header tag that specifies where to load a kernel that is not an ELF executable.
+const HEADER_TAG_ADDRESS: u16 = 2;
+
+/// The header tag that indicates that the kernel can be started with boot services available.
+const HEADER_TAG_EFI_BS: u16 = 7;
+
+/// The header tag that specifies the entry point in the EFI amd64 machine state.
+const HEADER_TAG_ENTRY_ADDRESS_EFI64: u16 = 9;
+
+/// The header tag that indicates that the kernel may be loaded at another address.
+const HEADER_TAG_RELOCATABLE: u16 = 10;
+
+/// The highest header tag that is defined by the specification.
+const HEADER_TAG_MAX: u16 = 10;
+
+/// The boot information tag that ends the tags.
+const TAG_END: u32 = 0;
+
+/// The boot information tag with the command line.
+const TAG_CMDLINE: u32 = 1;
+
+/// The boot information tag with the name of the boot loader.
+const TAG_BOOT_LOADER_NAME: u32 = 2;
+
+/// The boot information tag with a module.
+const TAG_MODULE: u32 = 3;
+
+/// The boot information tag with the amount of lower and upper memory.
+const TAG_BASIC_MEMINFO: u32 = 4;
+
+/// The boot information tag with the memory map.
+const TAG_MMAP: u32 = 6;
+
+/// The boot information tag with the framebuffer.
+const TAG_FRAMEBUFFER: u32 = 8;
+
+/// The boot information tag with the 64-bit EFI system table.
+const TAG_EFI64: u32 = 12;
+
+/// The boot information tag with a copy of the ACPI 1.0 RSDP.
+const TAG_ACPI_OLD: u32 = 14;
+
+/// The boot information tag with a copy of the ACPI 2.0 RSDP.
+const TAG_ACPI_NEW: u32 = 15;
+
+/// The boot information tag with the EFI memory map.
+const TAG_EFI_MMAP: u32 = 17;
+
+/// The boot information tag that indicates that boot services were not exited.
+const TAG_EFI_BS: u32 = 18;
+
+/// The boot information tag with the 64-bit EFI image handle.
+const TAG_EFI64_IH: u32 = 20;
+
+/// The boot information tag with the address that a relocatable kernel was loaded at.
+const TAG_LOAD_BASE_ADDR: u32 = 21;
+
+/// The boot information tags that may be requested.
+const SUPPORTED_TAGS: [u32; 13] = [
+    TAG_CMDLINE,
+    TAG_BOOT_LOADER_NAME,
+    TAG_MODULE,
+    TAG_BASIC_MEMINFO,
+    TAG_MMAP,
+    TAG_FRAMEBUFFER,
+    TAG_EFI64,
+    TAG_ACPI_OLD,
+    TAG_ACPI_NEW,
+    TAG_EFI_MMAP,
+    TAG_EFI_BS,
+    TAG_EFI64_IH,
+    TAG_LOAD_BASE_ADDR,
+];
+
+/// The memory type of available memory.
+const MEMORY_AVAILABLE: u32 = 1;
+
+/// The memory type of reserved memory.
+const MEMORY_RESERVED: u32 = 2;
+
+/// The memory type of ACPI reclaimable memory.
+const MEMORY_ACPI_RECLAIMABLE: u32 = 3;
+
+/// The memory type of memory that must be preserved on hibernation.
+const MEMORY_NVS: u32 = 4;
+
+/// The memory type of defective memory.
+const MEMORY_BADRAM: u32 = 5;
+
+/// The framebuffer type of a direct RGB framebuffer.
+const FRAMEBUFFER_TYPE_RGB: u8 = 1;
+
+/// The ELF program header type of a loadable segment.
+const PT_LOAD: u32 = 1;
+
+/// An `Error` that may result from booting a Multiboot2 kernel.
+#[derive(Error, Debug)]
+pub enum MultibootError {
+    /// The kernel does not have a Multiboot2 header.
+    #[error("Kernel does not have a Multiboot2 header")]
+    NoHeader,
+
+    /// The Multiboot2 header of the kernel is malformed.
+    #[error("Kernel has an invalid Multiboot2 header")]
+    InvalidHeader,
+
+    /// The kernel requires a header tag or boot information tag that is not supported.
+    #[error("Kernel requires unsupported Multiboot2 tag {0}")]
+    UnsupportedTag(u32),
+
+    /// The kernel does not support the EFI amd64 machine state.
+    #[error("Kernel does not support being started with EFI boot services on amd64")]
+    UnsupportedMachineState,
+
+    /// The kernel is not an ELF executable, and does not have an address tag.
+    #[error("Kernel is not a valid ELF executable")]
+    InvalidElf,
+
+    /// The kernel could not be decompressed.
+    #[error("Kernel could not be decompressed")]
+    InvalidGzip,
+
+    /// The kernel could not be loaded at its address, and could not be relocated.
+    #[error("Kernel could not be loaded at {0:#x}")]
+    LoadAddress(u64),
+
+    /// A module was not loaded within the 32-bit addresses that the boot information can describe.
+    #[error("Module {0} could not be loaded below 4 GiB")]
+    ModuleAddress(String),
+}
+
+/// The fields of the Multiboot2 header that are needed to boot a kernel.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct MultibootHeader {
+    /// The offset of the header in the kernel.
+    offset: usize,
+
+    /// The address tag, as `header_addr`, `load_addr`, `load_end_addr` and `bss_end_addr`.
+    address: Option<[u32; 4]>,
+
+    /// If the kernel can be started with boot services available.
+    efi_bs: bool,
+
+    /// The entry point in the EFI amd64 machine state.
+    entry_efi64: Option<u32>,
+
+    /// The relocatable tag, as `min_addr`, `max_addr` and `align`.
+    relocatable: Option<[u32; 3]>,
+}
+
+impl MultibootHeader {
+    /// Find and parse the Multiboot2 header of a kernel.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if there is no header, the header is invalid, or the kernel requires a tag that is not
+    /// supported.
+    fn parse(kernel: &[u8]) -> Result<Self, MultibootError> {
+        let offset = (0..kernel.len().min(SEARCH_LIMIT))
+            .step_by(ALIGN)
+            .find(|&offset| read_u32(kernel, offset) == Some(HEADER_MAGIC))
+            .ok_or(MultibootError::NoHeader)?;
+
+        let field =
+            |i: usize| read_u32(kernel, offset + i * 4).ok_or(MultibootError::InvalidHeader);
+        let (architecture, length, checksum) = (field(1)?, field(2)?, field(3)?);
+        let length = usize::try_from(length).map_err(|_| MultibootError::InvalidHeader)?;
+        if HEADER_MAGIC
+            .wrapping_add(architecture)
+            .wrapping_add(u32::try_from(length).unwrap_or_default())
+            .wrapping_add(checksum)
+            != 0
+            || length < 16
+        {
+            return Err(MultibootError::InvalidHeader);
+        }
+
+        let header = kernel
+            .get(offset..offset + length)
+            .ok_or(MultibootError::InvalidHeader)?;
+
+        let mut parsed = Self {
+            offset,
+            ..Self::default()
+        };
+
+        let mut tag = 16;
+        while tag + 8 <= header.len() {
+            let ty = read_u16(header, tag).unwrap_or_default();
+            let flags = read_u16(header, tag + 2).unwrap_or_default();
+            let size = usize::try_from(read_u32(header, tag + 4).unwrap_or_default())
+                .map_err(|_| MultibootError::InvalidHeader)?;
+            let data = header
+                .get(tag + 8..tag + size.max(8))
+                .ok_or(MultibootError::InvalidHeader)?;
+            let words: Vec<_> = data
+                .as_chunks::<4>()
+                .0
+                .iter()
+                .map(|x| u32::from_le_bytes(*x))
+                .collect();
+
+            match ty {
+                HEADER_TAG_END => return Ok(parsed),
+                HEADER_TAG_INFORMATION_REQUEST if flags & TAG_OPTIONAL == 0 => {
+                    if let Some(&request) = words.iter().find(|x| !SUPPORTED_TAGS.contains(x)) {
+                        return Err(MultibootError::UnsupportedTag(request));
+                    }
+                }
+                HEADER_TAG_ADDRESS => {
+                    parsed.address = Some(
+                        words
+                            .first_chunk::<4>()
+                            .copied()
+                            .ok_or(MultibootError::InvalidHeader)?,
+                    );
+                }
+                HEADER_TAG_EFI_BS => parsed.efi_bs = true,
+                HEADER_TAG_ENTRY_ADDRESS_EFI64 => parsed.entry_efi64 = words.first().copied(),
+                HEADER_TAG_RELOCATABLE => {
+                    parsed.relocatable = words.first_chunk::<3>().copied();
+                }
+                ty if ty > HEADER_TAG_MAX && flags & TAG_OPTIONAL == 0 => {
+                    return Err(MultibootError::UnsupportedTag(ty.into()));
+                }
+                _ => (),
+            }
+
+            tag += size.max(8).next_multiple_of(ALIGN);
+        }
+
+        Err(MultibootError::InvalidHeader)
+    }
+
+    /// Get the entry point in the EFI amd64 machine state.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the kernel cannot be started with boot services available on amd64.
+    fn entry(&self) -> Result<u64, MultibootError> {
+        match self.entry_efi64 {
+            Some(entry) if self.efi_bs => Ok(entry.into()),
+            _ => Err(MultibootError::UnsupportedMachineState),
+        }
+    }
+
+    /// Get the segments to load from the kernel.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the kernel does not have an address tag, and is not an ELF executable.
+    fn segments(&self, kernel: &[u8]) -> Result<Vec<Segment>, MultibootError> {
+        let Some([header_addr, load_addr, load_end_addr, bss_end_addr]) = self.address else {
+            return get_elf_segments(kernel);
+        };
+
+        let to_usize = |x: u32| usize::try_from(x).map_err(|_| MultibootError::InvalidHeader);
+        let offset = self
+            .offset
+            .checked_sub(to_usize(header_addr.wrapping_sub(load_addr))?)
+            .ok_or(MultibootError::InvalidHeader)?;
+        let file_size = if load_end_addr == 0 {
+            kernel.len() - offset
+        } else {
+            to_usize(load_end_addr.saturating_sub(load_addr))?
+        };
+        let mem_size = if bss_end_addr == 0 {
+            file_size
+        } else {
+            to_usize(bss_end_addr.saturating_sub(load_addr))?.max(file_size)
+        };
+
+        Ok(vec![Segment {
+            addr: load_addr.into(),
+            offset,
+            file_size,
+            mem_size,
+        }])
+    }
+
+    /// Get the lowest address of the segments of the kernel.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the kernel does not have any segments to load.
+    fn load_base(&self, kernel: &[u8]) -> Result<u64, MultibootError> {
+        self.segments(kernel)?
+            .iter()
+            .map(|x| x.addr)
+            .min()
+            .ok_or(MultibootError::InvalidElf)
+    }
+}
+
+/// A segment of the kernel that is loaded into memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Segment {
+    /// The physical address of the segment.
+    addr: u64,
+
+    /// The offset of the segment in the kernel.
+    offset: usize,
+
+    /// The size of the segment in the kernel.
+    file_size: usize,
+
+    /// The size of the segment in memory, where the bytes after the size in the kernel are zeroed.
+    mem_size: usize,
+}
+
+/// A builder for the Multiboot2 boot information.
+struct BootInfo(Vec<u8>);
+
+impl BootInfo {
+    /// Create a new [`BootInfo`] with space for the total size.
+    fn new() -> Self {
+        Self(vec![0; 8])
+    }
+
+    /// Add a tag with its data to the boot information.
+    fn tag(&mut self, ty: u32, data: &[&[u8]]) {
+        self.0.resize(self.0.len().next_multiple_of(ALIGN), 0);
+        let size = 8 + data.iter().map(|x| x.len()).sum::<usize>();
+        self.0.extend(ty.to_le_bytes());
+        self.0
+            .extend(u32::try_from(size).unwrap_or_default().to_le_bytes());
+        for data in data {
+            self.0.extend(*data);
+        }
+    }
+
+    /// Add a tag with a string to the boot information.
+    fn string_tag(&mut self, ty: u32, prefix: &[u8], string: &str) {
+        self.tag(ty, &[prefix, string.as_bytes(), &[0]]);
+    }
+
+    /// Add the memory map tag, given the memory regions as their address, size and Multiboot2 memory type.
+    fn memory_map(&mut self, regions: &[(u64, u64, u32)]) {
+        let entries: Vec<u8> = regions
+            .iter()
+            .flat_map(|&(addr, size, ty)| {
+                [
+                    addr.to_le_bytes().as_slice(),
+                    &size.to_le_bytes(),
+                    &ty.to_le_bytes(),
+                    &[0; 4],
+                ]
+                .concat()
+            })
+            .collect();
+        self.tag(
+            TAG_MMAP,
+            &[&24u32.to_le_bytes(), &0u32.to_le_bytes(), &entries],
+        );
+    }
+
+    /// Add the framebuffer tag.
+    fn framebuffer(&mut self, fb: &Framebuffer) {
+        let mut colors = [0; 6];
+        for (i, mask) in fb.masks.iter().take(3).enumerate() {
+            colors[i * 2] = u8::try_from(mask.trailing_zeros()).unwrap_or_default();
+            colors[i * 2 + 1] = u8::try_from(mask.count_ones()).unwrap_or_default();
+        }
+
+        self.tag(
+            TAG_FRAMEBUFFER,
+            &[
+                &fb.base.to_le_bytes(),
+                &fb.pitch.to_le_bytes(),
+                &fb.width.to_le_bytes(),
+                &fb.height.to_le_bytes(),
+                &[32, FRAMEBUFFER_TYPE_RGB, 0, 0],
+                &colors,
+            ],
+        );
+    }
+
+    /// Finish the boot information with the end tag, and write the total size.
+    fn finish(mut self) -> Vec<u8> {
+        self.tag(TAG_END, &[]);
+        let size = u32::try_from(self.0.len()).unwrap_or_default();
+        self.0[..4].copy_from_slice(&size.to_le_bytes());
+        self.0
+    }
+}
+
+/// Loads a boot option from a given [`Config`] as a Multiboot2 kernel.
+///
+/// If the kernel is started, then this never returns.
+///
+/// # Errors
+///
+/// May return an `Error` if the kernel or the modules could not be read, the kernel is not a supported Multiboot2
+/// kernel, the kernel or a module was refused while Secure Boot is enabled, or memory could not be allocated.
+pub(crate) fn load_boot_option(config: &Config) -> BootResult<Handle> {
+    let handle = *config
+        .fs_handle
+        .ok_or_else(|| LoadError::ConfigMissingHandle(config.filename.clone()))?;
+
+    let mut fs = UefiFileSystem::from_handle(handle)?;
+
+    let path = get_efi(config)?;
+    let kernel = read_verified(&mut fs, path, config.sha256, TPM2_PCR_BOOT_LOADER_CODE)?;
+    let kernel = decompress(kernel)?;
+
+    let header = MultibootHeader::parse(&kernel)?;
+    let entry = header.entry()?;
+
+    let cmdline = image_cmdline(path, config.options.as_deref().unwrap_or_default());
+    measure_cmdline(&str_to_cstr(&cmdline)?);
+
+    let mut info = BootInfo::new();
+    info.string_tag(TAG_CMDLINE, &[], &cmdline);
+    info.string_tag(TAG_BOOT_LOADER_NAME, &[], BOOT_LOADER_NAME);
+
+    let base = load_kernel(&kernel, &header)?;
+    let entry = entry.wrapping_add(base.wrapping_sub(header.load_base(&kernel)?));
+    if header.relocatable.is_some() {
+        info.tag(
+            TAG_LOAD_BASE_ADDR,
+            &[&u32::try_from(base).unwrap_or_default().to_le_bytes()],
+        );
+    }
+
+    for module in &config.modules {
+        let (path, cmdline) = split_cmdline(module);
+        let content = read_verified(&mut fs, path, None, TPM2_PCR_INITRD)?;
+        let region = allocate(
+            content.len(),
+            AllocateType::MaxAddress(u32::MAX.into()),
+            PAGE_SIZE,
+        )?;
+        region.copy_from_slice(&content);
+
+        let (Ok(start), Ok(len)) = (u32::try_from(address(region)), u32::try_from(region.len()))
+        else {
+            return Err(MultibootError::ModuleAddress(path.to_owned()).into());
+        };
+        let end = start
+            .checked_add(len)
+            .ok_or_else(|| MultibootError::ModuleAddress(path.to_owned()))?;
+        info.string_tag(
+            TAG_MODULE,
+            &[start.to_le_bytes(), end.to_le_bytes()].concat(),
+            &image_cmdline(path, cmdline),
+        );
+    }
+
+    // the random seed table is installed here, so that it is in the memory map of the boot information
+    bli::prepare_handoff(&mut RuntimeUefiVariableStorage, config);
+    log::logger().flush(); // flush before the memory map is captured, as nothing can be logged once boot services exit
+    add_system_tags(&mut info)?;
+    let info = info.finish();
+    let region = allocate(
+        info.len(),
+        AllocateType::MaxAddress(u32::MAX.into()),
+        PAGE_SIZE,
+    )?;
+    region.copy_from_slice(&info);
+
+    // SAFETY: the kernel supports the EFI amd64 machine state, was loaded at the address that the entry point was
+    // computed from, and the boot information is complete.
+    unsafe { enter_kernel(entry, address(region)) }
+}
+
+/// Add the tags of the memory map, the framebuffer, ACPI and EFI to the boot information.
+///
+/// # Errors
+///
+/// May return an `Error` if the memory map could not be retrieved.
+fn add_system_tags(info: &mut BootInfo) -> BootResult<()> {
+    let map = boot::memory_map(MemoryType::LOADER_DATA)?;
+    let regions: Vec<_> = map
+        .entries()
+        .map(|desc| {
+            (
+                desc.phys_start,
+                desc.page_count * PAGE_SIZE as u64,
+                memory_type(desc.ty),
+            )
+        })
+        .collect();
+
+    let (lower, upper) = basic_meminfo(&regions);
+    info.tag(
+        TAG_BASIC_MEMINFO,
+        &[&lower.to_le_bytes(), &upper.to_le_bytes()],
+    );
+    info.memory_map(&regions);
+
+    if let Some(fb) = get_framebuffer() {
+        info.framebuffer(&fb);
+    }
+
+    if let Some((ty, rsdp)) = get_rsdp() {
+        info.tag(ty, &[&rsdp]);
+    }
+
+    let systab = uefi::table::system_table_raw().map_or(0, |x| x.as_ptr().addr() as u64);
+    info.tag(TAG_EFI64, &[&systab.to_le_bytes()]);
+
+    let meta = map.meta();
+    let desc_size = u32::try_from(meta.desc_size).unwrap_or_default();
+    info.tag(
+        TAG_EFI_MMAP,
+        &[
+            &desc_size.to_le_bytes(),
+            &meta.desc_version.to_le_bytes(),
+            &map.buffer()[..meta.map_size],
+        ],
+    );
+    info.tag(TAG_EFI_BS, &[]);
+
+    let image_handle = boot::image_handle().as_ptr().addr() as u64;
+    info.tag(TAG_EFI64_IH, &[&image_handle.to_le_bytes()]);
+
+    Ok(())
+}
+
+/// Copy the segments of the kernel into memory, and return the address that the lowest segment was loaded at.
+///
+/// The kernel is loaded at the addresses of its segments if possible. Otherwise, if it is relocatable, then it is
+/// loaded within the range and with the alignment of its relocatable tag.
+///
+/// # Errors
+///
+/// May return an `Error` if the segments could not be read, or the kernel could not be loaded.
+fn load_kernel(kernel: &[u8], header: &MultibootHeader) -> BootResult<u64> {
+    let segments = header.segments(kernel)?;
+    let start = header.load_base(kernel)? & !(PAGE_SIZE as u64 - 1);
+    let mut end = start;
+    for segment in &segments {
+        let segment_end = segment
+            .addr
+            .checked_add(segment.mem_size as u64)
+            .ok_or(MultibootError::InvalidElf)?;
+        end = end.max(segment_end);
+    }
+    let size = end
+        .checked_sub(start)
+        .and_then(|x| usize::try_from(x).ok())
+        .ok_or(MultibootError::LoadAddress(start))?;
+
+    let region = match (
+        allocate(size, AllocateType::Address(start), PAGE_SIZE),
+        header.relocatable,
+    ) {
+        (Ok(region), _) => region,
+        (Err(_), Some([min_addr, max_addr, align])) => {
+            let align = usize::try_from(align)
+                .unwrap_or_default()
+                .max(PAGE_SIZE)
+                .next_power_of_two();
+            let region = allocate(size, AllocateType::MaxAddress(max_addr.into()), align)?;
+            if address(region) < min_addr.into() {
+                return Err(MultibootError::LoadAddress(start).into());
+            }
+            region
+        }
+        (Err(_), None) => return Err(MultibootError::LoadAddress(start).into()),
+    };
+
+    region.fill(0);
+    for segment in segments {
+        let offset = usize::try_from(segment.addr - start).unwrap_or_default();
+        let content = kernel
+            .get(segment.offset..segment.offset + segment.file_size)
+            .ok_or(MultibootError::InvalidElf)?;
+        region[offset..offset + content.len()].copy_from_slice(content);
+    }
+
+    Ok(address(region) + (header.load_base(kernel)? - start))
+}
+
+/// Get the loadable segments of an ELF executable, for both 32-bit and 64-bit ELF.
+///
+/// # Errors
+///
+/// May return an `Error` if the kernel is not a valid ELF executable, or it does not have any loadable segments.
+fn get_elf_segments(kernel: &[u8]) -> Result<Vec<Segment>, MultibootError> {
+    if kernel.get(..4) != Some(b"\x7FELF") {
+        return Err(MultibootError::InvalidElf);
+    }
+
+    let is_64 = match kernel.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(MultibootError::InvalidElf),
+    };
+
+    let to_usize = |x: u64| usize::try_from(x).map_err(|_| MultibootError::InvalidElf);
+    let (phoff, phentsize, phnum) = if is_64 {
+        (
+            read_u64(kernel, 0x20).map(to_usize),
+            read_u16(kernel, 0x36),
+            read_u16(kernel, 0x38),
+        )
+    } else {
+        (
+            read_u32(kernel, 0x1C).map(|x| to_usize(x.into())),
+            read_u16(kernel, 0x2A),
+            read_u16(kernel, 0x2C),
+        )
+    };
+    let (Some(phoff), Some(phentsize), Some(phnum)) = (phoff, phentsize, phnum) else {
+        return Err(MultibootError::InvalidElf);
+    };
+    let phoff = phoff?;
+
+    let mut segments = Vec::new();
+    for i in 0..usize::from(phnum) {
+        let ph = phoff + i * usize::from(phentsize);
+        let field = |offset64, offset32| {
+            if is_64 {
+                read_u64(kernel, ph + offset64)
+            } else {
+                read_u32(kernel, ph + offset32).map(u64::from)
+            }
+        };
+
+        if read_u32(kernel, ph) != Some(PT_LOAD) {
+            continue;
+        }
+
+        let (Some(offset), Some(addr), Some(file_size), Some(mem_size)) =
+            (field(8, 4), field(24, 12), field(32, 16), field(40, 20))
+        else {
+            return Err(MultibootError::InvalidElf);
+        };
+
+        if mem_size != 0 {
+            segments.push(Segment {
+                addr,
+                offset: to_usize(offset)?,
+                file_size: to_usize(file_size)?,
+                mem_size: to_usize(mem_size.max(file_size))?,
+            });
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(MultibootError::InvalidElf);
+    }
+    Ok(segments)
+}
+
+/// Decompress a kernel if it is compressed with gzip. Otherwise, the kernel is returned as is.
+///
+/// # Errors
+///
+/// May return an `Error` if the kernel is compressed, and could not be decompressed.
+fn decompress(kernel: Vec<u8>) -> Result<Vec<u8>, MultibootError> {
+    /// The flag of a gzip header that indicates a header CRC.
+    const FHCRC: u8 = 1 << 1;
+    /// The flag of a gzip header that indicates extra fields.
+    const FEXTRA: u8 = 1 << 2;
+    /// The flag of a gzip header that indicates a file name.
+    const FNAME: u8 = 1 << 3;
+    /// The flag of a gzip header that indicates a comment.
+    const FCOMMENT: u8 = 1 << 4;
+
+    if kernel.get(..3) != Some(&[0x1F, 0x8B, 8]) {
+        return Ok(kernel);
+    }
+
+    let flags = kernel.get(3).copied().unwrap_or_default();
+    let mut offset = 10;
+    if flags & FEXTRA != 0 {
+        offset += 2 + usize::from(read_u16(&kernel, offset).ok_or(MultibootError::InvalidGzip)?);
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let len = kernel
+                .get(offset..)
+                .and_then(|x| x.iter().position(|&b| b == 0))
+                .ok_or(MultibootError::InvalidGzip)?;
+            offset += len + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+
+    let data = kernel.get(offset..).ok_or(MultibootError::InvalidGzip)?;
+    miniz_oxide::inflate::decompress_to_vec(data).map_err(|_| MultibootError::InvalidGzip)
+}
+
+/// Split a module or kernel line into its path and its command line.
+fn split_cmdline(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    line.split_once(char::is_whitespace)
+        .map_or((line, ""), |(path, cmdline)| (path, cmdline.trim_start()))
+}
+
+/// Get the command line of a kernel or a module, which starts with its path like GRUB passes it.
+fn image_cmdline(path: &str, cmdline: &str) -> String {
+    if cmdline.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{path} {cmdline}")
+    }
+}
+
+/// Get a copy of the RSDP and the tag that it belongs in, preferring the ACPI 2.0 RSDP.
+fn get_rsdp() -> Option<(u32, Vec<u8>)> {
+    /// The size of the ACPI 1.0 RSDP.
+    const RSDP_V1_SIZE: usize = 20;
+    /// The offset of the length of the ACPI 2.0 RSDP.
+    const RSDP_LENGTH: usize = 20;
+
+    uefi::system::with_config_table(|tables| {
+        let find = |guid| {
+            tables
+                .iter()
+                .find(|x| x.guid == guid)
+                .map(|x| x.address.cast::<u8>())
+        };
+
+        let (ty, ptr) = find(ACPI2_GUID)
+            .map(|x| (TAG_ACPI_NEW, x))
+            .or_else(|| find(ACPI_GUID).map(|x| (TAG_ACPI_OLD, x)))?;
+        if ptr.is_null() {
+            return None;
+        }
+
+        // SAFETY: the firmware guarantees that the ACPI configuration tables point to a valid RSDP, which is at least
+        // as long as the ACPI 1.0 RSDP. The ACPI 2.0 RSDP is as long as its length field.
+        unsafe {
+            let len = if ty == TAG_ACPI_NEW {
+                ptr.add(RSDP_LENGTH).cast::<u32>().read_unaligned() as usize
+            } else {
+                RSDP_V1_SIZE
+            };
+            Some((ty, core::slice::from_raw_parts(ptr, len).to_owned()))
+        }
+    })
+}
+
+/// Get the amount of lower and upper memory in KiB from the memory regions.
+///
+/// Lower memory is the available memory that starts at 0, up to 640 KiB. Upper memory is the available memory that
+/// starts at 1 MiB, up to the first hole.
+fn basic_meminfo(regions: &[(u64, u64, u32)]) -> (u32, u32) {
+    /// The end of lower memory.
+    const LOWER_END: u64 = 640 * 1024;
+    /// The start of upper memory.
+    const UPPER_START: u64 = 1024 * 1024;
+
+    let contiguous_from = |start: u64| {
+        let mut end = start;
+        while let Some(&(addr, size, _)) = regions
+            .iter()
+            .find(|&&(addr, size, ty)| ty == MEMORY_AVAILABLE && addr <= end && addr + size > end)
+        {
+            end = addr + size;
+        }
+        end - start
+    };
+
+    let to_kib = |x: u64| u32::try_from(x / 1024).unwrap_or(u32::MAX);
+    (
+        to_kib(contiguous_from(0).min(LOWER_END)),
+        to_kib(contiguous_from(UPPER_START)),
+    )
+}
+
+/// Convert a UEFI [`MemoryType`] into a Multiboot2 memory type.
+///
+/// Memory that is used by boot services or the boot manager is available once boot services are exited.
+fn memory_type(ty: MemoryType) -> u32 {
+    match ty {
+        MemoryType::CONVENTIONAL
+        | MemoryType::LOADER_CODE
+        | MemoryType::LOADER_DATA
+        | MemoryType::BOOT_SERVICES_CODE
+        | MemoryType::BOOT_SERVICES_DATA => MEMORY_AVAILABLE,
+        MemoryType::ACPI_RECLAIM => MEMORY_ACPI_RECLAIMABLE,
+        MemoryType::ACPI_NON_VOLATILE => MEMORY_NVS,
+        MemoryType::UNUSABLE => MEMORY_BADRAM,
+        _ => MEMORY_RESERVED,
+    }
+}
+
+/// Call the entry point of the kernel in the EFI amd64 machine state.
+///
+/// # Safety
+///
+/// `entry` must be the EFI amd64 entry point of a loaded Multiboot2 kernel, and `info` must point to complete boot
+/// information.
+unsafe fn enter_kernel(entry: u64, info: u64) -> ! {
+    // SAFETY: the caller guarantees that the kernel is loaded and the boot information is complete. The stack is
+    // aligned for a call, and the shadow space that UEFI calls require is reserved.
+    unsafe {
+        asm!(
+            "mov rbx, {info}",
+            "and rsp, -16",
+            "sub rsp, 32",
+            "call {entry}",
+            "ud2",
+            info = in(reg) info,
+            entry = in(reg) entry,
+            in("rax") BOOTLOADER_MAGIC,
+            options(noreturn),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a Multiboot2 header at an offset in a kernel, with the given tags.
+    fn kernel_with_header(offset: usize, tags: &[(u16, u16, &[u32])]) -> Vec<u8> {
+        let mut header = Vec::new();
+        for &(ty, flags, data) in tags.iter().chain([(HEADER_TAG_END, 0, &[][..])].iter()) {
+            header.resize(header.len().next_multiple_of(ALIGN), 0);
+            header.extend(ty.to_le_bytes());
+            header.extend(flags.to_le_bytes());
+            header.extend(
+                u32::try_from(8 + data.len() * 4)
+                    .unwrap_or_default()
+                    .to_le_bytes(),
+            );
+            header.extend(data.iter().flat_map(|x| x.to_le_bytes()));
+        }
+
+        let length = u32::try_from(16 + header.len()).unwrap_or_default();
+        let checksum = 0u32.wrapping_sub(HEADER_MAGIC).wrapping_sub(length);
+
+        let mut kernel = vec![0; offset];
+        for field in [HEADER_MAGIC, 0, length, checksum] {
+            kernel.extend(field.to_le_bytes());
+        }
+        kernel.extend(header);
+        kernel
+    }
+
+    /// Build a 64-bit ELF executable with two loadable segments.
+    fn elf64() -> Vec<u8> {
+        let mut kernel = vec![0; 0x200];
+        kernel[..5].copy_from_slice(b"\x7FELF\x02");
+        kernel[0x20..0x28].copy_from_slice(&0x40u64.to_le_bytes());
+        kernel[0x36..0x38].copy_from_slice(&0x38u16.to_le_bytes());
+        kernel[0x38..0x3A].copy_from_slice(&3u16.to_le_bytes());
+
+        let segments: [(u32, u64, u64, u64, u64); 3] = [
+            (PT_LOAD, 0x100, 0x10_0000, 0x40, 0x80),
+            (2, 0x140, 0, 0x10, 0x10),
+            (PT_LOAD, 0x180, 0x10_1000, 0x20, 0x20),
+        ];
+        for (i, (ty, offset, addr, file_size, mem_size)) in segments.into_iter().enumerate() {
+            let ph = 0x40 + i * 0x38;
+            kernel[ph..ph + 4].copy_from_slice(&ty.to_le_bytes());
+            kernel[ph + 8..ph + 16].copy_from_slice(&offset.to_le_bytes());
+            kernel[ph + 24..ph + 32].copy_from_slice(&addr.to_le_bytes());
+            kernel[ph + 32..ph + 40].copy_from_slice(&file_size.to_le_bytes());
+            kernel[ph + 40..ph + 48].copy_from_slice(&mem_size.to_le_bytes());
+        }
+        kernel
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_header() {
+        let kernel = kernel_with_header(
+            64,
+            &[
+                (HEADER_TAG_INFORMATION_REQUEST, 0, &[TAG_CMDLINE, TAG_EFI64]),
+                (HEADER_TAG_EFI_BS, 0, &[]),
+                (HEADER_TAG_ENTRY_ADDRESS_EFI64, 0, &[0x10_0040]),
+                (
+                    HEADER_TAG_RELOCATABLE,
+                    0,
+                    &[0x10_0000, 0x1000_0000, 0x20_0000, 0],
+                ),
+                (HEADER_TAG_MAX + 1, TAG_OPTIONAL, &[]),
+            ],
+        );
+        let header = MultibootHeader::parse(&kernel).expect("Header should be valid");
+        assert_eq!(
+            header,
+            MultibootHeader {
+                offset: 64,
+                address: None,
+                efi_bs: true,
+                entry_efi64: Some(0x10_0040),
+                relocatable: Some([0x10_0000, 0x1000_0000, 0x20_0000]),
+            }
+        );
+        assert_eq!(header.entry().ok(), Some(0x10_0040));
+
+        let kernel = kernel_with_header(0, &[(HEADER_TAG_ENTRY_ADDRESS_EFI64, 0, &[0x10_0040])]);
+        let header = MultibootHeader::parse(&kernel).expect("Header should be valid");
+        assert!(matches!(
+            header.entry(),
+            Err(MultibootError::UnsupportedMachineState)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_invalid_header() {
+        assert!(matches!(
+            MultibootHeader::parse(&[0; 1024]),
+            Err(MultibootError::NoHeader)
+        ));
+
+        // the header must be aligned
+        let kernel = kernel_with_header(4, &[]);
+        assert!(matches!(
+            MultibootHeader::parse(&kernel),
+            Err(MultibootError::NoHeader)
+        ));
+
+        let mut kernel = kernel_with_header(0, &[]);
+        kernel[12] ^= 1;
+        assert!(matches!(
+            MultibootHeader::parse(&kernel),
+            Err(MultibootError::InvalidHeader)
+        ));
+
+        let kernel = kernel_with_header(0, &[(HEADER_TAG_INFORMATION_REQUEST, 0, &[13])]);
+        assert!(matches!(
+            MultibootHeader::parse(&kernel),
+            Err(MultibootError::UnsupportedTag(13))
+        ));
+
+        let kernel = kernel_with_header(0, &[(HEADER_TAG_MAX + 1, 0, &[])]);
+        assert!(matches!(
+            MultibootHeader::parse(&kernel),
+            Err(MultibootError::UnsupportedTag(11))
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_segments() {
+        let kernel = elf64();
+        let header = MultibootHeader::default();
+        assert_eq!(
+            header.segments(&kernel).ok(),
+            Some(vec![
+                Segment {
+                    addr: 0x10_0000,
+                    offset: 0x100,
+                    file_size: 0x40,
+                    mem_size: 0x80,
+                },
+                Segment {
+                    addr: 0x10_1000,
+                    offset: 0x180,
+                    file_size: 0x20,
+                    mem_size: 0x20,
+                },
+            ])
+        );
+        assert_eq!(header.load_base(&kernel).ok(), Some(0x10_0000));
+
+        let header = MultibootHeader {
+            offset: 0x20,
+            address: Some([0x20_0020, 0x20_0000, 0x20_0100, 0x20_0200]),
+            ..MultibootHeader::default()
+        };
+        assert_eq!(
+            header.segments(&kernel).ok(),
+            Some(vec![Segment {
+                addr: 0x20_0000,
+                offset: 0,
+                file_size: 0x100,
+                mem_size: 0x200,
+            }])
+        );
+
+        assert!(matches!(
+            MultibootHeader::default().segments(&[0; 64]),
+            Err(MultibootError::InvalidElf)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_boot_info() {
+        let mut info = BootInfo::new();
+        info.string_tag(TAG_CMDLINE, &[], "console=vga");
+        info.tag(TAG_EFI_BS, &[]);
+        info.memory_map(&[(0, 0x9_F000, MEMORY_AVAILABLE)]);
+        let info = info.finish();
+
+        assert_eq!(read_u32(&info, 0), u32::try_from(info.len()).ok());
+        assert_eq!(read_u32(&info, 8), Some(TAG_CMDLINE));
+        assert_eq!(read_u32(&info, 12), Some(8 + 12));
+        assert_eq!(&info[16..28], b"console=vga\0");
+
+        // every tag is aligned to 8 bytes
+        assert_eq!(read_u32(&info, 32), Some(TAG_EFI_BS));
+        assert_eq!(read_u32(&info, 40), Some(TAG_MMAP));
+        assert_eq!(read_u32(&info, 44), Some(16 + 24));
+        assert_eq!(read_u32(&info, 48), Some(24));
+        assert_eq!(read_u64(&info, 64), Some(0x9_F000));
+        assert_eq!(read_u32(&info, 72), Some(MEMORY_AVAILABLE));
+        assert_eq!(read_u32(&info, 80), Some(TAG_END));
+        assert_eq!(info.len(), 88);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_framebuffer() {
+        let mut info = BootInfo::new();
+        info.framebuffer(&Framebuffer {
+            width: 1024,
+            height: 768,
+            pitch: 4096,
+            base: 0x8000_0000,
+            size: 4096 * 768,
+            masks: [0x00FF_0000, 0xFF00, 0xFF, 0xFF00_0000],
+        });
+        let info = info.finish();
+
+        assert_eq!(read_u32(&info, 8), Some(TAG_FRAMEBUFFER));
+        assert_eq!(read_u64(&info, 16), Some(0x8000_0000));
+        assert_eq!(read_u32(&info, 24), Some(4096));
+        assert_eq!(read_u32(&info, 28), Some(1024));
+        assert_eq!(read_u32(&info, 32), Some(768));
+        assert_eq!(
+            &info[36..46],
+            &[32, FRAMEBUFFER_TYPE_RGB, 0, 0, 16, 8, 8, 8, 0, 8]
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_basic_meminfo() {
+        let regions = [
+            (0, 0x9_F000, MEMORY_AVAILABLE),
+            (0x9_F000, 0x6_1000, MEMORY_RESERVED),
+            (0x10_0000, 0x70_0000, MEMORY_AVAILABLE),
+            (0x80_0000, 0x10_0000, MEMORY_AVAILABLE),
+            (0x90_0000, 0x1000, MEMORY_ACPI_RECLAIMABLE),
+            (0x100_0000, 0x100_0000, MEMORY_AVAILABLE),
+        ];
+        assert_eq!(basic_meminfo(&regions), (636, 8 * 1024));
+        assert_eq!(basic_meminfo(&[]), (0, 0));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_decompress() {
+        let kernel = elf64();
+        assert_eq!(decompress(kernel.clone()).ok(), Some(kernel.clone()));
+
+        let mut gzip = vec![0x1F, 0x8B, 8, 1 << 3, 0, 0, 0, 0, 0, 0xFF]; // with a file name
+        gzip.extend(b"xen\0");
+        gzip.extend(miniz_oxide::deflate::compress_to_vec(&kernel, 6));
+        gzip.extend([0; 8]);
+        assert_eq!(decompress(gzip).ok(), Some(kernel));
+
+        assert!(matches!(
+            decompress(vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF]),
+            Err(MultibootError::InvalidGzip)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_split_cmdline() {
+        assert_eq!(
+            split_cmdline("/vmlinuz  root=/dev/sda1 ro"),
+            ("/vmlinuz", "root=/dev/sda1 ro")
+        );
+        assert_eq!(split_cmdline(" /initrd.img "), ("/initrd.img", ""));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_image_cmdline() {
+        assert_eq!(
+            image_cmdline("\\xen.gz", "dom0_mem=1G"),
+            "\\xen.gz dom0_mem=1G"
+        );
+        assert_eq!(image_cmdline("/initrd.img", ""), "/initrd.img");
+    }
+}
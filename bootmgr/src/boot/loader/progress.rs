@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Progress of the downloads of the network boot loaders
+//!
+//! The HTTP and TFTP boot loaders report the progress of their downloads through a [`ProgressCallback`], which a
+//! frontend may set with [`set_progress_callback`] to draw it. The progress is also logged every [`PROGRESS_STEP`]
+//! percent, whether there is a callback or not.
+
+use alloc::string::String;
+use core::cell::Cell;
+
+use log::info;
+
+/// The amount of progress in percent between each progress message.
+const PROGRESS_STEP: usize = 10;
+
+/// The function signature for a progress callback.
+///
+/// It is given the name of the file that is downloaded, such as its URL or path, the amount of bytes that were
+/// downloaded, and the total size of the file if it is known. It is called once when the download starts, and then
+/// every time more of the file is downloaded. When the download is finished, it is called with `done` equal to `len`,
+/// even if the total size was not known before.
+pub type ProgressCallback = fn(name: &str, done: usize, len: Option<usize>);
+
+/// The progress callback that was set by the frontend, if there is one.
+static PROGRESS_CALLBACK: ProgressCallbackCell = ProgressCallbackCell(Cell::new(None));
+
+/// Storage struct for the [`ProgressCallback`].
+struct ProgressCallbackCell(Cell<Option<ProgressCallback>>);
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for ProgressCallbackCell {}
+
+/// Set the callback that is called with the progress of downloads, or remove it with `None`.
+pub fn set_progress_callback(callback: Option<ProgressCallback>) {
+    PROGRESS_CALLBACK.0.set(callback);
+}
+
+/// The progress of a download, which is given to the [`ProgressCallback`] and logged every [`PROGRESS_STEP`] percent.
+pub(crate) struct Progress {
+    /// The name of the download.
+    name: String,
+
+    /// The total size of the download, if it is known.
+    len: Option<usize>,
+
+    /// The amount of bytes that were downloaded at the last update.
+    done: usize,
+
+    /// The percentage at which progress was last logged.
+    last: usize,
+}
+
+impl Progress {
+    /// Start reporting the progress of a download.
+    pub(crate) fn new(name: impl Into<String>, len: Option<usize>) -> Self {
+        let name = name.into();
+        match len {
+            Some(len) => info!("Downloading {name} ({len} bytes)"),
+            None => info!("Downloading {name}"),
+        }
+
+        if let Some(callback) = PROGRESS_CALLBACK.0.get() {
+            callback(&name, 0, len);
+        }
+        Self {
+            name,
+            len,
+            done: 0,
+            last: 0,
+        }
+    }
+
+    /// Update the progress of the download, given the amount of bytes that were downloaded.
+    pub(crate) fn update(&mut self, done: usize) {
+        self.done = done;
+        if let Some(callback) = PROGRESS_CALLBACK.0.get() {
+            callback(&self.name, done, self.len);
+        }
+
+        // the percentage cannot be known without the total size
+        let Some(len) = self.len else {
+            return;
+        };
+
+        let percent = get_percent(done, len);
+        if percent >= self.last + PROGRESS_STEP {
+            self.last = percent - percent % PROGRESS_STEP;
+            info!("Downloading {}: {percent}%", self.name);
+        }
+    }
+
+    /// Finish the download, given its total size.
+    ///
+    /// If the total size was not known, or the last update did not report the whole download, then the progress is
+    /// updated a final time with the total size.
+    pub(crate) fn finish(mut self, len: usize) {
+        if self.len != Some(len) || self.done != len {
+            self.len = Some(len);
+            self.update(len);
+        }
+    }
+}
+
+/// Get the percentage of a download that is done.
+fn get_percent(done: usize, len: usize) -> usize {
+    done.saturating_mul(100)
+        .checked_div(len)
+        .map_or(100, |x| x.min(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_progress() {
+        assert_eq!(get_percent(0, 1000), 0);
+        assert_eq!(get_percent(333, 1000), 33);
+        assert_eq!(get_percent(2000, 1000), 100);
+        assert_eq!(get_percent(0, 0), 100);
+
+        let mut progress = Progress {
+            name: "http://10.0.2.2/boot.efi".to_owned(),
+            len: Some(1000),
+            done: 0,
+            last: 0,
+        };
+        progress.update(50);
+        assert_eq!(progress.last, 0);
+        progress.update(250);
+        assert_eq!(progress.last, 20);
+        progress.update(1000);
+        assert_eq!(progress.last, 100);
+
+        let mut progress = Progress {
+            name: "bootx64.efi".to_owned(),
+            len: None,
+            done: 0,
+            last: 0,
+        };
+        progress.update(5000);
+        assert_eq!(progress.last, 0);
+    }
+}
@@ -3,71 +3,625 @@
 
 //! The boot loader for network executables (really EFI loaded over network)
 //!
-//! It downloads a file from a TFTP server, then uses that file buffer as an EFI executable.
-//! The current support for PXE is basic, and any more complex configurations (such as HTTP boot)
-//! should use a more comprehensive PXE loader such as `iPXE` instead. This should be preferred even if your
-//! configuration is very simple.
+//! It downloads a file from a TFTP server, then uses that file buffer as an EFI executable. The server is given by the
+//! address in the [`Config`], which may be either IPv4 or IPv6.
+//! The current support for PXE is basic, and any more complex configurations should use a more comprehensive PXE
+//! loader such as `iPXE` instead. This should be preferred even if your configuration is very simple.
 //!
-//! Currently, there are no plans to add support for more advanced configurations like HTTP boot.
+//! Boot files that are URLs are booted through the HTTP boot loader instead.
+//!
+//! Any initrds in the command line are downloaded from the same server, and are given to Linux through the initrd
+//! media device path, which is supported by the EFI stub since Linux 5.8.
+//!
+//! The progress of downloads is reported through [`super::progress`], by installing a PXE Base Code callback that
+//! counts the TFTP data packets as they are received. The same callback is used by [`dhcp_with_timeout`] to abort DHCP
+//! once it takes too long. Transient errors such as timeouts are retried with an exponential backoff. The loaded image is given a device path of the network interface followed by the IP addresses of the
+//! interface and the server, so that the image knows where it came from.
+//!
+//! # Safety
+//!
+//! This uses unsafe in three places.
+//!
+//! 1. [`boot::install_protocol_interface`] requires that the interface lasts as long as it is installed. The callback
+//!    interface is a static, so it lasts for the lifetime of the program.
+//! 2. [`boot::uninstall_protocol_interface`] requires that the interface was installed on the handle. The handle is
+//!    only returned once the interface was installed on it.
+//! 3. The callback creates a slice from the packet given by the caller. The caller must give a packet that is valid
+//!    for its length, and the pointer is checked to not be null.
 
-use alloc::vec;
-use core::{net::Ipv4Addr, str::FromStr};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ptr, slice,
+    time::Duration,
+};
 
+use log::warn;
 use uefi::{
-    Handle, boot,
-    proto::network::{IpAddress, pxe::BaseCode},
+    CStr8, Guid, Handle, Status, boot, guid,
+    proto::{
+        device_path::DevicePath,
+        loaded_image::LoadedImage,
+        network::{IpAddress, pxe::BaseCode},
+    },
 };
+use uefi_raw::Boolean;
 
 use crate::{
     BootResult,
     boot::{
-        loader::{LoadError, get_efi},
-        secure_boot::shim::shim_load_image,
+        loader::{
+            LoadError,
+            efi::{get_initrds, set_load_options},
+            get_efi,
+            initrd::install_initrd,
+            progress::Progress,
+        },
+        secure_boot::{self, hash_pin},
     },
     config::Config,
     system::{
         fs::ONE_GIGABYTE,
-        helper::{bytes_to_cstr8, locate_protocol, str_to_cstring},
+        helper::{bytes_to_cstr8, join_ip_to_device_path, locate_protocol, str_to_cstring},
+        syslog,
+        time::Instant,
+        tpm::{TPM2_PCR_INITRD, measure},
     },
 };
 
+/// The amount of times a TFTP operation is attempted before giving up.
+const ATTEMPTS: u32 = 4;
+
+/// The delay before the first retry, which is doubled for every retry after it.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The initial size of the buffer if the server does not report the size of a file.
+const UNKNOWN_SIZE_BUFFER: usize = 16 * 1024 * 1024;
+
+/// The size of the buffer for the [`DevicePath`] of a downloaded file.
+const DEVICE_PATH_SIZE: usize = 512;
+
+/// The GUID of the PXE Base Code callback protocol.
+const PXE_CALLBACK_GUID: Guid = guid!("245dca21-fb7b-11d3-8f01-00a0c969723b");
+
+/// The revision of the PXE Base Code callback protocol.
+const PXE_CALLBACK_REVISION: u64 = 0x0001_0000;
+
+/// The callback function for DHCP operations.
+const PXE_FUNCTION_DHCP: u32 = 1;
+
+/// The callback function for MTFTP operations.
+const PXE_FUNCTION_MTFTP: u32 = 3;
+
+/// The callback status to continue the operation.
+const PXE_CALLBACK_CONTINUE: u32 = 1;
+
+/// The callback status to abort the operation.
+const PXE_CALLBACK_ABORT: u32 = 2;
+
+/// The TFTP opcode of a data packet.
+const TFTP_OPCODE_DATA: u16 = 3;
+
+/// The size of the header of a TFTP data packet, which is the opcode and the block number.
+const TFTP_HEADER_SIZE: usize = 4;
+
+/// The PXE Base Code callback protocol, which is called by [`BaseCode`] for every packet if it is enabled.
+#[repr(C)]
+struct PxeCallbackProtocol {
+    /// The revision of the protocol.
+    revision: u64,
+
+    /// The callback function.
+    callback: unsafe extern "efiapi" fn(
+        this: *mut Self,
+        function: u32,
+        received: Boolean,
+        packet_len: u32,
+        packet: *const u8,
+    ) -> u32,
+}
+
+/// The callback interface that is installed on the handle of [`BaseCode`].
+static PXE_CALLBACK: PxeCallbackProtocol = PxeCallbackProtocol {
+    revision: PXE_CALLBACK_REVISION,
+    callback: pxe_callback,
+};
+
+/// The progress of the current download.
+static DOWNLOAD: Download = Download::new();
+
+/// The start and the timeout of the current DHCP operation, if there is one.
+static DHCP_DEADLINE: DhcpDeadline = DhcpDeadline(Cell::new(None));
+
+/// Storage struct for the deadline of the current DHCP operation.
+struct DhcpDeadline(Cell<Option<(Instant, Duration)>>);
+
+impl DhcpDeadline {
+    /// Check if the current DHCP operation has taken longer than its timeout.
+    fn expired(&self) -> bool {
+        self.0
+            .get()
+            .is_some_and(|(start, timeout)| start.elapsed() > timeout)
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for DhcpDeadline {}
+
+/// Storage struct for the progress of the current download.
+struct Download {
+    /// The progress of the download, if there is a download.
+    progress: RefCell<Option<Progress>>,
+
+    /// The amount of bytes that were received.
+    done: Cell<usize>,
+
+    /// The block number of the last data packet, so that retransmitted packets are not counted twice.
+    last_block: Cell<Option<u16>>,
+}
+
+impl Download {
+    /// Get a new instance of [`Download`].
+    const fn new() -> Self {
+        Self {
+            progress: RefCell::new(None),
+            done: Cell::new(0),
+            last_block: Cell::new(None),
+        }
+    }
+
+    /// Start counting the progress of a download.
+    fn start(&self, progress: Progress) {
+        *self.progress.borrow_mut() = Some(progress);
+        self.restart();
+    }
+
+    /// Reset the amount of received bytes, as the download is starting from the beginning.
+    fn restart(&self) {
+        self.done.set(0);
+        self.last_block.set(None);
+    }
+
+    /// Stop counting the progress of the download, and return its progress.
+    fn finish(&self) -> Option<Progress> {
+        self.restart();
+        self.progress.borrow_mut().take()
+    }
+
+    /// Count a received packet, if it is a new TFTP data packet.
+    fn receive(&self, packet: &[u8]) {
+        let [opcode_hi, opcode_lo, block_hi, block_lo, ..] = *packet else {
+            return;
+        };
+        if u16::from_be_bytes([opcode_hi, opcode_lo]) != TFTP_OPCODE_DATA {
+            return;
+        }
+
+        let block = u16::from_be_bytes([block_hi, block_lo]);
+        if self.last_block.replace(Some(block)) == Some(block) {
+            return;
+        }
+
+        let done = self.done.get() + packet.len() - TFTP_HEADER_SIZE;
+        self.done.set(done);
+        if let Some(progress) = self.progress.borrow_mut().as_mut() {
+            progress.update(done);
+        }
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for Download {}
+
 /// Loads a boot option from a given [`Config`] through TFTP.
 ///
+/// The `initrd=` options of the command line are downloaded from the same server, and given to the image through
+/// the initrd media device path, as the image has no filesystem to load them from. The rest of the command line is
+/// set as the load options of the image.
+///
 /// # Errors
 ///
 /// May return an `Error` if the firmware does not support [`BaseCode`], or the
-/// EFI executable is not a valid Latin-1 string, or the filename is not a valid
-/// IP address, or [`boot::load_image`] fails.
+/// EFI executable is not a valid Latin-1 string, or the [`Config`] does not have
+/// a server address, or a file could not be downloaded, or [`boot::load_image`] fails.
 pub(crate) fn load_boot_option(config: &Config) -> BootResult<Handle> {
     let mut base_code = locate_protocol::<BaseCode>()?;
 
-    let addr_as_octets = Ipv4Addr::from_str(&config.filename)
-        .map_err(LoadError::IpParse)?
-        .octets();
-    let addr = IpAddress::new_v4(addr_as_octets);
+    let server = config
+        .tftp_server
+        .ok_or_else(|| LoadError::ConfigMissingServer(config.filename.clone()))?;
+    let addr = IpAddress::from(server);
 
     if !base_code.mode().started() {
         // at this point it should already be started
-        base_code.start(true)?;
+        base_code.start(server.is_ipv6())?;
+    }
+
+    // the path of the executable was normalized to backslashes, but most TFTP servers only accept forward slashes
+    let efi = get_efi(config)?.replace('\\', "/");
+    let vec = read_file(&mut base_code, &addr, &efi)?;
+
+    // a hash from the network only adds a constraint, and the image is still validated as usual when it is loaded
+    if let Some(hash) = config.download_sha256 {
+        hash_pin::check_image(&[hash], &vec)?;
+    }
+
+    let options = config.options.as_deref().unwrap_or_default();
+    let mut initrd = Vec::new();
+    for path in get_initrds(options) {
+        let content = read_file(&mut base_code, &addr, path)?;
+        if let Err(e) = measure(TPM2_PCR_INITRD, &content, path) {
+            warn!("Failed to measure initrd {path}: {e}");
+        }
+        initrd.extend(content);
+    }
+
+    let mut buf = [0; DEVICE_PATH_SIZE];
+    let src = boot::LoadImageSource::FromBuffer {
+        buffer: &vec,
+        file_path: get_file_path(&base_code, server, &mut buf),
+    };
+    let handle = secure_boot::load_image(boot::image_handle(), src, config.sha256)?;
+
+    if !initrd.is_empty() {
+        install_initrd(initrd)?;
+    }
+
+    let cmdline = strip_initrds(options);
+    if !cmdline.is_empty() {
+        let mut image = boot::open_protocol_exclusive::<LoadedImage>(handle)?;
+        set_load_options(&mut image, &cmdline)?;
     }
 
-    let efi = get_efi(config)?;
+    Ok(handle)
+}
 
-    let filename = str_to_cstring(efi)?; // convert efi to a CString, not to be confused with a CString16
+/// Download a file from a TFTP server.
+///
+/// The progress of the download is reported through [`Progress`]. Transient errors are retried with an exponential
+/// backoff. If the server does not support the `tsize` option, the buffer is grown until the file fits into it.
+///
+/// # Errors
+///
+/// May return an `Error` if the path is not a valid Latin-1 string, or the file could not be downloaded.
+pub(crate) fn read_file(
+    base_code: &mut BaseCode,
+    addr: &IpAddress,
+    path: &str,
+) -> BootResult<Vec<u8>> {
+    let filename = str_to_cstring(path)?; // convert the path to a CString, not to be confused with a CString16
     let filename_bytes = filename.as_bytes_with_nul();
     let filename_cstr = bytes_to_cstr8(filename_bytes)?;
 
-    // if its too big, its due to 32 bit platform limitations, and it would not be possible to allocate a buffer
-    // greater than the pointer width max either way. truncating should generally be fine on 64 bit platforms though
-    let size = usize::try_from(base_code.tftp_get_file_size(&addr, filename_cstr)?)
-        .unwrap_or(ONE_GIGABYTE);
+    // the size is reported by the server, so it is capped to what is ever downloaded. a file that is bigger than that
+    // does not fit into the buffer, so the download fails instead
+    let size = match with_retries(|| base_code.tftp_get_file_size(addr, filename_cstr), stall) {
+        Ok(size) => Some(
+            usize::try_from(size)
+                .unwrap_or(ONE_GIGABYTE)
+                .min(ONE_GIGABYTE),
+        ),
+        Err(e) => {
+            warn!("Failed to get the size of {path}, the server may not support tsize: {e}");
+            None
+        }
+    };
 
-    let mut vec = vec![0; size];
-    base_code.tftp_read_file(&addr, filename_cstr, Some(&mut vec))?;
+    // the syslog backend uses the same protocol, so it must not send while the download is in progress
+    let _hold = syslog::hold();
+    DOWNLOAD.start(Progress::new(path, size));
+    let callback = install_callback(base_code)
+        .inspect_err(|e| {
+            warn!("Failed to install the PXE callback, progress will not be reported: {e}");
+        })
+        .ok();
+    let result = download(base_code, addr, path, filename_cstr, size);
+    uninstall_callback(base_code, callback);
 
-    let src = boot::LoadImageSource::FromBuffer {
-        buffer: &vec,
-        file_path: None,
+    // report the final size, as it may not have been known, or the callback may not have been installed
+    let progress = DOWNLOAD.finish();
+    let vec = result?;
+    if let Some(progress) = progress {
+        progress.finish(vec.len());
+    }
+    Ok(vec)
+}
+
+/// Download a file into a buffer of the given size, or a growing buffer if the size is unknown.
+///
+/// # Errors
+///
+/// May return an `Error` if the buffer could not be allocated, the file could not be downloaded, or it is bigger than
+/// one gigabyte.
+fn download(
+    base_code: &mut BaseCode,
+    addr: &IpAddress,
+    path: &str,
+    filename: &CStr8,
+    size: Option<usize>,
+) -> BootResult<Vec<u8>> {
+    let mut capacity = size.unwrap_or(UNKNOWN_SIZE_BUFFER);
+    loop {
+        let mut vec = Vec::new();
+        if vec.try_reserve_exact(capacity).is_err() {
+            return Err(LoadError::ContentLenTooLarge(path.to_owned(), capacity).into());
+        }
+        vec.resize(capacity, 0);
+        let result = with_retries(
+            || {
+                DOWNLOAD.restart();
+                base_code.tftp_read_file(addr, filename, Some(&mut vec))
+            },
+            stall,
+        );
+
+        match result {
+            Ok(len) => {
+                vec.truncate(usize::try_from(len).unwrap_or(capacity));
+                return Ok(vec);
+            }
+            Err(e)
+                if e.status() == Status::BUFFER_TOO_SMALL
+                    && size.is_none()
+                    && capacity < ONE_GIGABYTE =>
+            {
+                capacity = capacity.saturating_mul(2).min(ONE_GIGABYTE);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Run a TFTP operation, and retry it with an exponential backoff if it fails with a transient error.
+///
+/// The operation is attempted up to [`ATTEMPTS`] times, and `delay` is called with the time to wait between attempts.
+///
+/// # Errors
+///
+/// May return an `Error` if the operation fails with an error that is not transient, or every attempt failed.
+fn with_retries<T>(
+    mut operation: impl FnMut() -> uefi::Result<T>,
+    mut delay: impl FnMut(Duration),
+) -> uefi::Result<T> {
+    let mut wait = RETRY_DELAY;
+    for attempt in 1..ATTEMPTS {
+        match operation() {
+            Err(e) if is_transient(e.status()) => {
+                warn!("TFTP attempt {attempt} of {ATTEMPTS} failed, retrying in {wait:?}: {e}");
+                delay(wait);
+                wait = wait.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    operation()
+}
+
+/// Check if an error from [`BaseCode`] may go away when the operation is retried.
+fn is_transient(status: Status) -> bool {
+    matches!(
+        status,
+        Status::TIMEOUT | Status::NO_RESPONSE | Status::NOT_READY
+    )
+}
+
+/// Wait for the given [`Duration`] using the firmware.
+fn stall(duration: Duration) {
+    boot::stall(usize::try_from(duration.as_micros()).unwrap_or(usize::MAX));
+}
+
+/// Get the [`DevicePath`] of a file downloaded from a server, which is the path of the network interface followed by
+/// the IP addresses of the interface and the server.
+fn get_file_path<'a>(
+    base_code: &BaseCode,
+    server: IpAddr,
+    buf: &'a mut [u8],
+) -> Option<&'a DevicePath> {
+    let mode = base_code.mode();
+    let local = if mode.using_ipv6() {
+        IpAddr::V6(Ipv6Addr::from(mode.station_ip()))
+    } else {
+        IpAddr::V4(Ipv4Addr::from(mode.station_ip()))
     };
-    shim_load_image(boot::image_handle(), src)
+
+    let handle = boot::get_handle_for_protocol::<BaseCode>().ok()?;
+    let dev_path = boot::open_protocol_exclusive::<DevicePath>(handle).ok()?;
+    match join_ip_to_device_path(&dev_path, local, server, buf) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            warn!("Failed to build the device path of the network interface: {e}");
+            None
+        }
+    }
+}
+
+/// Configure [`BaseCode`] through DHCP, and abort it if it takes longer than a timeout.
+///
+/// The firmware only calls the callback when it sends or receives a DHCP packet, so DHCP is aborted at the first packet
+/// after the timeout. As DHCP could otherwise block for as long as the firmware waits for a reply, it is not done if
+/// the callback could not be installed.
+///
+/// # Errors
+///
+/// May return an `Error` if the callback could not be installed, or DHCP failed or was aborted.
+pub(crate) fn dhcp_with_timeout(base_code: &mut BaseCode, timeout: Duration) -> uefi::Result<()> {
+    let callback = install_callback(base_code)?;
+
+    DHCP_DEADLINE.0.set(Some((Instant::now(), timeout)));
+    let result = base_code.dhcp(false);
+    DHCP_DEADLINE.0.set(None);
+
+    uninstall_callback(base_code, Some(callback));
+    result
+}
+
+/// Enable the callbacks of [`BaseCode`], so that it calls [`pxe_callback`] for every packet.
+///
+/// Returns the handle that the callback protocol was installed on.
+///
+/// # Errors
+///
+/// May return an `Error` if there is no handle that supports [`BaseCode`], or the callback protocol could not be
+/// installed or enabled.
+fn install_callback(base_code: &mut BaseCode) -> uefi::Result<Handle> {
+    let handle = boot::get_handle_for_protocol::<BaseCode>()?;
+    let interface = ptr::from_ref(&PXE_CALLBACK).cast::<c_void>();
+
+    // SAFETY: the interface is a static, so it lasts as long as it is installed
+    unsafe { boot::install_protocol_interface(Some(handle), &PXE_CALLBACK_GUID, interface)? };
+
+    if let Err(e) = base_code.set_parameters(None, None, None, None, Some(true)) {
+        // SAFETY: the interface was just installed on the handle
+        let _ =
+            unsafe { boot::uninstall_protocol_interface(handle, &PXE_CALLBACK_GUID, interface) };
+        return Err(e);
+    }
+
+    Ok(handle)
+}
+
+/// Disable the callbacks of [`BaseCode`] and uninstall the callback protocol, if it was installed.
+fn uninstall_callback(base_code: &mut BaseCode, handle: Option<Handle>) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    let _ = base_code.set_parameters(None, None, None, None, Some(false));
+
+    let interface = ptr::from_ref(&PXE_CALLBACK).cast::<c_void>();
+
+    // SAFETY: the handle is only returned by install_callback once the interface was installed on it
+    let result =
+        unsafe { boot::uninstall_protocol_interface(handle, &PXE_CALLBACK_GUID, interface) };
+    if let Err(e) = result {
+        warn!("Failed to uninstall the PXE callback: {e}");
+    }
+}
+
+/// The PXE Base Code callback, which counts the data received by TFTP, and aborts DHCP once it has taken too long.
+///
+/// # Safety
+///
+/// The packet must be valid for the given length if it is not null.
+unsafe extern "efiapi" fn pxe_callback(
+    _this: *mut PxeCallbackProtocol,
+    function: u32,
+    received: Boolean,
+    packet_len: u32,
+    packet: *const u8,
+) -> u32 {
+    if function == PXE_FUNCTION_MTFTP && bool::from(received) && !packet.is_null() {
+        let len = usize::try_from(packet_len).unwrap_or_default();
+
+        // SAFETY: the packet is not null, and the caller must give a packet that is valid for its length
+        let packet = unsafe { slice::from_raw_parts(packet, len) };
+        DOWNLOAD.receive(packet);
+    }
+
+    if function == PXE_FUNCTION_DHCP && DHCP_DEADLINE.expired() {
+        return PXE_CALLBACK_ABORT;
+    }
+    PXE_CALLBACK_CONTINUE
+}
+
+/// Remove the `initrd=` options from a command line, as the initrds are provided through the initrd media device path.
+fn strip_initrds(options: &str) -> String {
+    options
+        .split_ascii_whitespace()
+        .filter(|option| !option.starts_with("initrd="))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_strip_initrds() {
+        assert_eq!(
+            strip_initrds("root=/dev/nfs initrd=/initrd.img  quiet initrd=/ucode.img"),
+            "root=/dev/nfs quiet"
+        );
+        assert_eq!(strip_initrds("initrd=/initrd.img"), "");
+        assert_eq!(strip_initrds(""), "");
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_with_retries() {
+        let mut delays = Vec::new();
+        let mut attempts = 0;
+        let result = with_retries(
+            || {
+                attempts += 1;
+                if attempts < 3 {
+                    Err(Status::TIMEOUT.into())
+                } else {
+                    Ok(attempts)
+                }
+            },
+            |delay| delays.push(delay),
+        );
+        assert_eq!(result, Ok(3));
+        assert_eq!(
+            delays,
+            [Duration::from_millis(500), Duration::from_millis(1000)]
+        );
+
+        let mut attempts = 0;
+        let result: uefi::Result<()> = with_retries(
+            || {
+                attempts += 1;
+                Err(Status::NO_RESPONSE.into())
+            },
+            |_| (),
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, ATTEMPTS);
+
+        let mut attempts = 0;
+        let result: uefi::Result<()> = with_retries(
+            || {
+                attempts += 1;
+                Err(Status::NOT_FOUND.into())
+            },
+            |_| (),
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_download_receive() {
+        let download = Download::new();
+        download.receive(&[0, 3, 0, 1, 1, 2, 3, 4]);
+        assert_eq!(download.done.get(), 4);
+
+        // retransmitted blocks are not counted twice
+        download.receive(&[0, 3, 0, 1, 1, 2, 3, 4]);
+        assert_eq!(download.done.get(), 4);
+
+        download.receive(&[0, 3, 0, 2, 5, 6]);
+        assert_eq!(download.done.get(), 6);
+
+        // acknowledgements and truncated packets are ignored
+        download.receive(&[0, 4, 0, 3, 0, 0, 0]);
+        download.receive(&[0, 3]);
+        assert_eq!(download.done.get(), 6);
+
+        download.restart();
+        assert_eq!(download.done.get(), 0);
+        assert!(download.finish().is_none());
+    }
 }
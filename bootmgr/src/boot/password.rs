@@ -0,0 +1,191 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Password protection for the editor and protected boot options.
+//!
+//! The password is stored in the [`BootConfig`](crate::boot::config::BootConfig) as a salted PBKDF2-HMAC-SHA256
+//! hash, in the format `pbkdf2-sha256$<iterations>$<salt>$<hash>`, where the salt and the hash are hexadecimal. Such
+//! a hash can be generated with Python:
+//!
+//! ```text
+//! python3 -c 'import hashlib, os, getpass; s = os.urandom(16); i = 100000; print(f"pbkdf2-sha256${i}${s.hex()}${hashlib.pbkdf2_hmac("sha256", getpass.getpass().encode(), s, i).hex()}")'
+//! ```
+//!
+//! When a password is set, the [`BootMgr`](crate::boot::BootMgr) starts out locked. While it is locked, the editor
+//! may not be used and boot options that are protected may not be booted, until it is unlocked with the password. If
+//! the hash could not be parsed, then [`PasswordHash::locked`] is used instead, so that it can never be unlocked.
+
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::boot::secure_boot::hash_pin::Sha256Hash;
+
+/// The prefix of a password hash.
+const PASSWORD_HASH_PREFIX: &str = "pbkdf2-sha256";
+
+/// An `Error` that may result from using a password protected feature.
+#[derive(Error, Debug)]
+pub enum PasswordError {
+    /// The boot option is protected, and the boot manager was not unlocked.
+    #[error("Boot option is protected by a password")]
+    Locked,
+}
+
+/// A salted PBKDF2-HMAC-SHA256 password hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordHash {
+    /// The amount of PBKDF2 iterations.
+    iterations: u32,
+
+    /// The salt of the hash.
+    salt: Vec<u8>,
+
+    /// The derived key of the password.
+    hash: Sha256Hash,
+}
+
+impl PasswordHash {
+    /// Hash a password with a salt and an amount of PBKDF2 iterations.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn new(password: &str, salt: &[u8], iterations: u32) -> Self {
+        Self {
+            iterations,
+            salt: salt.to_vec(),
+            hash: derive_key(password, salt, iterations),
+        }
+    }
+
+    /// Get a [`PasswordHash`] that no password matches.
+    ///
+    /// This is used when a hash could not be parsed, so that protection is kept instead of being turned off.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn locked() -> Self {
+        Self {
+            iterations: 0,
+            salt: Vec::new(),
+            hash: [0; 32],
+        }
+    }
+
+    /// Check if a password matches the hash.
+    ///
+    /// A hash with no iterations is never matched, as it can only come from [`PasswordHash::locked`]. The comparison is done in constant time, so that the time taken does not depend on how much of the hash
+    /// matches.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn verify(&self, password: &str) -> bool {
+        if self.iterations == 0 {
+            return false;
+        }
+
+        let hash = derive_key(password, &self.salt, self.iterations);
+        hash.iter()
+            .zip(&self.hash)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = ();
+
+    /// Get a [`PasswordHash`] from its string representation, which is `pbkdf2-sha256$<iterations>$<salt>$<hash>`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut fields = value.split('$');
+        if fields.next() != Some(PASSWORD_HASH_PREFIX) {
+            return Err(());
+        }
+
+        let iterations = fields.next().and_then(|x| x.parse().ok()).ok_or(())?;
+        let salt = fields.next().and_then(parse_hex).ok_or(())?;
+        let hash = fields
+            .next()
+            .and_then(parse_hex)
+            .and_then(|x| x.try_into().ok())
+            .ok_or(())?;
+
+        if iterations == 0 || salt.is_empty() || fields.next().is_some() {
+            return Err(());
+        }
+
+        Ok(Self {
+            iterations,
+            salt,
+            hash,
+        })
+    }
+}
+
+/// Derive a key from a password with PBKDF2-HMAC-SHA256.
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> Sha256Hash {
+    let mut hash = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut hash);
+    hash
+}
+
+/// Parse bytes from a string of hexadecimal characters.
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let (pairs, rest) = hex.as_bytes().as_chunks::<2>();
+    if !rest.is_empty() {
+        return None;
+    }
+
+    pairs
+        .iter()
+        .map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// The hash of `password`, generated with Python's `hashlib.pbkdf2_hmac`.
+    const HASH: &str = "pbkdf2-sha256$1000$73616c74$632c2812e46d4604102ba7618e9d6d7d2f8128f6266b4a03264d2a0460b7dcb3";
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_verify() {
+        let hash = PasswordHash::from_str(HASH).expect("Hash should be valid");
+        assert!(hash.verify("password"));
+        assert!(!hash.verify("Password"));
+        assert!(!hash.verify(""));
+        assert_eq!(PasswordHash::new("password", b"salt", 1000), hash);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_locked() {
+        let hash = PasswordHash::locked();
+        assert!(!hash.verify("password"));
+        assert!(!hash.verify(""));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_invalid_hash() {
+        assert!(PasswordHash::from_str("password").is_err());
+        assert!(PasswordHash::from_str(&HASH.replace("1000", "0")).is_err());
+        assert!(PasswordHash::from_str(&HASH.replace("73616c74", "")).is_err());
+        assert!(PasswordHash::from_str(&HASH.replace("73616c74", "7361c74")).is_err());
+        assert!(PasswordHash::from_str(&HASH[..HASH.len() - 2]).is_err());
+        assert!(PasswordHash::from_str(&[HASH, "$00"].concat()).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn doesnt_panic(x in "\\PC*") {
+            let _ = PasswordHash::from_str(&x);
+        }
+    }
+}
@@ -14,6 +14,9 @@
 //!
 //! Even though Shim is the main consumer of this type of module, the overall architecture is
 //! very pluggable and custom validators not simply delegating to Shim can be used as well.
+//! One such validator is provided in [`hash_pin`], which checks images against pinned SHA-256 hashes, and another
+//! in `authenticode`, which verifies the signatures of images without relying on Shim or the firmware. Every image
+//! that is loaded without Shim is also checked against SBAT revocations through `sbat`.
 //!
 //! This hooks onto `SecurityArch` and `Security2Arch` in order to replace their
 //! authenticators with custom ones using Shim or any other validator.
@@ -21,19 +24,41 @@
 //! These hooks are temporary and should be uninstalled after the image is loaded. This is done
 //! automatically through the `SecurityOverrideGuard` struct.
 
+use alloc::{borrow::Cow, string::String};
 use core::{cell::Cell, ptr::NonNull};
 
 use thiserror::Error;
-use uefi::{cstr16, proto::device_path::DevicePath, runtime::VariableVendor};
+use uefi::{
+    Handle, boot, cstr16,
+    proto::{device_path::DevicePath, media::fs::SimpleFileSystem},
+    runtime::VariableVendor,
+};
 
 use crate::{
-    BootResult, boot::secure_boot::security_override::SecurityOverrideInner,
-    system::variable::get_variable,
+    BootResult,
+    boot::secure_boot::{
+        hash_pin::Sha256Hash,
+        security_override::{SecurityOverrideInner, ValidatorMode},
+        shim::{shim_load_image, shim_verify},
+    },
+    system::{
+        fs::UefiFileSystem,
+        helper::device_path_to_text,
+        variable::{RuntimeUefiVariableStorage, get_variable},
+    },
 };
 
+#[cfg(feature = "authenticode")]
+pub mod authenticode;
+pub mod enroll;
+pub mod hash_pin;
+#[cfg(feature = "sbat")]
+pub mod sbat;
 pub mod security_hooks;
 pub mod security_override;
 pub mod shim;
+pub mod signature_list;
+pub mod status;
 
 /// An `Error` that may result from validating an image with Secure Boot.
 #[derive(Error, Debug)]
@@ -45,6 +70,52 @@ pub enum SecureBootError {
     /// A validator was not installed, but the security hooks were installed.
     #[error("Validator was not installed")]
     NoValidator,
+
+    /// Keys were attempted to be enrolled while the firmware was not in Setup Mode.
+    #[error("Firmware is not in Setup Mode")]
+    NotInSetupMode,
+
+    /// The SHA-256 hash of an image did not match any of the pinned hashes.
+    #[error("Image with SHA-256 hash {0} is not pinned")]
+    HashNotPinned(String),
+
+    /// The firmware did not call the security hooks, so an image could not be checked by an enforced validator.
+    #[error("Image could not be checked, as the firmware did not call the security hooks")]
+    ImageNotChecked,
+
+    /// An enforced validator rejected an image.
+    #[error("Image was rejected by the validator")]
+    ImageRejected,
+
+    /// An image that cannot be verified by the firmware was booted while Secure Boot is enabled, without being verified
+    /// by Shim or having a hash that is trusted by `db` or the allowlist variable.
+    #[error(
+        "Image cannot be verified by the firmware, and it is not verified by Shim or trusted by db"
+    )]
+    UnverifiedImage,
+
+    /// The SHA-256 hash of an image that cannot be verified by the firmware is forbidden by `dbx`.
+    #[error("Image with SHA-256 hash {0} is forbidden by dbx")]
+    HashForbidden(String),
+
+    /// A component of an image has a generation that is revoked by SBAT.
+    #[error(
+        "Image component {component} generation {generation} is revoked by SBAT, generation {required} is required"
+    )]
+    SbatRevoked {
+        /// The name of the revoked component.
+        component: String,
+
+        /// The generation of the component in the image.
+        generation: u32,
+
+        /// The minimum generation of the component that is allowed.
+        required: u32,
+    },
+
+    /// The `.sbat` section of an image could not be parsed.
+    #[error("Invalid SBAT data: {0}")]
+    InvalidSbat(String),
 }
 
 /// The function signature for a validator.
@@ -109,7 +180,19 @@ impl SecurityOverrideGuard {
     ///
     /// When the returned guard is dropped, the security override is automatically uninstalled.
     pub(super) fn new(validator: Validator, validator_ctx: Option<NonNull<u8>>) -> Self {
-        install_security_override(validator, validator_ctx);
+        Self::with_mode(validator, validator_ctx, ValidatorMode::Fallback)
+    }
+
+    /// Create a new [`SecurityOverrideGuard`] with a [`ValidatorMode`].
+    ///
+    /// Unlike [`SecurityOverrideGuard::new`], images that fail validation may be refused instead of falling back to
+    /// the firmware's verdict, depending on the mode.
+    fn with_mode(
+        validator: Validator,
+        validator_ctx: Option<NonNull<u8>>,
+        mode: ValidatorMode,
+    ) -> Self {
+        install_security_override(validator, validator_ctx, mode);
         Self
     }
 }
@@ -122,7 +205,7 @@ impl Drop for SecurityOverrideGuard {
 
 /// Tests if secure boot is enabled through a UEFI variable.
 #[must_use = "Has no effect if the result is unused"]
-fn secure_boot_enabled() -> bool {
+pub(crate) fn secure_boot_enabled() -> bool {
     matches!(
         get_variable::<bool>(cstr16!("SecureBoot"), Some(VariableVendor::GLOBAL_VARIABLE)),
         Ok(true)
@@ -132,10 +215,243 @@ fn secure_boot_enabled() -> bool {
 /// Installs a security override given a [`Validator`] and optionally a `validator_ctx`.
 ///
 /// You should use the [`SecurityOverrideGuard`] to safely ensure the override is dropped.
-fn install_security_override(validator: Validator, validator_ctx: Option<NonNull<u8>>) {
+fn install_security_override(
+    validator: Validator,
+    validator_ctx: Option<NonNull<u8>>,
+    mode: ValidatorMode,
+) {
     let security_override = &SECURITY_OVERRIDE;
 
-    security_override.set(Some(SecurityOverrideInner::new(validator, validator_ctx)));
+    security_override.set(Some(SecurityOverrideInner::new(
+        validator,
+        validator_ctx,
+        mode,
+    )));
+}
+
+/// Get the content of the image given to a validator.
+///
+/// If a file buffer was given, then that is used. Otherwise, the image that the [`DevicePath`] points to is read.
+///
+/// # Errors
+///
+/// May return an `Error` if neither were given, or the device path does not lead to a handle supporting
+/// `SimpleFileSystem`, or the system does not support `DevicePathToText`, or the file does not exist in the filesystem.
+fn get_image<'a>(
+    device_path: Option<&DevicePath>,
+    file_buffer: Option<&'a mut [u8]>,
+) -> BootResult<Cow<'a, [u8]>> {
+    if let Some(file_buffer) = file_buffer {
+        return Ok(Cow::Borrowed(file_buffer));
+    }
+
+    let Some(mut device_path) = device_path else {
+        return Err(SecureBootError::NoDevicePathOrFile.into());
+    };
+
+    let handle = boot::locate_device_path::<SimpleFileSystem>(&mut device_path)?;
+    let mut fs = UefiFileSystem::from_handle(handle)?;
+
+    let path = device_path_to_text(device_path)?;
+    Ok(Cow::Owned(fs.read(&path)?))
+}
+
+/// The context of an enforced validator.
+///
+/// This wraps the context of the validator, and records if the validator was called, as well as the error that the
+/// validator rejected the image with. The security hooks can only return a [`uefi::Status`], so the error is kept
+/// here for [`load_image_enforced`] to return instead of the error from `LoadImage`.
+struct EnforcedContext<T> {
+    /// The context of the validator.
+    inner: T,
+
+    /// The SBAT revocations that every image is checked against.
+    #[cfg(feature = "sbat")]
+    sbat: sbat::SbatPolicy,
+
+    /// If the validator was called at least once.
+    checked: Cell<bool>,
+
+    /// The error of the last image that was rejected.
+    error: Cell<Option<crate::error::BootError>>,
+}
+
+impl<T> EnforcedContext<T> {
+    /// Run a check on the image given to an enforced validator, given the context pointer of the validator.
+    ///
+    /// The image is checked against the SBAT revocations before it is given to the check.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if there is no context, the image could not be read, it is revoked, or the check failed.
+    ///
+    /// # Safety
+    ///
+    /// The context pointer must have been created by [`load_image_enforced`] with the same `T`.
+    unsafe fn check(
+        ctx: Option<NonNull<u8>>,
+        device_path: Option<&DevicePath>,
+        file_buffer: Option<&mut [u8]>,
+        check: impl FnOnce(&T, &[u8]) -> BootResult<()>,
+    ) -> BootResult<()> {
+        let Some(ctx) = ctx else {
+            return Err(SecureBootError::NoValidator.into());
+        };
+
+        // SAFETY: the caller guarantees that the context was created by `load_image_enforced`, which keeps the
+        // `EnforcedContext` alive and unmoved until the security override is dropped, and does not access it mutably
+        // in the meantime.
+        let ctx = unsafe { ctx.cast::<Self>().as_ref() };
+        ctx.checked.set(true);
+
+        let result = get_image(device_path, file_buffer).and_then(|image| {
+            #[cfg(feature = "sbat")]
+            ctx.sbat.check_image(&image)?;
+            check(&ctx.inner, &image)
+        });
+
+        result.map_err(|e| {
+            ctx.error.set(Some(e));
+            SecureBootError::ImageRejected.into()
+        })
+    }
+}
+
+/// Loads an image with an enforced validator, or a precheck.
+///
+/// The validator is given a pointer to an [`EnforcedContext`] wrapping `ctx`, which should be accessed through
+/// [`EnforcedContext::check`]. If the validator rejected the image, then its error is returned. If the firmware never
+/// called the security hooks, then the image is unloaded, so that an image is never started without being checked.
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be loaded, or it was rejected or not checked by the validator.
+fn load_image_enforced<T>(
+    parent: Handle,
+    source: boot::LoadImageSource<'_>,
+    validator: Validator,
+    ctx: T,
+    mode: ValidatorMode,
+) -> BootResult<Handle> {
+    let ctx = EnforcedContext {
+        inner: ctx,
+        #[cfg(feature = "sbat")]
+        sbat: sbat::SbatPolicy::from_system(),
+        checked: Cell::new(false),
+        error: Cell::new(None),
+    };
+
+    let handle = {
+        let _guard =
+            SecurityOverrideGuard::with_mode(validator, Some(NonNull::from(&ctx).cast()), mode);
+        boot::load_image(parent, source)
+    }; // override dropped (uninstalled) here
+
+    if let Some(e) = ctx.error.take() {
+        if let Ok(handle) = handle {
+            let _ = boot::unload_image(handle);
+        }
+        return Err(e);
+    }
+
+    let handle = handle?;
+    if !ctx.checked.get() {
+        let _ = boot::unload_image(handle);
+        return Err(SecureBootError::ImageNotChecked.into());
+    }
+
+    Ok(handle)
+}
+
+/// Get the [`ValidatorMode`] of a validator whose verdict should be final.
+///
+/// The verdict is only final while Secure Boot is disabled. Otherwise, the validator is a [`ValidatorMode::Precheck`],
+/// as a validator that trusts data on the ESP must never bypass the firmware's signature databases.
+fn enforced_mode() -> ValidatorMode {
+    if secure_boot_enabled() {
+        ValidatorMode::Precheck
+    } else {
+        ValidatorMode::Enforce
+    }
+}
+
+/// Loads an image with the validator that applies to it.
+///
+/// If `pin` is provided or the allowlist of hashes is not empty, then the image is checked through [`hash_pin`].
+/// Otherwise, if Authenticode verification is enabled, then the image is verified through `authenticode`. If neither
+/// apply, then the image is loaded through [`shim_load_image`]. If Shim is not loaded and Secure Boot is enabled,
+/// then the image is checked against SBAT revocations before the firmware verifies it.
+///
+/// While Secure Boot is enabled, an image that passes [`hash_pin`] or `authenticode` is still verified by the
+/// firmware, see [`enforced_mode`].
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be loaded, or it was rejected by a validator.
+pub(crate) fn load_image(
+    parent: Handle,
+    source: boot::LoadImageSource<'_>,
+    pin: Option<Sha256Hash>,
+) -> BootResult<Handle> {
+    let pins = hash_pin::get_pins(pin);
+    if !pins.is_empty() {
+        return load_image_enforced(
+            parent,
+            source,
+            hash_pin::hash_pin_validate,
+            pins,
+            enforced_mode(),
+        );
+    }
+
+    #[cfg(feature = "authenticode")]
+    if authenticode::enabled() {
+        return load_image_enforced(
+            parent,
+            source,
+            authenticode::authenticode_validate,
+            authenticode::TrustStore::from_system(),
+            enforced_mode(),
+        );
+    }
+
+    #[cfg(feature = "sbat")]
+    if !shim::shim_loaded() && secure_boot_enabled() {
+        return load_image_enforced(
+            parent,
+            source,
+            sbat::sbat_validate,
+            (),
+            ValidatorMode::Precheck,
+        );
+    }
+
+    shim_load_image(parent, source)
+}
+
+/// Checks an image that is not loaded through `LoadImage`, such as a Linux kernel that is booted directly.
+///
+/// As the firmware does not verify such an image, it must have one of the pinned hashes if there are any. The pinned
+/// hashes can be changed by anyone that can write to the ESP, so they are not enough while Secure Boot is enabled. The
+/// image must then also be verified by Shim, or have a hash that is trusted through
+/// [`hash_pin::check_trusted_image`].
+///
+/// # Errors
+///
+/// May return an `Error` if the hash of the image is not pinned, or Secure Boot is enabled and the image is neither
+/// verified by Shim nor trusted.
+#[cfg(target_arch = "x86_64")]
+pub(crate) fn check_unverified_image(image: &[u8], pin: Option<Sha256Hash>) -> BootResult<()> {
+    let pins = hash_pin::get_pins(pin);
+    if !pins.is_empty() {
+        hash_pin::check_image(&pins, image)?;
+    }
+
+    if !secure_boot_enabled() || shim_verify(image).is_ok() {
+        return Ok(());
+    }
+
+    hash_pin::check_trusted_image(&RuntimeUefiVariableStorage, image)
 }
 
 /// Uninstalls the security override. Should be used after installing the security override.
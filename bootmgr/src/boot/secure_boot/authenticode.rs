@@ -0,0 +1,757 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Authenticode verification of loaded images.
+//!
+//! This is a validator that verifies the Authenticode signatures of images in the boot manager itself, without
+//! delegating to Shim or the firmware. It is enabled through the `authenticode` key of the `BootConfig`, and is
+//! enforced even if Secure Boot is disabled. While Secure Boot is enabled, images that pass are still verified by
+//! the firmware, as the configuration and `\loader\certs` can be changed by anyone that can write to the ESP.
+//!
+//! The Authenticode hash of the image is computed, then checked against the signature databases:
+//!
+//! - If the hash is in `dbx`, then the image is refused.
+//! - If the hash is in `db`, then the image is trusted even if it is not signed.
+//! - Otherwise, the image must have a signature that covers the hash, made by a certificate that chains to a
+//!   certificate in `db` or in `\loader\certs`. Any certificate in that chain that is in `dbx` causes the image to
+//!   be refused. The signer certificate must have the code signing extended key usage, and every intermediate
+//!   certificate must be a CA that is allowed to sign code.
+//!
+//! Certificates in `\loader\certs` must be DER encoded. Only RSA signatures with SHA-256 are supported, which is what
+//! is used by the Microsoft UEFI CA and tools such as `sbsign`. As the firmware does not have a trusted source of
+//! time, the validity periods of certificates are not checked, which is the same as the firmware's own verification.
+
+use alloc::vec::Vec;
+use core::{
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use cms::{
+    cert::CertificateChoices,
+    content_info::ContentInfo,
+    signed_data::{SignedData, SignerIdentifier},
+};
+use der::{
+    Any, Decode, Encode, Sequence, SliceReader, Tag, Tagged,
+    asn1::{ObjectIdentifier, OctetString},
+};
+use log::warn;
+use rsa::{Pkcs1v15Sign, RsaPublicKey, pkcs1::DecodeRsaPublicKey};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uefi::{
+    CStr16, cstr16, fs::COMMON_SKIP_DIRS, proto::device_path::DevicePath, runtime::VariableVendor,
+};
+use x509_cert::{
+    Certificate,
+    ext::pkix::{BasicConstraints, ExtendedKeyUsage, KeyUsage},
+    spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned},
+};
+
+use crate::{
+    BootResult,
+    boot::secure_boot::{
+        EnforcedContext,
+        hash_pin::Sha256Hash,
+        signature_list::{
+            EFI_CERT_SHA256_GUID, EFI_CERT_X509_GUID, EFI_CERT_X509_SHA256_GUID, SignatureLists,
+        },
+    },
+    system::{
+        fs::UefiFileSystem,
+        helper::get_path_cstr,
+        variable::{RuntimeUefiVariableStorage, UefiVariableStorage},
+    },
+};
+
+mod pe;
+
+use pe::PeImage;
+
+/// The directory that contains additional trusted certificates.
+const CERTS_PREFIX: &CStr16 = cstr16!("\\loader\\certs");
+
+/// The maximum length of a certificate chain, from the signer to the trusted certificate.
+const MAX_CHAIN_DEPTH: usize = 8;
+
+/// The content type of a PKCS#7 `SignedData` structure.
+const ID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+
+/// The content type of an Authenticode `SpcIndirectDataContent` structure.
+const SPC_INDIRECT_DATA_OBJID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.4");
+
+/// The signed attribute that contains the content type.
+const ID_CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+
+/// The signed attribute that contains the digest of the content.
+const ID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+
+/// The SHA-256 digest algorithm.
+const ID_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+
+/// RSA keys, which is also used as the algorithm of PKCS#1 v1.5 signatures of signer infos.
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+
+/// PKCS#1 v1.5 signatures with SHA-256.
+const SHA256_WITH_RSA_ENCRYPTION: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+
+/// The extended key usage of certificates that sign code.
+const ID_KP_CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+
+/// The extended key usage that allows any usage.
+const ANY_EXTENDED_KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.29.37.0");
+
+/// If Authenticode verification is enabled.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// An `Error` that may result from verifying the Authenticode signature of an image.
+#[derive(Error, Debug)]
+pub enum AuthenticodeError {
+    /// The image is not a valid PE image.
+    #[error("Image is not a valid PE image")]
+    MalformedImage,
+
+    /// A signature or certificate could not be parsed.
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(der::Error),
+
+    /// A signature uses an algorithm that is not supported.
+    #[error("Signature uses an unsupported algorithm")]
+    UnsupportedAlgorithm,
+
+    /// The certificate of the signer is not included in the signature.
+    #[error("Certificate of the signer was not found")]
+    SignerNotFound,
+
+    /// The certificate of the signer does not have the code signing extended key usage.
+    #[error("Certificate of the signer is not allowed to sign code")]
+    SignerNotCodeSigning,
+
+    /// The image does not have a signature, and its hash is not in `db`.
+    #[error("Image is not signed, and its hash is not in db")]
+    Unsigned,
+
+    /// The hash of the image does not match the hash in its signature.
+    #[error("Image hash does not match the hash in its signature")]
+    HashMismatch,
+
+    /// A signature did not verify with the key of its certificate.
+    #[error("Signature could not be verified")]
+    BadSignature,
+
+    /// The signature does not chain to a trusted certificate.
+    #[error("Image is not signed by a trusted certificate")]
+    Untrusted,
+
+    /// The hash of the image is in `dbx`.
+    #[error("Image hash is forbidden by dbx")]
+    HashForbidden,
+
+    /// A certificate in the chain of the signature is in `dbx`.
+    #[error("Image is signed by a certificate that is forbidden by dbx")]
+    CertForbidden,
+}
+
+impl From<der::Error> for AuthenticodeError {
+    fn from(e: der::Error) -> Self {
+        Self::MalformedSignature(e)
+    }
+}
+
+/// The Authenticode `SpcIndirectDataContent` structure, which is the content that is signed.
+#[derive(Sequence)]
+struct SpcIndirectDataContent {
+    /// The type of the image, which is not needed for verification.
+    data: Any,
+
+    /// The Authenticode hash of the image.
+    message_digest: DigestInfo,
+}
+
+/// The `DigestInfo` structure, containing a hash and its algorithm.
+#[derive(Sequence)]
+struct DigestInfo {
+    /// The algorithm of the hash.
+    digest_algorithm: AlgorithmIdentifierOwned,
+
+    /// The hash.
+    digest: OctetString,
+}
+
+/// The certificates and hashes that images are verified against.
+#[derive(Default)]
+pub(crate) struct TrustStore {
+    /// The certificates that are trusted to sign images.
+    certs: Vec<Certificate>,
+
+    /// The Authenticode hashes of images that are trusted without a signature.
+    hashes: Vec<Sha256Hash>,
+
+    /// The certificates that are forbidden from signing images.
+    forbidden_certs: Vec<Certificate>,
+
+    /// The SHA-256 hashes of the `TBSCertificate` of forbidden certificates.
+    forbidden_tbs: Vec<Sha256Hash>,
+
+    /// The Authenticode hashes of forbidden images.
+    forbidden_hashes: Vec<Sha256Hash>,
+}
+
+impl TrustStore {
+    /// Get the [`TrustStore`] of the system, from `db`, `dbx`, and the certificates in `\loader\certs`.
+    pub(crate) fn from_system() -> Self {
+        let mut store = Self::default();
+        let vars = RuntimeUefiVariableStorage;
+        let vendor = VariableVendor::IMAGE_SECURITY_DATABASE;
+
+        if let Ok(Some(db)) = vars.get_variable_bytes(cstr16!("db"), &vendor) {
+            store.add_db(&db);
+        }
+        if let Ok(Some(dbx)) = vars.get_variable_bytes(cstr16!("dbx"), &vendor) {
+            store.add_dbx(&dbx);
+        }
+
+        if let Ok(mut fs) = UefiFileSystem::from_image_fs() {
+            let files: Vec<_> = fs
+                .read_dir(CERTS_PREFIX)
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .filter(|x| !x.is_directory())
+                .filter(|x| !COMMON_SKIP_DIRS.contains(&x.file_name()))
+                .collect();
+
+            for file in files {
+                if let Ok(path) = get_path_cstr(CERTS_PREFIX, file.file_name())
+                    && let Ok(cert) = fs.read(&path)
+                {
+                    store.add_cert(&cert);
+                }
+            }
+        }
+
+        store
+    }
+
+    /// Add a trusted DER encoded certificate to the [`TrustStore`].
+    ///
+    /// Certificates that could not be parsed are skipped with a warning.
+    pub(crate) fn add_cert(&mut self, cert: &[u8]) {
+        match Certificate::from_der(cert) {
+            Ok(cert) => self.certs.push(cert),
+            Err(e) => warn!("[AUTHENTICODE]: Skipping invalid certificate: {e}"),
+        }
+    }
+
+    /// Add the certificates and hashes of the allowed signature database `db` to the [`TrustStore`].
+    pub(crate) fn add_db(&mut self, db: &[u8]) {
+        for list in SignatureLists::new(db) {
+            match list.sig_type() {
+                EFI_CERT_X509_GUID => list.signatures().for_each(|x| self.add_cert(x)),
+                EFI_CERT_SHA256_GUID => self.hashes.extend(
+                    list.signatures()
+                        .filter_map(|x| Sha256Hash::try_from(x).ok()),
+                ),
+                _ => (),
+            }
+        }
+    }
+
+    /// Add the certificates and hashes of the forbidden signature database `dbx` to the [`TrustStore`].
+    pub(crate) fn add_dbx(&mut self, dbx: &[u8]) {
+        for list in SignatureLists::new(dbx) {
+            match list.sig_type() {
+                EFI_CERT_X509_GUID => self.forbidden_certs.extend(
+                    list.signatures()
+                        .filter_map(|x| Certificate::from_der(x).ok()),
+                ),
+                EFI_CERT_X509_SHA256_GUID => self.forbidden_tbs.extend(
+                    list.signatures()
+                        .filter_map(|x| Sha256Hash::try_from(x.get(..32)?).ok()),
+                ),
+                EFI_CERT_SHA256_GUID => self.forbidden_hashes.extend(
+                    list.signatures()
+                        .filter_map(|x| Sha256Hash::try_from(x).ok()),
+                ),
+                _ => (),
+            }
+        }
+    }
+
+    /// Check if a certificate is forbidden by `dbx`.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the certificate is forbidden, or could not be encoded.
+    fn check_forbidden(&self, cert: &Certificate) -> Result<(), AuthenticodeError> {
+        let tbs_hash: Sha256Hash = Sha256::digest(cert.tbs_certificate.to_der()?).into();
+        if self.forbidden_certs.contains(cert) || self.forbidden_tbs.contains(&tbs_hash) {
+            return Err(AuthenticodeError::CertForbidden);
+        }
+        Ok(())
+    }
+}
+
+/// Set if Authenticode verification is enabled.
+pub(crate) fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Get if Authenticode verification is enabled.
+pub(super) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Verify a PKCS#1 v1.5 signature over a SHA-256 hash with the key of a certificate.
+///
+/// # Errors
+///
+/// May return an `Error` if the key is not an RSA key, or the signature does not verify.
+fn verify_rsa(
+    key: &SubjectPublicKeyInfoOwned,
+    hashed: &[u8],
+    signature: &[u8],
+) -> Result<(), AuthenticodeError> {
+    if key.algorithm.oid != RSA_ENCRYPTION {
+        return Err(AuthenticodeError::UnsupportedAlgorithm);
+    }
+
+    let key = RsaPublicKey::from_pkcs1_der(key.subject_public_key.raw_bytes())
+        .map_err(|_| AuthenticodeError::UnsupportedAlgorithm)?;
+    key.verify(Pkcs1v15Sign::new::<Sha256>(), hashed, signature)
+        .map_err(|_| AuthenticodeError::BadSignature)
+}
+
+/// Check if a certificate was issued by another certificate.
+fn is_issued_by(cert: &Certificate, issuer: &Certificate) -> bool {
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject
+        || cert.signature_algorithm.oid != SHA256_WITH_RSA_ENCRYPTION
+    {
+        return false;
+    }
+
+    let Ok(tbs) = cert.tbs_certificate.to_der() else {
+        return false;
+    };
+    let Some(signature) = cert.signature.as_bytes() else {
+        return false;
+    };
+
+    verify_rsa(
+        &issuer.tbs_certificate.subject_public_key_info,
+        &Sha256::digest(tbs),
+        signature,
+    )
+    .is_ok()
+}
+
+/// Check if a certificate is allowed to issue the certificates of code signers.
+///
+/// The certificate must be a CA according to its basic constraints. If it has a key usage, then it must allow signing
+/// certificates, and if it has an extended key usage, then it must allow code signing.
+fn is_code_signing_ca(cert: &Certificate) -> bool {
+    let tbs = &cert.tbs_certificate;
+
+    if !matches!(tbs.get::<BasicConstraints>(), Ok(Some((_, constraints))) if constraints.ca) {
+        return false;
+    }
+
+    match tbs.get::<KeyUsage>() {
+        Ok(Some((_, usage))) if usage.key_cert_sign() => (),
+        Ok(None) => (),
+        _ => return false,
+    }
+
+    match tbs.get::<ExtendedKeyUsage>() {
+        Ok(Some((_, usage))) => usage
+            .0
+            .iter()
+            .any(|x| [ID_KP_CODE_SIGNING, ANY_EXTENDED_KEY_USAGE].contains(x)),
+        Ok(None) => true,
+        Err(_) => false,
+    }
+}
+
+/// Check if a certificate is allowed to sign code, which requires an extended key usage with code signing.
+fn is_code_signer(cert: &Certificate) -> bool {
+    matches!(
+        cert.tbs_certificate.get::<ExtendedKeyUsage>(),
+        Ok(Some((_, usage))) if usage.0.contains(&ID_KP_CODE_SIGNING)
+    )
+}
+
+/// Verify that a signer certificate chains to a trusted certificate, through the certificates of the signature.
+///
+/// Every intermediate certificate from the signature must be a CA that is allowed to issue code signing certificates,
+/// as checked by [`is_code_signing_ca`].
+///
+/// # Errors
+///
+/// May return an `Error` if the chain does not lead to a trusted certificate, or any certificate in the chain is
+/// forbidden.
+fn verify_chain(
+    signer: &Certificate,
+    certs: &[&Certificate],
+    store: &TrustStore,
+) -> Result<(), AuthenticodeError> {
+    let mut cert = signer;
+    for _ in 0..MAX_CHAIN_DEPTH {
+        store.check_forbidden(cert)?;
+        if store.certs.contains(cert) {
+            return Ok(());
+        }
+
+        if let Some(trusted) = store.certs.iter().find(|x| is_issued_by(cert, x)) {
+            return store.check_forbidden(trusted);
+        }
+
+        cert = certs
+            .iter()
+            .find(|x| **x != cert && is_code_signing_ca(x) && is_issued_by(cert, x))
+            .ok_or(AuthenticodeError::Untrusted)?;
+    }
+
+    Err(AuthenticodeError::Untrusted)
+}
+
+/// Verify a single PKCS#7 `SignedData` signature of an image, given the Authenticode hash of the image.
+///
+/// # Errors
+///
+/// May return an `Error` if the signature is malformed, does not cover the hash, does not verify, or is not made
+/// by a trusted certificate that is allowed to sign code.
+fn verify_signature(
+    signature: &[u8],
+    hash: &Sha256Hash,
+    store: &TrustStore,
+) -> Result<(), AuthenticodeError> {
+    // the signature may be followed by padding, so trailing data is not an error
+    let content_info = ContentInfo::decode(&mut SliceReader::new(signature)?)?;
+    if content_info.content_type != ID_SIGNED_DATA {
+        return Err(AuthenticodeError::UnsupportedAlgorithm);
+    }
+    let signed_data: SignedData = content_info.content.decode_as()?;
+
+    let encap = &signed_data.encap_content_info;
+    let content = encap
+        .econtent
+        .as_ref()
+        .filter(|_| encap.econtent_type == SPC_INDIRECT_DATA_OBJID)
+        .ok_or(AuthenticodeError::UnsupportedAlgorithm)?;
+
+    // Authenticode embeds the content directly as a SEQUENCE, while CMS wraps it in an OCTET STRING
+    let indirect: SpcIndirectDataContent = match content.tag() {
+        Tag::Sequence => content.decode_as()?,
+        Tag::OctetString => SpcIndirectDataContent::from_der(content.value())?,
+        tag => return Err(tag.unexpected_error(None).into()),
+    };
+    if indirect.message_digest.digest_algorithm.oid != ID_SHA256 {
+        return Err(AuthenticodeError::UnsupportedAlgorithm);
+    }
+    if indirect.message_digest.digest.as_bytes() != hash {
+        return Err(AuthenticodeError::HashMismatch);
+    }
+
+    let [signer_info] = signed_data.signer_infos.0.as_slice() else {
+        return Err(AuthenticodeError::UnsupportedAlgorithm);
+    };
+    if signer_info.digest_alg.oid != ID_SHA256
+        || ![RSA_ENCRYPTION, SHA256_WITH_RSA_ENCRYPTION]
+            .contains(&signer_info.signature_algorithm.oid)
+    {
+        return Err(AuthenticodeError::UnsupportedAlgorithm);
+    }
+
+    let attrs = signer_info
+        .signed_attrs
+        .as_ref()
+        .ok_or(AuthenticodeError::HashMismatch)?;
+    let attr = |oid| {
+        attrs
+            .iter()
+            .find(|x| x.oid == oid)
+            .and_then(|x| x.values.iter().next())
+    };
+    let content_type = attr(ID_CONTENT_TYPE)
+        .ok_or(AuthenticodeError::HashMismatch)?
+        .decode_as::<ObjectIdentifier>()?;
+    let message_digest = attr(ID_MESSAGE_DIGEST)
+        .ok_or(AuthenticodeError::HashMismatch)?
+        .decode_as::<OctetString>()?;
+    if content_type != encap.econtent_type
+        || message_digest.as_bytes() != &Sha256::digest(content.value())[..]
+    {
+        return Err(AuthenticodeError::HashMismatch);
+    }
+
+    let certs: Vec<_> = signed_data
+        .certificates
+        .iter()
+        .flat_map(|x| x.0.iter())
+        .filter_map(|x| match x {
+            CertificateChoices::Certificate(cert) => Some(cert),
+            CertificateChoices::Other(_) => None,
+        })
+        .collect();
+    let SignerIdentifier::IssuerAndSerialNumber(sid) = &signer_info.sid else {
+        return Err(AuthenticodeError::SignerNotFound);
+    };
+    let signer = certs
+        .iter()
+        .find(|x| {
+            x.tbs_certificate.issuer == sid.issuer
+                && x.tbs_certificate.serial_number == sid.serial_number
+        })
+        .ok_or(AuthenticodeError::SignerNotFound)?;
+    if !is_code_signer(signer) {
+        return Err(AuthenticodeError::SignerNotCodeSigning);
+    }
+
+    verify_rsa(
+        &signer.tbs_certificate.subject_public_key_info,
+        &Sha256::digest(attrs.to_der()?),
+        signer_info.signature.as_bytes(),
+    )?;
+
+    verify_chain(signer, &certs, store)
+}
+
+/// Verify an image against a [`TrustStore`].
+///
+/// The image is refused if its hash is in `dbx`, or any of its signatures are made by a forbidden certificate.
+/// Otherwise, the image is trusted if its hash is in `db`, or any of its signatures verify.
+///
+/// # Errors
+///
+/// May return an `Error` if the image is forbidden, or is not trusted.
+pub(crate) fn verify_image(image: &[u8], store: &TrustStore) -> Result<(), AuthenticodeError> {
+    let pe = PeImage::parse(image)?;
+    let hash = pe.authenticode_hash();
+
+    if store.forbidden_hashes.contains(&hash) {
+        return Err(AuthenticodeError::HashForbidden);
+    }
+
+    let mut result = if store.hashes.contains(&hash) {
+        Ok(())
+    } else {
+        Err(AuthenticodeError::Unsigned)
+    };
+
+    for signature in pe.signatures() {
+        match verify_signature(signature, &hash, store) {
+            Err(AuthenticodeError::CertForbidden) => return Err(AuthenticodeError::CertForbidden),
+            Ok(()) => result = Ok(()),
+            Err(e) if result.is_err() => result = Err(e),
+            Err(_) => (),
+        }
+    }
+
+    result
+}
+
+/// Authenticode validator with [`super::Validator`] function signature.
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be read, or failed verification.
+pub(super) fn authenticode_validate(
+    ctx: Option<NonNull<u8>>,
+    device_path: Option<&DevicePath>,
+    file_buffer: Option<&mut [u8]>,
+    _file_size: usize,
+) -> BootResult<()> {
+    // SAFETY: this validator is only installed by `super::load_image` through `load_image_enforced`, with a
+    // `TrustStore` as the context.
+    unsafe {
+        EnforcedContext::check(
+            ctx,
+            device_path,
+            file_buffer,
+            |store: &TrustStore, image| Ok(verify_image(image, store)?),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::boot::secure_boot::signature_list::build_list;
+
+    /// An image signed by [`SIGNER`].
+    const SIGNED: &[u8] = include_bytes!("../../../test-data/authenticode/signed.efi");
+
+    /// The same image as [`SIGNED`], signed by a certificate of [`CA`] without the code signing extended key usage.
+    const SIGNED_NO_EKU: &[u8] =
+        include_bytes!("../../../test-data/authenticode/signed-no-eku.efi");
+
+    /// The same image as [`SIGNED`], without a signature.
+    const UNSIGNED: &[u8] = include_bytes!("../../../test-data/authenticode/unsigned.efi");
+
+    /// The CA that issued [`SIGNER`].
+    const CA: &[u8] = include_bytes!("../../../test-data/authenticode/ca.der");
+
+    /// The certificate that signed [`SIGNED`].
+    const SIGNER: &[u8] = include_bytes!("../../../test-data/authenticode/signer.der");
+
+    /// A CA that is not related to the signature.
+    const OTHER_CA: &[u8] = include_bytes!("../../../test-data/authenticode/other-ca.der");
+
+    /// Get a [`TrustStore`] that trusts a set of certificates.
+    fn store_with(certs: &[&[u8]]) -> TrustStore {
+        let mut store = TrustStore::default();
+        for cert in certs {
+            store.add_cert(cert);
+        }
+        store
+    }
+
+    /// Get the Authenticode hash of an image.
+    fn hash_of(image: &[u8]) -> Sha256Hash {
+        PeImage::parse(image)
+            .map(|x| x.authenticode_hash())
+            .unwrap_or_default()
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_verify_signed() {
+        assert!(verify_image(SIGNED, &store_with(&[CA])).is_ok());
+        assert!(verify_image(SIGNED, &store_with(&[SIGNER])).is_ok());
+        assert!(verify_image(SIGNED, &store_with(&[OTHER_CA, CA])).is_ok());
+        assert!(matches!(
+            verify_image(SIGNED, &store_with(&[OTHER_CA])),
+            Err(AuthenticodeError::Untrusted)
+        ));
+        assert!(matches!(
+            verify_image(SIGNED, &TrustStore::default()),
+            Err(AuthenticodeError::Untrusted)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_verify_tampered() {
+        let mut image = SIGNED.to_vec();
+        image[0x210] ^= 0xff;
+        assert!(matches!(
+            verify_image(&image, &store_with(&[CA])),
+            Err(AuthenticodeError::HashMismatch)
+        ));
+        assert!(matches!(
+            verify_image(&image[..0x100], &store_with(&[CA])),
+            Err(AuthenticodeError::MalformedImage)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_verify_unsigned() {
+        assert!(matches!(
+            verify_image(UNSIGNED, &store_with(&[CA])),
+            Err(AuthenticodeError::Unsigned)
+        ));
+
+        let mut store = TrustStore::default();
+        store.add_db(&build_list(EFI_CERT_SHA256_GUID, &[&hash_of(UNSIGNED)]));
+        assert!(verify_image(UNSIGNED, &store).is_ok());
+
+        let mut store = TrustStore::default();
+        store.add_db(&build_list(EFI_CERT_X509_GUID, &[CA]));
+        assert!(verify_image(SIGNED, &store).is_ok());
+        assert!(verify_image(UNSIGNED, &store).is_err());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_verify_dbx() {
+        let mut store = store_with(&[CA]);
+        store.add_dbx(&build_list(EFI_CERT_SHA256_GUID, &[&hash_of(SIGNED)]));
+        assert!(matches!(
+            verify_image(SIGNED, &store),
+            Err(AuthenticodeError::HashForbidden)
+        ));
+
+        let mut store = store_with(&[CA]);
+        store.add_dbx(&build_list(EFI_CERT_X509_GUID, &[SIGNER]));
+        assert!(matches!(
+            verify_image(SIGNED, &store),
+            Err(AuthenticodeError::CertForbidden)
+        ));
+
+        let tbs = Certificate::from_der(CA)
+            .and_then(|x| x.tbs_certificate.to_der())
+            .unwrap_or_default();
+        let mut revoked = Sha256::digest(tbs).to_vec();
+        revoked.extend_from_slice(&[0; 16]);
+        let mut store = store_with(&[CA]);
+        store.add_dbx(&build_list(EFI_CERT_X509_SHA256_GUID, &[&revoked]));
+        assert!(matches!(
+            verify_image(SIGNED, &store),
+            Err(AuthenticodeError::CertForbidden)
+        ));
+
+        let mut store = store_with(&[CA]);
+        store.add_dbx(&build_list(EFI_CERT_SHA256_GUID, &[&[0; 32]]));
+        store.add_dbx(&build_list(EFI_CERT_X509_GUID, &[OTHER_CA]));
+        assert!(verify_image(SIGNED, &store).is_ok());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_code_signing_ca() {
+        let is_ca = |cert| Certificate::from_der(cert).is_ok_and(|x| is_code_signing_ca(&x));
+        assert!(is_ca(CA));
+        assert!(is_ca(OTHER_CA));
+        assert!(!is_ca(SIGNER));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_code_signer() {
+        assert!(Certificate::from_der(SIGNER).is_ok_and(|x| is_code_signer(&x)));
+        assert!(Certificate::from_der(CA).is_ok_and(|x| !is_code_signer(&x)));
+        assert!(matches!(
+            verify_image(SIGNED_NO_EKU, &store_with(&[CA])),
+            Err(AuthenticodeError::SignerNotCodeSigning)
+        ));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_invalid_certs() {
+        let store = store_with(&[b"not a certificate", &CA[..CA.len() - 1]]);
+        assert_eq!(store.certs.len(), 0);
+
+        let mut store = TrustStore::default();
+        store.add_db(&build_list(EFI_CERT_SHA256_GUID, &[&[0; 16]]));
+        store.add_db(&[0xff; 64]);
+        assert_eq!(store.hashes.len(), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn doesnt_panic(x in any::<Vec<u8>>()) {
+            let _ = verify_signature(&x, &[0; 32], &TrustStore::default());
+        }
+    }
+}
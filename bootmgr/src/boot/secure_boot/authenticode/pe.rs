@@ -0,0 +1,268 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Minimal PE parsing for Authenticode.
+//!
+//! Only the parts of the PE format that are needed to compute the Authenticode hash and to find the attribute
+//! certificate table are parsed. The image hash is computed as described in the "Windows Authenticode Portable
+//! Executable Signature Format" specification, which excludes the checksum, the certificate table data directory,
+//! and the certificate table itself.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::boot::secure_boot::{authenticode::AuthenticodeError, hash_pin::Sha256Hash};
+
+/// The offset of `e_lfanew` in the DOS header.
+const PE_POINTER_OFFSET: usize = 0x3c;
+
+/// The size of the PE signature and the COFF file header.
+const COFF_HEADER_SIZE: usize = 24;
+
+/// The size of a section header.
+const SECTION_HEADER_SIZE: usize = 40;
+
+/// The size of a data directory entry.
+const DATA_DIRECTORY_SIZE: usize = 8;
+
+/// The index of the certificate table in the data directories.
+const CERT_TABLE_INDEX: usize = 4;
+
+/// The optional header magic of a PE32 image.
+const PE32_MAGIC: u16 = 0x10b;
+
+/// The optional header magic of a PE32+ image.
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// The revision of `WIN_CERTIFICATE` structures.
+const WIN_CERT_REVISION: u16 = 0x0200;
+
+/// The `WIN_CERTIFICATE` type of a PKCS#7 `SignedData` structure.
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+/// The size of the header of a `WIN_CERTIFICATE` structure.
+const WIN_CERT_HEADER_SIZE: usize = 8;
+
+/// A parsed PE image.
+pub(crate) struct PeImage<'a> {
+    /// The content of the image.
+    data: &'a [u8],
+
+    /// The offset of the checksum in the optional header.
+    checksum: usize,
+
+    /// The offset of the certificate table data directory, if the image has one.
+    cert_dir: Option<usize>,
+
+    /// The size of the headers.
+    headers_size: usize,
+
+    /// The ranges of the raw data of every section, sorted by their offset in the file.
+    sections: Vec<Range<usize>>,
+
+    /// The range of the certificate table.
+    cert_table: Range<usize>,
+}
+
+/// Read a little endian `u16` from a slice.
+///
+/// # Errors
+///
+/// May return an `Error` if the slice is too short.
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, AuthenticodeError> {
+    data.get(offset..offset + 2)
+        .and_then(|x| x.try_into().ok())
+        .map(u16::from_le_bytes)
+        .ok_or(AuthenticodeError::MalformedImage)
+}
+
+/// Read a little endian `u32` from a slice as a `usize`.
+///
+/// # Errors
+///
+/// May return an `Error` if the slice is too short.
+fn read_u32(data: &[u8], offset: usize) -> Result<usize, AuthenticodeError> {
+    data.get(offset..offset + 4)
+        .and_then(|x| x.try_into().ok())
+        .map(u32::from_le_bytes)
+        .and_then(|x| usize::try_from(x).ok())
+        .ok_or(AuthenticodeError::MalformedImage)
+}
+
+/// Get a range of a slice, given a start and a length.
+///
+/// # Errors
+///
+/// May return an `Error` if the range is out of bounds.
+fn get_range(data: &[u8], start: usize, len: usize) -> Result<Range<usize>, AuthenticodeError> {
+    let end = start
+        .checked_add(len)
+        .filter(|&end| end <= data.len())
+        .ok_or(AuthenticodeError::MalformedImage)?;
+    Ok(start..end)
+}
+
+impl<'a> PeImage<'a> {
+    /// Parse a [`PeImage`] from the content of an image.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the image is not a PE32 or PE32+ image, or any of its headers are out of bounds.
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, AuthenticodeError> {
+        if !data.starts_with(b"MZ") {
+            return Err(AuthenticodeError::MalformedImage);
+        }
+
+        let pe = read_u32(data, PE_POINTER_OFFSET)?;
+        if data.get(pe..pe + 4) != Some(b"PE\0\0") {
+            return Err(AuthenticodeError::MalformedImage);
+        }
+
+        let section_count = usize::from(read_u16(data, pe + 6)?);
+        let opt_size = usize::from(read_u16(data, pe + 20)?);
+        let opt = pe + COFF_HEADER_SIZE;
+
+        let (rva_count, dirs) = match read_u16(data, opt)? {
+            PE32_MAGIC => (read_u32(data, opt + 92)?, opt + 96),
+            PE32_PLUS_MAGIC => (read_u32(data, opt + 108)?, opt + 112),
+            _ => return Err(AuthenticodeError::MalformedImage),
+        };
+
+        let checksum = opt + 64;
+        let headers_size = read_u32(data, opt + 60)?;
+        if checksum + 4 > headers_size || headers_size > data.len() {
+            return Err(AuthenticodeError::MalformedImage);
+        }
+
+        let (cert_dir, cert_table) = if rva_count > CERT_TABLE_INDEX {
+            let cert_dir = dirs + CERT_TABLE_INDEX * DATA_DIRECTORY_SIZE;
+            let start = read_u32(data, cert_dir)?;
+            let len = read_u32(data, cert_dir + 4)?;
+            if cert_dir < checksum + 4 || cert_dir + DATA_DIRECTORY_SIZE > headers_size {
+                return Err(AuthenticodeError::MalformedImage);
+            }
+            let table = if len == 0 {
+                data.len()..data.len()
+            } else {
+                get_range(data, start, len)?
+            };
+            (Some(cert_dir), table)
+        } else {
+            (None, data.len()..data.len())
+        };
+
+        let section_headers = get_range(data, opt + opt_size, section_count * SECTION_HEADER_SIZE)?;
+        let mut sections = data[section_headers]
+            .as_chunks::<SECTION_HEADER_SIZE>()
+            .0
+            .iter()
+            .map(|header| get_range(data, read_u32(header, 20)?, read_u32(header, 16)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        sections.retain(|x| !x.is_empty());
+        sections.sort_unstable_by_key(|x| x.start);
+
+        Ok(Self {
+            data,
+            checksum,
+            cert_dir,
+            headers_size,
+            sections,
+            cert_table,
+        })
+    }
+
+    /// Compute the Authenticode SHA-256 hash of the image.
+    ///
+    /// The headers are hashed without the checksum and the certificate table data directory, followed by every
+    /// section in the order of their offset in the file, followed by any data after the sections that is not a part
+    /// of the certificate table.
+    pub(crate) fn authenticode_hash(&self) -> Sha256Hash {
+        let mut hasher = Sha256::new();
+        let headers = &self.data[..self.headers_size];
+
+        hasher.update(&headers[..self.checksum]);
+        match self.cert_dir {
+            Some(cert_dir) => {
+                hasher.update(&headers[self.checksum + 4..cert_dir]);
+                hasher.update(&headers[cert_dir + DATA_DIRECTORY_SIZE..]);
+            }
+            None => hasher.update(&headers[self.checksum + 4..]),
+        }
+
+        let mut bytes_hashed = self.headers_size;
+        for section in &self.sections {
+            hasher.update(&self.data[section.clone()]);
+            bytes_hashed += section.len();
+        }
+
+        let end = self.data.len().saturating_sub(self.cert_table.len());
+        if let Some(extra) = self.data.get(bytes_hashed..end) {
+            hasher.update(extra);
+        }
+
+        hasher.finalize().into()
+    }
+
+    /// Get the PKCS#7 `SignedData` structures in the certificate table of the image.
+    ///
+    /// Certificates of other types are skipped, and the iterator stops at the first malformed entry.
+    pub(crate) fn signatures(&self) -> impl Iterator<Item = &'a [u8]> {
+        let mut table = &self.data[self.cert_table.clone()];
+        core::iter::from_fn(move || {
+            loop {
+                let len = read_u32(table, 0).ok()?;
+                let revision = read_u16(table, 4).ok()?;
+                let cert_type = read_u16(table, 6).ok()?;
+                let entry = table.get(WIN_CERT_HEADER_SIZE..len)?;
+
+                table = table.get(len.next_multiple_of(8)..).unwrap_or_default();
+                if revision == WIN_CERT_REVISION && cert_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                    return Some(entry);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_pe() {
+        let image = include_bytes!("../../../../test-data/authenticode/signed.efi");
+        let pe = PeImage::parse(image).expect("Fixture should be a valid PE image");
+        assert_eq!(pe.sections.len(), 1);
+        assert_eq!(pe.signatures().count(), 1);
+
+        let unsigned = include_bytes!("../../../../test-data/authenticode/unsigned.efi");
+        let unsigned = PeImage::parse(unsigned).expect("Fixture should be a valid PE image");
+        assert_eq!(unsigned.signatures().count(), 0);
+        assert_eq!(unsigned.authenticode_hash(), pe.authenticode_hash());
+
+        assert!(PeImage::parse(b"MZ").is_err());
+        assert!(PeImage::parse(&image[..0x100]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn doesnt_panic(x in any::<Vec<(u16, u8)>>()) {
+            let mut image = include_bytes!("../../../../test-data/authenticode/signed.efi").to_vec();
+            let len = image.len();
+            for (offset, byte) in x {
+                image[usize::from(offset) % len] = byte;
+            }
+            if let Ok(pe) = PeImage::parse(&image) {
+                let _ = pe.authenticode_hash();
+                let _ = pe.signatures().count();
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Secure Boot key enrollment.
+//!
+//! When the firmware is in Setup Mode, the `PK`, `KEK`, and `db` variables can be written without the firmware
+//! having any keys enrolled yet. Key sets are stored in `\loader\keys\<name>`, and must contain `PK.auth`,
+//! `KEK.auth`, and `db.auth`. These are signed variable updates as produced by tools such as `sign-efi-sig-list`,
+//! which are written as is with time-based authenticated write access.
+//!
+//! Depending on the [`SecureBootEnroll`] setting, every key set is offered as a boot option, or the key set named
+//! `auto` is enrolled automatically after a countdown. The system is rebooted after enrollment, as enrolling the
+//! `PK` takes the firmware out of Setup Mode.
+
+use alloc::{format, vec::Vec};
+
+use log::{error, warn};
+use uefi::{
+    CStr16, Handle, Status, boot, cstr16,
+    fs::COMMON_SKIP_DIRS,
+    proto::{console::text::Input, loaded_image::LoadedImage, media::file::FileInfo},
+    runtime::{self, ResetType, VariableAttributes, VariableVendor},
+    system::with_stdin,
+};
+
+use crate::{
+    BootResult,
+    boot::{
+        action::BootAction, config::SecureBootEnroll, loader::LoadError,
+        secure_boot::SecureBootError,
+    },
+    config::{Config, builder::ConfigBuilder, parsers::Parsers},
+    system::{
+        fs::UefiFileSystem,
+        helper::{get_path_cstr, str_to_cstr},
+        variable::get_variable,
+    },
+};
+
+/// The directory that contains key sets.
+const KEYS_PREFIX: &CStr16 = cstr16!("\\loader\\keys");
+
+/// The name of the key set that is enrolled automatically.
+const AUTO_KEYS: &str = "auto";
+
+/// The amount of seconds to wait before enrolling the keys automatically.
+const ENROLL_COUNTDOWN: u64 = 15;
+
+/// The variables to enroll, in the order they must be enrolled in.
+///
+/// The `PK` is enrolled last, as the firmware leaves Setup Mode after the `PK` is enrolled.
+const ENROLL_VARIABLES: [(&CStr16, &CStr16, VariableVendor); 3] = [
+    (
+        cstr16!("db"),
+        cstr16!("db.auth"),
+        VariableVendor::IMAGE_SECURITY_DATABASE,
+    ),
+    (
+        cstr16!("KEK"),
+        cstr16!("KEK.auth"),
+        VariableVendor::GLOBAL_VARIABLE,
+    ),
+    (
+        cstr16!("PK"),
+        cstr16!("PK.auth"),
+        VariableVendor::GLOBAL_VARIABLE,
+    ),
+];
+
+/// The attributes of the enrolled variables.
+const ENROLL_ATTRS: VariableAttributes = VariableAttributes::NON_VOLATILE
+    .union(VariableAttributes::BOOTSERVICE_ACCESS)
+    .union(VariableAttributes::RUNTIME_ACCESS)
+    .union(VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS);
+
+/// Tests if the firmware is in Setup Mode through a UEFI variable.
+#[must_use = "Has no effect if the result is unused"]
+pub(crate) fn setup_mode_enabled() -> bool {
+    matches!(
+        get_variable::<bool>(cstr16!("SetupMode"), Some(VariableVendor::GLOBAL_VARIABLE)),
+        Ok(true)
+    )
+}
+
+/// Get a [`Config`] for every complete key set in `\loader\keys`, on the filesystem of the boot manager.
+pub(crate) fn get_enroll_configs() -> Vec<Config> {
+    let Some(handle) = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle())
+        .ok()
+        .and_then(|image| image.device())
+    else {
+        return Vec::new();
+    };
+    let Ok(mut fs) = UefiFileSystem::from_handle(handle) else {
+        return Vec::new();
+    };
+
+    let dirs: Vec<_> = fs
+        .read_dir(KEYS_PREFIX)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|x| x.is_directory())
+        .filter(|x| !COMMON_SKIP_DIRS.contains(&x.file_name())) // excludes "." and ".."
+        .collect();
+
+    dirs.iter()
+        .filter(|dir| is_complete_key_set(&mut fs, dir))
+        .map(|dir| {
+            let name = dir.file_name();
+            ConfigBuilder::new(format!("{name}"), "")
+                .efi_path(format!("{KEYS_PREFIX}\\{name}"))
+                .title(format!("Enroll Secure Boot Keys: {name}"))
+                .action(BootAction::EnrollKeys)
+                .origin(Parsers::Special)
+                .fs_handle(handle)
+                .build()
+        })
+        .collect()
+}
+
+/// Check if a directory in `\loader\keys` contains every variable needed for enrollment.
+fn is_complete_key_set(fs: &mut UefiFileSystem, dir: &FileInfo) -> bool {
+    let Ok(dir) = get_path_cstr(KEYS_PREFIX, dir.file_name()) else {
+        return false;
+    };
+
+    ENROLL_VARIABLES
+        .iter()
+        .all(|(_, file, _)| get_path_cstr(&dir, file).is_ok_and(|path| fs.exists(&path)))
+}
+
+/// Enroll the key set named `auto` after a countdown, if it exists and the setting allows for it.
+///
+/// The countdown can be cancelled by pressing any key. If the keys are enrolled, then the system is rebooted.
+pub(crate) fn auto_enroll(configs: &[Config], setting: SecureBootEnroll) {
+    if setting != SecureBootEnroll::Force {
+        return;
+    }
+
+    let Some(config) = configs
+        .iter()
+        .find(|x| x.action == BootAction::EnrollKeys && x.filename == AUTO_KEYS)
+    else {
+        return;
+    };
+
+    if countdown()
+        && let Err(e) = enroll_keys(config)
+    {
+        error!("Failed to enroll Secure Boot keys: {e}");
+    }
+}
+
+/// Count down before enrolling keys automatically.
+///
+/// Returns `false` if any key was pressed during the countdown.
+fn countdown() -> bool {
+    for remaining in (1..=ENROLL_COUNTDOWN).rev() {
+        warn!(
+            "Enrolling Secure Boot keys from {KEYS_PREFIX}\\{AUTO_KEYS} in {remaining} seconds, press any key to cancel"
+        );
+        for _ in 0..10 {
+            if with_stdin(Input::read_key).is_ok_and(|key| key.is_some()) {
+                warn!("Secure Boot key enrollment cancelled");
+                return false;
+            }
+            boot::stall(100_000);
+        }
+    }
+    true
+}
+
+/// Enroll the key set of a [`Config`] into the firmware, then reboot.
+///
+/// Every variable is read before any are written, so that a missing file does not result in a partial enrollment.
+///
+/// # Errors
+///
+/// May return an `Error` if the firmware is not in Setup Mode, the key set could not be read, or the firmware
+/// rejected any of the variables.
+pub(crate) fn enroll_keys(config: &Config) -> BootResult<Handle> {
+    let handle = *config
+        .fs_handle
+        .ok_or_else(|| LoadError::ConfigMissingHandle(config.filename.clone()))?;
+    let dir = config
+        .efi_path
+        .as_deref()
+        .ok_or_else(|| LoadError::ConfigMissingEfi(config.filename.clone()))?;
+
+    if !setup_mode_enabled() {
+        return Err(SecureBootError::NotInSetupMode.into());
+    }
+
+    let mut fs = UefiFileSystem::from_handle(handle)?;
+    let dir = str_to_cstr(dir)?;
+
+    let mut contents = Vec::with_capacity(ENROLL_VARIABLES.len());
+    for (_, file, _) in ENROLL_VARIABLES {
+        contents.push(fs.read(&get_path_cstr(&dir, file)?)?);
+    }
+
+    for ((name, _, vendor), content) in ENROLL_VARIABLES.iter().zip(contents) {
+        runtime::set_variable(name, vendor, ENROLL_ATTRS, &content)?;
+    }
+
+    warn!("Secure Boot keys enrolled from {dir}, rebooting");
+    boot::stall(1_000_000);
+    runtime::reset(ResetType::COLD, Status::SUCCESS, None)
+}
@@ -0,0 +1,326 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Hash pinning of loaded images.
+//!
+//! This is a validator that does not depend on Shim or the firmware's signature databases. Instead, the SHA-256
+//! hash of every loaded image is checked against a set of pinned hashes. These are taken from the `sha256` key of
+//! the entry that is being booted if it has one, or otherwise from the allowlist. The allowlist is made of the
+//! hashes in `\loader\sha256sums` on the partition of the boot manager, in the same format as the output of
+//! `sha256sum`, and the raw 32 byte hashes in the `ImageSha256Allowlist` variable. The variable is only read if it
+//! cannot be written by the OS, which means that it must either be only accessible to boot services, or require
+//! time based authenticated writes. Otherwise, it is ignored with a warning.
+//!
+//! If there are any pinned hashes, then images that do not match are refused even if Secure Boot is disabled. While
+//! Secure Boot is disabled, images that do match are trusted without consulting the firmware. While it is enabled,
+//! they must still be verified by the firmware, as the pinned hashes can be changed by anyone that can write to the
+//! ESP. If the firmware never calls the security hooks, then the image is unloaded, so that an image is never started
+//! without its hash being checked.
+//!
+//! Images that are booted directly rather than through `LoadImage` cannot be verified by the firmware. While Secure
+//! Boot is enabled, such an image must instead have a SHA-256 hash that is trusted by a source outside of the ESP,
+//! which is checked by [`check_trusted_image`]. These are the SHA-256 hashes in `db`, and the `ImageSha256Allowlist`
+//! variable if it cannot be written by the OS. An image with a SHA-256 hash in `dbx` is always refused.
+
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt::Write, ptr::NonNull};
+
+use log::warn;
+use sha2::{Digest, Sha256};
+use uefi::{
+    CStr16, cstr16,
+    proto::device_path::DevicePath,
+    runtime::{VariableAttributes, VariableVendor},
+};
+
+use crate::{
+    BootResult,
+    boot::secure_boot::{
+        EnforcedContext, SecureBootError,
+        signature_list::{EFI_CERT_SHA256_GUID, SignatureList, SignatureLists},
+    },
+    system::{
+        fs::UefiFileSystem,
+        variable::{BOOTMGR_GUID, RuntimeUefiVariableStorage, UefiVariableStorage},
+    },
+};
+
+/// The path to the allowlist of hashes on the partition of the boot manager.
+const ALLOWLIST_PATH: &CStr16 = cstr16!("\\loader\\sha256sums");
+
+/// The variable that contains the allowlist of hashes, in the boot manager's variable namespace.
+const ALLOWLIST_VARIABLE: &CStr16 = cstr16!("ImageSha256Allowlist");
+
+/// A SHA-256 hash.
+pub(crate) type Sha256Hash = [u8; 32];
+
+/// Parse a SHA-256 hash from a string of 64 hexadecimal characters.
+pub(crate) fn parse_sha256(hex: &str) -> Option<Sha256Hash> {
+    let hex = hex.as_bytes();
+    if hex.len() != 64 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (byte, pair) in hash.iter_mut().zip(hex.as_chunks::<2>().0) {
+        let pair = str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(hash)
+}
+
+/// Format a SHA-256 hash as lowercase hexadecimal characters.
+fn format_sha256(hash: &Sha256Hash) -> String {
+    hash.iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Parse the hashes of an allowlist in the format of `sha256sum`.
+///
+/// The hash is the first word of every line, and anything after it (such as the filename) is ignored. Empty lines
+/// and lines starting with `#` are skipped, and invalid hashes are skipped with a warning.
+fn parse_allowlist(content: &str) -> impl Iterator<Item = Sha256Hash> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let hex = line.split_ascii_whitespace().next().unwrap_or_default();
+            let hash = parse_sha256(hex);
+            if hash.is_none() {
+                warn!("[HASH PIN]: Found invalid SHA-256 hash {hex} in {ALLOWLIST_PATH}");
+            }
+            hash
+        })
+}
+
+/// Get the allowlist of hashes from the `ImageSha256Allowlist` variable in a [`UefiVariableStorage`].
+///
+/// If the variable is accessible at runtime without time based authenticated writes, then the OS is able to write
+/// to it, so it is not trusted and no hashes are returned.
+fn get_variable_allowlist(vars: &impl UefiVariableStorage) -> Vec<Sha256Hash> {
+    let Ok(Some((var, attrs))) =
+        vars.get_variable_bytes_with_attributes(ALLOWLIST_VARIABLE, &VariableVendor(BOOTMGR_GUID))
+    else {
+        return Vec::new();
+    };
+
+    if attrs.contains(VariableAttributes::RUNTIME_ACCESS)
+        && !attrs.contains(VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS)
+    {
+        warn!("[HASH PIN]: Ignoring {ALLOWLIST_VARIABLE}, as it can be written by the OS");
+        return Vec::new();
+    }
+
+    var.as_chunks::<32>().0.to_vec()
+}
+
+/// Get the allowlist of hashes from the filesystem of the boot manager and from a [`UefiVariableStorage`].
+fn get_allowlist(vars: &impl UefiVariableStorage) -> Vec<Sha256Hash> {
+    let mut pins = Vec::new();
+
+    if let Ok(mut fs) = UefiFileSystem::from_image_fs()
+        && let Ok(content) = fs.read(ALLOWLIST_PATH)
+        && let Ok(content) = str::from_utf8(&content)
+    {
+        pins.extend(parse_allowlist(content));
+    }
+
+    pins.extend(get_variable_allowlist(vars));
+    pins
+}
+
+/// Check if the SHA-256 hash of an image is one of the pinned hashes.
+///
+/// # Errors
+///
+/// May return an `Error` if the hash of the image is not pinned.
+pub(crate) fn check_image(pins: &[Sha256Hash], image: &[u8]) -> BootResult<()> {
+    let hash: Sha256Hash = Sha256::digest(image).into();
+    if pins.contains(&hash) {
+        Ok(())
+    } else {
+        Err(SecureBootError::HashNotPinned(format_sha256(&hash)).into())
+    }
+}
+
+/// Get the SHA-256 hashes of a signature database in a [`UefiVariableStorage`].
+fn get_db_hashes(vars: &impl UefiVariableStorage, name: &CStr16) -> Vec<Sha256Hash> {
+    let Ok(Some(db)) = vars.get_variable_bytes(name, &VariableVendor::IMAGE_SECURITY_DATABASE)
+    else {
+        return Vec::new();
+    };
+
+    SignatureLists::new(&db)
+        .filter(|list| list.sig_type() == EFI_CERT_SHA256_GUID)
+        .flat_map(SignatureList::signatures)
+        .filter_map(|x| Sha256Hash::try_from(x).ok())
+        .collect()
+}
+
+/// Check if the SHA-256 hash of an image is trusted by a source outside of the ESP.
+///
+/// The image is refused if its hash is in `dbx`. Otherwise, it is trusted if its hash is in `db`, or in the
+/// `ImageSha256Allowlist` variable if that cannot be written by the OS.
+///
+/// # Errors
+///
+/// May return an `Error` if the hash of the image is forbidden by `dbx`, or is not trusted.
+pub(crate) fn check_trusted_image(vars: &impl UefiVariableStorage, image: &[u8]) -> BootResult<()> {
+    let hash: Sha256Hash = Sha256::digest(image).into();
+    if get_db_hashes(vars, cstr16!("dbx")).contains(&hash) {
+        return Err(SecureBootError::HashForbidden(format_sha256(&hash)).into());
+    }
+
+    if get_db_hashes(vars, cstr16!("db")).contains(&hash)
+        || get_variable_allowlist(vars).contains(&hash)
+    {
+        Ok(())
+    } else {
+        Err(SecureBootError::UnverifiedImage.into())
+    }
+}
+
+/// Hash pinning validator with [`super::Validator`] function signature.
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be read, or the hash of the image is not pinned.
+pub(super) fn hash_pin_validate(
+    ctx: Option<NonNull<u8>>,
+    device_path: Option<&DevicePath>,
+    file_buffer: Option<&mut [u8]>,
+    _file_size: usize,
+) -> BootResult<()> {
+    // SAFETY: this validator is only installed by `super::load_image` through `load_image_enforced`, with the pins as
+    // the context.
+    unsafe {
+        EnforcedContext::check(
+            ctx,
+            device_path,
+            file_buffer,
+            |pins: &Vec<Sha256Hash>, image| check_image(pins, image),
+        )
+    }
+}
+
+/// Get the hashes that an image must match.
+///
+/// If `pin` is provided, then the image must have that hash. Otherwise, the image must have a hash in the allowlist.
+/// If there is no pin and the allowlist is empty, then no hashes are returned, and the image is not hash pinned.
+pub(super) fn get_pins(pin: Option<Sha256Hash>) -> Vec<Sha256Hash> {
+    match pin {
+        Some(pin) => vec![pin],
+        None => get_allowlist(&RuntimeUefiVariableStorage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boot::secure_boot::signature_list::build_list, system::variable::MemoryUefiVariableStorage,
+    };
+
+    /// The SHA-256 hash of `hello`.
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_sha256() {
+        let hash = parse_sha256(HELLO_SHA256);
+        assert!(hash.is_some());
+        assert_eq!(
+            hash.map(|x| format_sha256(&x)).as_deref(),
+            Some(HELLO_SHA256)
+        );
+        assert_eq!(
+            parse_sha256(&HELLO_SHA256.to_ascii_uppercase()),
+            parse_sha256(HELLO_SHA256)
+        );
+        assert_eq!(parse_sha256(&HELLO_SHA256[1..]), None);
+        assert_eq!(parse_sha256(&HELLO_SHA256.replace('c', "g")), None);
+        assert_eq!(parse_sha256(&HELLO_SHA256.replacen("2c", "+c", 1)), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_allowlist() {
+        let content = alloc::format!(
+            "# pinned images\n\n{HELLO_SHA256}  vmlinuz-linux\ninvalid  shellx64.efi\n{}\n",
+            "0".repeat(64)
+        );
+        let pins: Vec<_> = parse_allowlist(&content).collect();
+        assert_eq!(pins.len(), 2);
+        assert_eq!(Some(pins[0]), parse_sha256(HELLO_SHA256));
+        assert_eq!(pins[1], [0; 32]);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_check_image() {
+        let pins: Vec<_> = parse_sha256(HELLO_SHA256).into_iter().collect();
+        assert!(check_image(&pins, b"hello").is_ok());
+        assert!(check_image(&pins, b"hello!").is_err());
+        assert!(check_image(&[], b"hello").is_err());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_variable_allowlist() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let vendor = VariableVendor(BOOTMGR_GUID);
+        let boot_only = VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS;
+        let runtime = boot_only | VariableAttributes::RUNTIME_ACCESS;
+
+        let _ = vars.set_variable_bytes(ALLOWLIST_VARIABLE, &vendor, runtime, &[1; 64]);
+        assert_eq!(get_variable_allowlist(&vars), Vec::<Sha256Hash>::new());
+
+        let authenticated = runtime | VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        let _ = vars.set_variable_bytes(ALLOWLIST_VARIABLE, &vendor, authenticated, &[1; 32]);
+        assert_eq!(get_variable_allowlist(&vars), [[1; 32]]);
+
+        let _ = vars.set_variable_bytes(ALLOWLIST_VARIABLE, &vendor, boot_only, &[2; 64]);
+        assert_eq!(get_variable_allowlist(&vars), [[2; 32], [2; 32]]);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_check_trusted_image() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let db_vendor = VariableVendor::IMAGE_SECURITY_DATABASE;
+        let attrs = VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS
+            | VariableAttributes::TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        let hello = parse_sha256(HELLO_SHA256).unwrap_or_default();
+        assert!(check_trusted_image(&vars, b"hello").is_err());
+
+        let db = build_list(EFI_CERT_SHA256_GUID, &[&[0; 32], &hello]);
+        let _ = vars.set_variable_bytes(cstr16!("db"), &db_vendor, attrs, &db);
+        assert!(check_trusted_image(&vars, b"hello").is_ok());
+        assert!(check_trusted_image(&vars, b"hello!").is_err());
+
+        let dbx = build_list(EFI_CERT_SHA256_GUID, &[&hello]);
+        let _ = vars.set_variable_bytes(cstr16!("dbx"), &db_vendor, attrs, &dbx);
+        assert!(check_trusted_image(&vars, b"hello").is_err());
+
+        let mut vars = MemoryUefiVariableStorage::default();
+        let vendor = VariableVendor(BOOTMGR_GUID);
+        let _ = vars.set_variable_bytes(ALLOWLIST_VARIABLE, &vendor, attrs, &hello);
+        assert!(check_trusted_image(&vars, b"hello").is_ok());
+    }
+}
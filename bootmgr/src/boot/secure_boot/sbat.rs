@@ -0,0 +1,308 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! SBAT revocation checking of loaded images.
+//!
+//! SBAT (UEFI Secure Boot Advanced Targeting) revokes vulnerable images by a generation number instead of by their
+//! hash or certificate. An image may have an `.sbat` section, which is a CSV file with a line for every component
+//! the image is made of. Each line starts with the name of the component and its generation, followed by vendor
+//! information that is not used for revocation.
+//!
+//! The revocations are taken from the `SbatLevel` variable that is written by Shim, or from a policy bundled with
+//! the boot manager, whichever has the newer datestamp. The first line of a revocation list is `sbat,1,<datestamp>`,
+//! followed by a line for every revoked component with the minimum generation that is still allowed. An image is
+//! refused if any of its components has a lower generation than that.
+//!
+//! Shim already checks SBAT for the images it loads, so this only applies to images that are loaded without Shim.
+//! Images without an `.sbat` section are not checked, as kernels and Windows do not carry SBAT metadata.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::ptr::NonNull;
+
+use object::{Object, ObjectSection};
+use uefi::{
+    CStr16, Identify, cstr16,
+    proto::{device_path::DevicePath, shim::ShimLock},
+    runtime::VariableVendor,
+};
+
+use crate::{
+    BootResult,
+    boot::secure_boot::{EnforcedContext, SecureBootError},
+    system::variable::{RuntimeUefiVariableStorage, UefiVariableStorage},
+};
+
+/// The variable that contains the revocations, in Shim's variable namespace.
+const SBAT_LEVEL_VARIABLE: &CStr16 = cstr16!("SbatLevel");
+
+/// The bundled revocations, which are the same as the `latest` revocations of Shim 15.8 (`SBAT_VAR_LATEST`).
+const BUNDLED_POLICY: &str = "sbat,1,2024010900\nshim,4\ngrub,3\ngrub.debian,4\n";
+
+/// A single line of an `.sbat` section or a revocation list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SbatEntry<'a> {
+    /// The name of the component.
+    component: &'a str,
+
+    /// The generation of the component.
+    generation: u32,
+}
+
+/// Parse the component names and generations of a CSV file in the SBAT format.
+///
+/// Empty lines are skipped.
+///
+/// # Errors
+///
+/// May return an `Error` if any line does not have a component name and a valid generation.
+fn parse_entries(csv: &str) -> BootResult<Vec<SbatEntry<'_>>> {
+    csv.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields = line.split(',');
+            let component = fields.next().filter(|x| !x.is_empty());
+            let generation = fields.next().and_then(|x| x.trim().parse().ok());
+            match (component, generation) {
+                (Some(component), Some(generation)) => Ok(SbatEntry {
+                    component,
+                    generation,
+                }),
+                _ => Err(SecureBootError::InvalidSbat(line.to_owned()).into()),
+            }
+        })
+        .collect()
+}
+
+/// A revocation list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SbatPolicy {
+    /// The datestamp of the revocation list, in the format `YYYYMMDDXX`.
+    datestamp: u64,
+
+    /// The content of the revocation list.
+    content: String,
+}
+
+impl SbatPolicy {
+    /// Parse a [`SbatPolicy`] from the content of a revocation list.
+    ///
+    /// Returns [`None`] if the first line is not a valid `sbat,1,<datestamp>` header, or any line is invalid.
+    fn parse(content: &str) -> Option<Self> {
+        let content = content.trim_end_matches('\0');
+        let mut header = content.lines().next()?.trim().split(',');
+        if header.next()? != "sbat" || header.next()? != "1" {
+            return None;
+        }
+        let datestamp = header.next()?.parse().ok()?;
+        parse_entries(content).ok()?;
+
+        Some(Self {
+            datestamp,
+            content: content.to_owned(),
+        })
+    }
+
+    /// Get the [`SbatPolicy`] of the system.
+    pub(crate) fn from_system() -> Self {
+        Self::from_vars(&RuntimeUefiVariableStorage)
+    }
+
+    /// Get the newer [`SbatPolicy`] out of the `SbatLevel` variable in a [`UefiVariableStorage`] and the bundled
+    /// revocations.
+    fn from_vars(vars: &impl UefiVariableStorage) -> Self {
+        let bundled = Self::parse(BUNDLED_POLICY).unwrap_or_else(|| Self {
+            datestamp: 0,
+            content: BUNDLED_POLICY.to_owned(),
+        });
+
+        vars.get_variable_bytes(SBAT_LEVEL_VARIABLE, &VariableVendor(ShimLock::GUID))
+            .ok()
+            .flatten()
+            .and_then(|var| Self::parse(str::from_utf8(&var).ok()?))
+            .filter(|var| var.datestamp > bundled.datestamp)
+            .unwrap_or(bundled)
+    }
+
+    /// Check the content of an `.sbat` section against the revocation list.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the section is invalid, or any of its components are revoked.
+    fn check_sbat(&self, sbat: &str) -> BootResult<()> {
+        let revocations = parse_entries(&self.content)?;
+        for entry in parse_entries(sbat)? {
+            if let Some(revocation) = revocations
+                .iter()
+                .skip(1) // skip the sbat,1,<datestamp> header
+                .find(|x| x.component == entry.component && x.generation > entry.generation)
+            {
+                return Err(SecureBootError::SbatRevoked {
+                    component: entry.component.to_owned(),
+                    generation: entry.generation,
+                    required: revocation.generation,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the `.sbat` section of an image against the revocation list.
+    ///
+    /// Images that do not have an `.sbat` section are allowed.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the image is not a valid PE image, its `.sbat` section is invalid, or any of its
+    /// components are revoked.
+    pub(crate) fn check_image(&self, image: &[u8]) -> BootResult<()> {
+        let pe = object::File::parse(image).map_err(|_| {
+            SecureBootError::InvalidSbat("Image is not a valid PE image".to_owned())
+        })?;
+        let Some(section) = pe.section_by_name(".sbat") else {
+            return Ok(());
+        };
+
+        let data = section
+            .data()
+            .map_err(|_| SecureBootError::InvalidSbat(".sbat is out of bounds".to_owned()))?;
+        let data = data.split(|&x| x == 0).next().unwrap_or_default(); // the section may be padded with zeroes
+        let sbat = str::from_utf8(data)
+            .map_err(|_| SecureBootError::InvalidSbat(".sbat is not valid UTF-8".to_owned()))?;
+
+        self.check_sbat(sbat)
+    }
+}
+
+/// SBAT validator with [`super::Validator`] function signature.
+///
+/// As every enforced validator checks SBAT, this does nothing else, and is installed as a precheck before the
+/// firmware verifies the image.
+///
+/// # Errors
+///
+/// May return an `Error` if the image could not be read, or any of its components are revoked.
+pub(super) fn sbat_validate(
+    ctx: Option<NonNull<u8>>,
+    device_path: Option<&DevicePath>,
+    file_buffer: Option<&mut [u8]>,
+    _file_size: usize,
+) -> BootResult<()> {
+    // SAFETY: this validator is only installed by `super::load_image` through `load_image_enforced`, with `()` as the
+    // context.
+    unsafe { EnforcedContext::check(ctx, device_path, file_buffer, |(): &(), _| Ok(())) }
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::runtime::VariableAttributes;
+
+    use super::*;
+    use crate::{error::BootError, system::variable::MemoryUefiVariableStorage};
+
+    /// The `.sbat` section of a GRUB image.
+    const GRUB_SBAT: &str =
+        "sbat,1,SBAT Version,sbat,1,https://github.com/rhboot/shim/blob/main/SBAT.md
+grub,4,Free Software Foundation,grub,2.12,https://www.gnu.org/software/grub/
+grub.debian,4,Debian,grub2,2.12-1,https://tracker.debian.org/pkg/grub2
+";
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_entries() {
+        let entries = parse_entries(GRUB_SBAT).expect("Valid SBAT should be parsed");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[1],
+            SbatEntry {
+                component: "grub",
+                generation: 4
+            }
+        );
+
+        assert!(parse_entries("grub").is_err());
+        assert!(parse_entries("grub,x").is_err());
+        assert!(parse_entries(",1").is_err());
+        assert!(parse_entries("\n\n").is_ok_and(|x| x.is_empty()));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_check_sbat() {
+        let policy = SbatPolicy::parse(BUNDLED_POLICY).expect("Bundled policy should be valid");
+        assert!(policy.check_sbat(GRUB_SBAT).is_ok());
+        assert!(policy.check_sbat("shim,4\n").is_ok());
+        assert!(policy.check_sbat("shim,3\n").is_err());
+        assert!(policy.check_sbat("other,1\n").is_ok());
+
+        let revoked = policy.check_sbat(&GRUB_SBAT.replace("grub.debian,4", "grub.debian,3"));
+        assert!(matches!(
+            revoked,
+            Err(BootError::SecureBootError(SecureBootError::SbatRevoked { ref component, generation: 3, required: 4 }))
+                if component == "grub.debian"
+        ));
+        assert!(policy.check_sbat("grub,x\n").is_err());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_policy_from_vars() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let vendor = VariableVendor(ShimLock::GUID);
+        let attrs = VariableAttributes::BOOTSERVICE_ACCESS;
+        let bundled = SbatPolicy::parse(BUNDLED_POLICY);
+        assert_eq!(Some(SbatPolicy::from_vars(&vars)), bundled);
+
+        let older = b"sbat,1,2021030218\n";
+        let _ = vars.set_variable_bytes(SBAT_LEVEL_VARIABLE, &vendor, attrs, older);
+        assert_eq!(Some(SbatPolicy::from_vars(&vars)), bundled);
+
+        let newer = b"sbat,1,2099010100\ngrub,5\n\0";
+        let _ = vars.set_variable_bytes(SBAT_LEVEL_VARIABLE, &vendor, attrs, newer);
+        let policy = SbatPolicy::from_vars(&vars);
+        assert_eq!(policy.datestamp, 2_099_010_100);
+        assert!(policy.check_sbat(GRUB_SBAT).is_err());
+
+        let _ = vars.set_variable_bytes(SBAT_LEVEL_VARIABLE, &vendor, attrs, b"grub,5\n");
+        assert_eq!(Some(SbatPolicy::from_vars(&vars)), bundled);
+    }
+
+    /// A PE image with a single section.
+    const IMAGE: &[u8] = include_bytes!("../../../test-data/authenticode/unsigned.efi");
+
+    /// Get a copy of [`IMAGE`] where the only section is an `.sbat` section.
+    fn image_with_sbat(sbat: &str) -> Vec<u8> {
+        let mut image = IMAGE.to_vec();
+        let header = image
+            .windows(8)
+            .position(|x| x == b".text\0\0\0")
+            .unwrap_or_default();
+        image[header..header + 8].copy_from_slice(b".sbat\0\0\0");
+        image[0x200..0x400].fill(0);
+        image[0x200..0x200 + sbat.len()].copy_from_slice(sbat.as_bytes());
+        image
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_check_image() {
+        let policy = SbatPolicy::from_vars(&MemoryUefiVariableStorage::default());
+        assert!(policy.check_image(IMAGE).is_ok());
+        assert!(policy.check_image(&image_with_sbat(GRUB_SBAT)).is_ok());
+        assert!(
+            policy
+                .check_image(&image_with_sbat("sbat,1\ngrub,2\n"))
+                .is_err()
+        );
+        assert!(policy.check_image(b"not an image").is_err());
+    }
+}
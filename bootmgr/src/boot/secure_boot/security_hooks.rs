@@ -10,14 +10,16 @@
 //! These security hooks are very simple in nature, and follow a series of steps:
 //! 1. Take the raw pointers passed to the hooks, and parse them as safer equivalents ([`Option<DevicePath>`], `&mut [u8]`)
 //! 2. Pass those safer equivalents to the custom validator
-//! 3. If the validator returns a failed status, then pass those raw pointers to the original validators.
+//! 3. If the validator returns a failed status, then pass those raw pointers to the original validators, unless the
+//!    validator is enforced or a precheck, in which case the image is refused.
+//! 4. If the validator is a precheck and the image passed, then also pass those raw pointers to the original validators.
 //!
 //! It will also provide an implementation for `SecurityOverrideInner` for installing those hooks into the security override
 //! state.
 //!
 //! # Safety
 //!
-//! This module uses unsafe in 5 places. This is quite dangerous, though in the context of how these security hooks are called,
+//! This module uses unsafe in 7 places. This is quite dangerous, though in the context of how these security hooks are called,
 //! it should still be quite safe even considering the risks.
 //!
 //! 1. Unsafe is required to call FFI functions such as the original hook. This requires one condition, which is upheld in the
@@ -31,6 +33,8 @@
 //! 5. Unsafe is required to convert FFI [`DevicePath`]s into regular [`DevicePath`]s. However, this should be safe as long as
 //!    the data is valid (which is true in the normal calling context of the program). There are checks to see if the pointer is
 //!    non-null and aligned as well. In addition, the pointer is not modified at all as it is passed as `*const`.
+//! 6. Unsafe is required to call the original hook after a precheck passes. See point 1.
+//! 7. See point 6.
 
 use core::ffi::c_void;
 
@@ -41,7 +45,10 @@ use uefi::{
 };
 
 use crate::{
-    boot::secure_boot::{SECURITY_OVERRIDE, security_override::SecurityOverrideInner},
+    boot::secure_boot::{
+        SECURITY_OVERRIDE,
+        security_override::{SecurityOverrideInner, ValidatorMode},
+    },
     system::protos::{Security2Arch, Security2ArchProtocol, SecurityArch, SecurityArchProtocol},
 };
 
@@ -110,7 +117,8 @@ impl SecurityOverrideInner {
 /// The override hook for [`SecurityArchProtocol`].
 ///
 /// This calls the custom validator to validate the `file` parameter. If the validator fails, then the original hook
-/// will be used to verify the image.
+/// will be used to verify the image, unless the validator is enforced or a precheck. If a precheck passes, then the
+/// original hook is still used to verify the image.
 ///
 /// # Safety
 ///
@@ -127,6 +135,10 @@ unsafe extern "efiapi" fn auth_state_hook(
         .get()
         .call_validator(ffi_ptr_to_device_path(file), None)
     {
+        Err(e) if security_override.get().mode != ValidatorMode::Fallback => {
+            warn!("{e}"); // an enforced validator or a precheck has the final verdict on failure
+            Status::SECURITY_VIOLATION
+        }
         Err(e) => {
             warn!("{e}"); // if we get an error, log it and call the original hook to be the final verdict
 
@@ -137,6 +149,15 @@ unsafe extern "efiapi" fn auth_state_hook(
                     .call_original_hook(this, auth_status, file)
             }
         }
+        Ok(()) if security_override.get().mode == ValidatorMode::Precheck => {
+            // a precheck that passed leaves the final verdict to the original hook
+            // SAFETY: if UEFI LoadImage is calling this hook, these arguments should be completely valid and safe
+            unsafe {
+                security_override
+                    .get()
+                    .call_original_hook(this, auth_status, file)
+            }
+        }
         _ => Status::SUCCESS, // if there was no error, return success (the image is valid)
     }
 }
@@ -144,7 +165,8 @@ unsafe extern "efiapi" fn auth_state_hook(
 /// The override hook for [`Security2ArchProtocol`].
 ///
 /// This calls the custom validator to validate the either the `device_path` or `file_buffer` parameters. If the
-/// validator fails, then the original hook will be used to verify the image.
+/// validator fails, then the original hook will be used to verify the image, unless the validator is enforced or a
+/// precheck. If a precheck passes, then the original hook is still used to verify the image.
 ///
 /// # Safety
 ///
@@ -173,6 +195,10 @@ unsafe extern "efiapi" fn authentication_hook(
         .get()
         .call_validator(ffi_ptr_to_device_path(device_path), slice)
     {
+        Err(e) if security_override.get().mode != ValidatorMode::Fallback => {
+            warn!("{e}"); // an enforced validator or a precheck has the final verdict on failure
+            Status::SECURITY_VIOLATION
+        }
         Err(e) => {
             warn!("{e}"); // if we get an error, log it and call the original hook to be the final verdict
 
@@ -187,6 +213,19 @@ unsafe extern "efiapi" fn authentication_hook(
                 )
             }
         }
+        Ok(()) if security_override.get().mode == ValidatorMode::Precheck => {
+            // a precheck that passed leaves the final verdict to the original hook
+            // SAFETY: if UEFI LoadImage is calling this hook, these arguments should be completely valid and safe
+            unsafe {
+                security_override.get().call_original_hook2(
+                    this,
+                    device_path,
+                    file_buffer,
+                    file_size,
+                    boot_policy,
+                )
+            }
+        }
         _ => Status::SUCCESS, // if there was no error, return success (the image is valid)
     }
 }
@@ -73,6 +73,30 @@ pub(super) struct SecurityOverrideInner {
 
     /// The context for the validator if required.
     pub(super) validator_ctx: Option<NonNull<u8>>,
+
+    /// How the verdict of the validator is combined with the verdict of the original hooks.
+    pub(super) mode: ValidatorMode,
+}
+
+/// How the verdict of a validator is combined with the verdict of the original hooks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) enum ValidatorMode {
+    /// Images that pass the validator are trusted, and images that fail are passed to the original hooks.
+    ///
+    /// The validator is only installed if Secure Boot is enabled.
+    #[default]
+    Fallback,
+
+    /// The verdict of the validator is final, and the validator is installed even if Secure Boot is disabled.
+    ///
+    /// This is only allowed while Secure Boot is disabled. Otherwise, the validator is installed as a
+    /// [`ValidatorMode::Precheck`], so that the firmware's signature databases are never skipped.
+    Enforce,
+
+    /// Images that fail the validator are refused, and images that pass are passed to the original hooks.
+    ///
+    /// The validator is only installed if Secure Boot is enabled.
+    Precheck,
 }
 
 impl SecurityOverrideInner {
@@ -80,9 +104,13 @@ impl SecurityOverrideInner {
     ///
     /// This will essentially create a new instance of [`SecurityOverrideInner`] through default,
     /// then use `install_validator` on that instance, then return that instance.
-    pub(super) fn new(validator: Validator, validator_ctx: Option<NonNull<u8>>) -> Self {
+    pub(super) fn new(
+        validator: Validator,
+        validator_ctx: Option<NonNull<u8>>,
+        mode: ValidatorMode,
+    ) -> Self {
         let mut security_override = Self::default();
-        security_override.install_validator(validator, validator_ctx);
+        security_override.install_validator(validator, validator_ctx, mode);
         security_override
     }
 
@@ -90,12 +118,16 @@ impl SecurityOverrideInner {
     ///
     /// This validator must be of type [`Validator`], and may optionally have a persistent `validator_ctx` state.
     /// This context is a `NonNull<u8>` and should be cast to and from whatever type you're using as context.
+    ///
+    /// The `mode` decides how the verdict of the validator is combined with the verdict of the original hooks, and if
+    /// the validator is installed when Secure Boot is disabled. See [`ValidatorMode`].
     pub(super) fn install_validator(
         &mut self,
         validator: Validator,
         validator_ctx: Option<NonNull<u8>>,
+        mode: ValidatorMode,
     ) {
-        if self.should_skip_install(validator, validator_ctx) {
+        if self.should_skip_install(validator, validator_ctx, mode) {
             return;
         }
 
@@ -104,6 +136,11 @@ impl SecurityOverrideInner {
 
         self.validator = Some(validator);
         self.validator_ctx = validator_ctx;
+        self.mode = if mode == ValidatorMode::Enforce && secure_boot_enabled() {
+            ValidatorMode::Precheck // the firmware must still check db and dbx when Secure Boot is enabled
+        } else {
+            mode
+        };
     }
 
     /// Uninstalls the custom validator.
@@ -119,11 +156,12 @@ impl SecurityOverrideInner {
     /// Checks if the security override should not be installed.
     ///
     /// If the validators are exactly the same (function pointer addresses are equal), or secure boot
-    /// is not enabled, then it returns [`false`].
+    /// is not enabled and the validator is not enforced, then it returns [`true`].
     fn should_skip_install(
         &self,
         validator: Validator,
         validator_ctx: Option<NonNull<u8>>,
+        mode: ValidatorMode,
     ) -> bool {
         if let Some(security_validator) = self.validator {
             if core::ptr::fn_addr_eq(validator, security_validator)
@@ -135,7 +173,7 @@ impl SecurityOverrideInner {
             self.uninstall_validator();
         }
 
-        if !secure_boot_enabled() {
+        if mode != ValidatorMode::Enforce && !secure_boot_enabled() {
             return true;
         }
 
@@ -18,16 +18,15 @@ use uefi::{
     Handle, Identify,
     boot::{self, ScopedProtocol},
     cstr16,
-    proto::{device_path::DevicePath, media::fs::SimpleFileSystem, shim::ShimLock},
+    proto::{device_path::DevicePath, shim::ShimLock},
     runtime::{VariableAttributes, VariableVendor},
 };
 
 use crate::{
     BootResult,
-    boot::secure_boot::{SecureBootError, SecurityOverrideGuard, secure_boot_enabled},
+    boot::secure_boot::{SecureBootError, SecurityOverrideGuard, get_image, secure_boot_enabled},
     system::{
-        fs::UefiFileSystem,
-        helper::{device_path_to_text, locate_protocol},
+        helper::locate_protocol,
         protos::ShimImageLoader,
         variable::{get_variable, set_variable},
     },
@@ -37,23 +36,29 @@ use crate::{
 ///
 /// # Errors
 ///
-/// May return an `Error` if the device path does not lead to a handle supporting [`SimpleFileSystem`],
+/// May return an `Error` if the device path does not lead to a handle supporting `SimpleFileSystem`,
 /// or the system does not support `DevicePathToText`, or the file does not exist in the filesystem.
 fn validate_from_device_path(
-    mut device_path: &DevicePath,
+    device_path: &DevicePath,
     shim: &ScopedProtocol<ShimLock>,
 ) -> BootResult<()> {
-    let handle = boot::locate_device_path::<SimpleFileSystem>(&mut device_path)?;
-    let mut fs = UefiFileSystem::from_handle(handle)?;
-
-    let path = device_path_to_text(device_path)?;
-    let file_buffer = fs.read(&path)?;
+    let file_buffer = get_image(Some(device_path), None)?;
 
     Ok(shim.verify(&file_buffer)?)
 }
 
+/// Verify an image that is not loaded through `LoadImage` with [`ShimLock`], if Shim is loaded.
+///
+/// # Errors
+///
+/// May return an `Error` if Shim is not loaded, or the image failed verification.
+pub(super) fn shim_verify(image: &[u8]) -> BootResult<()> {
+    let shim = locate_protocol::<ShimLock>()?;
+    Ok(shim.verify(image)?)
+}
+
 /// Checks for the presence of [`ShimLock`].
-fn shim_loaded() -> bool {
+pub(super) fn shim_loaded() -> bool {
     boot::get_handle_for_protocol::<ShimLock>().is_ok()
 }
 
@@ -0,0 +1,175 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Parsing for `EFI_SIGNATURE_LIST` structures.
+//!
+//! The signature databases (`db`, `dbx`, `KEK`, `PK`, and Shim's `MokList`) are stored as a sequence of signature lists.
+//! Each list has a header containing the type of signature it contains and the size of each signature, followed by
+//! the signatures themselves. Every signature starts with the GUID of its owner, followed by the signature data, such
+//! as an X.509 certificate or a SHA-256 hash.
+
+#[cfg(test)]
+use alloc::vec::Vec;
+
+use uefi::{Guid, guid};
+
+/// The size of the header of a signature list, without the signature header.
+const SIGNATURE_LIST_HEADER_SIZE: usize = 28;
+
+/// The size of the owner GUID at the start of every signature.
+const SIGNATURE_OWNER_SIZE: usize = 16;
+
+/// The signature type of a SHA-256 hash.
+pub(crate) const EFI_CERT_SHA256_GUID: Guid = guid!("c1c41626-504c-4092-aca9-41f936934328");
+
+/// The signature type of a DER encoded X.509 certificate.
+#[cfg(any(test, feature = "authenticode"))]
+pub(crate) const EFI_CERT_X509_GUID: Guid = guid!("a5c059a1-94e4-4aa7-87b5-ab155c2bf072");
+
+/// The signature type of the SHA-256 hash of the `TBSCertificate` of an X.509 certificate, followed by a revocation
+/// time.
+#[cfg(feature = "authenticode")]
+pub(crate) const EFI_CERT_X509_SHA256_GUID: Guid = guid!("3bd2a492-96c0-4079-b420-fcf98ef103ed");
+
+/// A single signature list in a signature database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SignatureList<'a> {
+    /// The type of the signatures in the list.
+    sig_type: Guid,
+
+    /// The size of each signature, including the owner GUID.
+    sig_size: usize,
+
+    /// The signatures in the list.
+    signatures: &'a [u8],
+}
+
+impl<'a> SignatureList<'a> {
+    /// Get the amount of signatures in the list.
+    pub(crate) const fn len(&self) -> usize {
+        self.signatures.len() / self.sig_size
+    }
+
+    /// Get the type of the signatures in the list.
+    pub(crate) const fn sig_type(&self) -> Guid {
+        self.sig_type
+    }
+
+    /// Get the data of every signature in the list, without the owner GUID.
+    pub(crate) fn signatures(self) -> impl Iterator<Item = &'a [u8]> {
+        self.signatures
+            .chunks_exact(self.sig_size)
+            .map(|x| &x[SIGNATURE_OWNER_SIZE..])
+    }
+}
+
+/// An iterator over the signature lists in a signature database.
+///
+/// The iterator stops at the first malformed signature list.
+pub(crate) struct SignatureLists<'a>(&'a [u8]);
+
+impl<'a> SignatureLists<'a> {
+    /// Create a new [`SignatureLists`] from the content of a signature database.
+    pub(crate) const fn new(db: &'a [u8]) -> Self {
+        Self(db)
+    }
+}
+
+impl<'a> Iterator for SignatureLists<'a> {
+    type Item = SignatureList<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.0.get(..SIGNATURE_LIST_HEADER_SIZE)?;
+        let read_u32 = |start: usize| {
+            header[start..start + 4]
+                .try_into()
+                .ok()
+                .map(u32::from_le_bytes)
+                .and_then(|x| usize::try_from(x).ok())
+        };
+        let list_size = read_u32(16)?;
+        let header_size = read_u32(20)?;
+        let sig_size = read_u32(24)?;
+
+        let list = self.0.get(..list_size)?;
+        let signatures = list.get(SIGNATURE_LIST_HEADER_SIZE.checked_add(header_size)?..)?;
+        if sig_size <= SIGNATURE_OWNER_SIZE || signatures.len() % sig_size != 0 {
+            self.0 = &[];
+            return None;
+        }
+
+        self.0 = &self.0[list_size..];
+        Some(SignatureList {
+            sig_type: Guid::from_bytes(header[..16].try_into().ok()?),
+            sig_size,
+            signatures,
+        })
+    }
+}
+
+/// Count every signature in a signature database.
+pub(crate) fn count_signatures(db: &[u8]) -> usize {
+    SignatureLists::new(db).map(|list| list.len()).sum()
+}
+
+/// Build a signature list of a signature type from a set of signatures of equal size, for use in tests.
+#[cfg(test)]
+pub(crate) fn build_list(sig_type: Guid, sigs: &[&[u8]]) -> Vec<u8> {
+    let sig_size = SIGNATURE_OWNER_SIZE + sigs.first().map_or(0, |x| x.len());
+    let list_size = SIGNATURE_LIST_HEADER_SIZE + sig_size * sigs.len();
+
+    let mut list = Vec::with_capacity(list_size);
+    list.extend_from_slice(&sig_type.to_bytes());
+    for size in [list_size, 0, sig_size] {
+        list.extend_from_slice(&u32::try_from(size).unwrap_or(0).to_le_bytes());
+    }
+    for sig in sigs {
+        list.extend_from_slice(&[0; SIGNATURE_OWNER_SIZE]);
+        list.extend_from_slice(sig);
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_signature_lists() {
+        let mut db = build_list(EFI_CERT_SHA256_GUID, &[&[1; 32], &[2; 32], &[3; 32]]);
+        db.extend(build_list(EFI_CERT_X509_GUID, &[b"certificate"]));
+
+        let lists: Vec<_> = SignatureLists::new(&db).collect();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].len(), 3);
+        assert_eq!(lists[1].len(), 1);
+        assert_eq!(lists[1].sig_type(), EFI_CERT_X509_GUID);
+        assert_eq!(lists[0].signatures().nth(1), Some(&[2; 32][..]));
+        assert_eq!(count_signatures(&db), 4);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_malformed_signature_list() {
+        let mut db = build_list(EFI_CERT_SHA256_GUID, &[&[1; 32]]);
+        db.truncate(db.len() - 1);
+        assert_eq!(count_signatures(&db), 0);
+        assert_eq!(count_signatures(&[]), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn doesnt_panic(x in any::<Vec<u8>>()) {
+            let _ = count_signatures(&x);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Reporting of the Secure Boot state of the system.
+//!
+//! This gathers the Secure Boot mode variables defined by the UEFI specification, whether Shim is loaded, and
+//! whether Shim's own validation was disabled through `mokutil --disable-validation`, into a [`SecureBootStatus`]
+//! that frontends can display.
+
+use alloc::{format, string::String};
+
+use uefi::{Identify, boot, cstr16, proto::shim::ShimLock, runtime::VariableVendor};
+
+use crate::{
+    boot::secure_boot::signature_list::count_signatures,
+    system::{
+        protos::ShimImageLoader,
+        variable::{RuntimeUefiVariableStorage, UefiVariableStorage},
+    },
+};
+
+/// The version of Shim that is loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShimVersion {
+    /// Shim v16 or later, which provides `ShimImageLoader` and validates images loaded through `LoadImage` itself.
+    ImageLoader,
+
+    /// Shim before v16, which only provides `ShimLock`.
+    Lock,
+}
+
+impl ShimVersion {
+    /// Get the version of Shim that is loaded, if Shim is loaded at all.
+    fn detect() -> Option<Self> {
+        if boot::get_handle_for_protocol::<ShimImageLoader>().is_ok() {
+            Some(Self::ImageLoader)
+        } else if boot::get_handle_for_protocol::<ShimLock>().is_ok() {
+            Some(Self::Lock)
+        } else {
+            None
+        }
+    }
+
+    /// Get the name of the protocol that identifies this version of Shim.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ImageLoader => "ShimImageLoader",
+            Self::Lock => "ShimLock",
+        }
+    }
+}
+
+/// The Secure Boot state of the system.
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each bool mirrors an independent UEFI variable"
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SecureBootStatus {
+    /// If Secure Boot is enforced by the firmware.
+    pub secure_boot: bool,
+
+    /// If the firmware is in Setup Mode, where no `PK` is enrolled.
+    pub setup_mode: bool,
+
+    /// If the firmware is in Audit Mode, where image verification failures are logged but not enforced.
+    pub audit_mode: bool,
+
+    /// If the firmware is in Deployed Mode, where the Secure Boot mode cannot be changed from the OS.
+    pub deployed_mode: bool,
+
+    /// The version of Shim that is loaded, if any.
+    pub shim: Option<ShimVersion>,
+
+    /// If Shim's validation was disabled through `MokSBState`.
+    pub mok_sb_state: bool,
+
+    /// The amount of signatures in the allowed signature database `db`.
+    pub db_entries: usize,
+
+    /// The amount of signatures in the forbidden signature database `dbx`.
+    pub dbx_entries: usize,
+}
+
+impl SecureBootStatus {
+    /// Get the current [`SecureBootStatus`] of the system.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn new() -> Self {
+        Self::from_vars(&RuntimeUefiVariableStorage, ShimVersion::detect())
+    }
+
+    /// Get a [`SecureBootStatus`] from a [`UefiVariableStorage`] and the version of Shim that is loaded.
+    ///
+    /// Variables that could not be read are treated as if they are unset.
+    pub(crate) fn from_vars(vars: &impl UefiVariableStorage, shim: Option<ShimVersion>) -> Self {
+        let global = Some(VariableVendor::GLOBAL_VARIABLE);
+        let flag = |name| vars.get_variable::<bool>(name, global).unwrap_or(false);
+        let count = |name, vendor| {
+            vars.get_variable_bytes(name, &vendor)
+                .ok()
+                .flatten()
+                .map_or(0, |db| count_signatures(&db))
+        };
+
+        Self {
+            secure_boot: flag(cstr16!("SecureBoot")),
+            setup_mode: flag(cstr16!("SetupMode")),
+            audit_mode: flag(cstr16!("AuditMode")),
+            deployed_mode: flag(cstr16!("DeployedMode")),
+            shim,
+            mok_sb_state: vars
+                .get_variable::<bool>(cstr16!("MokSBState"), Some(VariableVendor(ShimLock::GUID)))
+                .unwrap_or(false),
+            db_entries: count(cstr16!("db"), VariableVendor::IMAGE_SECURITY_DATABASE),
+            dbx_entries: count(cstr16!("dbx"), VariableVendor::IMAGE_SECURITY_DATABASE),
+        }
+    }
+
+    /// Get the fields of the status as labels and values, for displaying in a frontend.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn fields(&self) -> [(&'static str, String); 8] {
+        let yes_no = |x: bool| String::from(if x { "yes" } else { "no" });
+        [
+            ("Secure Boot", yes_no(self.secure_boot)),
+            ("Setup Mode", yes_no(self.setup_mode)),
+            ("Audit Mode", yes_no(self.audit_mode)),
+            ("Deployed Mode", yes_no(self.deployed_mode)),
+            (
+                "Shim",
+                self.shim
+                    .map_or_else(|| String::from("not loaded"), |x| String::from(x.as_str())),
+            ),
+            (
+                "Shim validation",
+                String::from(if self.mok_sb_state {
+                    "disabled"
+                } else {
+                    "enabled"
+                }),
+            ),
+            ("db entries", format!("{}", self.db_entries)),
+            ("dbx entries", format!("{}", self.dbx_entries)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::runtime::VariableAttributes;
+
+    use super::*;
+    use crate::{
+        boot::secure_boot::signature_list::{EFI_CERT_SHA256_GUID, build_list},
+        system::variable::MemoryUefiVariableStorage,
+    };
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_status_from_vars() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let attrs = VariableAttributes::BOOTSERVICE_ACCESS;
+        let global = VariableVendor::GLOBAL_VARIABLE;
+        let db = VariableVendor::IMAGE_SECURITY_DATABASE;
+
+        assert_eq!(
+            SecureBootStatus::from_vars(&vars, None),
+            SecureBootStatus::default()
+        );
+
+        let _ = vars.set_variable_bytes(cstr16!("SecureBoot"), &global, attrs, &[1]);
+        let _ = vars.set_variable_bytes(cstr16!("DeployedMode"), &global, attrs, &[1]);
+        let _ = vars.set_variable_bytes(cstr16!("SetupMode"), &global, attrs, &[0]);
+        let _ = vars.set_variable_bytes(
+            cstr16!("MokSBState"),
+            &VariableVendor(ShimLock::GUID),
+            attrs,
+            &[1],
+        );
+
+        let mut db_content = build_list(EFI_CERT_SHA256_GUID, &[&[0; 32][..]; 2]);
+        db_content.extend(build_list(EFI_CERT_SHA256_GUID, &[&[0; 32]]));
+        let _ = vars.set_variable_bytes(cstr16!("db"), &db, attrs, &db_content);
+        let _ = vars.set_variable_bytes(
+            cstr16!("dbx"),
+            &db,
+            attrs,
+            &build_list(EFI_CERT_SHA256_GUID, &[&[0; 32][..]; 5]),
+        );
+
+        let status = SecureBootStatus::from_vars(&vars, Some(ShimVersion::Lock));
+        assert_eq!(
+            status,
+            SecureBootStatus {
+                secure_boot: true,
+                setup_mode: false,
+                audit_mode: false,
+                deployed_mode: true,
+                shim: Some(ShimVersion::Lock),
+                mok_sb_state: true,
+                db_entries: 3,
+                dbx_entries: 5,
+            }
+        );
+        assert_eq!(status.fields()[4].1, "ShimLock");
+    }
+}
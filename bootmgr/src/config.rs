@@ -10,6 +10,7 @@ use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
+use core::net::IpAddr;
 
 use log::{error, warn};
 use thiserror::Error;
@@ -21,14 +22,14 @@ use uefi::{
 
 use crate::{
     BootResult,
-    boot::action::BootAction,
+    boot::{action::BootAction, config::BootConfig},
     config::{
         parsers::{Parsers, parse_all_configs},
         types::{Architecture, DevicetreePath, EfiPath, FsHandle, MachineId, SortKey},
     },
     system::{
-        fs::{UefiFileSystem, is_target_partition},
-        helper::get_arch,
+        fs::{UefiFileSystem, is_on_image_disk, is_target_partition, is_xbootldr_partition},
+        helper::{get_arch, get_pe_header_offset, is_pe_machine_supported, str_to_cstr},
     },
 };
 
@@ -48,7 +49,8 @@ pub enum ConfigError {
     #[error("Config \"{0}\" missing EFI")]
     ConfigMissingEfi(String),
 
-    /// The [`Config`]'s architecture field did not match the system architecture.
+    /// The [`Config`]'s architecture field or the machine type of its EFI executable did not match the system
+    /// architecture.
     #[error("Config \"{0}\" has non-matching architecture")]
     NonMatchingArch(String),
 
@@ -84,17 +86,46 @@ pub struct Config {
     /// The path to an EFI executable, if one is required.
     pub efi_path: Option<EfiPath>,
 
+    /// The SHA-256 hash that the EFI executable must have, if it is pinned.
+    pub sha256: Option<[u8; 32]>,
+
+    /// The SHA-256 hash that a downloaded EFI executable must have, in addition to being validated as usual.
+    ///
+    /// Unlike [`Config::sha256`], this is received from the network, so it can only cause an image to be refused, and
+    /// never causes an image to be trusted.
+    pub download_sha256: Option<[u8; 32]>,
+
+    /// The modules that are loaded with a Multiboot2 kernel, as their path followed by their command line.
+    pub modules: Vec<String>,
+
     /// The [`BootAction`] of the entry, for deciding which loader to use.
     pub action: BootAction,
 
+    /// If the entry may only be booted after the boot manager is unlocked with the password.
+    pub protected: bool,
+
     /// Checks if an entry is bad, for sorting and deranking.
     ///
     /// This does not necessarily mean that it's invalid, only that it's known to fail.
     pub bad: bool,
 
+    /// The path to the file that contains the boot counter of the entry, if it has one.
+    ///
+    /// This is updated once the boot counter is decremented when the entry is booted.
+    pub boot_counter_path: Option<String>,
+
+    /// The amount of tries left in the boot counter of the entry, if it has one.
+    pub tries_left: Option<u32>,
+
+    /// The amount of boot attempts done in the boot counter of the entry, if it has one.
+    pub tries_done: Option<u32>,
+
     /// The [`FsHandle`] of the entry, if one is required.
     pub fs_handle: Option<FsHandle>,
 
+    /// The address of the TFTP server that the entry is downloaded from, if it is booted through TFTP.
+    pub tftp_server: Option<IpAddr>,
+
     /// The parser from which the entry originated from, if there was one.
     pub origin: Option<Parsers>,
 
@@ -138,8 +169,10 @@ impl Config {
     ///
     /// May return an `Error` if any of the error criteria are met:
     /// 1. Non matching architecture with system
-    /// 2. Nonexistent EFI executable if [`BootAction`] is [`BootAction::BootEfi`] or [`BootAction::BootTftp`]
+    /// 2. Nonexistent EFI executable if [`BootAction`] is [`BootAction::BootEfi`], [`BootAction::BootTftp`],
+    ///    [`BootAction::BootLinux`], [`BootAction::BootMultiboot`] or [`BootAction::BootNext`]
     /// 3. (if applicable) Nonexistent devicetree
+    /// 4. (if applicable) EFI executable with a machine type that cannot run on the system
     fn validate(&self) -> Result<(), ConfigError> {
         self.validate_arch()?;
         self.validate_efi()?;
@@ -171,6 +204,9 @@ impl Config {
     /// If the filename is empty, then the index of the boot option is used. This is because at least some way of differentiating
     /// the boot option from other boot options is required. This will only be the case if the index is provided.
     /// If the index is not provided, then the boot option will simply be labeled "Unknown".
+    ///
+    /// If the boot option is bad, then " (Bad)" is added to the title. Otherwise, if the boot option has a boot counter,
+    /// then the amount of tries left is added to the title.
     #[must_use = "Has no effect if the result is unused"]
     pub fn get_preferred_title(&self, option: Option<usize>) -> String {
         let mut title = self.title.clone().unwrap_or_else(|| {
@@ -182,6 +218,15 @@ impl Config {
         });
         if self.bad {
             title.push_str(" (Bad)");
+        } else if let Some(tries_left) = self.tries_left {
+            let mut buffer = itoa::Buffer::new();
+            title.push_str(" (");
+            title.push_str(buffer.format(tries_left));
+            title.push_str(if tries_left == 1 {
+                " try left)"
+            } else {
+                " tries left)"
+            });
         }
         title
     }
@@ -196,7 +241,7 @@ impl Config {
             && let Some(arch) = get_arch()
             && target != &arch
         {
-            return Err(ConfigError::NonMatchingArch((**target).clone()));
+            return Err(ConfigError::NonMatchingArch(self.filename.clone()));
         }
         Ok(())
     }
@@ -205,11 +250,17 @@ impl Config {
     ///
     /// # Errors
     ///
-    /// May return an `Error` if there is no EFI path, and the action field is one of [`BootAction::BootEfi`] or
-    /// [`BootAction::BootTftp`].
+    /// May return an `Error` if there is no EFI path, and the action field is one of [`BootAction::BootEfi`],
+    /// [`BootAction::BootTftp`], [`BootAction::BootLinux`], [`BootAction::BootMultiboot`] or [`BootAction::BootNext`].
     fn validate_efi(&self) -> Result<(), ConfigError> {
-        if matches!(self.action, BootAction::BootEfi | BootAction::BootTftp)
-            && self.efi_path.is_none()
+        if matches!(
+            self.action,
+            BootAction::BootEfi
+                | BootAction::BootTftp
+                | BootAction::BootLinux
+                | BootAction::BootMultiboot
+                | BootAction::BootNext
+        ) && self.efi_path.is_none()
         {
             return Err(ConfigError::ConfigMissingEfi(self.filename.clone()));
         }
@@ -237,6 +288,11 @@ impl Config {
             {
                 return Err(ConfigError::NotExist("EFI", (**efi_path).clone()));
             }
+            if let Some(efi_path) = &self.efi_path
+                && get_efi_machine_supported(&mut fs, efi_path) == Some(false)
+            {
+                return Err(ConfigError::NonMatchingArch(self.filename.clone()));
+            }
             if let Some(devicetree_path) = &self.devicetree_path
                 && !fs.exists_str(devicetree_path).unwrap_or(false)
             {
@@ -245,21 +301,59 @@ impl Config {
                     (**devicetree_path).clone(),
                 ));
             }
-        } else if self.action == BootAction::BootEfi {
+        } else if matches!(
+            self.action,
+            BootAction::BootEfi
+                | BootAction::BootLinux
+                | BootAction::BootMultiboot
+                | BootAction::BootNext
+        ) {
             return Err(ConfigError::ConfigMissingHandle(self.filename.clone()));
         }
         Ok(())
     }
 }
 
+/// Checks if an EFI executable can run on the system by reading the machine type from its PE/COFF header.
+///
+/// Only the start of the file is read, rather than the entire file. This is usually enough to contain both the DOS
+/// header and the PE header, so the file is only read again if the PE header is past the start of the file. Returns
+/// `None` if the headers could not be read.
+fn get_efi_machine_supported(fs: &mut UefiFileSystem, efi_path: &str) -> Option<bool> {
+    /// The size of the start of the file that is read.
+    const HEADER_READ_SIZE: usize = 1024;
+    /// The size of the PE signature and the machine type.
+    const PE_HEADER_SIZE: usize = 6;
+
+    let path = str_to_cstr(efi_path).ok()?;
+
+    let mut header = [0; HEADER_READ_SIZE];
+    let read = fs.read_at(&path, 0, &mut header).ok()?;
+    let offset = get_pe_header_offset(&header[..read])?;
+
+    if let Some(pe_header) = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| header[..read].get(offset..offset.checked_add(PE_HEADER_SIZE)?))
+    {
+        return is_pe_machine_supported(pe_header);
+    }
+
+    let mut pe_header = [0; PE_HEADER_SIZE];
+    let read = fs.read_at(&path, offset, &mut pe_header).ok()?;
+    is_pe_machine_supported(&pe_header[..read])
+}
+
 /// Gets every [`Config`] from every filesystem that is available, and returns it in a [`Vec<Config>`]
 ///
 /// It will also validate and sort the [`Config`]s.
 ///
+/// `XBOOTLDR` partitions are only searched if they are on the same disk as the bootloader, unless
+/// [`BootConfig::xbootldr_any_disk`] is set.
+///
 /// # Errors
 ///
 /// May return an `Error` if there are no handles in the system that support [`SimpleFileSystem`].
-pub(crate) fn scan_configs() -> BootResult<Vec<Config>> {
+pub(crate) fn scan_configs(boot_config: &BootConfig) -> BootResult<Vec<Config>> {
     let mut configs = Vec::with_capacity(4); // a system is likely to have up to 4 configs
     let handles = boot::locate_handle_buffer(SearchType::from_proto::<SimpleFileSystem>())?;
 
@@ -268,6 +362,13 @@ pub(crate) fn scan_configs() -> BootResult<Vec<Config>> {
             continue;
         }
 
+        if !boot_config.xbootldr_any_disk
+            && is_xbootldr_partition(handle)
+            && !is_on_image_disk(handle)
+        {
+            continue;
+        }
+
         let mut fs = UefiFileSystem::from_handle(handle)?;
 
         parse_all_configs(&mut fs, handle, &mut configs);
@@ -306,6 +407,31 @@ mod tests {
     ///
     /// May return an `Error` if the newtype constructors failed.
     ///
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_preferred_title() {
+        let mut config = Config {
+            title: Some("Linux".to_owned()),
+            tries_left: Some(2),
+            tries_done: Some(1),
+            ..Config::default()
+        };
+        assert_eq!(config.get_preferred_title(None), "Linux (2 tries left)");
+
+        config.tries_left = Some(1);
+        assert_eq!(config.get_preferred_title(None), "Linux (1 try left)");
+
+        config.bad = true;
+        assert_eq!(config.get_preferred_title(None), "Linux (Bad)");
+
+        config.title = None;
+        config.bad = false;
+        config.tries_left = None;
+        assert_eq!(config.get_preferred_title(Some(3)), "3");
+    }
+
     /// # Panics
     ///
     /// May panic if the [`Config`] is not good.
@@ -3,13 +3,14 @@
 
 //! Configuration builder.
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
+use core::net::IpAddr;
 
 use log::warn;
 use uefi::Handle;
 
 use crate::{
-    boot::action::BootAction,
+    boot::{action::BootAction, secure_boot::hash_pin::parse_sha256},
     config::{
         Config,
         parsers::Parsers,
@@ -55,9 +56,17 @@ impl ConfigBuilder {
             devicetree_path: None,
             architecture: None,
             efi_path: None,
+            sha256: None,
+            download_sha256: None,
+            modules: Vec::new(),
+            protected: false,
             bad: false,
+            boot_counter_path: None,
+            tries_left: None,
+            tries_done: None,
             action: BootAction::BootEfi,
             fs_handle: None,
+            tftp_server: None,
             origin: None,
             filename,
             suffix,
@@ -146,10 +155,30 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the path to the file containing the boot counter of a [`Config`]
+    pub fn boot_counter_path(mut self, boot_counter_path: impl Into<String>) -> Self {
+        self.0.boot_counter_path = Some(boot_counter_path.into());
+        self
+    }
+
+    /// Sets the amount of tries left in the boot counter of a [`Config`]
+    pub const fn tries_left(mut self, tries_left: u32) -> Self {
+        self.0.tries_left = Some(tries_left);
+        self
+    }
+
+    /// Sets the amount of boot attempts done in the boot counter of a [`Config`]
+    pub const fn tries_done(mut self, tries_done: u32) -> Self {
+        self.0.tries_done = Some(tries_done);
+        self
+    }
+
     /// Sets the [`BootAction`] of a [`Config`]
     ///
-    /// This can be one of [`BootAction::BootEfi`], [`BootAction::BootTftp`], [`BootAction::Reboot`], [`BootAction::Shutdown`],
-    /// and [`BootAction::ResetToFirmware`]. You should never need to use this
+    /// This can be one of [`BootAction::BootEfi`], [`BootAction::BootTftp`], [`BootAction::BootHttp`],
+    /// [`BootAction::BootLinux`], [`BootAction::BootMultiboot`], [`BootAction::BootNext`], [`BootAction::Reboot`],
+    /// [`BootAction::Shutdown`], [`BootAction::ResetToFirmware`], and [`BootAction::EnrollKeys`]. You should never need
+    /// to use this
     pub const fn action(mut self, action: BootAction) -> Self {
         self.0.action = action;
         self
@@ -170,6 +199,14 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the address of the TFTP server of a [`Config`].
+    ///
+    /// This is required to be set for [`BootAction::BootTftp`], as the EFI executable is downloaded from it.
+    pub const fn tftp_server(mut self, tftp_server: IpAddr) -> Self {
+        self.0.tftp_server = Some(tftp_server);
+        self
+    }
+
     /// Sets the origin of a [`Config`].
     ///
     /// This is one of the parsers that generate [`Config`]s.
@@ -190,6 +227,58 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the SHA-256 hash that the EFI executable of a [`Config`] must have.
+    ///
+    /// This must be formatted as 64 hexadecimal characters. Otherwise, this will have no effect
+    pub fn sha256(mut self, sha256: impl Into<String>) -> Self {
+        let sha256 = sha256.into();
+        self.0.sha256 = parse_sha256(&sha256);
+        if self.0.sha256.is_none() {
+            warn!("\"{sha256}\" is not a valid SHA-256 hash");
+        }
+        self
+    }
+
+    /// Sets the SHA-256 hash that the EFI executable of a [`Config`] must have, from the raw bytes of the hash.
+    pub const fn sha256_bytes(mut self, sha256: [u8; 32]) -> Self {
+        self.0.sha256 = Some(sha256);
+        self
+    }
+
+    /// Sets the SHA-256 hash that a downloaded EFI executable of a [`Config`] must have, in addition to being validated
+    /// as usual.
+    ///
+    /// This must be formatted as 64 hexadecimal characters. Otherwise, this will have no effect
+    pub fn download_sha256(mut self, sha256: impl Into<String>) -> Self {
+        let sha256 = sha256.into();
+        self.0.download_sha256 = parse_sha256(&sha256);
+        if self.0.download_sha256.is_none() {
+            warn!("\"{sha256}\" is not a valid SHA-256 hash");
+        }
+        self
+    }
+
+    /// Sets the SHA-256 hash that a downloaded EFI executable of a [`Config`] must have, from the raw bytes of the
+    /// hash.
+    pub const fn download_sha256_bytes(mut self, sha256: [u8; 32]) -> Self {
+        self.0.download_sha256 = Some(sha256);
+        self
+    }
+
+    /// Adds a module to a [`Config`], which is loaded with a Multiboot2 kernel.
+    ///
+    /// This is the path to the module, followed by its command line.
+    pub fn module(mut self, module: impl Into<String>) -> Self {
+        self.0.modules.push(module.into());
+        self
+    }
+
+    /// Sets if a [`Config`] may only be booted after the boot manager is unlocked with the password.
+    pub const fn set_protected(mut self, protected: bool) -> Self {
+        self.0.protected = protected;
+        self
+    }
+
     /// Builds a [`Config`]
     #[must_use = "Has no effect if the result is unused"]
     pub fn build(self) -> Config {
@@ -211,8 +300,13 @@ impl ConfigBuilder {
 
 impl From<&Config> for ConfigBuilder {
     fn from(value: &Config) -> Self {
-        Self::new(&value.filename, &value.suffix)
+        let builder = Self::new(&value.filename, &value.suffix)
+            .action(value.action)
             .set_bad(value.bad)
+            .set_protected(value.protected)
+            .assign_if_some(value.boot_counter_path.as_ref(), Self::boot_counter_path)
+            .assign_if_some(value.tries_left, Self::tries_left)
+            .assign_if_some(value.tries_done, Self::tries_done)
             .assign_if_some(value.title.as_ref(), Self::title)
             .assign_if_some(value.version.as_ref(), Self::version)
             .assign_if_some(value.machine_id.as_deref(), Self::machine_id)
@@ -221,8 +315,12 @@ impl From<&Config> for ConfigBuilder {
             .assign_if_some(value.devicetree_path.as_deref(), Self::devicetree_path)
             .assign_if_some(value.architecture.as_deref(), Self::architecture)
             .assign_if_some(value.efi_path.as_deref(), Self::efi_path)
+            .assign_if_some(value.sha256, Self::sha256_bytes)
+            .assign_if_some(value.download_sha256, Self::download_sha256_bytes)
             .assign_if_some(value.fs_handle.as_deref().copied(), Self::fs_handle)
-            .assign_if_some(value.origin, Self::origin)
+            .assign_if_some(value.tftp_server, Self::tftp_server)
+            .assign_if_some(value.origin, Self::origin);
+        value.modules.iter().fold(builder, Self::module)
     }
 }
 
@@ -279,4 +377,19 @@ mod tests {
             Some(&"\\baz\\baz.qux".to_owned())
         );
     }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_from_config() {
+        let config = ConfigBuilder::new("vmlinuz.conf", ".conf")
+            .efi_path("\\vmlinuz")
+            .action(BootAction::BootLinux)
+            .build();
+
+        let rebuilt = ConfigBuilder::from(&config).build();
+        assert_eq!(rebuilt.action, BootAction::BootLinux);
+        assert_eq!(rebuilt.efi_path, config.efi_path);
+    }
 }
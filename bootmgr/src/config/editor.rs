@@ -7,9 +7,10 @@ use alloc::{borrow::ToOwned, string::String};
 
 use tinyvec::ArrayVec;
 
-use crate::config::{Config, builder::ConfigBuilder};
+use crate::config::{Config, builder::ConfigBuilder, editor::policy::EditPolicy};
 
 pub mod persist;
+pub mod policy;
 
 /// The editor for [`Config`]s.
 ///
@@ -22,22 +23,50 @@ pub struct ConfigEditor {
 
     /// Stores the editable fields that are in the [`Config`].
     fields: ArrayVec<[(&'static str, String); 8]>,
+
+    /// Stores which of the fields are read-only.
+    locked: ArrayVec<[bool; 8]>,
 }
 
 impl ConfigEditor {
-    /// Create a new instance of [`ConfigEditor`].
+    /// Create a new instance of [`ConfigEditor`], where every field may be edited.
     #[must_use = "Has no effect if the result is unused"]
     pub fn new(config: &Config) -> Self {
+        Self::with_policy(config, &EditPolicy::default())
+    }
+
+    /// Create a new instance of [`ConfigEditor`], where the fields that are read-only by an [`EditPolicy`] may not
+    /// be edited.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn with_policy(config: &Config, policy: &EditPolicy) -> Self {
         let fields = config
             .get_str_fields()
             .map(|(k, v)| (k, v.map(ToOwned::to_owned).unwrap_or_default()))
             .collect();
-        Self { idx: 0, fields }
+        let locked = config
+            .get_str_fields()
+            .map(|(k, _)| policy.is_locked(config, k))
+            .collect();
+        Self {
+            idx: 0,
+            fields,
+            locked,
+        }
     }
 
     /// Update the selected field at idx.
+    ///
+    /// If the field is read-only, then this does nothing.
     pub fn update_selected(&mut self, input: &str) {
-        input.clone_into(&mut self.fields[self.idx].1);
+        if !self.is_locked(self.idx) {
+            input.clone_into(&mut self.fields[self.idx].1);
+        }
+    }
+
+    /// Check if the field at an index is read-only.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn is_locked(&self, idx: usize) -> bool {
+        self.locked.get(idx).copied().unwrap_or_default()
     }
 
     /// Get the current index.
@@ -96,27 +125,29 @@ impl ConfigEditor {
     }
 
     /// Build the [`ConfigEditor`] into a [`Config`] given the previous [`Config`].
+    ///
+    /// Fields that are read-only are not changed.
     pub fn build(&self, config: &mut Config) {
-        let builder =
-            self.fields
-                .iter()
-                .fold(ConfigBuilder::from(&*config), |builder, (key, val)| {
-                    if val.trim().is_empty() {
-                        builder
-                    } else {
-                        match *key {
-                            "title" => builder.title(val),
-                            "version" => builder.version(val),
-                            "machine_id" => builder.machine_id(val),
-                            "sort_key" => builder.sort_key(val),
-                            "options" => builder.options(val),
-                            "devicetree" => builder.devicetree_path(val),
-                            "architecture" => builder.architecture(val),
-                            "efi" => builder.efi_path(val),
-                            _ => builder,
-                        }
+        let builder = self.fields.iter().enumerate().fold(
+            ConfigBuilder::from(&*config),
+            |builder, (idx, (key, val))| {
+                if val.trim().is_empty() || self.is_locked(idx) {
+                    builder
+                } else {
+                    match *key {
+                        "title" => builder.title(val),
+                        "version" => builder.version(val),
+                        "machine_id" => builder.machine_id(val),
+                        "sort_key" => builder.sort_key(val),
+                        "options" => builder.options(val),
+                        "devicetree" => builder.devicetree_path(val),
+                        "architecture" => builder.architecture(val),
+                        "efi" => builder.efi_path(val),
+                        _ => builder,
                     }
-                });
+                }
+            },
+        );
         *config = builder.build();
     }
 }
@@ -170,4 +201,29 @@ mod tests {
         assert!(config.machine_id.is_none());
         assert!(config.sort_key.is_none());
     }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_locked_fields() {
+        let mut config = ConfigBuilder::new("foo.efi", ".efi")
+            .efi_path("\\EFI\\Linux\\foo.efi")
+            .options("quiet")
+            .origin(crate::config::parsers::Parsers::Uki)
+            .build();
+        let policy =
+            EditPolicy::with_secure_boot(&crate::boot::config::BootConfig::default(), true);
+        let mut editor = ConfigEditor::with_policy(&config, &policy);
+        assert!(editor.go_to_field("options"));
+        assert!(editor.is_locked(editor.idx()));
+        editor.update_selected("init=/bin/sh");
+        assert_eq!(editor.current_field(), "quiet");
+        assert!(editor.go_to_field("title"));
+        assert!(!editor.is_locked(editor.idx()));
+        editor.update_selected("foo");
+        editor.build(&mut config);
+        assert_eq!(config.options, Some("quiet".to_owned()));
+        assert_eq!(config.title, Some("foo".to_owned()));
+    }
 }
@@ -10,7 +10,15 @@ use uefi::{CStr16, cstr16};
 
 use crate::{
     BootResult,
-    config::{Config, builder::ConfigBuilder, parsers::Parsers},
+    config::{
+        Config,
+        builder::ConfigBuilder,
+        editor::policy::EditPolicy,
+        parsers::{
+            Parsers,
+            counter::{BootCounter, update_boot_counter},
+        },
+    },
     system::fs::UefiFileSystem,
 };
 
@@ -119,29 +127,48 @@ impl PersistentConfig {
 
     /// Optionally swap a mutable [`Config`] with one that is stored in the [`PersistentConfig`].
     ///
-    /// This will only swap the 8 fields that the editor is able to edit.
-    pub fn swap_config_in_persist<'a>(&'a self, config: &'a mut Config) {
+    /// This will only swap the 8 fields that the editor is able to edit, and fields that are read-only by the
+    /// [`EditPolicy`] are not swapped.
+    pub fn swap_config_in_persist<'a>(&'a self, config: &'a mut Config, policy: &EditPolicy) {
         if let Some(persist_config) = self.0.get(&config.filename)
             && persist_config.origin.as_deref() == config.origin.map(Parsers::as_str)
         {
+            let unlocked = |field: &str, value: Option<&'a String>| {
+                value.filter(|_| !policy.is_locked(config, field))
+            };
             *config = ConfigBuilder::from(&*config)
-                .assign_if_some(persist_config.title.as_ref(), ConfigBuilder::title)
-                .assign_if_some(persist_config.version.as_ref(), ConfigBuilder::version)
                 .assign_if_some(
-                    persist_config.machine_id.as_deref(),
+                    unlocked("title", persist_config.title.as_ref()),
+                    ConfigBuilder::title,
+                )
+                .assign_if_some(
+                    unlocked("version", persist_config.version.as_ref()),
+                    ConfigBuilder::version,
+                )
+                .assign_if_some(
+                    unlocked("machine_id", persist_config.machine_id.as_ref()),
                     ConfigBuilder::machine_id,
                 )
-                .assign_if_some(persist_config.sort_key.as_deref(), ConfigBuilder::sort_key)
-                .assign_if_some(persist_config.options.as_ref(), ConfigBuilder::options)
                 .assign_if_some(
-                    persist_config.devicetree_path.as_deref(),
+                    unlocked("sort_key", persist_config.sort_key.as_ref()),
+                    ConfigBuilder::sort_key,
+                )
+                .assign_if_some(
+                    unlocked("options", persist_config.options.as_ref()),
+                    ConfigBuilder::options,
+                )
+                .assign_if_some(
+                    unlocked("devicetree", persist_config.devicetree_path.as_ref()),
                     ConfigBuilder::devicetree_path,
                 )
                 .assign_if_some(
-                    persist_config.architecture.as_deref(),
+                    unlocked("architecture", persist_config.architecture.as_ref()),
                     ConfigBuilder::architecture,
                 )
-                .assign_if_some(persist_config.efi_path.as_deref(), ConfigBuilder::efi_path)
+                .assign_if_some(
+                    unlocked("efi", persist_config.efi_path.as_ref()),
+                    ConfigBuilder::efi_path,
+                )
                 .build();
         }
     }
@@ -157,3 +184,52 @@ impl PersistentConfig {
         self.0.remove(&config.filename);
     }
 }
+
+/// Reset the boot counter of a [`Config`], renaming its file back to the full amount of tries.
+///
+/// This allows for an entry that was marked as bad to be tried again. The [`Config`] is updated to reflect the reset
+/// boot counter, and will no longer be marked as bad. If the [`Config`] does not have a boot counter, then this does
+/// nothing.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem of the [`Config`] could not be opened, or the file could not be renamed.
+pub fn reset_boot_counter(config: &mut Config) -> BootResult<()> {
+    update_boot_counter(config, BootCounter::reset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boot::config::BootConfig;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_swap_locked_fields() {
+        let config = ConfigBuilder::new("foo.efi", ".efi")
+            .title("foo")
+            .options("quiet")
+            .origin(Parsers::Uki)
+            .build();
+        let edited = ConfigBuilder::from(&config)
+            .title("bar")
+            .options("init=/bin/sh")
+            .build();
+
+        let mut persist = PersistentConfig::default();
+        persist.add_config_to_persist(&edited);
+
+        let mut swapped = config.clone();
+        let policy = EditPolicy::with_secure_boot(&BootConfig::default(), true);
+        persist.swap_config_in_persist(&mut swapped, &policy);
+        assert_eq!(swapped.title, Some("bar".to_owned()));
+        assert_eq!(swapped.options, Some("quiet".to_owned()));
+
+        let mut swapped = config;
+        let policy = EditPolicy::with_secure_boot(&BootConfig::default(), false);
+        persist.swap_config_in_persist(&mut swapped, &policy);
+        assert_eq!(swapped.options, Some("init=/bin/sh".to_owned()));
+    }
+}
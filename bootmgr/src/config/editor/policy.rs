@@ -0,0 +1,104 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! The editing policy, which decides which fields of a [`Config`] are read-only.
+//!
+//! A field is read-only if any of these apply:
+//!
+//! 1. The field is listed in [`BootConfig::locked_fields`].
+//! 2. Secure Boot is enabled, and the field is `efi`. Changing the EFI executable of a signed entry would let anyone at
+//!    the console boot a different image instead.
+//! 3. Secure Boot is enabled, the [`Config`] is a UKI, and the field is `options`. The command line of a UKI is a part
+//!    of the signed image, and systemd-stub ignores the command line that it is given when Secure Boot is enabled.
+//!
+//! The editor does not change read-only fields, and persistent overlays are not applied to them.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    boot::{config::BootConfig, secure_boot::secure_boot_enabled},
+    config::{Config, parsers::Parsers},
+};
+
+/// Fields that are read-only when Secure Boot is enabled.
+const SECURE_BOOT_LOCKED_FIELDS: [&str; 1] = ["efi"];
+
+/// Fields of UKIs that are read-only when Secure Boot is enabled.
+const UKI_LOCKED_FIELDS: [&str; 1] = ["options"];
+
+/// The policy that decides which fields of a [`Config`] may be edited.
+#[derive(Clone, Debug, Default)]
+pub struct EditPolicy {
+    /// If Secure Boot is enabled.
+    secure_boot: bool,
+
+    /// The fields that are always read-only.
+    locked_fields: Vec<String>,
+}
+
+impl EditPolicy {
+    /// Create a new [`EditPolicy`] from a [`BootConfig`] and the current Secure Boot state.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn new(boot_config: &BootConfig) -> Self {
+        Self::with_secure_boot(boot_config, secure_boot_enabled())
+    }
+
+    /// Create a new [`EditPolicy`] from a [`BootConfig`] and a given Secure Boot state.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn with_secure_boot(boot_config: &BootConfig, secure_boot: bool) -> Self {
+        Self {
+            secure_boot,
+            locked_fields: boot_config.locked_fields.clone(),
+        }
+    }
+
+    /// Check if a field of a [`Config`] is read-only.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn is_locked(&self, config: &Config, field: &str) -> bool {
+        if self.locked_fields.iter().any(|x| x == field) {
+            return true;
+        }
+
+        self.secure_boot
+            && (SECURE_BOOT_LOCKED_FIELDS.contains(&field)
+                || (matches!(config.origin, Some(Parsers::Uki))
+                    && UKI_LOCKED_FIELDS.contains(&field)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+
+    use super::*;
+    use crate::config::builder::ConfigBuilder;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_policy() {
+        let bls = ConfigBuilder::new("foo.conf", ".conf")
+            .origin(Parsers::Bls)
+            .build();
+        let uki = ConfigBuilder::new("foo.efi", ".efi")
+            .origin(Parsers::Uki)
+            .build();
+        let boot_config = BootConfig {
+            locked_fields: vec!["title".to_owned()],
+            ..BootConfig::default()
+        };
+
+        let policy = EditPolicy::with_secure_boot(&boot_config, false);
+        assert!(policy.is_locked(&bls, "title"));
+        assert!(!policy.is_locked(&bls, "efi"));
+        assert!(!policy.is_locked(&uki, "options"));
+
+        let policy = EditPolicy::with_secure_boot(&boot_config, true);
+        assert!(policy.is_locked(&bls, "title"));
+        assert!(policy.is_locked(&bls, "efi"));
+        assert!(!policy.is_locked(&bls, "options"));
+        assert!(policy.is_locked(&uki, "options"));
+        assert!(!policy.is_locked(&uki, "version"));
+    }
+}
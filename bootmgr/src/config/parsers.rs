@@ -35,6 +35,9 @@ use crate::{config::Config, features, system::fs::UefiFileSystem};
 /// The BLS (BLS type 1) parser.
 pub mod bls;
 
+/// Boot counting for BLS type #1 and type #2 files.
+pub(crate) mod counter;
+
 /// The fallback boot EFI detector.
 pub mod fallback;
 
@@ -13,23 +13,48 @@
 //! linux /vmlinuz-linux
 //! options root=UUID=e09d636b-0cd9-4e84-8a39-84432cfc2b8e ro
 //! ```
+//!
+//! In addition to the keys in the specification, a `sha256` key may be specified. If it is, then the EFI executable
+//! must have that SHA-256 hash, or it will be refused. A `protected` key may also be specified. If it is `true`, then
+//! the entry may only be booted after the boot manager is unlocked with the password.
+//!
+//! On `x86_64`, if the `linux` kernel was built without the EFI stub, then it is booted directly through the Linux boot
+//! protocol instead of through EFI.
+//!
+//! A Multiboot2 kernel, such as Xen, may be booted with the `multiboot` key instead of `linux` or `efi`, which is the
+//! path to the kernel followed by its command line. Each of its modules is specified with a `module` key, which is the
+//! path to the module followed by its command line. The `options` are appended to the command line of the kernel.
+//!
+//! ```text
+//! title Xen
+//! multiboot /xen.gz dom0_mem=2G
+//! module /vmlinuz-linux root=UUID=e09d636b-0cd9-4e84-8a39-84432cfc2b8e ro
+//! module /initramfs-linux.img
+//! ```
+//!
+//! The same format is used for boot menus that are downloaded over TFTP, where a single file contains multiple entries,
+//! each starting at its `title` key. See [`crate::boot::action::pxe`].
 
 use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
+use core::net::IpAddr;
 
-use log::{error, warn};
-use uefi::{CStr16, CString16, Handle, cstr16, proto::media::file::FileInfo};
+use log::warn;
+use uefi::{CStr16, Handle, cstr16, proto::media::file::FileInfo};
 
+#[cfg(target_arch = "x86_64")]
+use crate::boot::loader::linux::is_raw_kernel;
 use crate::{
     BootResult,
+    boot::action::BootAction,
     config::{
         Config,
         builder::ConfigBuilder,
-        parsers::{ConfigParser, Parsers},
+        parsers::{ConfigParser, Parsers, counter::CountedFile},
     },
     error::BootError,
     system::{
         fs::{FsError, UefiFileSystem},
-        helper::{get_path_cstr, str_to_cstr},
+        helper::get_path_cstr,
     },
 };
 
@@ -39,84 +64,6 @@ const BLS_PREFIX: &CStr16 = cstr16!("\\loader\\entries");
 /// The configuration suffix.
 const BLS_SUFFIX: &str = ".conf";
 
-/// An implementation of the `BootLoaderSpec` boot counting feature.
-///
-/// A general overview of the BLS boot counting is as follows:
-/// 1. The OS provides a configuration file with a boot counter annotated at the end of it (such as +3 or +3-0)
-/// 2. The bootloader sees this filename and changes the boot counter to be one attempt less (+3 -> +2-1)
-/// 3. If the OS is able to be booted, then it will see this boot counter on the next boot and remove the boot counter.
-/// 4. Otherwise, if the boot counter is not removed, the boot loader will see this boot counter again, and rename it (+2-1 -> +1-2).
-/// 5. Once the counter reaches 0 (+1-2 -> +0-3), the boot loader will mark this entry as "bad" and derank it.
-///
-/// This implementation will check for the boot counter, then decrement it, or if the boot counter is 0, then it will mark the entry as bad.
-struct BootCounter {
-    /// The base name of the configuration name (without .conf, or boot counting)
-    base_name: String,
-
-    /// The amount of tries left as in the configuration name
-    left: u32,
-
-    /// The amount of boot attempts done as in the configuration name.
-    done: u32,
-}
-
-impl BootCounter {
-    /// Create a new [`BootCounter`] given a filename containing a boot counter.
-    ///
-    /// Will return [`None`] if there is no boot counter, or the file does not contain a valid
-    /// boot counter.
-    fn new(filename: impl Into<String>) -> Option<Self> {
-        let filename = filename.into();
-
-        let filename = filename.trim_end_matches(BLS_SUFFIX);
-        let v: Vec<&str> = filename.rsplitn(2, '+').collect();
-
-        if v.len() != 2 {
-            return None;
-        }
-
-        let counter = v[0];
-        let (left, done) = match counter.split_once('-') {
-            Some((l, d)) => (l.parse().ok()?, d.parse().ok()?),
-            None => (counter.parse().ok()?, 0),
-        };
-
-        Some(Self {
-            base_name: v[1].to_owned(),
-            left,
-            done,
-        })
-    }
-
-    /// Convert the current [`BootCounter`] into a filename for renaming.
-    ///
-    /// # Errors
-    ///
-    /// May return an `Error` if the filename could not be converted into a [`CString16`].
-    fn to_filename(&self) -> BootResult<CString16> {
-        let str = if self.done > 0 {
-            format!("{}+{}-{}.conf", self.base_name, self.left, self.done)
-        } else {
-            format!("{}+{}.conf", self.base_name, self.left)
-        };
-
-        Ok(str_to_cstr(&str)?)
-    }
-
-    /// Decrement the [`BootCounter`] if the tries were not exhausted.
-    const fn decrement(&mut self) {
-        if self.left > 0 {
-            self.left -= 1;
-            self.done += 1;
-        }
-    }
-
-    /// Check if the [`BootCounter`] is bad, or if the tries left is 0.
-    const fn is_bad(&self) -> bool {
-        self.left == 0
-    }
-}
-
 /// The parser for `BootLoaderSpec` type #1 configuration files
 #[derive(Default)]
 pub struct BlsConfig {
@@ -141,6 +88,12 @@ pub struct BlsConfig {
     /// The efi path of the configuration.
     efi: Option<String>,
 
+    /// The Multiboot2 kernel path and command line of the configuration.
+    multiboot: Option<String>,
+
+    /// The Multiboot2 modules of the configuration, as their path followed by their command line.
+    modules: Vec<String>,
+
     /// The options of the configuration.
     options: Option<String>,
 
@@ -152,6 +105,12 @@ pub struct BlsConfig {
 
     /// The architecture of the configuration.
     architecture: Option<String>,
+
+    /// The SHA-256 hash that the EFI executable of the configuration must have.
+    sha256: Option<String>,
+
+    /// If the configuration is protected by the password.
+    protected: bool,
 }
 
 impl BlsConfig {
@@ -200,26 +159,57 @@ impl BlsConfig {
                     }
                 }
                 "efi" => self.efi = Some(value),
+                "multiboot" => self.multiboot = Some(value),
+                "module" => self.modules.push(value),
                 "options" => self.options = Some(value),
                 "devicetree" => self.devicetree = Some(value),
                 "devicetree_overlay" => self.devicetree_overlay = Some(value),
                 "architecture" => self.architecture = Some(value.to_ascii_lowercase()),
+                "sha256" => self.sha256 = Some(value),
+                "protected" => {
+                    if let Ok(value) = value.parse() {
+                        self.protected = value;
+                    }
+                }
                 _ => warn!("[BLS PARSER]: Found unrecognized key {key} with value {value}"),
             }
         }
     }
 
-    /// Joins both options and initrd options
+    /// Joins both options and initrd options, where the initrd options are only added for Linux kernels.
     #[must_use = "Has no effect if the result is unused"]
     fn get_options(&self) -> String {
         let mut options = String::new();
         if let Some(opts) = &self.options {
             options.push_str(opts);
         }
-        self.initrd_options(&mut options);
+        if self.linux.is_some() {
+            self.initrd_options(&mut options);
+        }
         options
     }
 
+    /// Splits the multiboot key into the path to the kernel and its command line, joined with the options.
+    ///
+    /// Returns `None` if there is no multiboot key.
+    #[must_use = "Has no effect if the result is unused"]
+    fn get_multiboot(&self) -> Option<(String, String)> {
+        let multiboot = self.multiboot.as_deref()?;
+        let (path, args) = multiboot
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((multiboot.trim(), ""));
+
+        let mut options = args.trim().to_owned();
+        if let Some(opts) = &self.options {
+            if !options.is_empty() {
+                options.push(' ');
+            }
+            options.push_str(opts);
+        }
+        Some((path.to_owned(), options))
+    }
+
     /// Obtains all specified initrd files as options for the cmdline
     fn initrd_options(&self, buffer: &mut String) {
         if let Some(initrd) = &self.initrd {
@@ -271,16 +261,34 @@ fn get_bls_config(
     };
 
     let bls_config = BlsConfig::new(buf, Some(bytes));
-    let options = bls_config.get_options();
 
-    let Some(efi_path) = bls_config.linux.or(bls_config.efi) else {
-        return Ok(None);
+    let (efi_path, options, action) = if let Some((path, options)) = bls_config.get_multiboot() {
+        (path, options, BootAction::BootMultiboot)
+    } else {
+        let options = bls_config.get_options();
+        let action = match &bls_config.linux {
+            #[cfg(target_arch = "x86_64")]
+            Some(linux) if is_raw_kernel(fs, linux) => BootAction::BootLinux,
+            _ => BootAction::BootEfi,
+        };
+
+        let Some(efi_path) = bls_config.linux.or(bls_config.efi) else {
+            return Ok(None);
+        };
+        (efi_path, options, action)
     };
 
-    let config = ConfigBuilder::new(file.file_name(), BLS_SUFFIX)
+    let counted = CountedFile::new(BLS_PREFIX, file.file_name(), BLS_SUFFIX);
+
+    let config = ConfigBuilder::new(counted.entry_id, BLS_SUFFIX)
         .efi_path(efi_path)
+        .action(action)
         .options(options)
-        .set_bad(check_bad(file, fs))
+        .set_bad(counted.bad)
+        .set_protected(bls_config.protected)
+        .assign_if_some(counted.path, ConfigBuilder::boot_counter_path)
+        .assign_if_some(counted.tries_left, ConfigBuilder::tries_left)
+        .assign_if_some(counted.tries_done, ConfigBuilder::tries_done)
         .fs_handle(handle)
         .origin(Parsers::Bls)
         .assign_if_some(bls_config.title, ConfigBuilder::title)
@@ -288,44 +296,97 @@ fn get_bls_config(
         .assign_if_some(bls_config.machine_id, ConfigBuilder::machine_id)
         .assign_if_some(bls_config.sort_key, ConfigBuilder::sort_key)
         .assign_if_some(bls_config.devicetree, ConfigBuilder::devicetree_path)
-        .assign_if_some(bls_config.architecture, ConfigBuilder::architecture);
+        .assign_if_some(bls_config.architecture, ConfigBuilder::architecture)
+        .assign_if_some(bls_config.sha256, ConfigBuilder::sha256);
+    let config = bls_config
+        .modules
+        .into_iter()
+        .fold(config, ConfigBuilder::module);
 
     Ok(Some(config.build()))
 }
 
-/// Check if a certain config is bad given the [`FileInfo`] and a `SimpleFileSystem` protocol.
-fn check_bad(file: &FileInfo, fs: &mut UefiFileSystem) -> bool {
-    let counter = BootCounter::new(file.file_name());
+/// Parse a boot menu that was downloaded from a TFTP server into [`Config`]s that are booted from that server.
+///
+/// The menu consists of BLS entries that follow each other, where every `title` key starts a new entry. Lines before
+/// the first `title` are ignored. Entries without a `linux` or `efi` key are skipped, and `multiboot` entries are not
+/// supported, as their modules cannot be downloaded. Each entry is named after its title and its index in the menu, so
+/// that entries with the same title are still told apart.
+///
+/// The path prefix is prepended to the paths of the executable and the initrds that are not absolute, like PXELINUX does
+/// with the path prefix option of DHCP (option 210).
+///
+/// As the menu is received from the network, the `sha256` key of an entry only adds a constraint on the image, which is
+/// still validated as usual.
+pub(crate) fn parse_tftp_menu(content: &[u8], server: IpAddr, prefix: &str) -> Vec<Config> {
+    let Ok(content) = str::from_utf8(content) else {
+        return Vec::new();
+    };
 
-    if let Some(mut counter) = counter {
-        if counter.is_bad() {
-            return true; // tries exhausted
+    let mut entries: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let is_title = line
+            .trim()
+            .split_once(' ')
+            .is_some_and(|(key, _)| key.eq_ignore_ascii_case("title"));
+
+        match entries.last_mut() {
+            Some(entry) if !is_title => {
+                entry.push_str(line);
+                entry.push('\n');
+            }
+            _ if is_title => entries.push(line.to_owned() + "\n"),
+            _ => (),
         }
+    }
 
-        counter.decrement();
-
-        let Ok(counter_name) = counter.to_filename() else {
-            return false; // if we cant even convert the boot counter into a filename, just return
-        };
-
-        let Ok(src) = get_path_cstr(BLS_PREFIX, file.file_name()) else {
-            return false;
-        };
-
-        let Ok(dst) = get_path_cstr(BLS_PREFIX, &counter_name) else {
-            return false;
-        };
+    entries
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let mut bls_config = BlsConfig::new(entry.as_bytes(), None);
+            bls_config.initrd = bls_config.initrd.map(|initrd| {
+                initrd
+                    .split_ascii_whitespace()
+                    .map(|path| with_prefix(prefix, path))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+            let options = bls_config.get_options();
+            let efi_path = with_prefix(prefix, &bls_config.linux.or(bls_config.efi)?);
+            let label = bls_config.title.as_ref().unwrap_or(&efi_path);
+            let filename = format!("{label}-{index}");
+
+            let config = ConfigBuilder::new(filename, "")
+                .efi_path(efi_path)
+                .options(options)
+                .tftp_server(server)
+                .action(BootAction::BootTftp)
+                .set_protected(bls_config.protected)
+                .origin(Parsers::Special)
+                .assign_if_some(bls_config.title, ConfigBuilder::title)
+                .assign_if_some(bls_config.version, ConfigBuilder::version)
+                .assign_if_some(bls_config.sort_key, ConfigBuilder::sort_key)
+                .assign_if_some(bls_config.sha256, ConfigBuilder::download_sha256);
+
+            Some(config.build())
+        })
+        .collect()
+}
 
-        if let Err(e) = fs.rename(&src, &dst) {
-            error!("{e}");
-        }
+/// Prepend a path prefix to a path that is not absolute.
+fn with_prefix(prefix: &str, path: &str) -> String {
+    if path.starts_with(['/', '\\']) {
+        path.to_owned()
+    } else {
+        format!("{prefix}{path}")
     }
-
-    false
 }
 
 #[cfg(test)]
 mod tests {
+    use core::net::Ipv4Addr;
+
     use proptest::prelude::*;
 
     use super::*;
@@ -356,6 +417,70 @@ mod tests {
         );
     }
 
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_tftp_menu() {
+        let menu = b"
+            # ignored, as it is before the first entry
+            linux /ignored
+
+            title Arch Linux
+            linux /arch/vmlinuz-linux
+            initrd /arch/initramfs-linux.img
+            options ip=dhcp
+
+            title No Kernel
+            options quiet
+
+            title Memtest
+            efi /memtest.efi
+            sha256 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+
+            title Arch Linux
+            linux arch/vmlinuz-linux-lts
+            initrd arch/initramfs-linux-lts.img
+        ";
+        let server = IpAddr::V4(Ipv4Addr::new(10, 0, 2, 2));
+        let configs = parse_tftp_menu(menu, server, "/pxe/");
+        assert_eq!(configs.len(), 3);
+
+        assert_eq!(configs[0].title.as_deref(), Some("Arch Linux"));
+        assert_eq!(configs[0].filename, "Arch Linux-0");
+        assert_eq!(configs[0].tftp_server, Some(server));
+        assert_eq!(
+            configs[0].efi_path.as_deref().map(String::as_str),
+            Some("\\arch\\vmlinuz-linux")
+        );
+        assert_eq!(
+            configs[0].options.as_deref(),
+            Some("ip=dhcp initrd=/arch/initramfs-linux.img")
+        );
+        assert_eq!(configs[0].action, BootAction::BootTftp);
+
+        assert_eq!(configs[1].title.as_deref(), Some("Memtest"));
+        assert_eq!(
+            configs[1].efi_path.as_deref().map(String::as_str),
+            Some("\\memtest.efi")
+        );
+        assert_eq!(configs[1].sha256, None);
+        assert!(configs[1].download_sha256.is_some());
+
+        // entries with the same title get different filenames, and relative paths are under the path prefix
+        assert_eq!(configs[2].filename, "Arch Linux-3");
+        assert_eq!(
+            configs[2].efi_path.as_deref().map(String::as_str),
+            Some("\\pxe\\arch\\vmlinuz-linux-lts")
+        );
+        assert_eq!(
+            configs[2].options.as_deref(),
+            Some("initrd=/pxe/arch/initramfs-linux-lts.img")
+        );
+
+        assert!(parse_tftp_menu(&[0xFF, 0xFE], server, "").is_empty());
+    }
+
     /// # Panics
     ///
     /// May panic if the assertions fail.
@@ -374,6 +499,13 @@ mod tests {
             Some("/intel-ucode.img /initramfs-linux.img".to_owned())
         );
         assert_eq!(bls_config.get_options(), "root=PARTUUID=dcba4321-fe65-hg87-ji09-vutsrqponmlk ro initrd=/intel-ucode.img initrd=/initramfs-linux.img".to_owned());
+
+        // initrds are only given to Linux kernels
+        let bls_config = BlsConfig::new(
+            b"efi /shellx64.efi\ninitrd /initramfs-linux.img\noptions quiet",
+            None,
+        );
+        assert_eq!(bls_config.get_options(), "quiet".to_owned());
     }
 
     /// # Panics
@@ -411,42 +543,82 @@ mod tests {
     ///
     /// May panic if the assertions fail.
     #[test]
-    fn test_invalid_keys() {
+    fn test_sha256() {
         let config = b"
             title Linux
-            invalid invalid
-            someother invalid
+            linux /vmlinuz-linux
+            sha256 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
         ";
         let bls_config = BlsConfig::new(config, None);
-        assert_eq!(bls_config.title, Some("Linux".to_owned())); // valid keys should still be parsed
+        assert_eq!(
+            bls_config.sha256,
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_owned())
+        );
+        assert!(!bls_config.protected);
     }
 
     /// # Panics
     ///
     /// May panic if the assertions fail.
     #[test]
-    fn test_boot_counter() {
-        let filename = "somelinuxconf+3.conf";
+    fn test_protected() {
+        let config = b"
+            title Linux
+            linux /vmlinuz-linux
+            protected true
+        ";
+        let bls_config = BlsConfig::new(config, None);
+        assert!(bls_config.protected);
+    }
 
-        // if this panics, it indicates a failure in the boot counter parser.
-        let mut ctr = BootCounter::new(filename)
-            .expect("Failed to create a boot counter from valid filename in test");
-        ctr.decrement();
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_multiboot() {
+        let config = b"
+            title Xen
+            multiboot /xen.gz dom0_mem=2G
+            module /vmlinuz-linux root=/dev/sda1 ro
+            module /initramfs-linux.img
+            initrd /ignored.img
+            options console=vga
+        ";
+        let bls_config = BlsConfig::new(config, None);
         assert_eq!(
-            ctr.to_filename().ok(),
-            CString16::try_from("somelinuxconf+2-1.conf").ok()
+            bls_config.modules,
+            ["/vmlinuz-linux root=/dev/sda1 ro", "/initramfs-linux.img"]
         );
-        ctr.decrement();
         assert_eq!(
-            ctr.to_filename().ok(),
-            CString16::try_from("somelinuxconf+1-2.conf").ok()
+            bls_config.get_multiboot(),
+            Some(("/xen.gz".to_owned(), "dom0_mem=2G console=vga".to_owned()))
         );
-        ctr.decrement();
+
+        let bls_config = BlsConfig::new(b"multiboot /xen.gz", None);
         assert_eq!(
-            ctr.to_filename().ok(),
-            CString16::try_from("somelinuxconf+0-3.conf").ok()
+            bls_config.get_multiboot(),
+            Some(("/xen.gz".to_owned(), String::new()))
         );
-        assert!(ctr.is_bad());
+
+        let bls_config = BlsConfig::new(b"multiboot /xen.gz\t  dom0_mem=2G", None);
+        assert_eq!(
+            bls_config.get_multiboot(),
+            Some(("/xen.gz".to_owned(), "dom0_mem=2G".to_owned()))
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_invalid_keys() {
+        let config = b"
+            title Linux
+            invalid invalid
+            someother invalid
+        ";
+        let bls_config = BlsConfig::new(config, None);
+        assert_eq!(bls_config.title, Some("Linux".to_owned())); // valid keys should still be parsed
     }
 
     proptest! {
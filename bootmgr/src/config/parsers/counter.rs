@@ -0,0 +1,336 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! An implementation of the `BootLoaderSpec` boot counting feature.
+//!
+//! Boot counting applies to both BLS type #1 configuration files in `\loader\entries` and BLS type #2 files (UKIs)
+//! in `\EFI\Linux`. A boot counter is annotated in the filename of the file, right before the suffix, such as
+//! `linux+3.conf` or `linux+2-1.efi`.
+//!
+//! Boot counters are only read while the files are scanned. The boot counter of a boot option is decremented once it
+//! is selected to be booted, through [`decrement_boot_counter`].
+
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+};
+
+use log::warn;
+use uefi::{CStr16, CString16};
+
+use crate::{
+    BootResult,
+    config::{Config, types::EfiPath},
+    system::{fs::UefiFileSystem, helper::str_to_cstr},
+};
+
+/// An implementation of the `BootLoaderSpec` boot counting feature.
+///
+/// A general overview of the BLS boot counting is as follows:
+/// 1. The OS provides a file with a boot counter annotated at the end of it (such as +3 or +3-0)
+/// 2. The bootloader sees this filename and changes the boot counter to be one attempt less (+3 -> +2-1)
+/// 3. If the OS is able to be booted, then it will see this boot counter on the next boot and remove the boot counter.
+/// 4. Otherwise, if the boot counter is not removed, the boot loader will see this boot counter again, and rename it (+2-1 -> +1-2).
+/// 5. Once the counter reaches 0 (+1-2 -> +0-3), the boot loader will mark this entry as "bad" and derank it.
+///
+/// This implementation will check for the boot counter, then decrement it once the entry is booted, or if the boot counter
+/// is 0, then it will mark the entry as bad.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BootCounter {
+    /// The base name of the file (without the suffix, or boot counting)
+    base_name: String,
+
+    /// The suffix of the file, as it appears in the filename.
+    suffix: String,
+
+    /// The amount of tries left as in the filename.
+    left: u32,
+
+    /// The amount of boot attempts done as in the filename.
+    done: u32,
+}
+
+impl BootCounter {
+    /// Create a new [`BootCounter`] given a filename containing a boot counter and the suffix of the file.
+    ///
+    /// The suffix is compared case-insensitively, as filenames are usually case-insensitive in UEFI.
+    ///
+    /// Will return [`None`] if there is no boot counter, or the file does not contain a valid
+    /// boot counter.
+    pub(crate) fn new(filename: &str, suffix: &str) -> Option<Self> {
+        let split = filename.len().checked_sub(suffix.len())?;
+        let (filename, file_suffix) = (filename.get(..split)?, filename.get(split..)?);
+
+        if !file_suffix.eq_ignore_ascii_case(suffix) {
+            return None;
+        }
+
+        let (base_name, counter) = filename.rsplit_once('+')?;
+
+        let (left, done) = match counter.split_once('-') {
+            Some((l, d)) => (l.parse().ok()?, d.parse().ok()?),
+            None => (counter.parse().ok()?, 0),
+        };
+
+        Some(Self {
+            base_name: base_name.to_owned(),
+            suffix: file_suffix.to_owned(),
+            left,
+            done,
+        })
+    }
+
+    /// Get the entry identifier of the file, which is the filename with the boot counter removed.
+    ///
+    /// This ensures that the entry stays the same to the Boot Loader Interface while the boot counter changes.
+    #[must_use = "Has no effect if the result is unused"]
+    pub(crate) fn entry_id(&self) -> String {
+        format!("{}{}", self.base_name, self.suffix)
+    }
+
+    /// Convert the current [`BootCounter`] into a filename for renaming.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the filename could not be converted into a [`CString16`].
+    pub(crate) fn to_filename(&self) -> BootResult<CString16> {
+        let str = if self.done > 0 {
+            format!(
+                "{}+{}-{}{}",
+                self.base_name, self.left, self.done, self.suffix
+            )
+        } else {
+            format!("{}+{}{}", self.base_name, self.left, self.suffix)
+        };
+
+        Ok(str_to_cstr(&str)?)
+    }
+
+    /// Get the amount of tries left.
+    pub(crate) const fn left(&self) -> u32 {
+        self.left
+    }
+
+    /// Get the amount of boot attempts done.
+    pub(crate) const fn done(&self) -> u32 {
+        self.done
+    }
+
+    /// Reset the [`BootCounter`] back to its full amount of tries.
+    pub(crate) const fn reset(&mut self) {
+        self.left = self.left.saturating_add(self.done);
+        self.done = 0;
+    }
+
+    /// Decrement the [`BootCounter`] if the tries were not exhausted.
+    pub(crate) const fn decrement(&mut self) {
+        if self.left > 0 {
+            self.left -= 1;
+            self.done += 1;
+        }
+    }
+
+    /// Check if the [`BootCounter`] is bad, or if the tries left is 0.
+    pub(crate) const fn is_bad(&self) -> bool {
+        self.left == 0
+    }
+}
+
+/// Update the boot counter of a [`Config`], renaming its file to the updated boot counter.
+///
+/// The [`Config`] is updated to reflect the new boot counter, including its EFI path if the file being renamed is the
+/// EFI executable itself. If the [`Config`] does not have a boot counter, then this does nothing.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem of the [`Config`] could not be opened, or the file could not be renamed.
+pub(crate) fn update_boot_counter(
+    config: &mut Config,
+    update: impl FnOnce(&mut BootCounter),
+) -> BootResult<()> {
+    let (Some(path), Some(handle)) = (&config.boot_counter_path, config.fs_handle) else {
+        return Ok(());
+    };
+    let Some(mut counter) = BootCounter::new(path, &config.suffix) else {
+        return Ok(());
+    };
+
+    update(&mut counter);
+
+    let src = str_to_cstr(path)?;
+    let dst = counter.to_filename()?;
+
+    let mut fs = UefiFileSystem::from_handle(*handle)?;
+    fs.rename(&src, &dst)?;
+
+    let dst = dst.to_string();
+    if config.efi_path.as_deref().is_some_and(|x| x == path) {
+        config.efi_path = EfiPath::new(&dst).ok(); // the file being renamed may be the EFI executable itself
+    }
+
+    config.boot_counter_path = Some(dst);
+    config.tries_left = Some(counter.left());
+    config.tries_done = Some(counter.done());
+    config.bad = counter.is_bad();
+
+    Ok(())
+}
+
+/// Decrement the boot counter of a [`Config`] that is about to be booted.
+///
+/// If the tries of the boot counter are already exhausted, then the boot counter is left untouched. If the filesystem
+/// is read-only, then the boot counter is also left untouched, as it can never be updated.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem of the [`Config`] could not be opened, or the file could not be renamed.
+pub(crate) fn decrement_boot_counter(config: &mut Config) -> BootResult<()> {
+    if config.boot_counter_path.is_none() || config.bad {
+        return Ok(());
+    }
+
+    if let Some(handle) = config.fs_handle
+        && UefiFileSystem::from_handle(*handle)?.is_read_only()
+    {
+        warn!(
+            "Could not update boot counter of {}, as the filesystem is read-only",
+            config.filename
+        );
+        return Ok(());
+    }
+
+    update_boot_counter(config, BootCounter::decrement)
+}
+
+/// A file that may have a boot counter.
+pub(crate) struct CountedFile {
+    /// The entry identifier of the file, which is the filename with the boot counter removed.
+    pub(crate) entry_id: String,
+
+    /// If the file has exhausted its tries.
+    pub(crate) bad: bool,
+
+    /// The path of the file, if it has a boot counter.
+    pub(crate) path: Option<String>,
+
+    /// The amount of tries left, if the file has a boot counter.
+    pub(crate) tries_left: Option<u32>,
+
+    /// The amount of boot attempts done, if the file has a boot counter.
+    pub(crate) tries_done: Option<u32>,
+}
+
+impl CountedFile {
+    /// Read the boot counter of a file in the `prefix` directory, given its filename and suffix.
+    ///
+    /// The boot counter is not decremented here, as only the boot option that is booted should have its boot counter
+    /// decremented. If the file does not have a boot counter, then the entry identifier will simply be the original
+    /// filename.
+    pub(crate) fn new(prefix: &CStr16, filename: &CStr16, suffix: &str) -> Self {
+        let name = filename.to_string();
+
+        let Some(counter) = BootCounter::new(&name, suffix) else {
+            return Self {
+                entry_id: name,
+                bad: false,
+                path: None,
+                tries_left: None,
+                tries_done: None,
+            };
+        };
+
+        Self {
+            entry_id: counter.entry_id(),
+            path: Some(format!("{prefix}\\{name}")),
+            bad: counter.is_bad(),
+            tries_left: Some(counter.left()),
+            tries_done: Some(counter.done()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_boot_counter() {
+        let filename = "somelinuxconf+3.conf";
+
+        // if this panics, it indicates a failure in the boot counter parser.
+        let mut ctr = BootCounter::new(filename, ".conf")
+            .expect("Failed to create a boot counter from valid filename in test");
+        ctr.decrement();
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("somelinuxconf+2-1.conf").ok()
+        );
+        ctr.decrement();
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("somelinuxconf+1-2.conf").ok()
+        );
+        ctr.decrement();
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("somelinuxconf+0-3.conf").ok()
+        );
+        assert!(ctr.is_bad());
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_uki_boot_counter() {
+        let mut ctr = BootCounter::new("linux-6.16+2-1.EFI", ".efi")
+            .expect("Failed to create a boot counter from valid filename in test");
+        assert_eq!(ctr.entry_id(), "linux-6.16.EFI".to_owned());
+
+        ctr.decrement();
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("linux-6.16+1-2.EFI").ok()
+        );
+        assert_eq!(ctr.entry_id(), "linux-6.16.EFI".to_owned());
+
+        ctr.reset();
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("linux-6.16+3.EFI").ok()
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_path_boot_counter() {
+        let mut ctr = BootCounter::new("\\loader\\entries\\linux+0-3.conf", ".conf")
+            .expect("Failed to create a boot counter from valid path in test");
+        assert!(ctr.is_bad());
+
+        ctr.reset();
+        assert!(!ctr.is_bad());
+        assert_eq!(
+            ctr.to_filename().ok(),
+            CString16::try_from("\\loader\\entries\\linux+3.conf").ok()
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_no_boot_counter() {
+        assert_eq!(BootCounter::new("linux.efi", ".efi"), None);
+        assert_eq!(BootCounter::new("linux+3.conf", ".efi"), None);
+        assert_eq!(BootCounter::new("linux+a-b.efi", ".efi"), None);
+        assert_eq!(BootCounter::new(".efi", ".efi"), None);
+        assert_eq!(BootCounter::new("efi", ".efi"), None);
+    }
+}
@@ -15,7 +15,7 @@ use crate::{
     config::{
         Config,
         builder::ConfigBuilder,
-        parsers::{ConfigParser, Parsers},
+        parsers::{ConfigParser, Parsers, counter::CountedFile},
     },
     system::{
         fs::{FsError, UefiFileSystem},
@@ -197,9 +197,15 @@ fn get_uki_config(
 
     let uki_config = UkiConfig::new(&content)?;
 
+    let counted = CountedFile::new(UKI_PREFIX, file.file_name(), UKI_SUFFIX);
+
     let efi_path = format!("{UKI_PREFIX}\\{}", file.file_name());
-    let config = ConfigBuilder::new(file.file_name(), UKI_SUFFIX)
+    let config = ConfigBuilder::new(counted.entry_id, UKI_SUFFIX)
         .efi_path(efi_path)
+        .set_bad(counted.bad)
+        .assign_if_some(counted.path, ConfigBuilder::boot_counter_path)
+        .assign_if_some(counted.tries_left, ConfigBuilder::tries_left)
+        .assign_if_some(counted.tries_done, ConfigBuilder::tries_done)
         .title(uki_config.title)
         .sort_key(uki_config.sort_key)
         .fs_handle(handle)
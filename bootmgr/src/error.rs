@@ -40,10 +40,29 @@ pub enum BootError {
     #[error("Devicetree Error: {0}")]
     DevicetreeError(#[from] crate::boot::devicetree::DevicetreeError),
 
+    /// A Linux kernel could not be booted through the Linux boot protocol.
+    #[cfg(target_arch = "x86_64")]
+    #[error("Linux Boot Error: {0}")]
+    LinuxError(#[from] crate::boot::loader::linux::LinuxError),
+
+    /// A Multiboot2 kernel could not be booted.
+    #[cfg(target_arch = "x86_64")]
+    #[error("Multiboot Error: {0}")]
+    MultibootError(#[from] crate::boot::loader::multiboot::MultibootError),
+
+    /// A password protected boot option was used while the boot manager was locked.
+    #[error("Password Error: {0}")]
+    PasswordError(#[from] crate::boot::password::PasswordError),
+
     /// An error occurred while interacting with UEFI variables.
     #[error("UEFI Variable Error: {0}")]
     VarError(#[from] crate::system::variable::VarError),
 
+    /// The Authenticode signature of an image could not be verified.
+    #[cfg(feature = "authenticode")]
+    #[error("Authenticode Error: {0}")]
+    AuthenticodeError(#[from] crate::boot::secure_boot::authenticode::AuthenticodeError),
+
     /// The UKI executable could not be parsed for any reason.
     #[cfg(feature = "uki")]
     #[error("Uki Parse Error: {0}")]
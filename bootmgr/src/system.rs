@@ -7,8 +7,11 @@ pub mod drivers;
 pub mod fs;
 pub mod helper;
 pub mod log_backend;
+pub mod log_file;
 pub mod protos;
+pub mod syslog;
 pub mod time;
+pub mod tpm;
 pub mod variable;
 
 mod global_allocator;
@@ -18,7 +18,7 @@ use uefi::{
 
 use crate::{
     BootResult,
-    boot::secure_boot::shim::shim_load_image,
+    boot::secure_boot,
     system::{
         fs::UefiFileSystem,
         helper::{get_path_cstr, join_to_device_path, str_to_cstr},
@@ -51,7 +51,7 @@ fn load_driver(driver_path: &CStr16, file: &FileInfo, buf: &mut [u8]) -> BootRes
     };
 
     // use Shim if available to load the image, incase the driver is in mok or something
-    let handle = shim_load_image(boot::image_handle(), src)?;
+    let handle = secure_boot::load_image(boot::image_handle(), src, None)?;
 
     let image = boot::open_protocol_exclusive::<LoadedImage>(handle)?;
 
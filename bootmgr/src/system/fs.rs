@@ -28,20 +28,26 @@ use thiserror::Error;
 use uefi::{
     CStr16, CString16, Char16, Guid, Handle, Status,
     boot::{self, ScopedProtocol},
+    data_types::Align,
     fs::{CHARACTER_DENY_LIST, COMMON_SKIP_DIRS, UefiDirectoryIter},
     guid,
-    proto::media::{
-        file::{
-            Directory, File, FileAttribute, FileInfo, FileMode, FileSystemVolumeLabel, RegularFile,
+    proto::{
+        device_path::DevicePath,
+        loaded_image::LoadedImage,
+        media::{
+            file::{
+                Directory, File, FileAttribute, FileInfo, FileMode, FileSystemInfo,
+                FileSystemVolumeLabel, RegularFile,
+            },
+            fs::SimpleFileSystem,
+            partition::{GptPartitionType, PartitionInfo},
         },
-        fs::SimpleFileSystem,
-        partition::{GptPartitionType, PartitionInfo},
     },
 };
 
 use crate::{
     BootResult,
-    system::helper::{cstr_ends_with, str_to_cstr},
+    system::helper::{cstr_ends_with, is_same_disk, str_to_cstr},
 };
 
 /// The size of one gigabyte in bytes. This is the default value if a file is too big to be read.
@@ -99,6 +105,10 @@ pub enum FsError {
     #[error("Failed to delete file")]
     DeleteErr(Status),
 
+    /// A file could not be renamed.
+    #[error("Failed to rename file")]
+    RenameErr(Status),
+
     /// A file could not be flushed.
     #[error("Failed to flush file")]
     FlushErr(Status),
@@ -163,6 +173,18 @@ impl UefiFileSystem {
         Ok(info.volume_label().to_owned())
     }
 
+    /// Checks if the filesystem is read-only.
+    ///
+    /// If the volume could not be opened or does not support [`FileSystemInfo`], then this will return `false`,
+    /// as the filesystem cannot be verified to be read-only.
+    pub fn is_read_only(&mut self) -> bool {
+        self.0
+            .open_volume()
+            .ok()
+            .and_then(|mut root| root.get_boxed_info::<FileSystemInfo>().ok())
+            .is_some_and(|info| info.read_only())
+    }
+
     /// Checks if a file exists from a [`Handle`] to a partition.
     ///
     /// It makes no distinction between whether a file could not be verified to exist or a file that really
@@ -193,6 +215,22 @@ impl UefiFileSystem {
         Ok(self.exists(&str_to_cstr(path)?))
     }
 
+    /// Gets the size of a file in bytes.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the volume couldn't be opened, the path does not point to a valid file, or the
+    /// information of the file could not be read.
+    pub fn file_size(&mut self, path: &CStr16) -> Result<u64, FsError> {
+        let mut file = self.get_regular_file(path)?;
+
+        let info = file
+            .get_boxed_info::<FileInfo>()
+            .map_err(|e| FsError::ReadErr(e.status()))?;
+
+        Ok(info.file_size())
+    }
+
     /// Returns a [`UefiDirectoryIter`] of files in the path from a handle to a partition.
     ///
     /// # Errors
@@ -274,6 +312,28 @@ impl UefiFileSystem {
         Ok(buf)
     }
 
+    /// Reads part of a file into a buffer, starting from an offset into the file.
+    ///
+    /// Unlike [`Self::read_into`], the buffer does not have to be large enough to fit the entire file. This is useful
+    /// for reading only the header of a file. Returns the number of bytes that were read, which may be less than the
+    /// size of the buffer if the end of the file was reached.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the volume couldn't be opened, the path does not point to a valid file, or
+    /// the file could not be read for any reason.
+    pub fn read_at(
+        &mut self,
+        path: &CStr16,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, FsError> {
+        let mut file = self.get_regular_file(path)?;
+        file.set_position(offset).map_err(|_| FsError::SeekErr)?;
+
+        file.read(buf).map_err(|e| FsError::ReadErr(e.status()))
+    }
+
     /// Copy a file onto another file.
     ///
     /// This implements buffered reading and writing, with a fixed size of 4 KiB. This buffer is a stack allocated array
@@ -322,19 +382,43 @@ impl UefiFileSystem {
 
     /// Renames a file into another file.
     ///
-    /// This essentially copies a file into another file, then deletes the original file.
+    /// The file is renamed in place by changing the name in its [`FileInfo`], so the content of the file is not
+    /// copied. If the destination file already exists, then it is replaced.
     ///
     /// # Errors
     ///
-    /// May return an `Error` if the volume couldn't be opened, any of the two paths don't point to a valid file,
-    /// the source file could not be read, or the source file could not be deleted.
+    /// May return an `Error` if the volume couldn't be opened, the source path does not point to a valid file, or
+    /// the file could not be renamed.
     pub fn rename(&mut self, src: &CStr16, dst: &CStr16) -> Result<(), FsError> {
         if src == dst {
             return Ok(());
         }
 
-        self.copy(src, dst)?;
-        self.delete(src)?;
+        let mut file = self.get_mut_file(src)?;
+        let info = file
+            .get_boxed_info::<FileInfo>()
+            .map_err(|e| FsError::ReadErr(e.status()))?;
+
+        let _ = self.delete(dst); // the file cannot be renamed if the destination exists
+
+        let mut storage = vec![0; size_of_val(&*info) + dst.num_bytes() + FileInfo::alignment()];
+        let storage = FileInfo::align_buf(&mut storage)
+            .ok_or(FsError::RenameErr(Status::BUFFER_TOO_SMALL))?;
+        let new_info = FileInfo::new(
+            storage,
+            info.file_size(),
+            info.physical_size(),
+            *info.create_time(),
+            *info.last_access_time(),
+            *info.modification_time(),
+            info.attribute(),
+            dst,
+        )
+        .map_err(|_| FsError::RenameErr(Status::BUFFER_TOO_SMALL))?;
+
+        file.set_info(new_info)
+            .map_err(|e| FsError::RenameErr(e.status()))?;
+        file.flush().map_err(|e| FsError::FlushErr(e.status()))?;
 
         Ok(())
     }
@@ -479,6 +563,36 @@ pub(crate) fn is_target_partition(handle: Handle) -> bool {
     true
 }
 
+/// Checks if a partition is an `XBOOTLDR` partition.
+///
+/// This will return false if the partition does not support [`PartitionInfo`], or is not a GPT partition.
+pub(crate) fn is_xbootldr_partition(handle: Handle) -> bool {
+    boot::open_protocol_exclusive::<PartitionInfo>(handle).is_ok_and(|info| {
+        info.gpt_partition_entry()
+            .is_some_and(|entry| { entry.partition_type_guid.0 } == XBOOTLDR_PARTITION)
+    })
+}
+
+/// Checks if a partition is located on the same disk as the partition that `bootmgr-rs` was loaded from.
+///
+/// If the [`DevicePath`] of either the partition or the image's partition could not be obtained, then it is
+/// not possible to tell which disk the partition is on, so this will return true.
+pub(crate) fn is_on_image_disk(handle: Handle) -> bool {
+    let Some(image_path) = get_image_device_path() else {
+        return true;
+    };
+
+    boot::open_protocol_exclusive::<DevicePath>(handle)
+        .map_or(true, |dev_path| is_same_disk(&image_path, &dev_path))
+}
+
+/// Get the [`DevicePath`] of the partition that `bootmgr-rs` was loaded from.
+fn get_image_device_path() -> Option<Box<DevicePath>> {
+    let image = boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()).ok()?;
+    let dev_path = boot::open_protocol_exclusive::<DevicePath>(image.device()?).ok()?;
+    Some(dev_path.to_boxed())
+}
+
 /// Get the unique partition GUID of a particular partition.
 ///
 /// Not to be confused with the partition type GUID, the unique partition GUID allows
@@ -14,7 +14,7 @@
 //!    handle `ExitBootServices`.
 
 use alloc::{ffi::CString, string::String};
-use core::mem::MaybeUninit;
+use core::{mem::MaybeUninit, net::IpAddr};
 
 use thiserror::Error;
 use tinyvec::TinyVec;
@@ -25,7 +25,8 @@ use uefi::{
     proto::{
         ProtocolPointer,
         device_path::{
-            DevicePath, PoolDevicePath, build,
+            DevicePath, DevicePathNode, DeviceSubType, DeviceType, PoolDevicePath, build,
+            messaging,
             text::{AllowShortcuts, DevicePathToText, DisplayOnly},
         },
     },
@@ -36,6 +37,12 @@ use crate::{BootResult, config::types::Architecture};
 /// The length of a BLS machine-id.
 const MACHINE_ID_LEN: usize = 32;
 
+/// The IP protocol number of UDP, which TFTP is transferred over.
+const IP_PROTOCOL_UDP: u16 = 17;
+
+/// The port of a TFTP server.
+const TFTP_PORT: u16 = 69;
+
 /// The max length of a path in UEFI.
 const MAX_PATH: usize = 256;
 
@@ -168,6 +175,59 @@ pub fn get_arch() -> Option<Architecture> {
     }
 }
 
+/// Gets the offset of the PE header from the DOS header at the start of a PE/COFF image.
+///
+/// Returns `None` if the DOS header is not valid.
+#[must_use = "Has no effect if the result is unused"]
+pub(crate) fn get_pe_header_offset(dos_header: &[u8]) -> Option<u64> {
+    /// The offset of the `e_lfanew` field in the DOS header.
+    const E_LFANEW_OFFSET: usize = 0x3c;
+
+    if !dos_header.starts_with(b"MZ") {
+        return None;
+    }
+
+    let e_lfanew = dos_header.get(E_LFANEW_OFFSET..E_LFANEW_OFFSET + 4)?;
+    Some(u64::from(u32::from_le_bytes(e_lfanew.try_into().ok()?)))
+}
+
+/// Checks if the PE header of a PE/COFF image has a machine type that can run on this system.
+///
+/// The provided slice should start at the PE signature. Returns `None` if the PE header is not valid.
+///
+/// EFI Byte Code images are considered to be able to run on any system, as the firmware may provide an interpreter
+/// for them.
+#[must_use = "Has no effect if the result is unused"]
+pub(crate) fn is_pe_machine_supported(pe_header: &[u8]) -> Option<bool> {
+    /// The machine type of an x86 image.
+    const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+    /// The machine type of an ARM image.
+    const IMAGE_FILE_MACHINE_ARM: u16 = 0x01c0;
+    /// The machine type of an ARM Thumb or Thumb-2 image.
+    const IMAGE_FILE_MACHINE_THUMB: u16 = 0x01c2;
+    /// The machine type of an ARM Thumb-2 image.
+    const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x01c4;
+    /// The machine type of an EFI Byte Code image.
+    const IMAGE_FILE_MACHINE_EBC: u16 = 0x0ebc;
+    /// The machine type of an x64 image.
+    const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+    /// The machine type of a 64-bit ARM image.
+    const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+    let machine = pe_header.strip_prefix(b"PE\0\0")?.get(0..2)?;
+
+    Some(match u16::from_le_bytes(machine.try_into().ok()?) {
+        IMAGE_FILE_MACHINE_EBC => true,
+        IMAGE_FILE_MACHINE_I386 => cfg!(target_arch = "x86"),
+        IMAGE_FILE_MACHINE_AMD64 => cfg!(target_arch = "x86_64"),
+        IMAGE_FILE_MACHINE_ARM | IMAGE_FILE_MACHINE_THUMB | IMAGE_FILE_MACHINE_ARMNT => {
+            cfg!(target_arch = "arm")
+        }
+        IMAGE_FILE_MACHINE_ARM64 => cfg!(target_arch = "aarch64"),
+        _ => false,
+    })
+}
+
 /// Gets the joined [`DevicePath`] given an existing [`DevicePath`] (likely to a partition) and a file's path.
 ///
 /// The provided mutable buffer must be large enough to fit the final [`DevicePath`].
@@ -188,6 +248,101 @@ pub(crate) fn join_to_device_path(
     Ok(dev_path.append_path(path)?)
 }
 
+/// Gets the joined [`DevicePath`] given an existing [`DevicePath`] (likely to a network interface) and a URI.
+///
+/// The provided mutable buffer must be large enough to fit the URI node.
+///
+/// # Errors
+///
+/// May return an `Error` if the buffer is too small for the URI node.
+pub(crate) fn join_uri_to_device_path(
+    dev_path: &DevicePath,
+    uri: &str,
+    buf: &mut [u8],
+) -> Result<PoolDevicePath, DevicePathError> {
+    let buf = slice_to_maybe_uninit(buf);
+    let path: &DevicePath = build::DevicePathBuilder::with_buf(buf)
+        .push(&build::messaging::Uri {
+            value: uri.as_bytes(),
+        })?
+        .finalize()?;
+    Ok(dev_path.append_path(path)?)
+}
+
+/// Gets the [`DevicePath`] of a file downloaded over TFTP, given the [`DevicePath`] of a network interface, the address
+/// of the interface and the address of the server.
+///
+/// The nodes after the MAC address node of the interface are replaced with an IPv4 or IPv6 node, as the interface may
+/// already end with an IP node that has no addresses. The provided mutable buffer must be large enough to fit the
+/// final [`DevicePath`].
+///
+/// # Errors
+///
+/// May return an `Error` if the buffer is too small for the [`DevicePath`].
+pub(crate) fn join_ip_to_device_path<'a>(
+    dev_path: &DevicePath,
+    local: IpAddr,
+    remote: IpAddr,
+    buf: &'a mut [u8],
+) -> Result<&'a DevicePath, DevicePathError> {
+    let buf = slice_to_maybe_uninit(buf);
+    let mut builder = build::DevicePathBuilder::with_buf(buf);
+    for node in dev_path.node_iter() {
+        builder = builder.push(&node)?;
+        if node.device_type() == DeviceType::MESSAGING
+            && node.sub_type() == DeviceSubType::MESSAGING_MAC_ADDRESS
+        {
+            break;
+        }
+    }
+
+    let builder = match remote {
+        IpAddr::V4(remote) => builder.push(&build::messaging::Ipv4 {
+            local_ip_address: match local {
+                IpAddr::V4(local) => local.octets(),
+                IpAddr::V6(_) => [0; 4],
+            },
+            remote_ip_address: remote.octets(),
+            local_port: 0,
+            remote_port: TFTP_PORT,
+            protocol: IP_PROTOCOL_UDP,
+            ip_address_origin: messaging::Ipv4AddressOrigin::DHCP,
+            gateway_ip_address: [0; 4],
+            subnet_mask: [0; 4],
+        })?,
+        IpAddr::V6(remote) => builder.push(&build::messaging::Ipv6 {
+            local_ip_address: match local {
+                IpAddr::V4(_) => [0; 16],
+                IpAddr::V6(local) => local.octets(),
+            },
+            remote_ip_address: remote.octets(),
+            local_port: 0,
+            remote_port: TFTP_PORT,
+            protocol: IP_PROTOCOL_UDP,
+            ip_address_origin: messaging::Ipv6AddressOrigin::STATEFUL_CONFIGURATION,
+            prefix_length: 0,
+            gateway_ip_address: [0; 16],
+        })?,
+    };
+
+    Ok(builder.finalize()?)
+}
+
+/// Checks if two [`DevicePath`]s point to partitions (or files) on the same disk.
+///
+/// The disk of a [`DevicePath`] is identified by every node that comes before the first media node (such as a
+/// `HardDrive` node). If both of these prefixes are exactly equal, then the two [`DevicePath`]s are on the same disk.
+#[must_use = "Has no effect if the result is unused"]
+pub(crate) fn is_same_disk(a: &DevicePath, b: &DevicePath) -> bool {
+    /// Get the nodes of a [`DevicePath`] that identify the disk.
+    fn disk_nodes(path: &DevicePath) -> impl Iterator<Item = &DevicePathNode> {
+        path.node_iter()
+            .take_while(|node| node.device_type() != DeviceType::MEDIA)
+    }
+
+    disk_nodes(a).eq(disk_nodes(b))
+}
+
 /// Normalizes a path to make it more aligned with UEFI expectations
 ///
 /// Currently this means replacing all forward slashes with backslashes.
@@ -347,6 +502,90 @@ mod tests {
         }
     }
 
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if any of the [`DevicePath`]s could not be built.
+    #[test]
+    fn test_is_same_disk() -> Result<(), DevicePathError> {
+        use uefi::{
+            guid,
+            proto::device_path::media::{PartitionFormat, PartitionSignature},
+        };
+
+        fn disk_path<'a>(
+            buf: &'a mut [u8],
+            port: u16,
+            partition_number: u32,
+        ) -> Result<&'a DevicePath, DevicePathError> {
+            Ok(
+                build::DevicePathBuilder::with_buf(slice_to_maybe_uninit(buf))
+                    .push(&build::acpi::Acpi {
+                        hid: 0x0a03_41d0,
+                        uid: 0,
+                    })?
+                    .push(&build::hardware::Pci {
+                        function: 0,
+                        device: 0x1f,
+                    })?
+                    .push(&build::messaging::Sata {
+                        hba_port_number: port,
+                        port_multiplier_port_number: 0xffff,
+                        logical_unit_number: 0,
+                    })?
+                    .push(&build::media::HardDrive {
+                        partition_number,
+                        partition_start: u64::from(partition_number) * 2048,
+                        partition_size: 2048,
+                        partition_signature: PartitionSignature::Guid(guid!(
+                            "bc13c2ff-59e6-4262-a352-b275fd6f7172"
+                        )),
+                        partition_format: PartitionFormat::GPT,
+                    })?
+                    .finalize()?,
+            )
+        }
+
+        let (mut esp_buf, mut xbootldr_buf, mut other_buf) = ([0; 256], [0; 256], [0; 256]);
+        let esp = disk_path(&mut esp_buf, 0, 1)?;
+        let xbootldr = disk_path(&mut xbootldr_buf, 0, 2)?;
+        let other = disk_path(&mut other_buf, 1, 2)?;
+
+        assert!(is_same_disk(esp, xbootldr));
+        assert!(!is_same_disk(esp, other));
+        assert!(!is_same_disk(xbootldr, other));
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_pe_header() {
+        let mut dos_header = [0; 64];
+        dos_header[0..2].copy_from_slice(b"MZ");
+        dos_header[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        assert_eq!(get_pe_header_offset(&dos_header), Some(0x80));
+        assert_eq!(get_pe_header_offset(&dos_header[..0x3e]), None);
+        assert_eq!(get_pe_header_offset(&[0; 64]), None);
+
+        assert_eq!(
+            is_pe_machine_supported(b"PE\0\0\x64\x86"),
+            Some(cfg!(target_arch = "x86_64"))
+        );
+        assert_eq!(
+            is_pe_machine_supported(b"PE\0\0\x64\xaa"),
+            Some(cfg!(target_arch = "aarch64"))
+        );
+        assert_eq!(is_pe_machine_supported(b"PE\0\0\xbc\x0e"), Some(true));
+        assert_eq!(is_pe_machine_supported(b"PE\0\0\x64\x50"), Some(false)); // riscv64
+        assert_eq!(is_pe_machine_supported(b"PE\0\0\x64"), None);
+        assert_eq!(is_pe_machine_supported(b"NE\0\0\x64\x86"), None);
+    }
+
     /// # Panics
     ///
     /// May panic if the assertions fail.
@@ -2,13 +2,26 @@
 // SPDX-License-Identifier: MIT
 
 //! Simple UEFI backend for the [`log`] crate.
+//!
+//! [`UefiLogger`] writes messages to the console. [`RingLogger`] keeps the most recent messages in memory so that
+//! frontends can show them, such as in a log viewer, and gives every message to another logger. [`NullLogger`]
+//! discards every message, for frontends that draw over the console.
 
-use alloc::boxed::Box;
-use core::fmt::Write;
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cell::RefCell, fmt::Write};
 
-use log::{Level, Metadata, Record};
+use log::{Level, LevelFilter, Metadata, Record};
 use uefi::{runtime, system::with_stdout};
 
+/// The amount of messages that are kept by a [`RingLogger`].
+pub const RING_CAPACITY: usize = 256;
+
 /// A simple logging backend for UEFI.
 #[derive(Default)]
 pub struct UefiLogger;
@@ -49,3 +62,166 @@ impl log::Log for UefiLogger {
 
     fn flush(&self) {}
 }
+
+/// A logging backend that discards every message.
+#[derive(Default)]
+pub struct NullLogger;
+
+impl log::Log for NullLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        false
+    }
+
+    fn log(&self, _record: &Record) {}
+
+    fn flush(&self) {}
+}
+
+/// A message that was kept by a [`RingLogger`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The level of the message.
+    pub level: Level,
+
+    /// The file and line that the message was logged from.
+    pub location: String,
+
+    /// The message.
+    pub message: String,
+}
+
+/// A logging backend that keeps the last [`RING_CAPACITY`] messages in memory, and gives them to another logger.
+///
+/// Every message that passes the maximum level of [`log`] is kept, so the level may be raised to see more messages
+/// in a log viewer without them being written to the console.
+#[derive(Default)]
+pub struct RingLogger<L> {
+    /// The logger that every message is also given to.
+    inner: L,
+
+    /// The messages that were kept, from oldest to newest.
+    entries: RefCell<VecDeque<LogEntry>>,
+}
+
+impl<L: log::Log> RingLogger<L> {
+    /// Constructs a new [`RingLogger`] that also gives messages to another logger.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn new(inner: L) -> Self {
+        Self {
+            inner,
+            entries: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Get the kept messages that are at least as severe as a level, from oldest to newest.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn entries(&self, level: LevelFilter) -> Vec<LogEntry> {
+        self.entries.try_borrow().map_or_else(
+            |_| Vec::new(),
+            |entries| {
+                entries
+                    .iter()
+                    .filter(|x| x.level <= level)
+                    .cloned()
+                    .collect()
+            },
+        )
+    }
+
+    /// Get the amount of kept messages that are at least as severe as a level.
+    #[must_use = "Has no effect if the result is unused"]
+    pub fn count(&self, level: LevelFilter) -> usize {
+        self.entries.try_borrow().map_or(0, |entries| {
+            entries.iter().filter(|x| x.level <= level).count()
+        })
+    }
+
+    /// Keep a message, dropping the oldest message if the buffer is full.
+    fn push(&self, entry: LogEntry) {
+        let Ok(mut entries) = self.entries.try_borrow_mut() else {
+            return;
+        };
+        if entries.len() >= RING_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl<L: Sync> Sync for RingLogger<L> {}
+
+impl<L: log::Log> log::Log for RingLogger<L> {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        let file = record.file().unwrap_or_default();
+        let line = record.line().unwrap_or_default();
+        self.push(LogEntry {
+            level: record.level(),
+            location: format!("{file}:{line}"),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+
+    use log::Log;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_ring_logger() {
+        let logger = RingLogger::new(NullLogger);
+        for level in [Level::Error, Level::Warn, Level::Info, Level::Debug] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .file(Some("bls.rs"))
+                    .line(Some(10))
+                    .args(format_args!("{level} message"))
+                    .build(),
+            );
+        }
+
+        assert_eq!(logger.count(LevelFilter::Trace), 4);
+        assert_eq!(logger.count(LevelFilter::Off), 0);
+
+        let entries = logger.entries(LevelFilter::Warn);
+        assert_eq!(
+            entries,
+            [
+                LogEntry {
+                    level: Level::Error,
+                    location: "bls.rs:10".to_owned(),
+                    message: "ERROR message".to_owned(),
+                },
+                LogEntry {
+                    level: Level::Warn,
+                    location: "bls.rs:10".to_owned(),
+                    message: "WARN message".to_owned(),
+                },
+            ]
+        );
+
+        for _ in 0..RING_CAPACITY {
+            logger.log(&Record::builder().level(Level::Trace).build());
+        }
+        assert_eq!(logger.count(LevelFilter::Trace), RING_CAPACITY);
+        assert_eq!(logger.count(LevelFilter::Debug), 0);
+    }
+}
@@ -0,0 +1,375 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Log file backend for the [`log`] crate, and crash reports.
+//!
+//! [`FileLogger`] appends messages to `\loader\bootmgr-rs.log` on the same filesystem as the boot manager, and gives
+//! them to another logger, such as [`super::log_backend::UefiLogger`]. Once the log grows past [`MAX_LOG_SIZE`], it
+//! is rotated to `\loader\bootmgr-rs.log.old`, replacing the previous rotated log.
+//!
+//! Writing to the log file is enabled by the `log_file` key of the configuration file. Messages are buffered until
+//! [`start`] is called, which is done by [`crate::boot::BootMgr::new`] once the configuration file was read. Messages
+//! that are logged while the filesystem is in use are also buffered, and written with the next message.
+//!
+//! The last [`RECENT_LINES`] messages are always kept in memory, whether the log file is enabled or not, so that they
+//! can be included in the crash report that [`write_crash_report`] writes to `\loader\bootmgr-rs-crash.log`.
+//!
+//! ```no_run
+//! use bootmgr::system::{log_backend::UefiLogger, log_file::FileLogger};
+//!
+//! static LOGGER: FileLogger<UefiLogger> = FileLogger::new(UefiLogger::new());
+//!
+//! let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Info));
+//! ```
+
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+};
+use core::{
+    cell::{Cell, RefCell},
+    fmt::{Display, Write},
+    panic::{Location, PanicInfo},
+};
+
+use log::{Level, Metadata, Record};
+use uefi::{
+    CStr16, cstr16,
+    runtime::{self, Time},
+};
+
+use crate::{BootResult, system::fs::UefiFileSystem};
+
+/// The path of the log file.
+const LOG_PATH: &CStr16 = cstr16!("\\loader\\bootmgr-rs.log");
+
+/// The path that the log file is rotated to.
+const OLD_LOG_PATH: &CStr16 = cstr16!("\\loader\\bootmgr-rs.log.old");
+
+/// The path of the crash report.
+const CRASH_PATH: &CStr16 = cstr16!("\\loader\\bootmgr-rs-crash.log");
+
+/// The size of the log file in bytes after which it is rotated.
+pub const MAX_LOG_SIZE: u64 = 256 * 1024;
+
+/// The amount of messages that are kept in memory for the crash report.
+pub const RECENT_LINES: usize = 32;
+
+/// The maximum amount of messages that are buffered before the oldest are dropped.
+const MAX_PENDING: usize = 256;
+
+/// The state of the log file.
+static LOG_FILE: LogFile = LogFile::new();
+
+/// Storage struct for the state of the log file.
+struct LogFile {
+    /// Whether the log file is enabled, or `None` if it is not known yet.
+    enabled: Cell<Option<bool>>,
+
+    /// The messages that were not written yet.
+    pending: RefCell<VecDeque<String>>,
+
+    /// The last messages that were logged.
+    recent: RefCell<VecDeque<String>>,
+
+    /// The boot option that was last selected to be booted.
+    selected: RefCell<Option<String>>,
+
+    /// Whether messages are being written, so that writing does not recurse.
+    writing: Cell<bool>,
+}
+
+impl LogFile {
+    /// Get a new instance of [`LogFile`].
+    const fn new() -> Self {
+        Self {
+            enabled: Cell::new(None),
+            pending: RefCell::new(VecDeque::new()),
+            recent: RefCell::new(VecDeque::new()),
+            selected: RefCell::new(None),
+            writing: Cell::new(false),
+        }
+    }
+
+    /// Add a message to the recent messages, and to the buffer if the log file may be enabled.
+    fn push(&self, message: String) {
+        if self.enabled.get() != Some(false)
+            && let Ok(mut pending) = self.pending.try_borrow_mut()
+        {
+            if pending.len() >= MAX_PENDING {
+                pending.pop_front();
+            }
+            pending.push_back(message.clone());
+        }
+
+        if let Ok(mut recent) = self.recent.try_borrow_mut() {
+            if recent.len() >= RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(message);
+        }
+    }
+
+    /// Write the buffered messages to the log file, if it is enabled and the filesystem is not in use.
+    fn flush(&self) {
+        if self.enabled.get() != Some(true) || self.writing.replace(true) {
+            return;
+        }
+
+        let _ = self.write_pending();
+        self.writing.set(false);
+    }
+
+    /// Write the buffered messages to the log file, rotating it if it grows too large.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the filesystem could not be opened, or the log file could not be written.
+    fn write_pending(&self) -> BootResult<()> {
+        if self.pending.borrow().is_empty() {
+            return Ok(());
+        }
+
+        let mut fs = UefiFileSystem::from_image_fs()?;
+        if !fs.exists(LOG_PATH) {
+            fs.create(LOG_PATH)?;
+        }
+        let mut size = fs.file_size(LOG_PATH)?;
+
+        while let Some(message) = self.pending.borrow_mut().pop_front() {
+            let len = u64::try_from(message.len()).unwrap_or(u64::MAX);
+            let result = if needs_rotation(size, len) {
+                rotate(&mut fs).map(|()| size = 0)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| fs.append(LOG_PATH, message.as_bytes()));
+
+            if let Err(e) = result {
+                self.pending.borrow_mut().push_front(message);
+                return Err(e);
+            }
+            size = size.saturating_add(len);
+        }
+
+        Ok(())
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for LogFile {}
+
+/// A logging backend that appends messages to a log file, and gives them to another logger.
+#[derive(Default)]
+pub struct FileLogger<L> {
+    /// The logger that every message is also given to.
+    inner: L,
+}
+
+impl<L: log::Log> FileLogger<L> {
+    /// Constructs a new [`FileLogger`] that also gives messages to another logger.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl<L: log::Log> log::Log for FileLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        if record.level() > Level::Info {
+            return;
+        }
+
+        let time = runtime::get_time().unwrap_or(runtime::Time::invalid());
+        let level = record.level();
+        let file = record.file().unwrap_or_default();
+        let line = record.line().unwrap_or_default();
+        let args = record.args();
+
+        LOG_FILE.push(format!("[{time} {level} {file}:{line}] - {args}\n"));
+        LOG_FILE.flush();
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        LOG_FILE.flush();
+    }
+}
+
+/// Start writing messages to the log file if it is enabled, or stop buffering them if it is not.
+pub fn start(enabled: bool) {
+    LOG_FILE.enabled.set(Some(enabled));
+    if enabled {
+        LOG_FILE.flush();
+    } else {
+        LOG_FILE.pending.borrow_mut().clear();
+    }
+}
+
+/// Record the boot option that was selected to be booted, so that it is included in a crash report.
+pub fn set_selected_entry(name: &str) {
+    if let Ok(mut selected) = LOG_FILE.selected.try_borrow_mut() {
+        *selected = Some(name.to_string());
+    }
+}
+
+/// Write a crash report to `\loader\bootmgr-rs-crash.log`, replacing the previous one.
+///
+/// The report contains the panic message, its location, the boot option that was selected, and the last log messages.
+/// This is called by the panic handler of the `panic_handler` feature, and may be called by other panic handlers. The
+/// selected boot option and the last log messages are borrowed rather than copied, so that little is allocated.
+///
+/// # Errors
+///
+/// May return an `Error` if the filesystem of the boot manager could not be opened or is read-only, or the report
+/// could not be written.
+pub fn write_crash_report(info: &PanicInfo) -> BootResult<()> {
+    let time = runtime::get_time().ok();
+    let selected = LOG_FILE.selected.try_borrow().ok();
+    let recent = LOG_FILE.recent.try_borrow().ok();
+
+    let report = format_crash_report(
+        time.as_ref(),
+        &info.message(),
+        info.location(),
+        selected.as_ref().and_then(|x| x.as_deref()),
+        recent.iter().flat_map(|x| x.iter()),
+    );
+
+    let mut fs = UefiFileSystem::from_image_fs()?;
+    if fs.exists(CRASH_PATH) {
+        fs.delete(CRASH_PATH)?;
+    }
+    fs.create(CRASH_PATH)?;
+    fs.write(CRASH_PATH, report.as_bytes())?;
+    Ok(())
+}
+
+/// Check if the log file must be rotated before a message of a given length is appended.
+///
+/// A log file that is empty is never rotated, so that a message larger than [`MAX_LOG_SIZE`] is still written.
+const fn needs_rotation(size: u64, len: u64) -> bool {
+    size > 0 && size.saturating_add(len) > MAX_LOG_SIZE
+}
+
+/// Move the log file to the path of the rotated log, replacing the previous rotated log, then create a new log file.
+///
+/// # Errors
+///
+/// May return an `Error` if the files could not be deleted, renamed, or created.
+fn rotate(fs: &mut UefiFileSystem) -> BootResult<()> {
+    if fs.exists(OLD_LOG_PATH) {
+        fs.delete(OLD_LOG_PATH)?;
+    }
+    fs.rename(LOG_PATH, OLD_LOG_PATH)?;
+    fs.create(LOG_PATH)?;
+    Ok(())
+}
+
+/// Format the content of a crash report.
+fn format_crash_report<'a>(
+    time: Option<&Time>,
+    message: &dyn Display,
+    location: Option<&Location>,
+    selected: Option<&str>,
+    recent: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let mut report = String::from("bootmgr-rs crash report\n\n");
+
+    if let Some(time) = time {
+        let _ = writeln!(report, "Time: {time}");
+    }
+    let _ = writeln!(report, "Message: {message}");
+    if let Some(location) = location {
+        let _ = writeln!(report, "Location: {location}");
+    }
+    let _ = writeln!(report, "Selected entry: {}", selected.unwrap_or("none"));
+
+    let _ = writeln!(report, "\nLast log messages:");
+    for line in recent {
+        report.push_str(line);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::ToOwned;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_push() {
+        let log_file = LogFile::new();
+        for i in 0..=MAX_PENDING {
+            log_file.push(i.to_string());
+        }
+        assert_eq!(log_file.pending.borrow().len(), MAX_PENDING);
+        assert_eq!(log_file.recent.borrow().len(), RECENT_LINES);
+        assert_eq!(
+            log_file.recent.borrow().back().map(String::as_str),
+            Some("256")
+        );
+
+        // messages are not buffered once it is known the log file is disabled
+        log_file.enabled.set(Some(false));
+        log_file.pending.borrow_mut().clear();
+        log_file.push("message".to_owned());
+        assert!(log_file.pending.borrow().is_empty());
+        assert_eq!(
+            log_file.recent.borrow().back().map(String::as_str),
+            Some("message")
+        );
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_needs_rotation() {
+        assert!(!needs_rotation(0, MAX_LOG_SIZE * 2));
+        assert!(!needs_rotation(100, 100));
+        assert!(!needs_rotation(MAX_LOG_SIZE - 100, 100));
+        assert!(needs_rotation(MAX_LOG_SIZE - 100, 101));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_crash_report() {
+        let recent = ["[WARN] first\n".to_owned(), "[ERROR] second\n".to_owned()];
+        let location = Location::caller();
+        let report = format_crash_report(
+            None,
+            &"index out of bounds",
+            Some(location),
+            Some("arch.conf"),
+            &recent,
+        );
+
+        assert_eq!(
+            report,
+            format!(
+                "bootmgr-rs crash report\n\nMessage: index out of bounds\nLocation: {location}\nSelected entry: \
+                 arch.conf\n\nLast log messages:\n[WARN] first\n[ERROR] second\n"
+            )
+        );
+
+        let report = format_crash_report(None, &"oops", None, None, &[]);
+        assert_eq!(
+            report,
+            "bootmgr-rs crash report\n\nMessage: oops\nSelected entry: none\n\nLast log messages:\n"
+        );
+    }
+}
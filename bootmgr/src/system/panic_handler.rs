@@ -3,17 +3,53 @@
 
 //! Simple panic handler that stalls for 10 seconds, then shuts down the system.
 //!
+//! Before shutting down, a crash report is written to the filesystem of the boot manager with
+//! [`super::log_file::write_crash_report`], so that it can be collected from the running OS. If writing the report
+//! panics again, then the report is skipped on the second panic.
+//!
 //! This is enabled when the `panic_handler` feature is enabled, in case the user wanted to roll their
 //! own panic handler implementation or for fuzzing/testing.
 
 #![cfg(feature = "panic_handler")]
-use core::fmt::Write;
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::system::log_file::write_crash_report;
+
+/// Whether the panic handler was already entered, so that a panic while writing the crash report does not recurse.
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 /// The panic handler.
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     uefi::system::with_stdout(|stdout| {
         let _ = writeln!(stdout, "[PANIC]: {info}");
+    });
+    let report = if PANICKING.swap(true, Ordering::Relaxed) {
+        None
+    } else {
+        Some(write_crash_report(info))
+    };
+    uefi::system::with_stdout(|stdout| {
+        match &report {
+            None => {
+                let _ = writeln!(
+                    stdout,
+                    "Panicked while handling a panic, no crash report was written"
+                );
+            }
+            Some(Ok(())) => {
+                let _ = writeln!(
+                    stdout,
+                    "A crash report was written to \\loader\\bootmgr-rs-crash.log"
+                );
+            }
+            Some(Err(e)) => {
+                let _ = writeln!(stdout, "Failed to write a crash report: {e}");
+            }
+        }
         let _ = writeln!(stdout, "Press a key to shut down");
     });
     uefi::system::with_stdin(|stdin| {
@@ -0,0 +1,491 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! Syslog backend for the [`log`] crate, which sends messages to a collector over UDP.
+//!
+//! Messages are formatted as RFC 5424 syslog messages, and are sent through [`BaseCode::udp_write`] to port 514 of the
+//! collector. The collector is given by the `syslog_server` key of the configuration file, or otherwise by the log
+//! server option of the DHCP reply (option 7) if the network was started for PXE boot.
+//!
+//! [`SyslogLogger`] wraps another logger, such as [`super::log_backend::UefiLogger`], so that every message is still
+//! written to the console. Messages are buffered until [`start`] is called with the collector, which is done by
+//! [`crate::boot::BootMgr::new`] once the configuration file was read. If there is no collector, then the buffer is
+//! dropped and messages are only given to the wrapped logger.
+//!
+//! If the network was not started yet, such as for PXE boot, then it is started by [`start`] through DHCP. DHCP is
+//! aborted if it takes longer than [`CONNECT_TIMEOUT`], so that starting up is not held up for as long as the firmware
+//! waits for a reply. If the network could not be started, then the buffer is dropped.
+//!
+//! Loaders that exit boot services themselves flush the logger before doing so, as nothing can be sent afterwards.
+//!
+//! ```no_run
+//! use bootmgr::system::{log_backend::UefiLogger, syslog::SyslogLogger};
+//!
+//! static LOGGER: SyslogLogger<UefiLogger> = SyslogLogger::new(UefiLogger::new());
+//!
+//! let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(log::LevelFilter::Info));
+//! ```
+//!
+//! # Safety
+//!
+//! This uses unsafe in one place.
+//!
+//! 1. [`BaseCode`] is opened with [`OpenProtocolAttributes::GetProtocol`], as it may already be opened exclusively
+//!    by a network boot loader. Sending is held off with [`hold`] while a loader is using the protocol, so that it is
+//!    never used by two callers at once.
+
+use alloc::{
+    borrow::ToOwned,
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+};
+use core::{
+    cell::{Cell, RefCell},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use log::{Level, Metadata, Record};
+use uefi::{
+    boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol},
+    proto::network::{
+        IpAddress,
+        pxe::{BaseCode, UdpOpFlags},
+    },
+    runtime::{self, Time},
+};
+
+use crate::boot::loader::tftp;
+
+/// The UDP port of syslog collectors.
+pub const SYSLOG_PORT: u16 = 514;
+
+/// The name of the application in syslog messages.
+const APP_NAME: &str = "bootmgr-rs";
+
+/// The facility of syslog messages, which is user-level messages.
+const FACILITY: u8 = 1;
+
+/// The maximum amount of messages that are buffered before the oldest are dropped.
+const MAX_PENDING: usize = 256;
+
+/// The maximum size of a syslog message, which keeps it within a single Ethernet frame.
+const MAX_MESSAGE_SIZE: usize = 1024;
+
+/// The time after which DHCP is aborted when the network is started for syslog.
+///
+/// The firmware only checks this when it sends or receives a DHCP packet, so DHCP may run for up to one more
+/// retransmission.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the syslog backend.
+static SYSLOG: Syslog = Syslog::new();
+
+/// Storage struct for the state of the syslog backend.
+struct Syslog {
+    /// Whether messages are buffered or sent, which is `false` once it is known there is no collector.
+    enabled: Cell<bool>,
+
+    /// The address of the collector, if it is known.
+    collector: Cell<Option<SocketAddr>>,
+
+    /// The messages that were not sent yet.
+    pending: RefCell<VecDeque<String>>,
+
+    /// The amount of [`Hold`]s that are alive, which hold off sending.
+    holds: Cell<usize>,
+
+    /// Whether messages are being sent, so that sending does not recurse.
+    sending: Cell<bool>,
+}
+
+impl Syslog {
+    /// Get a new instance of [`Syslog`].
+    const fn new() -> Self {
+        Self {
+            enabled: Cell::new(true),
+            collector: Cell::new(None),
+            pending: RefCell::new(VecDeque::new()),
+            holds: Cell::new(0),
+            sending: Cell::new(false),
+        }
+    }
+
+    /// Add a message to the buffer, dropping the oldest message if it is full.
+    fn push(&self, message: String) {
+        let Ok(mut pending) = self.pending.try_borrow_mut() else {
+            return;
+        };
+        if pending.len() >= MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back(message);
+    }
+
+    /// Send the buffered messages to the collector, if it is known, the network was started and sending is not held
+    /// off.
+    ///
+    /// If a message could not be sent, then it is kept in the buffer and sending is tried again with the next message.
+    fn flush(&self) {
+        let Some(collector) = self.collector.get() else {
+            return;
+        };
+        if self.holds.get() > 0 || !is_network_started() || self.sending.replace(true) {
+            return;
+        }
+
+        while let Some(message) = self.pending.borrow_mut().pop_front() {
+            if send(collector, &message).is_err() {
+                self.pending.borrow_mut().push_front(message);
+                break;
+            }
+        }
+
+        self.sending.set(false);
+    }
+}
+
+// SAFETY: uefi is a single threaded environment, thread safety is irrelevant
+unsafe impl Sync for Syslog {}
+
+/// A logging backend that sends messages to a syslog collector, and gives them to another logger.
+#[derive(Default)]
+pub struct SyslogLogger<L> {
+    /// The logger that every message is also given to.
+    inner: L,
+}
+
+impl<L: log::Log> SyslogLogger<L> {
+    /// Constructs a new [`SyslogLogger`] that also gives messages to another logger.
+    #[must_use = "Has no effect if the result is unused"]
+    pub const fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl<L: log::Log> log::Log for SyslogLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+
+        if !SYSLOG.enabled.get() || record.level() > Level::Info {
+            return;
+        }
+
+        let time = runtime::get_time().ok();
+        let hostname = get_station_ip().map(|x| x.to_string());
+        let msg = format!(
+            "{}:{} {}",
+            record.file().unwrap_or_default(),
+            record.line().unwrap_or_default(),
+            record.args()
+        );
+
+        SYSLOG.push(format_message(
+            record.level(),
+            time.as_ref(),
+            hostname.as_deref(),
+            &msg,
+        ));
+        SYSLOG.flush();
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        SYSLOG.flush();
+    }
+}
+
+/// Holds off sending messages while it is alive, which is used while a network boot loader uses [`BaseCode`].
+///
+/// Messages are still buffered, and are sent once every [`Hold`] was dropped.
+pub(crate) struct Hold(());
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        SYSLOG.holds.set(SYSLOG.holds.get().saturating_sub(1));
+        SYSLOG.flush();
+    }
+}
+
+/// Hold off sending messages until the returned [`Hold`] is dropped.
+pub(crate) fn hold() -> Hold {
+    SYSLOG.holds.set(SYSLOG.holds.get() + 1);
+    Hold(())
+}
+
+/// Start sending messages to a collector, or stop buffering them if there is no collector.
+///
+/// The network is started through DHCP if it was not started yet, which is aborted after [`CONNECT_TIMEOUT`]. If the
+/// network could not be started, then the buffered messages are dropped and no more messages are sent.
+pub fn start(collector: Option<SocketAddr>) {
+    let Some(collector) = collector else {
+        disable();
+        return;
+    };
+
+    if let Err(e) = start_network(collector.is_ipv6()) {
+        disable();
+        log::warn!("Failed to start the network for syslog: {e}");
+        return;
+    }
+
+    SYSLOG.collector.set(Some(collector));
+    log::info!("Sending logs to the syslog collector at {collector}");
+    SYSLOG.flush();
+}
+
+/// Stop buffering messages, and drop those that are in the buffer.
+fn disable() {
+    SYSLOG.enabled.set(false);
+    if let Ok(mut pending) = SYSLOG.pending.try_borrow_mut() {
+        pending.clear();
+    }
+}
+
+/// Parse the address of a collector, which is either an IP address or a socket address with a port.
+#[must_use = "Has no effect if the result is unused"]
+pub fn parse_collector(value: &str) -> Option<SocketAddr> {
+    value.parse().ok().or_else(|| {
+        value
+            .parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, SYSLOG_PORT))
+    })
+}
+
+/// Start [`BaseCode`] and configure it through DHCP if it was not started yet.
+///
+/// If DHCP failed or took longer than [`CONNECT_TIMEOUT`], then [`BaseCode`] is stopped again.
+///
+/// # Errors
+///
+/// May return an `Error` if [`BaseCode`] is not supported, or could not be started, or DHCP failed or was aborted.
+fn start_network(ipv6: bool) -> uefi::Result<()> {
+    let mut base_code = open_base_code()?;
+    if base_code.mode().started() {
+        return Ok(());
+    }
+
+    base_code.start(ipv6)?;
+    tftp::dhcp_with_timeout(&mut base_code, CONNECT_TIMEOUT).inspect_err(|_| {
+        let _ = base_code.stop();
+    })
+}
+
+/// Open [`BaseCode`] without requiring exclusive access.
+///
+/// # Errors
+///
+/// May return an `Error` if there is no handle that supports [`BaseCode`].
+fn open_base_code() -> uefi::Result<ScopedProtocol<BaseCode>> {
+    let handle = boot::get_handle_for_protocol::<BaseCode>()?;
+
+    // SAFETY: sending is held off while a network boot loader uses the protocol
+    unsafe {
+        boot::open_protocol::<BaseCode>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+/// Check if [`BaseCode`] was started, either by [`start`] or by something else such as the PXE boot of bootmgr-rs.
+fn is_network_started() -> bool {
+    open_base_code().is_ok_and(|base_code| base_code.mode().started())
+}
+
+/// Get the IP address of the network interface, if the network was started.
+fn get_station_ip() -> Option<IpAddr> {
+    if SYSLOG.collector.get().is_none() || SYSLOG.holds.get() > 0 {
+        return None;
+    }
+
+    let base_code = open_base_code().ok()?;
+    let mode = base_code.mode();
+    if !mode.started() {
+        return None;
+    }
+
+    let ip = mode.station_ip();
+    Some(if mode.using_ipv6() {
+        IpAddr::V6(Ipv6Addr::from(ip))
+    } else {
+        IpAddr::V4(Ipv4Addr::from(ip))
+    })
+}
+
+/// Send a message to a collector in a UDP datagram.
+///
+/// # Errors
+///
+/// May return an `Error` if [`BaseCode`] could not be opened, or the datagram could not be sent.
+fn send(collector: SocketAddr, message: &str) -> uefi::Result<()> {
+    let mut base_code = open_base_code()?;
+    let addr = IpAddress::from(collector.ip());
+    let mut src_port = 0;
+
+    base_code.udp_write(
+        UdpOpFlags::ANY_SRC_PORT,
+        &addr,
+        collector.port(),
+        None,
+        None,
+        Some(&mut src_port),
+        None,
+        message.as_bytes(),
+    )
+}
+
+/// Format an RFC 5424 syslog message.
+///
+/// The time and host name are replaced with the nil value if they are not known, and the message is truncated to
+/// [`MAX_MESSAGE_SIZE`].
+fn format_message(level: Level, time: Option<&Time>, hostname: Option<&str>, msg: &str) -> String {
+    let timestamp = time
+        .filter(|x| x.is_valid().is_ok())
+        .map_or_else(|| "-".to_owned(), format_timestamp);
+
+    let mut message = format!(
+        "<{}>1 {timestamp} {} {APP_NAME} - - - {msg}",
+        FACILITY * 8 + severity(level),
+        hostname.unwrap_or("-"),
+    );
+
+    if message.len() > MAX_MESSAGE_SIZE {
+        let mut end = MAX_MESSAGE_SIZE;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+    message
+}
+
+/// Format a [`Time`] as an RFC 3339 timestamp.
+///
+/// A time without a time zone is assumed to be UTC.
+fn format_timestamp(time: &Time) -> String {
+    let offset = match time.time_zone() {
+        None | Some(0) => "Z".to_owned(),
+        Some(minutes) => {
+            let sign = if minutes < 0 { '-' } else { '+' };
+            let minutes = minutes.unsigned_abs();
+            format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+        }
+    };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}{offset}",
+        time.year(),
+        time.month(),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        time.nanosecond() / 1000,
+    )
+}
+
+/// Get the syslog severity of a [`Level`].
+const fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::runtime::{Daylight, TimeParams};
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_format_message() {
+        let time = Time::new(TimeParams {
+            year: 2025,
+            month: 7,
+            day: 4,
+            hour: 13,
+            minute: 5,
+            second: 9,
+            nanosecond: 123_456_789,
+            time_zone: Some(-90),
+            daylight: Daylight::empty(),
+        })
+        .unwrap_or(Time::invalid());
+
+        assert_eq!(
+            format_message(
+                Level::Warn,
+                Some(&time),
+                Some("10.0.2.15"),
+                "boot.rs:1 hello"
+            ),
+            "<12>1 2025-07-04T13:05:09.123456-01:30 10.0.2.15 bootmgr-rs - - - boot.rs:1 hello"
+        );
+        assert_eq!(
+            format_message(Level::Error, None, None, "hello"),
+            "<11>1 - - bootmgr-rs - - - hello"
+        );
+        assert_eq!(
+            format_message(Level::Info, Some(&Time::invalid()), None, "hello"),
+            "<14>1 - - bootmgr-rs - - - hello"
+        );
+
+        let long = "é".repeat(MAX_MESSAGE_SIZE);
+        let message = format_message(Level::Info, None, None, &long);
+        assert!(message.len() <= MAX_MESSAGE_SIZE);
+        assert!(message.ends_with('é'));
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_parse_collector() {
+        assert_eq!(parse_collector("10.0.2.2"), "10.0.2.2:514".parse().ok());
+        assert_eq!(
+            parse_collector("10.0.2.2:1514"),
+            "10.0.2.2:1514".parse().ok()
+        );
+        assert_eq!(
+            parse_collector("[2001:db8::1]:514"),
+            "[2001:db8::1]:514".parse().ok()
+        );
+        assert_eq!(
+            parse_collector("2001:db8::1").map(|x| x.to_string()),
+            Some("[2001:db8::1]:514".to_owned())
+        );
+        assert_eq!(parse_collector("syslog.example.com"), None);
+    }
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_pending() {
+        let syslog = Syslog::new();
+        for i in 0..=MAX_PENDING {
+            syslog.push(i.to_string());
+        }
+
+        let pending = syslog.pending.borrow();
+        assert_eq!(pending.len(), MAX_PENDING);
+        assert_eq!(pending.front().map(String::as_str), Some("1"));
+    }
+}
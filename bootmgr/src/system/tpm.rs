@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+// SPDX-License-Identifier: MIT
+
+//! TPM2 measurements through the TCG2 protocol.
+//!
+//! This measures data that the boot manager hands to the OS into the same PCRs that systemd uses, so that the
+//! measurements can be predicted and attested in the same way. Every measurement is also logged in the TCG event log
+//! as an `EV_IPL` event, with a UTF-16 description of what was measured.
+//!
+//! EFI executables are not measured here, as the firmware already measures every image loaded through
+//! `LoadImage` into PCR 4. Linux kernels that are booted directly are not loaded through `LoadImage`, so they are
+//! measured into PCR 4 here instead.
+
+use uefi::proto::tcg::{
+    EventType, PcrIndex,
+    v2::{HashLogExtendEventFlags, PcrEventInputs, Tcg},
+};
+
+use crate::{
+    BootResult,
+    system::helper::{locate_protocol, str_to_cstr},
+};
+
+/// The PCR that boot loader code, such as directly booted kernels, is measured into.
+#[cfg(target_arch = "x86_64")]
+pub(crate) const TPM2_PCR_BOOT_LOADER_CODE: PcrIndex = PcrIndex(4);
+
+/// The PCR that initrds are measured into.
+pub(crate) const TPM2_PCR_INITRD: PcrIndex = PcrIndex(9);
+
+/// The PCR that the kernel command line and devicetree blobs are measured into.
+pub(crate) const TPM2_PCR_KERNEL_CONFIG: PcrIndex = PcrIndex(12);
+
+/// Get the TCG2 protocol, if there is a TPM present on the system.
+fn get_tcg() -> Option<uefi::boot::ScopedProtocol<Tcg>> {
+    let mut tcg = locate_protocol::<Tcg>().ok()?;
+    tcg.get_capability()
+        .is_ok_and(|cap| cap.tpm_present())
+        .then_some(tcg)
+}
+
+/// Get the bitmap of active PCR banks, where each bit corresponds to a hash algorithm.
+///
+/// May return `None` if there is no TPM present on the system.
+pub(crate) fn get_active_pcr_banks() -> Option<u32> {
+    get_tcg()?
+        .get_active_pcr_banks()
+        .ok()
+        .map(|banks| banks.bits())
+}
+
+/// Measure data into a PCR, and log the measurement in the event log with a description.
+///
+/// Returns `false` if there is no TPM present on the system, in which case nothing is measured.
+///
+/// # Errors
+///
+/// May return an `Error` if the description could not be converted into UCS-2, or the TPM failed to extend the PCR.
+pub(crate) fn measure(pcr: PcrIndex, data: &[u8], description: &str) -> BootResult<bool> {
+    let Some(mut tcg) = get_tcg() else {
+        return Ok(false);
+    };
+
+    let description = str_to_cstr(description)?;
+    let event = PcrEventInputs::new_in_box(
+        pcr,
+        EventType::IPL,
+        bytemuck::must_cast_slice(description.to_u16_slice_with_nul()),
+    )?;
+
+    tcg.hash_log_extend_event(HashLogExtendEventFlags::empty(), data, &event)?;
+    Ok(true)
+}
@@ -5,7 +5,9 @@
 //!
 //! These store a value into a UEFI variable in a custom vendor namespace.
 
-use alloc::string::String;
+#[cfg(test)]
+use alloc::vec::Vec;
+use alloc::{boxed::Box, string::String};
 
 use thiserror::Error;
 use uefi::{
@@ -16,11 +18,16 @@ use uefi::{
 use crate::BootResult;
 
 /// The custom variable namespace for the boot manager.
-const BOOTMGR_GUID: uefi::Guid = guid!("23600d08-561e-4e68-a024-1d7d6e04ee4e");
+pub(crate) const BOOTMGR_GUID: uefi::Guid = guid!("23600d08-561e-4e68-a024-1d7d6e04ee4e");
 
 /// The maximum size of a singular type to be stored in a UEFI variable in bytes.
 const MAX_SIZE: usize = size_of::<u64>();
 
+/// The default attributes for a variable, which is persistent and accessible at boot and runtime.
+const DEFAULT_ATTRS: VariableAttributes = VariableAttributes::NON_VOLATILE
+    .union(VariableAttributes::BOOTSERVICE_ACCESS)
+    .union(VariableAttributes::RUNTIME_ACCESS);
+
 /// An `Error` that may result from attempting to get value from a UEFI variable.
 #[derive(Error, Debug)]
 pub enum VarError {
@@ -43,48 +50,87 @@ pub enum VarError {
 
 /// A trait for implementations of UEFI variable storage.
 ///
-/// Usually this will use runtime services.
-trait UefiVariableStorage {
-    /// Get a variable given its name, a variable vendor, and a mutable byte slice.
+/// Usually this will use runtime services, through [`RuntimeUefiVariableStorage`]. Other implementations, like an
+/// in-memory variable store, can be used for testing code that sets or gets variables on host.
+pub(crate) trait UefiVariableStorage {
+    /// Get the content of a variable given its name and a variable vendor.
+    ///
+    /// Returns [`None`] if the variable does not exist.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to get the variable.
+    fn get_variable_bytes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> BootResult<Option<Box<[u8]>>>;
+
+    /// Get the content of a variable along with its attributes given its name and a variable vendor.
+    ///
+    /// Returns [`None`] if the variable does not exist.
     ///
     /// # Errors
     ///
     /// May return an `Error` if the underlying variable storage failed to get the variable.
-    fn get_variable<T: UefiVariable + 'static>(
+    fn get_variable_bytes_with_attributes(
+        &self,
         name: &CStr16,
         vendor: &VariableVendor,
-    ) -> BootResult<T>;
+    ) -> BootResult<Option<(Box<[u8]>, VariableAttributes)>>;
 
-    /// Set a variable given its name, a variable vendor, variable attributes, and the chosen type.
+    /// Set the content of a variable given its name, a variable vendor, and variable attributes.
+    ///
+    /// If the content is empty, then the variable will be deleted.
     ///
     /// # Errors
     ///
     /// May return an `Error` if the underlying variable storage failed to set the variable.
-    fn set_variable<T: UefiVariable + 'static>(
+    fn set_variable_bytes(
+        &mut self,
         name: &CStr16,
         vendor: &VariableVendor,
         attributes: VariableAttributes,
-        num: Option<T>,
+        data: &[u8],
     ) -> BootResult<()>;
-}
-
-/// UEFI variable storage implementation with runtime services..
-struct RuntimeUefiVariableStorage;
 
-impl UefiVariableStorage for RuntimeUefiVariableStorage {
-    fn get_variable<T: UefiVariable>(name: &CStr16, vendor: &VariableVendor) -> BootResult<T> {
-        let mut buf = [0; MAX_SIZE];
-        match runtime::get_variable(name, vendor, &mut buf) {
-            Ok((var, _)) => Ok(T::from_bytes(var)),
-            Err(e) if e.status() == Status::NOT_FOUND => Ok(T::default()), // pretend that we got all zeroes if its not found
-            Err(e) => Err(VarError::GetErr(e).into()),
+    /// Get a variable of a [`UefiVariable`] given its name and a variable vendor.
+    ///
+    /// See [`get_variable`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to get the variable, or the variable
+    /// was not the same size as the [`UefiVariable`].
+    fn get_variable<T: UefiVariable>(
+        &self,
+        name: &CStr16,
+        vendor: Option<VariableVendor>,
+    ) -> BootResult<T> {
+        let vendor = vendor.unwrap_or(VariableVendor(BOOTMGR_GUID));
+        match self.get_variable_bytes(name, &vendor)? {
+            Some(var) if var.len() == T::SIZE => Ok(T::from_bytes(&var)),
+            Some(var) => Err(VarError::GetErr(uefi::Error::new(
+                Status::BAD_BUFFER_SIZE,
+                Some(var.len()),
+            ))
+            .into()),
+            None => Ok(T::default()), // pretend that we got all zeroes if its not found
         }
     }
 
+    /// Set a variable to a [`UefiVariable`] given its name, a variable vendor, and variable attributes.
+    ///
+    /// See [`set_variable`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to set the variable.
     fn set_variable<T: UefiVariable>(
+        &mut self,
         name: &CStr16,
-        vendor: &VariableVendor,
-        attributes: VariableAttributes,
+        vendor: Option<VariableVendor>,
+        attrs: Option<VariableAttributes>,
         num: Option<T>,
     ) -> BootResult<()> {
         let mut bytes = 0;
@@ -92,12 +138,200 @@ impl UefiVariableStorage for RuntimeUefiVariableStorage {
         if let Some(num) = num {
             bytes = num.to_bytes(&mut buf);
         }
-        Ok(runtime::set_variable(
-            name,
-            vendor,
-            attributes,
-            &buf[0..bytes],
-        )?)
+        self.set_variable_u8_slice(name, vendor, attrs, &buf[0..bytes])
+    }
+
+    /// Set a variable to a [`u8`] slice given its name, a variable vendor, and variable attributes.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to set the variable.
+    fn set_variable_u8_slice(
+        &mut self,
+        name: &CStr16,
+        vendor: Option<VariableVendor>,
+        attrs: Option<VariableAttributes>,
+        bytes: &[u8],
+    ) -> BootResult<()> {
+        let vendor = vendor.unwrap_or(VariableVendor(BOOTMGR_GUID));
+        let attrs = attrs.unwrap_or(DEFAULT_ATTRS);
+        self.set_variable_bytes(name, &vendor, attrs, bytes)
+    }
+
+    /// Set a variable to a [`u16`] slice given its name, a variable vendor, and variable attributes.
+    ///
+    /// See [`set_variable_u16_slice`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to set the variable.
+    fn set_variable_u16_slice(
+        &mut self,
+        name: &CStr16,
+        vendor: Option<VariableVendor>,
+        attrs: Option<VariableAttributes>,
+        bytes: Option<&[u16]>,
+    ) -> BootResult<()> {
+        let bytes = bytes.unwrap_or(&[] as &[u16]);
+        self.set_variable_u8_slice(name, vendor, attrs, bytemuck::must_cast_slice(bytes))
+    }
+
+    /// Set a variable to a [`str`] slice given its name, a variable vendor, and variable attributes.
+    ///
+    /// See [`set_variable_str`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to set the variable, or the string could
+    /// not be converted into UCS-2.
+    fn set_variable_str(
+        &mut self,
+        name: &CStr16,
+        vendor: Option<VariableVendor>,
+        attrs: Option<VariableAttributes>,
+        str: Option<&str>,
+    ) -> BootResult<()> {
+        let mut buf = [0; 256];
+        let str = if let Some(str) = str {
+            Some(
+                CStr16::from_str_with_buf(str, &mut buf)
+                    .map_err(VarError::Ucs2ConvErr)?
+                    .to_u16_slice_with_nul(),
+            )
+        } else {
+            None
+        };
+        self.set_variable_u16_slice(name, vendor, attrs, str)
+    }
+
+    /// Get a variable of a [`str`] slice given its name and a variable vendor.
+    ///
+    /// See [`get_variable_str`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// May return an `Error` if the underlying variable storage failed to get the variable, or the variable
+    /// was not a valid UCS-2 string.
+    fn get_variable_str(
+        &self,
+        name: &CStr16,
+        vendor: Option<VariableVendor>,
+    ) -> BootResult<String> {
+        let vendor = vendor.unwrap_or(VariableVendor(BOOTMGR_GUID));
+        let Some(var) = self.get_variable_bytes(name, &vendor)? else {
+            return Ok(String::new());
+        };
+        let str = bytemuck::try_cast_slice(&var).map_err(VarError::CastErr)?;
+
+        Ok(String::from(
+            CStr16::from_u16_with_nul(str).map_err(VarError::StrErr)?,
+        ))
+    }
+}
+
+/// UEFI variable storage implementation with runtime services.
+pub(crate) struct RuntimeUefiVariableStorage;
+
+impl UefiVariableStorage for RuntimeUefiVariableStorage {
+    fn get_variable_bytes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> BootResult<Option<Box<[u8]>>> {
+        Ok(self
+            .get_variable_bytes_with_attributes(name, vendor)?
+            .map(|(var, _)| var))
+    }
+
+    fn get_variable_bytes_with_attributes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> BootResult<Option<(Box<[u8]>, VariableAttributes)>> {
+        match runtime::get_variable_boxed(name, vendor) {
+            Ok(var) => Ok(Some(var)),
+            Err(e) if e.status() == Status::NOT_FOUND => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set_variable_bytes(
+        &mut self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+        attributes: VariableAttributes,
+        data: &[u8],
+    ) -> BootResult<()> {
+        Ok(runtime::set_variable(name, vendor, attributes, data)?)
+    }
+}
+
+/// UEFI variable storage implementation in memory, for testing code that uses variables on host.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryUefiVariableStorage {
+    /// The variables that are stored, along with their vendor and attributes.
+    pub(crate) vars: Vec<(String, uefi::Guid, VariableAttributes, Vec<u8>)>,
+}
+
+#[cfg(test)]
+impl MemoryUefiVariableStorage {
+    /// Get the position of a variable given its name and a variable vendor.
+    fn position(&self, name: &CStr16, vendor: &VariableVendor) -> Option<usize> {
+        let name = String::from(name);
+        self.vars
+            .iter()
+            .position(|(var, guid, _, _)| *var == name && *guid == vendor.0)
+    }
+
+    /// Get the attributes of a variable given its name and a variable vendor, if it exists.
+    pub(crate) fn attributes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> Option<VariableAttributes> {
+        self.position(name, vendor).map(|idx| self.vars[idx].2)
+    }
+}
+
+#[cfg(test)]
+impl UefiVariableStorage for MemoryUefiVariableStorage {
+    fn get_variable_bytes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> BootResult<Option<Box<[u8]>>> {
+        Ok(self
+            .position(name, vendor)
+            .map(|idx| self.vars[idx].3.clone().into_boxed_slice()))
+    }
+
+    fn get_variable_bytes_with_attributes(
+        &self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+    ) -> BootResult<Option<(Box<[u8]>, VariableAttributes)>> {
+        Ok(self.position(name, vendor).map(|idx| {
+            let (_, _, attributes, var) = &self.vars[idx];
+            (var.clone().into_boxed_slice(), *attributes)
+        }))
+    }
+
+    fn set_variable_bytes(
+        &mut self,
+        name: &CStr16,
+        vendor: &VariableVendor,
+        attributes: VariableAttributes,
+        data: &[u8],
+    ) -> BootResult<()> {
+        if let Some(idx) = self.position(name, vendor) {
+            self.vars.remove(idx);
+        }
+        if !data.is_empty() {
+            self.vars
+                .push((String::from(name), vendor.0, attributes, data.to_vec()));
+        }
+        Ok(())
     }
 }
 
@@ -247,16 +481,7 @@ pub fn set_variable<T: UefiVariable + 'static>(
     attrs: Option<VariableAttributes>,
     num: Option<T>,
 ) -> BootResult<()> {
-    let vendor = vendor.unwrap_or(runtime::VariableVendor(BOOTMGR_GUID));
-    let attrs = attrs.map_or_else(
-        || {
-            VariableAttributes::NON_VOLATILE
-                | VariableAttributes::BOOTSERVICE_ACCESS
-                | VariableAttributes::RUNTIME_ACCESS
-        },
-        |x| x,
-    );
-    RuntimeUefiVariableStorage::set_variable(name, &vendor, attrs, num)
+    RuntimeUefiVariableStorage.set_variable(name, vendor, attrs, num)
 }
 
 /// Gets a UEFI variable of a [`UefiVariable`] given the name
@@ -277,8 +502,7 @@ pub fn get_variable<T: UefiVariable + 'static>(
     name: &CStr16,
     vendor: Option<VariableVendor>,
 ) -> BootResult<T> {
-    let vendor = vendor.unwrap_or(runtime::VariableVendor(BOOTMGR_GUID));
-    RuntimeUefiVariableStorage::get_variable(name, &vendor)
+    RuntimeUefiVariableStorage.get_variable(name, vendor)
 }
 
 /// Sets a UEFI variable to a [`u16`] slice given the name.
@@ -300,22 +524,7 @@ pub fn set_variable_u16_slice(
     attrs: Option<VariableAttributes>,
     bytes: Option<&[u16]>,
 ) -> BootResult<()> {
-    let vendor = vendor.unwrap_or(runtime::VariableVendor(BOOTMGR_GUID));
-    let attrs = attrs.map_or_else(
-        || {
-            VariableAttributes::NON_VOLATILE
-                | VariableAttributes::BOOTSERVICE_ACCESS
-                | VariableAttributes::RUNTIME_ACCESS
-        },
-        |x| x,
-    );
-    let bytes = bytes.unwrap_or(&[] as &[u16]);
-    Ok(runtime::set_variable(
-        name,
-        &vendor,
-        attrs,
-        bytemuck::must_cast_slice(bytes),
-    )?)
+    RuntimeUefiVariableStorage.set_variable_u16_slice(name, vendor, attrs, bytes)
 }
 
 /// Sets a UEFI variable to a [`str`] slice given the name.
@@ -333,17 +542,7 @@ pub fn set_variable_str(
     attrs: Option<VariableAttributes>,
     str: Option<&str>,
 ) -> BootResult<()> {
-    let mut buf = [0; 256];
-    let str = if let Some(str) = str {
-        Some(
-            CStr16::from_str_with_buf(str, &mut buf)
-                .map_err(VarError::Ucs2ConvErr)?
-                .to_u16_slice_with_nul(),
-        )
-    } else {
-        None
-    };
-    set_variable_u16_slice(name, vendor, attrs, str)
+    RuntimeUefiVariableStorage.set_variable_str(name, vendor, attrs, str)
 }
 
 /// Gets a UEFI variable of a [`str`] slice given the name
@@ -362,15 +561,43 @@ pub fn set_variable_str(
 /// converted into a u16 slice, or the variable could not be converted into a [`CString16`], then an error will be
 /// returned.
 pub fn get_variable_str(name: &CStr16, vendor: Option<VariableVendor>) -> BootResult<String> {
-    let vendor = vendor.unwrap_or(runtime::VariableVendor(BOOTMGR_GUID));
-    let var = match runtime::get_variable_boxed(name, &vendor) {
-        Ok((var, _)) => var,
-        Err(e) if e.status() == Status::NOT_FOUND => return Ok(String::new()),
-        Err(e) => return Err(e.into()),
-    };
-    let str = bytemuck::try_cast_slice(&var).map_err(VarError::CastErr)?;
-
-    Ok(String::from(
-        CStr16::from_u16_with_nul(str).map_err(VarError::StrErr)?,
-    ))
+    RuntimeUefiVariableStorage.get_variable_str(name, vendor)
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::cstr16;
+
+    use super::*;
+
+    /// # Panics
+    ///
+    /// May panic if the assertions fail.
+    #[test]
+    fn test_memory_storage() {
+        let mut vars = MemoryUefiVariableStorage::default();
+        let name = cstr16!("SomeVariable");
+
+        assert_eq!(vars.get_variable::<u32>(name, None).ok(), Some(0));
+        assert!(vars.set_variable(name, None, None, Some(7u32)).is_ok());
+        assert_eq!(vars.get_variable::<u32>(name, None).ok(), Some(7));
+        assert_eq!(
+            vars.attributes(name, &VariableVendor(BOOTMGR_GUID)),
+            Some(DEFAULT_ATTRS)
+        );
+        assert!(vars.get_variable::<u64>(name, None).is_err()); // the size does not match
+
+        assert!(
+            vars.set_variable_str(name, None, None, Some("text"))
+                .is_ok()
+        );
+        assert_eq!(
+            vars.get_variable_str(name, None).ok().as_deref(),
+            Some("text")
+        );
+
+        assert!(vars.set_variable_str(name, None, None, None).is_ok());
+        assert_eq!(vars.get_variable_str(name, None).ok().as_deref(), Some(""));
+        assert_eq!(vars.vars.len(), 0);
+    }
 }
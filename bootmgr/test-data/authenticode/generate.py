@@ -0,0 +1,195 @@
+# SPDX-FileCopyrightText: 2025 some100 <ootinnyoo@outlook.com>
+# SPDX-License-Identifier: MIT
+
+"""Generate the Authenticode test fixtures.
+
+This creates a test CA, a signing certificate issued by that CA, an unrelated CA, and a minimal PE32+ image that is
+signed with an Authenticode signature in the same layout that sbsign and signtool produce. The same image is also
+signed by a certificate of the test CA that lacks the code signing extended key usage. Only the `cryptography`
+package is required.
+
+    python3 generate.py
+"""
+
+import datetime
+import hashlib
+import struct
+from pathlib import Path
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import padding, rsa
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+OUT = Path(__file__).parent
+
+
+def der(tag: int, content: bytes) -> bytes:
+    length = len(content)
+    if length < 0x80:
+        return bytes([tag, length]) + content
+    encoded = length.to_bytes((length.bit_length() + 7) // 8, "big")
+    return bytes([tag, 0x80 | len(encoded)]) + encoded + content
+
+
+def seq(*items: bytes) -> bytes:
+    return der(0x30, b"".join(items))
+
+
+def set_of(*items: bytes) -> bytes:
+    return der(0x31, b"".join(sorted(items)))
+
+
+def oid(dotted: str) -> bytes:
+    parts = [int(x) for x in dotted.split(".")]
+    body = bytes([parts[0] * 40 + parts[1]])
+    for part in parts[2:]:
+        chunk = [part & 0x7F]
+        part >>= 7
+        while part:
+            chunk.insert(0, 0x80 | (part & 0x7F))
+            part >>= 7
+        body += bytes(chunk)
+    return der(0x06, body)
+
+
+def integer(value: int) -> bytes:
+    return der(0x02, value.to_bytes(value.bit_length() // 8 + 1, "big"))
+
+
+NULL = b"\x05\x00"
+SHA256 = seq(oid("2.16.840.1.101.3.4.2.1"), NULL)
+RSA = seq(oid("1.2.840.113549.1.1.1"), NULL)
+SPC_INDIRECT_DATA = "1.3.6.1.4.1.311.2.1.4"
+
+
+def make_cert(name, key, issuer_name, issuer_key, ca, eku=None):
+    now = datetime.datetime(2025, 1, 1, tzinfo=datetime.timezone.utc)
+    builder = (
+        x509.CertificateBuilder()
+        .subject_name(x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, name)]))
+        .issuer_name(x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, issuer_name)]))
+        .public_key(key.public_key())
+        .serial_number(x509.random_serial_number())
+        .not_valid_before(now)
+        .not_valid_after(now + datetime.timedelta(days=365 * 30))
+        .add_extension(x509.BasicConstraints(ca=ca, path_length=None), critical=True)
+    )
+    if eku is not None:
+        builder = builder.add_extension(x509.ExtendedKeyUsage(eku), critical=False)
+    return builder.sign(issuer_key, hashes.SHA256())
+
+
+def make_pe() -> bytearray:
+    """Build a minimal PE32+ image with a single section."""
+    pe_offset = 0x40
+    opt_size = 112 + 16 * 8
+    headers_size = 0x200
+    text = b"\xc3" + bytes(range(255)) * 2  # ret, followed by filler
+
+    image = bytearray(headers_size)
+    image[0:2] = b"MZ"
+    struct.pack_into("<I", image, 0x3C, pe_offset)
+    image[pe_offset : pe_offset + 4] = b"PE\0\0"
+    struct.pack_into("<HHIIIHH", image, pe_offset + 4, 0x8664, 1, 0, 0, 0, opt_size, 0x22)
+
+    opt = pe_offset + 24
+    struct.pack_into("<H", image, opt, 0x20B)
+    struct.pack_into("<I", image, opt + 16, 0x1000)  # AddressOfEntryPoint
+    struct.pack_into("<II", image, opt + 32, 0x1000, 0x200)  # SectionAlignment, FileAlignment
+    struct.pack_into("<II", image, opt + 56, 0x2000, headers_size)  # SizeOfImage, SizeOfHeaders
+    struct.pack_into("<H", image, opt + 68, 10)  # EFI application
+    struct.pack_into("<I", image, opt + 108, 16)  # NumberOfRvaAndSizes
+
+    section = opt + opt_size
+    image[section : section + 8] = b".text\0\0\0"
+    raw_size = (len(text) + 0x1FF) & ~0x1FF
+    struct.pack_into("<IIII", image, section + 8, len(text), 0x1000, raw_size, headers_size)
+    struct.pack_into("<I", image, section + 36, 0x60000020)
+
+    image += text + bytes(raw_size - len(text))
+    return image
+
+
+def authenticode_hash(image: bytes) -> bytes:
+    pe_offset = struct.unpack_from("<I", image, 0x3C)[0]
+    opt = pe_offset + 24
+    checksum = opt + 64
+    cert_dir = opt + 112 + 4 * 8
+    headers_size = struct.unpack_from("<I", image, opt + 60)[0]
+
+    digest = hashlib.sha256()
+    digest.update(image[:checksum])
+    digest.update(image[checksum + 4 : cert_dir])
+    digest.update(image[cert_dir + 8 : headers_size])
+    digest.update(image[headers_size:])
+    return digest.digest()
+
+
+def sign(image: bytearray, key, cert, chain) -> bytearray:
+    spc_pe_image_data = seq(b"\x03\x01\x00", der(0xA0, der(0xA2, b"\x80\x00")))
+    indirect = seq(
+        seq(oid("1.3.6.1.4.1.311.2.1.15"), spc_pe_image_data),
+        seq(SHA256, der(0x04, authenticode_hash(image))),
+    )
+    indirect_value = indirect[2:] if indirect[1] < 0x80 else indirect[2 + (indirect[1] & 0x7F) :]
+
+    attrs = [
+        seq(oid("1.2.840.113549.1.9.3"), set_of(oid(SPC_INDIRECT_DATA))),
+        seq(oid("1.2.840.113549.1.9.4"), set_of(der(0x04, hashlib.sha256(indirect_value).digest()))),
+    ]
+    signed_attrs = set_of(*attrs)
+    signature = key.sign(signed_attrs, padding.PKCS1v15(), hashes.SHA256())
+
+    issuer = cert.issuer.public_bytes()
+    signer_info = seq(
+        integer(1),
+        seq(issuer, integer(cert.serial_number)),
+        SHA256,
+        b"\xa0" + signed_attrs[1:],
+        RSA,
+        der(0x04, signature),
+    )
+    certs = b"".join(c.public_bytes(serialization.Encoding.DER) for c in [cert, *chain])
+    signed_data = seq(
+        integer(1),
+        set_of(SHA256),
+        seq(oid(SPC_INDIRECT_DATA), der(0xA0, indirect)),
+        der(0xA0, certs),
+        set_of(signer_info),
+    )
+    content_info = seq(oid("1.2.840.113549.1.7.2"), der(0xA0, signed_data))
+
+    length = 8 + len(content_info)
+    padded = (length + 7) & ~7
+    win_cert = struct.pack("<IHH", length, 0x0200, 0x0002) + content_info + bytes(padded - length)
+
+    signed = bytearray(image)
+    pe_offset = struct.unpack_from("<I", signed, 0x3C)[0]
+    struct.pack_into("<II", signed, pe_offset + 24 + 112 + 4 * 8, len(signed), padded)
+    return signed + win_cert
+
+
+def main():
+    ca_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    ca = make_cert("bootmgr-rs test CA", ca_key, "bootmgr-rs test CA", ca_key, True)
+    signer_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    signer = make_cert(
+        "bootmgr-rs test signer", signer_key, "bootmgr-rs test CA", ca_key, False, [ExtendedKeyUsageOID.CODE_SIGNING]
+    )
+    no_eku_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    no_eku = make_cert("bootmgr-rs test signer without EKU", no_eku_key, "bootmgr-rs test CA", ca_key, False)
+    other_key = rsa.generate_private_key(public_exponent=65537, key_size=2048)
+    other = make_cert("bootmgr-rs other CA", other_key, "bootmgr-rs other CA", other_key, True)
+
+    image = make_pe()
+    (OUT / "ca.der").write_bytes(ca.public_bytes(serialization.Encoding.DER))
+    (OUT / "signer.der").write_bytes(signer.public_bytes(serialization.Encoding.DER))
+    (OUT / "other-ca.der").write_bytes(other.public_bytes(serialization.Encoding.DER))
+    (OUT / "unsigned.efi").write_bytes(image)
+    (OUT / "signed.efi").write_bytes(sign(image, signer_key, signer, []))
+    (OUT / "signed-no-eku.efi").write_bytes(sign(image, no_eku_key, no_eku, []))
+
+
+if __name__ == "__main__":
+    main()